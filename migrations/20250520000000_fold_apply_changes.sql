@@ -0,0 +1,100 @@
+-- Folds the hand-run apply_changes.sql into the versioned migrations.
+-- Databases created from 20230424000000_consolidated already have all of this;
+-- the statements below only fill in what older hand-maintained databases may lack.
+-- Unlike apply_changes.sql, existing role permissions are NOT reset, so grants
+-- customised through /api/permissions are preserved.
+
+CREATE TABLE IF NOT EXISTS item_statuses (
+  id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+  name VARCHAR(64) NOT NULL UNIQUE,
+  description TEXT,
+  color VARCHAR(32)
+);
+
+INSERT INTO item_statuses (name, description, color) VALUES
+('active', 'Item is available for use', 'green'),
+('maintenance', 'Item is under maintenance', 'orange'),
+('borrowed', 'Item is currently borrowed', 'blue'),
+('reserved', 'Item is reserved for future use', 'purple'),
+('damaged', 'Item is damaged but still usable', 'yellow'),
+('unusable', 'Item is damaged beyond use', 'red')
+ON CONFLICT (name) DO NOTHING;
+
+DO $$
+BEGIN
+  IF NOT EXISTS (SELECT 1 FROM information_schema.columns WHERE table_name = 'items' AND column_name = 'status_id') THEN
+    ALTER TABLE items ADD COLUMN status_id UUID REFERENCES item_statuses(id);
+    UPDATE items SET status_id = (SELECT id FROM item_statuses WHERE name = 'active');
+    ALTER TABLE items ALTER COLUMN status_id SET NOT NULL;
+  END IF;
+END $$;
+
+ALTER TABLE user_roles ADD COLUMN IF NOT EXISTS description TEXT;
+
+UPDATE user_roles SET description = 'Full system access' WHERE name = 'admin' AND description IS NULL;
+UPDATE user_roles SET description = 'Basic inventory management' WHERE name = 'staff' AND description IS NULL;
+
+INSERT INTO user_roles (name, description) VALUES
+('manager', 'Department manager with approval rights'),
+('caretaker', 'Responsible for maintenance and condition reporting'),
+('borrower', 'Can only view and request to borrow items'),
+('procurement_officer', 'Can manage procurement requests')
+ON CONFLICT (name) DO NOTHING;
+
+CREATE TABLE IF NOT EXISTS permissions (
+  id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+  name VARCHAR(64) NOT NULL UNIQUE,
+  description TEXT
+);
+
+INSERT INTO permissions (name, description) VALUES
+('view_items', 'Dapat melihat item inventaris'),
+('add_items', 'Dapat menambahkan item inventaris baru'),
+('edit_items', 'Dapat mengedit item inventaris yang ada'),
+('delete_items', 'Dapat menghapus item inventaris'),
+('manage_categories', 'Dapat mengelola kategori item'),
+('manage_locations', 'Dapat mengelola lokasi'),
+('manage_users', 'Dapat mengelola akun pengguna'),
+('approve_procurements', 'Dapat menyetujui permintaan pengadaan'),
+('manage_donations', 'Dapat mengelola donasi'),
+('view_reports', 'Dapat melihat laporan sistem'),
+('manage_item_status', 'Dapat mengubah status item'),
+('borrow_items', 'Dapat meminjam item'),
+('admin_access', 'Akses administratif penuh'),
+('manage_permissions', 'Dapat mengelola izin peran'),
+('manage_roles', 'Dapat mengelola peran pengguna'),
+('approve_borrowings', 'Dapat menyetujui permintaan peminjaman item'),
+('manage_borrowings', 'Dapat mengelola semua peminjaman'),
+('view_all_borrowings', 'Dapat melihat semua peminjaman')
+ON CONFLICT (name) DO NOTHING;
+
+CREATE TABLE IF NOT EXISTS role_permissions (
+  id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+  role_id UUID REFERENCES user_roles(id) ON DELETE CASCADE,
+  permission_id UUID REFERENCES permissions(id) ON DELETE CASCADE,
+  UNIQUE(role_id, permission_id)
+);
+
+-- Admin keeps every permission
+INSERT INTO role_permissions (role_id, permission_id)
+SELECT r.id, p.id
+FROM user_roles r, permissions p
+WHERE r.name = 'admin'
+ON CONFLICT DO NOTHING;
+
+CREATE TABLE IF NOT EXISTS item_borrowings (
+  id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+  item_id UUID NOT NULL REFERENCES items(id) ON DELETE CASCADE,
+  borrower_id UUID NOT NULL REFERENCES users(id),
+  quantity INTEGER NOT NULL DEFAULT 1,
+  borrowed_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+  expected_return_date TIMESTAMPTZ NOT NULL,
+  actual_return_date TIMESTAMPTZ,
+  approved_by UUID REFERENCES users(id),
+  notes TEXT,
+  status VARCHAR(32) NOT NULL DEFAULT 'pending'
+);
+
+CREATE INDEX IF NOT EXISTS idx_item_borrowings_item_id ON item_borrowings(item_id);
+CREATE INDEX IF NOT EXISTS idx_item_borrowings_borrower_id ON item_borrowings(borrower_id);
+CREATE INDEX IF NOT EXISTS idx_items_status_id ON items(status_id);
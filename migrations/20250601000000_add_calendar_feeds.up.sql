@@ -0,0 +1,13 @@
+-- Tokenized iCalendar feeds for borrowings
+-- Each feed belongs either to a borrower (user_id) or to a location (location_id)
+CREATE TABLE IF NOT EXISTS calendar_feeds (
+  id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+  token VARCHAR(64) NOT NULL UNIQUE,
+  user_id UUID REFERENCES users(id) ON DELETE CASCADE,
+  location_id UUID REFERENCES locations(id) ON DELETE CASCADE,
+  created_by UUID REFERENCES users(id) ON DELETE SET NULL,
+  created_at TIMESTAMPTZ DEFAULT now(),
+  CONSTRAINT calendar_feeds_owner_check CHECK ((user_id IS NULL) <> (location_id IS NULL))
+);
+
+CREATE INDEX IF NOT EXISTS idx_calendar_feeds_created_by ON calendar_feeds(created_by);
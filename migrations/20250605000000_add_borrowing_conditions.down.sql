@@ -0,0 +1,2 @@
+ALTER TABLE item_borrowings DROP COLUMN IF EXISTS return_condition_id;
+ALTER TABLE item_borrowings DROP COLUMN IF EXISTS checkout_condition_id;
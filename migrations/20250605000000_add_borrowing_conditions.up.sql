@@ -0,0 +1,3 @@
+-- Record item condition at checkout (approval) and at return for handover receipts
+ALTER TABLE item_borrowings ADD COLUMN IF NOT EXISTS checkout_condition_id UUID REFERENCES conditions(id);
+ALTER TABLE item_borrowings ADD COLUMN IF NOT EXISTS return_condition_id UUID REFERENCES conditions(id);
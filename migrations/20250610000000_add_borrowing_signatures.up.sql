@@ -0,0 +1,13 @@
+-- Drawn signatures captured at checkout (handover) and at return
+CREATE TABLE IF NOT EXISTS borrowing_signatures (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    borrowing_id UUID NOT NULL REFERENCES item_borrowings(id) ON DELETE CASCADE,
+    stage VARCHAR(16) NOT NULL CHECK (stage IN ('checkout', 'return')),
+    signer_role VARCHAR(16) NOT NULL CHECK (signer_role IN ('borrower', 'staff')),
+    signed_by UUID REFERENCES users(id) ON DELETE SET NULL,
+    image_url TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    UNIQUE (borrowing_id, stage, signer_role)
+);
+
+CREATE INDEX IF NOT EXISTS idx_borrowing_signatures_borrowing_id ON borrowing_signatures(borrowing_id);
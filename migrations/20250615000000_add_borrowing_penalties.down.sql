@@ -0,0 +1,3 @@
+DROP TABLE IF EXISTS borrower_penalties;
+DROP TABLE IF EXISTS penalty_rules;
+DELETE FROM permissions WHERE name = 'manage_penalties';
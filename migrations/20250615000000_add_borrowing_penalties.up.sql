@@ -0,0 +1,41 @@
+-- Late-return penalty rules (per category, NULL category = default rule)
+CREATE TABLE IF NOT EXISTS penalty_rules (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    category_id UUID REFERENCES categories(id) ON DELETE CASCADE,
+    amount_per_day BIGINT NOT NULL CHECK (amount_per_day >= 0),
+    grace_days INTEGER NOT NULL DEFAULT 0 CHECK (grace_days >= 0),
+    max_amount BIGINT CHECK (max_amount IS NULL OR max_amount >= 0),
+    is_active BOOLEAN NOT NULL DEFAULT TRUE,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE UNIQUE INDEX IF NOT EXISTS idx_penalty_rules_category ON penalty_rules(category_id) WHERE category_id IS NOT NULL;
+CREATE UNIQUE INDEX IF NOT EXISTS idx_penalty_rules_default ON penalty_rules((category_id IS NULL)) WHERE category_id IS NULL;
+
+-- Ledger of penalties charged to borrowers, one per late borrowing
+CREATE TABLE IF NOT EXISTS borrower_penalties (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    borrowing_id UUID NOT NULL UNIQUE REFERENCES item_borrowings(id) ON DELETE CASCADE,
+    borrower_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    rule_id UUID REFERENCES penalty_rules(id) ON DELETE SET NULL,
+    days_late INTEGER NOT NULL,
+    amount BIGINT NOT NULL,
+    status VARCHAR(16) NOT NULL DEFAULT 'pending' CHECK (status IN ('pending', 'settled', 'waived')),
+    resolved_by UUID REFERENCES users(id) ON DELETE SET NULL,
+    resolved_at TIMESTAMPTZ,
+    resolution_note TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_borrower_penalties_borrower_id ON borrower_penalties(borrower_id);
+
+INSERT INTO permissions (name, description) VALUES
+('manage_penalties', 'Dapat mengelola aturan denda dan melunasi/menghapus denda peminjam')
+ON CONFLICT (name) DO UPDATE SET description = EXCLUDED.description;
+
+INSERT INTO role_permissions (role_id, permission_id)
+SELECT r.id, p.id
+FROM user_roles r, permissions p
+WHERE r.name = 'admin' AND p.name = 'manage_penalties'
+ON CONFLICT DO NOTHING;
@@ -0,0 +1,19 @@
+-- Token API pribadi untuk script dan integrasi. Hanya hash SHA-256 token yang disimpan;
+-- `scopes` berisi nama permission yang boleh dipakai token (bagian dari permission user).
+CREATE TABLE IF NOT EXISTS api_tokens (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    name TEXT NOT NULL,
+    token_hash TEXT NOT NULL UNIQUE,
+    -- Awal token (mis. `inman_pat_1a2b3c`) supaya user bisa mengenali token di daftar
+    token_prefix TEXT NOT NULL,
+    scopes TEXT[] NOT NULL DEFAULT '{}',
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    -- NULL berarti tidak kedaluwarsa
+    expires_at TIMESTAMPTZ,
+    last_used_at TIMESTAMPTZ,
+    last_used_ip TEXT,
+    revoked_at TIMESTAMPTZ
+);
+
+CREATE INDEX IF NOT EXISTS idx_api_tokens_user_id ON api_tokens(user_id);
@@ -0,0 +1,27 @@
+-- Login OIDC yang sedang berjalan: dibuat saat user diarahkan ke provider dan dipakai
+-- sekali di callback. Hanya hash `state` yang disimpan.
+CREATE TABLE IF NOT EXISTS oidc_login_states (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    state_hash TEXT NOT NULL UNIQUE,
+    nonce TEXT NOT NULL,
+    -- PKCE code verifier untuk menukar authorization code
+    code_verifier TEXT NOT NULL,
+    -- Path frontend tujuan setelah login berhasil
+    redirect_path TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    expires_at TIMESTAMPTZ NOT NULL
+);
+
+-- Akun di provider OIDC yang terhubung ke user, berdasarkan issuer + subject (`sub`)
+CREATE TABLE IF NOT EXISTS user_identities (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    issuer TEXT NOT NULL,
+    subject TEXT NOT NULL,
+    email TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    last_login_at TIMESTAMPTZ,
+    UNIQUE (issuer, subject)
+);
+
+CREATE INDEX IF NOT EXISTS idx_user_identities_user_id ON user_identities(user_id);
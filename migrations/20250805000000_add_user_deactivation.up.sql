@@ -0,0 +1,6 @@
+-- User tidak dihapus supaya nama di riwayat peminjaman dan log barang tetap ada;
+-- user nonaktif tidak bisa login dan token/session-nya tidak berlaku
+ALTER TABLE users
+    ADD COLUMN IF NOT EXISTS is_active BOOLEAN NOT NULL DEFAULT TRUE,
+    ADD COLUMN IF NOT EXISTS deactivated_at TIMESTAMPTZ,
+    ADD COLUMN IF NOT EXISTS deactivated_by UUID REFERENCES users(id) ON DELETE SET NULL;
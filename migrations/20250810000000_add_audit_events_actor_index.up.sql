@@ -0,0 +1,2 @@
+-- Filter `actor_id` di GET /api/audit-events
+CREATE INDEX IF NOT EXISTS idx_audit_events_actor_id ON audit_events(actor_id, created_at);
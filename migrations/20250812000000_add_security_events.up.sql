@@ -0,0 +1,16 @@
+-- Log keamanan: login berhasil/gagal, logout, refresh token, ganti password, dan akses ditolak
+CREATE TABLE IF NOT EXISTS security_events (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    event_type TEXT NOT NULL,
+    -- NULL jika user tidak dikenal, mis. login dengan nama yang tidak terdaftar
+    user_id UUID REFERENCES users(id) ON DELETE SET NULL,
+    -- Nama yang dipakai saat login, disimpan apa adanya
+    username TEXT,
+    details JSONB,
+    ip_address TEXT,
+    user_agent TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_security_events_user_id ON security_events(user_id, created_at);
+CREATE INDEX IF NOT EXISTS idx_security_events_created_at ON security_events(created_at);
@@ -0,0 +1,2 @@
+ALTER TABLE borrowing_signatures DROP COLUMN IF EXISTS storage_backend;
+ALTER TABLE borrowing_signatures RENAME COLUMN file_id TO image_url;
@@ -0,0 +1,6 @@
+-- Tanda tangan disimpan privat dan diambil lewat endpoint yang mengecek izin, jadi
+-- yang disimpan adalah backend dan ID file, bukan URL publik
+ALTER TABLE borrowing_signatures RENAME COLUMN image_url TO file_id;
+ALTER TABLE borrowing_signatures
+    ADD COLUMN storage_backend VARCHAR(16) NOT NULL DEFAULT 'google_drive'
+    CHECK (storage_backend IN ('google_drive', 'local'));
@@ -0,0 +1,3 @@
+-- URL lama tidak bisa dibentuk ulang (tergantung BASE_URL saat upload); nama file
+-- dibiarkan, jadi revert migrasi ini tidak mengubah data.
+SELECT 1;
@@ -0,0 +1,7 @@
+-- Tanda tangan yang diupload dengan STORAGE_BACKEND=local dulu disimpan sebagai URL
+-- `{base_url}/uploads/{file}` dan terbaca sebagai file ID Google Drive. Simpan sebagai
+-- backend local dengan nama file saja supaya bisa dibaca lagi dari direktori upload.
+UPDATE borrowing_signatures
+SET storage_backend = 'local',
+    file_id = regexp_replace(file_id, '^.*/uploads/', '')
+WHERE file_id LIKE '%/uploads/%';
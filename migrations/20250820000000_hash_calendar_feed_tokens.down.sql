@@ -0,0 +1,8 @@
+-- Token asli tidak bisa dibentuk ulang dari hash; feed mendapat token acak baru,
+-- jadi URL feed lama tidak berlaku lagi setelah revert.
+ALTER TABLE calendar_feeds ADD COLUMN IF NOT EXISTS token VARCHAR(64);
+UPDATE calendar_feeds SET token = replace(gen_random_uuid()::text, '-', '') || replace(gen_random_uuid()::text, '-', '');
+ALTER TABLE calendar_feeds ALTER COLUMN token SET NOT NULL;
+ALTER TABLE calendar_feeds ADD CONSTRAINT calendar_feeds_token_key UNIQUE (token);
+ALTER TABLE calendar_feeds DROP COLUMN IF EXISTS token_hash;
+ALTER TABLE calendar_feeds DROP COLUMN IF EXISTS token_prefix;
@@ -0,0 +1,14 @@
+-- Token feed kalender disimpan sebagai hash SHA-256 (hex), sama seperti token lain.
+-- URL feed yang sudah ada tetap berlaku karena hash dihitung dari token lama.
+ALTER TABLE calendar_feeds ADD COLUMN IF NOT EXISTS token_hash TEXT;
+-- Awal token supaya user bisa mengenali feed di daftar
+ALTER TABLE calendar_feeds ADD COLUMN IF NOT EXISTS token_prefix TEXT;
+
+UPDATE calendar_feeds
+SET token_hash = encode(sha256(convert_to(token, 'UTF8')), 'hex'),
+    token_prefix = left(token, 8);
+
+ALTER TABLE calendar_feeds ALTER COLUMN token_hash SET NOT NULL;
+ALTER TABLE calendar_feeds ALTER COLUMN token_prefix SET NOT NULL;
+ALTER TABLE calendar_feeds ADD CONSTRAINT calendar_feeds_token_hash_key UNIQUE (token_hash);
+ALTER TABLE calendar_feeds DROP COLUMN token;
@@ -0,0 +1,290 @@
+//! CLI admin untuk bootstrap dan pemeliharaan, misalnya membuat user admin pertama
+//! (endpoint `POST /api/users` sendiri membutuhkan user admin).
+//!
+//! Contoh: `cargo run --bin inman-admin -- create-user --name admin --role admin`
+
+use clap::{Parser, Subcommand};
+use sqlx::PgPool;
+use std::io::{BufRead, Write};
+use std::process::ExitCode;
+
+use rustrest::config::Config;
+use rustrest::routes::lookup::{list_user_roles, seed_default_lookups};
+use rustrest::routes::permissions::{
+    find_permission_id, grant_permission, list_permissions, list_role_permissions, revoke_permission,
+    role_permission_snapshot,
+};
+use rustrest::routes::user::{find_role_id, find_user_id, insert_user, set_password};
+use rustrest::services::audit::AuditEvent;
+use rustrest::services::login_throttle::{unlock_account, unlock_ip};
+use rustrest::services::password_reset::create_password_reset;
+use rustrest::services::security_log::SecurityEvent;
+use rustrest::services::passwords::check_password_policy;
+use rustrest::services::two_factor::disable_two_factor;
+
+#[derive(Parser)]
+#[command(name = "inman-admin", about = "Admin tooling untuk inventory backend")]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Buat user baru dengan role dan password
+    CreateUser {
+        #[arg(long)]
+        name: String,
+        #[arg(long, default_value = "staff")]
+        role: String,
+        /// Jika tidak diisi, password dibaca dari stdin
+        #[arg(long)]
+        password: Option<String>,
+    },
+    /// Reset password user
+    ResetPassword {
+        #[arg(long)]
+        name: String,
+        /// Jika tidak diisi, password dibaca dari stdin
+        #[arg(long)]
+        password: Option<String>,
+    },
+    /// Buat link reset password untuk user yang lupa password (butuh FRONTEND_URL)
+    ResetLink {
+        #[arg(long)]
+        name: String,
+    },
+    /// Matikan 2FA user yang kehilangan authenticator dan kode pemulihannya
+    #[command(name = "reset-2fa")]
+    Reset2fa {
+        #[arg(long)]
+        name: String,
+    },
+    /// Buka kunci login akun atau IP setelah terlalu banyak password salah
+    Unlock {
+        #[arg(long, required_unless_present = "ip")]
+        name: Option<String>,
+        #[arg(long)]
+        ip: Option<String>,
+    },
+    /// Tampilkan semua role
+    ListRoles,
+    /// Tampilkan semua permission, atau permission milik satu role
+    ListPermissions {
+        #[arg(long)]
+        role: Option<String>,
+    },
+    /// Berikan permission ke role
+    Grant {
+        #[arg(long)]
+        role: String,
+        #[arg(long)]
+        permission: String,
+    },
+    /// Cabut permission dari role
+    Revoke {
+        #[arg(long)]
+        role: String,
+        #[arg(long)]
+        permission: String,
+    },
+    /// Isi table lookup (kategori, kondisi, status, ...) dengan nilai default
+    SeedLookups,
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    dotenv::dotenv().ok();
+    let cli = Cli::parse();
+
+    // CLI hanya butuh DATABASE_URL, jadi konfigurasi lain tidak divalidasi
+    let config = match Config::from_sources() {
+        Ok(config) if !config.database.url.is_empty() => config,
+        Ok(_) => {
+            eprintln!("DATABASE_URL harus di-set");
+            return ExitCode::FAILURE;
+        }
+        Err(e) => {
+            eprintln!("{}", e);
+            return ExitCode::FAILURE;
+        }
+    };
+    let pool = match PgPool::connect(&config.database.url).await {
+        Ok(pool) => pool,
+        Err(e) => {
+            eprintln!("Gagal connect ke database: {}", e);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    match run(&pool, &config, cli.command).await {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+async fn run(pool: &PgPool, config: &Config, command: Command) -> Result<(), Box<dyn std::error::Error>> {
+    match command {
+        Command::CreateUser { name, role, password } => {
+            let role_id = find_role_id(pool, &role)
+                .await?
+                .ok_or_else(|| format!("Role '{}' tidak ditemukan", role))?;
+            if find_user_id(pool, &name).await?.is_some() {
+                return Err(format!("User '{}' sudah ada", name).into());
+            }
+            let password = password_or_prompt(password)?;
+            // Cek kebijakan password sebelum user dibuat, supaya tidak tertinggal user tanpa password
+            check_password_policy(&config.password, &password, Some(&name))?;
+            let user = insert_user(pool, &name, role_id).await?;
+            set_password(pool, &config.password, user.id, &password).await?;
+            AuditEvent::new("user.created", "user", Some(user.id))
+                .after(serde_json::json!({ "name": user.name, "role_id": role_id, "via": "inman-admin" }))
+                .record(pool)
+                .await?;
+            println!("User '{}' dibuat dengan role '{}' (id {})", user.name, role, user.id);
+        },
+        Command::ResetPassword { name, password } => {
+            let user_id = find_user_id(pool, &name)
+                .await?
+                .ok_or_else(|| format!("User '{}' tidak ditemukan", name))?;
+            let password = password_or_prompt(password)?;
+            set_password(pool, &config.password, user_id, &password).await?;
+            SecurityEvent::new("password.changed")
+                .user(user_id)
+                .details(serde_json::json!({ "via": "inman-admin" }))
+                .record(pool)
+                .await?;
+            println!("Password user '{}' sudah di-reset", name);
+        },
+        Command::ResetLink { name } => {
+            let user_id = find_user_id(pool, &name)
+                .await?
+                .ok_or_else(|| format!("User '{}' tidak ditemukan", name))?;
+            if config.cors.frontend_url.is_empty() {
+                return Err("FRONTEND_URL harus di-set untuk membuat link reset".into());
+            }
+            let reset = create_password_reset(pool, config, user_id, None).await?;
+            AuditEvent::new("password_reset.issued", "user", Some(user_id))
+                .after(serde_json::json!({ "expires_at": reset.expires_at, "via": "inman-admin" }))
+                .record(pool)
+                .await?;
+            println!("Link reset password untuk '{}' (berlaku sampai {}):", name, reset.expires_at);
+            println!("{}", reset.url);
+        },
+        Command::Reset2fa { name } => {
+            let user_id = find_user_id(pool, &name)
+                .await?
+                .ok_or_else(|| format!("User '{}' tidak ditemukan", name))?;
+            if disable_two_factor(pool, user_id).await? {
+                AuditEvent::new("two_factor.reset", "user", Some(user_id))
+                    .after(serde_json::json!({ "via": "inman-admin" }))
+                    .record(pool)
+                    .await?;
+                println!("2FA user '{}' sudah dimatikan", name);
+            } else {
+                println!("User '{}' belum memakai 2FA", name);
+            }
+        },
+        Command::Unlock { name, ip } => {
+            if let Some(name) = name {
+                match unlock_account(pool, &name).await? {
+                    true => println!("Login user '{}' sudah dibuka", name),
+                    false => println!("User '{}' tidak sedang terkunci", name),
+                }
+            }
+            if let Some(ip) = ip {
+                match unlock_ip(pool, &ip).await? {
+                    true => println!("Login dari IP {} sudah dibuka", ip),
+                    false => println!("IP {} tidak sedang terkunci", ip),
+                }
+            }
+        },
+        Command::ListRoles => {
+            for role in list_user_roles(pool).await? {
+                println!("{}  {}", role.id, role.name);
+            }
+        },
+        Command::ListPermissions { role } => {
+            let permissions = match role {
+                Some(role) => {
+                    let role_id = find_role_id(pool, &role)
+                        .await?
+                        .ok_or_else(|| format!("Role '{}' tidak ditemukan", role))?;
+                    list_role_permissions(pool, role_id).await?
+                },
+                None => list_permissions(pool).await?,
+            };
+            for permission in permissions {
+                println!("{:<24} {}", permission.name, permission.description.unwrap_or_default());
+            }
+        },
+        Command::Grant { role, permission } => {
+            let (role_id, permission_id) = resolve_role_permission(pool, &role, &permission).await?;
+            match grant_permission(pool, role_id, permission_id).await? {
+                Some(_) => {
+                    let mut after = role_permission_snapshot(pool, role_id, permission_id).await?;
+                    after["via"] = "inman-admin".into();
+                    AuditEvent::new("role.permission_granted", "role", Some(role_id))
+                        .after(after)
+                        .record(pool)
+                        .await?;
+                    println!("Permission '{}' diberikan ke role '{}'", permission, role);
+                },
+                None => println!("Role '{}' sudah memiliki permission '{}'", role, permission),
+            }
+        },
+        Command::Revoke { role, permission } => {
+            let (role_id, permission_id) = resolve_role_permission(pool, &role, &permission).await?;
+            let mut before = role_permission_snapshot(pool, role_id, permission_id).await?;
+            if revoke_permission(pool, role_id, permission_id).await? {
+                before["via"] = "inman-admin".into();
+                AuditEvent::new("role.permission_revoked", "role", Some(role_id))
+                    .before(before)
+                    .record(pool)
+                    .await?;
+                println!("Permission '{}' dicabut dari role '{}'", permission, role);
+            } else {
+                println!("Role '{}' tidak memiliki permission '{}'", role, permission);
+            }
+        },
+        Command::SeedLookups => {
+            let inserted = seed_default_lookups(pool).await?;
+            println!("{} nilai lookup baru ditambahkan", inserted);
+        },
+    }
+    Ok(())
+}
+
+async fn resolve_role_permission(
+    pool: &PgPool,
+    role: &str,
+    permission: &str,
+) -> Result<(uuid::Uuid, uuid::Uuid), Box<dyn std::error::Error>> {
+    let role_id = find_role_id(pool, role)
+        .await?
+        .ok_or_else(|| format!("Role '{}' tidak ditemukan", role))?;
+    let permission_id = find_permission_id(pool, permission)
+        .await?
+        .ok_or_else(|| format!("Permission '{}' tidak ditemukan", permission))?;
+    Ok((role_id, permission_id))
+}
+
+fn password_or_prompt(password: Option<String>) -> Result<String, Box<dyn std::error::Error>> {
+    let password = match password {
+        Some(password) => password,
+        None => {
+            eprint!("Password: ");
+            std::io::stderr().flush()?;
+            let mut line = String::new();
+            std::io::stdin().lock().read_line(&mut line)?;
+            line.trim_end_matches(['\r', '\n']).to_string()
+        },
+    };
+    if password.is_empty() {
+        return Err("Password tidak boleh kosong".into());
+    }
+    Ok(password)
+}
@@ -0,0 +1,205 @@
+//! Error type bersama untuk semua handler.
+//!
+//! Setiap error dikirim sebagai JSON dengan bentuk yang sama:
+//! `{"error": "<pesan>", "code": "<kode>", "request_id": "<id>"}`, ditambah field
+//! lain dari `details` jika ada. `error` tetap berisi pesan agar frontend lama
+//! tetap bisa membacanya; `code` stabil dan aman dipakai untuk logika di client.
+
+use actix_web::http::{header, StatusCode};
+use actix_web::{HttpResponse, ResponseError};
+use serde::Serialize;
+use std::fmt;
+use utoipa::ToSchema;
+
+use crate::middleware::request_id::current_request_id;
+
+/// Bentuk body JSON semua response error
+#[derive(Serialize, ToSchema)]
+pub struct ErrorBody {
+    /// Pesan untuk ditampilkan ke user
+    #[schema(example = "Item not found")]
+    pub error: String,
+    /// Kode stabil untuk logika di client, mis. `not_found` atau `borrowing_blocked`
+    #[schema(example = "not_found")]
+    pub code: String,
+    pub request_id: Option<String>,
+    /// Field tambahan khusus untuk beberapa error, mis. `standing` pada `borrowing_blocked`
+    #[serde(flatten)]
+    #[schema(value_type = Object)]
+    pub details: serde_json::Map<String, serde_json::Value>,
+}
+
+#[derive(Debug)]
+pub enum ApiError {
+    /// 400 bad_request
+    BadRequest(String),
+    /// 401 unauthorized
+    Unauthorized(String),
+    /// 403 forbidden
+    Forbidden(String),
+    /// 404 not_found
+    NotFound(String),
+    /// 409 conflict
+    Conflict(String),
+    /// 413 payload_too_large
+    PayloadTooLarge(String),
+    /// 422 unprocessable_entity
+    Unprocessable(String),
+    /// 429 too_many_requests, dengan header `Retry-After`
+    TooManyRequests { message: String, retry_after_secs: u64 },
+    /// 503 service_unavailable
+    ServiceUnavailable(String),
+    /// 500 internal_error; pesan hanya di-log, tidak dikirim ke client
+    Internal(String),
+    /// Error dengan kode khusus dan field tambahan, mis. peminjam yang diblokir
+    Detailed {
+        status: StatusCode,
+        code: &'static str,
+        message: String,
+        details: serde_json::Value,
+    },
+}
+
+impl ApiError {
+    pub fn internal(error: impl fmt::Display) -> Self {
+        ApiError::Internal(error.to_string())
+    }
+
+    pub fn code(&self) -> &'static str {
+        match self {
+            ApiError::BadRequest(_) => "bad_request",
+            ApiError::Unauthorized(_) => "unauthorized",
+            ApiError::Forbidden(_) => "forbidden",
+            ApiError::NotFound(_) => "not_found",
+            ApiError::Conflict(_) => "conflict",
+            ApiError::PayloadTooLarge(_) => "payload_too_large",
+            ApiError::Unprocessable(_) => "unprocessable_entity",
+            ApiError::TooManyRequests { .. } => "too_many_requests",
+            ApiError::ServiceUnavailable(_) => "service_unavailable",
+            ApiError::Internal(_) => "internal_error",
+            ApiError::Detailed { code, .. } => code,
+        }
+    }
+
+    fn message(&self) -> &str {
+        match self {
+            ApiError::BadRequest(message)
+            | ApiError::Unauthorized(message)
+            | ApiError::Forbidden(message)
+            | ApiError::NotFound(message)
+            | ApiError::Conflict(message)
+            | ApiError::PayloadTooLarge(message)
+            | ApiError::Unprocessable(message)
+            | ApiError::ServiceUnavailable(message)
+            | ApiError::Internal(message) => message,
+            ApiError::TooManyRequests { message, .. } | ApiError::Detailed { message, .. } => message,
+        }
+    }
+}
+
+impl fmt::Display for ApiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.code(), self.message())
+    }
+}
+
+impl ResponseError for ApiError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            ApiError::BadRequest(_) => StatusCode::BAD_REQUEST,
+            ApiError::Unauthorized(_) => StatusCode::UNAUTHORIZED,
+            ApiError::Forbidden(_) => StatusCode::FORBIDDEN,
+            ApiError::NotFound(_) => StatusCode::NOT_FOUND,
+            ApiError::Conflict(_) => StatusCode::CONFLICT,
+            ApiError::PayloadTooLarge(_) => StatusCode::PAYLOAD_TOO_LARGE,
+            ApiError::Unprocessable(_) => StatusCode::UNPROCESSABLE_ENTITY,
+            ApiError::TooManyRequests { .. } => StatusCode::TOO_MANY_REQUESTS,
+            ApiError::ServiceUnavailable(_) => StatusCode::SERVICE_UNAVAILABLE,
+            ApiError::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            ApiError::Detailed { status, .. } => *status,
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        let request_id = current_request_id();
+        let message = match self {
+            ApiError::Internal(message) => {
+                eprintln!("[ERROR] [{}] {}", request_id.as_deref().unwrap_or("-"), message);
+                "Internal server error"
+            },
+            _ => self.message(),
+        };
+
+        let mut response = HttpResponse::build(self.status_code());
+        let details = match self {
+            ApiError::Detailed { details: serde_json::Value::Object(details), .. } => details.clone(),
+            ApiError::TooManyRequests { retry_after_secs, .. } => {
+                response.insert_header((header::RETRY_AFTER, retry_after_secs.to_string()));
+                let mut details = serde_json::Map::new();
+                details.insert("retry_after".to_string(), (*retry_after_secs).into());
+                details
+            },
+            _ => serde_json::Map::new(),
+        };
+        response.json(ErrorBody {
+            error: message.to_string(),
+            code: self.code().to_string(),
+            request_id,
+            details,
+        })
+    }
+}
+
+impl From<sqlx::Error> for ApiError {
+    fn from(error: sqlx::Error) -> Self {
+        match &error {
+            sqlx::Error::RowNotFound => ApiError::NotFound("Resource not found".to_string()),
+            sqlx::Error::Database(db_error) => {
+                let constraint = db_error.constraint().unwrap_or("unknown");
+                match db_error.code().as_deref() {
+                    // unique_violation
+                    Some("23505") => ApiError::Conflict(format!("Duplicate value violates {}", constraint)),
+                    // foreign_key_violation: referensi tidak ada, atau data masih dipakai saat dihapus
+                    Some("23503") => ApiError::Unprocessable(format!(
+                        "Referenced record does not exist or is still in use ({})",
+                        constraint
+                    )),
+                    // not_null_violation / check_violation
+                    Some("23502") | Some("23514") => ApiError::Unprocessable(format!("Invalid value ({})", constraint)),
+                    // invalid_text_representation, mis. enum atau UUID tidak valid
+                    Some("22P02") => ApiError::BadRequest("Invalid input value".to_string()),
+                    _ => ApiError::Internal(error.to_string()),
+                }
+            },
+            _ => ApiError::Internal(error.to_string()),
+        }
+    }
+}
+
+/// Ganti pesan conflict generik dengan pesan yang lebih jelas untuk client,
+/// mis. `.map_err(conflict_on_unique("Role sudah ada"))`
+pub fn conflict_on_unique(message: &'static str) -> impl FnOnce(sqlx::Error) -> ApiError {
+    move |error| match &error {
+        sqlx::Error::Database(db_error) if db_error.is_unique_violation() => ApiError::Conflict(message.to_string()),
+        _ => error.into(),
+    }
+}
+
+impl std::error::Error for ApiError {}
+
+/// Service yang mengembalikan `Box<dyn Error>` bisa menyisipkan `ApiError`
+/// (mis. validasi upload); error lain dianggap internal
+impl From<Box<dyn std::error::Error>> for ApiError {
+    fn from(error: Box<dyn std::error::Error>) -> Self {
+        match error.downcast::<ApiError>() {
+            Ok(api_error) => *api_error,
+            Err(error) => ApiError::Internal(error.to_string()),
+        }
+    }
+}
+
+/// Error untuk body JSON, path, dan query yang tidak bisa di-parse,
+/// dipasang lewat `JsonConfig`/`PathConfig`/`QueryConfig` di `main.rs`
+pub fn extractor_error(error: impl fmt::Display) -> actix_web::Error {
+    ApiError::BadRequest(error.to_string()).into()
+}
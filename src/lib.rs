@@ -0,0 +1,12 @@
+//! Library crate berisi seluruh modul aplikasi, dipakai bersama oleh
+//! server (`src/main.rs`) dan CLI admin (`src/bin/inman-admin.rs`).
+
+pub mod config;
+pub mod error;
+pub mod middleware;
+pub mod migrations;
+pub mod openapi;
+pub mod routes;
+pub mod services;
+#[doc(hidden)]
+pub mod test_support;
@@ -1,21 +1,21 @@
-use actix_web::{App, HttpServer, middleware::Logger};
+use actix_web::{web, App, HttpServer, middleware::{from_fn, Logger}};
 
 use actix_cors::Cors;
 use sqlx::PgPool;
 use actix_web::web::Data;
-mod routes;
-mod middleware;
-mod services;
-use routes::user::user_config;
-use routes::auth::{check_user, login, logout};
-use routes::me::me;
-use routes::items::items_config;
-use routes::upload::upload_config;
-use routes::permissions::permissions_config;
-use routes::borrowings::borrowings_config;
-use services::drive_storage::{DriveConfig, DriveClient, GoogleCredentials, create_drive_client, ensure_folder_exists};
+use rustrest::{migrations, routes, services};
+use rustrest::error::extractor_error;
+use rustrest::middleware::permission_guard::PermissionCache;
+use rustrest::middleware::request_id::request_id;
+use rustrest::middleware::security_log::log_permission_denials;
+use rustrest::openapi::openapi_config;
+use services::drive_storage::{DriveConfig, create_drive_client, empty_drive_client, ensure_folder_exists};
+use services::mailer::Mailer;
+use services::ldap::LdapAuthenticator;
+use services::oidc::OidcClient;
+use rustrest::config::{Config, StorageBackend};
 use std::sync::Arc;
-use std::path::Path;
+use std::time::Duration;
 use tokio::sync::Mutex;
 
 // Models and user routes moved to routes/user.rs
@@ -23,104 +23,103 @@ use tokio::sync::Mutex;
 async fn main() -> std::io::Result<()> {
     dotenv::dotenv().ok();
     env_logger::init();
-    let db_url = std::env::var("DATABASE_URL").expect("DATABASE_URL harus di-set");
-    let db_pool = PgPool::connect(&db_url).await.expect("Gagal connect ke database");
     
-    let port = std::env::var("PORT")
-    .ok()
-    .and_then(|p| p.parse().ok())
-    .unwrap_or(8080);
-
-    let frontend_urls = Arc::new(vec![
-        std::env::var("FRONTEND_URL").expect("FRONTEND_URL harus di-set"),
-        "http://localhost:5173".to_string(),
-    ]);
+    // Subcommand: rustrest migrate [status|run|revert]
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.first().map(String::as_str) == Some("migrate") {
+        // Subcommand migrate hanya butuh DATABASE_URL, jadi konfigurasi lain tidak divalidasi
+        let db_url = match Config::from_sources() {
+            Ok(config) if !config.database.url.is_empty() => config.database.url,
+            Ok(_) => exit_with_error("DATABASE_URL harus di-set"),
+            Err(e) => exit_with_error(e),
+        };
+        let db_pool = PgPool::connect(&db_url).await.expect("Gagal connect ke database");
+        if let Err(e) = migrations::cli(&db_pool, &args[1..]).await {
+            exit_with_error(e);
+        }
+        return Ok(());
+    }
+    
+    let config = match Config::load() {
+        Ok(config) => config,
+        Err(e) => exit_with_error(e),
+    };
+    let db_pool = PgPool::connect(&config.database.url).await.expect("Gagal connect ke database");
+    
+    if config.server.run_migrations {
+        migrations::run(&db_pool).await.expect("Gagal menjalankan migrasi database");
+        println!("[INFO] Migrasi database sudah terbaru");
+    } else {
+        println!("[INFO] RUN_MIGRATIONS=false, migrasi tidak dijalankan saat startup");
+    }
     
-    // Konfigurasi upload file
-    let upload_dir = Path::new(&std::env::var("UPLOAD_DIR").unwrap_or_else(|_| "uploads".to_string())).to_path_buf();
+    let port = config.server.port;
+    let cors_urls = Arc::new(config.cors_origins());
     
     // Pastikan direktori upload ada (untuk file statis)
+    let upload_dir = config.upload.dir.clone();
     if !upload_dir.exists() {
         std::fs::create_dir_all(&upload_dir).expect("Gagal membuat direktori upload");
     }
     
-    // Konfigurasi BASE_URL untuk akses API
-    let base_url = std::env::var("BASE_URL").unwrap_or_else(|_| {
-        format!("http://localhost:{}", port)
-    });
-    println!("[INFO] Menggunakan BASE_URL: {}", base_url);
+    println!("[INFO] Menggunakan BASE_URL: {}", config.base_url());
     
-    // Konfigurasi Google Drive
-    let mut drive_config = DriveConfig::default();
-    drive_config.base_url = base_url.clone();
+    // Konfigurasi storage upload
+    let drive_config = DriveConfig::from(&config);
     
     // Inisialisasi Drive client
-    let drive_client = match create_drive_client(&drive_config).await {
-        Ok(mut client) => {
-            // Pastikan folder untuk upload sudah ada
-            let folder_id = match ensure_folder_exists(&mut client, &drive_config.folder_id).await {
-                Ok(id) => {
-                    println!("Berhasil terhubung ke Google Drive. Folder ID: {}", id);
-                    // Update folder_id jika berbeda
-                    if drive_config.folder_id != id {
-                        println!("Folder ID diperbarui: {}", id);
-                    }
-                    id
-                },
-                Err(e) => {
-                    eprintln!("Warning: Gagal memastikan folder ada: {}", e);
-                    eprintln!("Pastikan kredensial Google Drive sudah benar.");
-                    eprintln!("Upload akan fallback ke penyimpanan lokal jika Google Drive tidak tersedia.");
-                    drive_config.folder_id.clone()
-                }
-            };
-            
-            // Buat DriveConfig baru dengan folder_id yang diperbarui
-            let mut updated_config = drive_config.clone();
-            updated_config.folder_id = folder_id;
-            
-            client
-        },
-        Err(e) => {
-            eprintln!("Warning: Gagal membuat Google Drive client: {}", e);
-            eprintln!("Pastikan kredensial Google Drive sudah benar.");
-            eprintln!("Upload akan fallback ke penyimpanan lokal jika Google Drive tidak tersedia.");
-            
-            // Buat dummy client kosong untuk fallback
-            match create_drive_client(&DriveConfig {
-                credentials_json: "{}".to_string(),
-                ..DriveConfig::default()
-            }).await {
-                Ok(client) => client,
-                Err(e) => {
-                    eprintln!("Warning: Gagal membuat dummy client untuk Google Drive: {}", e);
-                    // Buat client dengan credentials kosong tanpa parsing JSON
-                    DriveClient {
-                        client: reqwest::Client::new(),
-                        credentials: GoogleCredentials {
-                            r#type: "".to_string(),
-                            project_id: "".to_string(),
-                            private_key_id: "".to_string(),
-                            private_key: "".to_string(),
-                            client_email: "".to_string(),
-                            client_id: "".to_string(),
-                            auth_uri: "".to_string(),
-                            token_uri: "".to_string(),
-                            auth_provider_x509_cert_url: "".to_string(),
-                            client_x509_cert_url: "".to_string(),
-                        },
-                        access_token: None,
-                        token_expiry: None,
+    let drive_client = if drive_config.backend == StorageBackend::Local {
+        if config.storage.backend.is_none() {
+            println!("[WARN] GOOGLE_CREDENTIALS_JSON belum di-set, upload disimpan lokal di {} (set STORAGE_BACKEND untuk memilih backend)", upload_dir.display());
+        } else {
+            println!("[INFO] STORAGE_BACKEND=local, file disimpan di {}", upload_dir.display());
+        }
+        empty_drive_client()
+    } else {
+        match create_drive_client(&drive_config).await {
+            Ok(mut client) => {
+                // Pastikan folder untuk upload sudah ada
+                match ensure_folder_exists(&mut client, &drive_config.folder_id).await {
+                    Ok(id) => {
+                        println!("Berhasil terhubung ke Google Drive. Folder ID: {}", id);
+                        // Update folder_id jika berbeda
+                        if drive_config.folder_id != id {
+                            println!("Folder ID diperbarui: {}", id);
+                        }
+                    },
+                    Err(e) => {
+                        eprintln!("Warning: Gagal memastikan folder ada: {}", e);
+                        eprintln!("Pastikan kredensial Google Drive sudah benar.");
                     }
-                }
+                };
+                client
+            },
+            Err(e) => {
+                eprintln!("Warning: Gagal membuat Google Drive client: {}", e);
+                eprintln!("Pastikan kredensial Google Drive sudah benar.");
+                empty_drive_client()
             }
         }
     };
     
     let drive_client = Arc::new(Mutex::new(drive_client));
     
-    let cors_urls = frontend_urls.clone();
     let upload_dir_for_server = upload_dir.clone();
+    // Satu cache untuk semua worker, supaya invalidasi berlaku di semua worker
+    let permission_cache = Data::new(PermissionCache::new(Duration::from_secs(config.permissions.cache_ttl_secs)));
+    let mailer = Data::new(Mailer::new(&config.mail));
+    if !mailer.is_configured() {
+        println!("[INFO] SMTP_URL belum di-set, email (mis. reset password) tidak dikirim");
+    }
+    let oidc_client = Data::new(OidcClient::new(&config));
+    if oidc_client.is_configured() {
+        println!("[INFO] Login SSO (OIDC) aktif, callback: {}", config.oidc_redirect_url());
+    }
+    let ldap = Data::new(LdapAuthenticator::new(&config));
+    if ldap.is_configured() {
+        println!("[INFO] Login LDAP aktif: {}", config.ldap.url.as_deref().unwrap_or_default());
+    }
+    let config = Data::new(config);
 
     HttpServer::new(move || {
         let cors_urls = cors_urls.clone();
@@ -128,9 +127,22 @@ async fn main() -> std::io::Result<()> {
 
         App::new()
             .app_data(Data::new(db_pool.clone()))
+            .app_data(config.clone())
             .app_data(Data::new(drive_config.clone()))
             .app_data(Data::new(drive_client.clone()))
-            .wrap(Logger::default())
+            .app_data(permission_cache.clone())
+            .app_data(mailer.clone())
+            .app_data(oidc_client.clone())
+            .app_data(ldap.clone())
+            // Body JSON, path, dan query yang tidak valid dikirim dengan format ApiError
+            .app_data(web::JsonConfig::default().error_handler(|e, _| extractor_error(e)))
+            .app_data(web::PathConfig::default().error_handler(|e, _| extractor_error(e)))
+            .app_data(web::QueryConfig::default().error_handler(|e, _| extractor_error(e)))
+            // Request yang ditolak 403 dicatat di log keamanan (security_events)
+            .wrap(from_fn(log_permission_denials))
+            // Didaftarkan sebelum Logger supaya header X-Request-Id ikut tercatat di log
+            .wrap(from_fn(request_id))
+            .wrap(Logger::new(r#"%a "%r" %s %b "%{Referer}i" "%{User-Agent}i" %T %{x-request-id}o"#))
             // Konfigurasi CORS
             .wrap(
                 Cors::default()
@@ -150,41 +162,15 @@ async fn main() -> std::io::Result<()> {
                     .prefer_utf8(true)
                     .disable_content_disposition()
             )
-            
-            .service(check_user)
-            .service(login)
-            .service(logout)
-            .service(me)
-            .service(
-                actix_web::web::scope("/api/users")
-                                        .configure(user_config)
-            )
-            .service(
-                actix_web::web::scope("/api/items")
-                    .configure(items_config)
-            )
-            .service(
-                actix_web::web::scope("/api/lookup")
-                    .configure(routes::lookup::lookup_config)
-            )
-            .service(
-                actix_web::web::scope("/api/me")
-                                        .service(me)
-            )
-            .service(
-                actix_web::web::scope("/api/upload")
-                    .configure(upload_config)
-            )
-            .service(
-                actix_web::web::scope("/api/permissions")
-                    .configure(permissions_config)
-            )
-            .service(
-                actix_web::web::scope("/api/borrowings")
-                    .configure(borrowings_config)
-            )
+            .configure(openapi_config)
+            .configure(routes::configure)
     })
     .bind(("0.0.0.0", port))?    
     .run()
     .await
 }
+
+fn exit_with_error(error: impl std::fmt::Display) -> ! {
+    eprintln!("{}", error);
+    std::process::exit(1);
+}
@@ -1,82 +1,148 @@
 // Import beberapa tipe dan trait dari actix_web yang dibutuhkan untuk membuat extractor custom
-use actix_web::{dev::Payload, Error, FromRequest, HttpRequest};
-// Import future Ready untuk mengembalikan hasil secara langsung (synchronous)
-use futures::future::{ready, Ready};
-// Derive trait Debug, Deserialize (untuk parsing dari JWT), dan Clone untuk struct Claims
-#[derive(Debug, serde::Deserialize, Clone)]
+use actix_web::{dev::Payload, web, Error, FromRequest, HttpMessage, HttpRequest};
+// Config aplikasi untuk JWT secret
+use crate::config::Config;
+// Error API dengan kode yang konsisten
+use crate::error::ApiError;
+// Cek session di database (token yang session-nya sudah dicabut ditolak)
+use crate::services::sessions::{session_is_active, ClientInfo};
+// Token API pribadi (`inman_pat_...`) diterima selain JWT
+use crate::services::api_tokens::{authenticate_api_token, ApiTokenGrant, API_TOKEN_PREFIX};
+// Future yang di-box karena extractor perlu query database
+use futures::future::LocalBoxFuture;
+use sqlx::PgPool;
+// Derive trait Debug, Serialize/Deserialize (untuk encode/decode JWT), dan Clone untuk struct Claims
+#[derive(Debug, serde::Serialize, serde::Deserialize, Clone)]
 // Struct Claims adalah representasi data yang ada di dalam JWT (JSON Web Token)
 pub struct Claims {
     pub sub: String, // "sub" biasanya adalah user id atau identifier unik lain
     #[allow(dead_code)] // Attribute ini agar Rust tidak warning kalau exp tidak dipakai
     pub exp: usize,    // "exp" adalah waktu kadaluarsa token (epoch timestamp)
     pub role: String,  // "role" biasanya untuk otorisasi (misal: admin, user, dll)
+    pub jti: String,   // "jti" berisi ID session di tabel sessions (atau ID token API)
+    // Terisi jika request memakai token API; tidak pernah ada di dalam JWT
+    #[serde(skip)]
+    pub api_token: Option<ApiTokenGrant>,
+}
+
+impl Claims {
+    /// ID user dari claim "sub"
+    pub fn user_id(&self) -> Result<uuid::Uuid, ApiError> {
+        uuid::Uuid::parse_str(&self.sub).map_err(|_| ApiError::BadRequest("Invalid user ID".to_string()))
+    }
+
+    /// ID session dari claim "jti"
+    pub fn session_id(&self) -> Result<uuid::Uuid, ApiError> {
+        uuid::Uuid::parse_str(&self.jti).map_err(|_| ApiError::Unauthorized("Invalid session".to_string()))
+    }
+
+    /// Tolak request yang memakai token API, untuk endpoint yang hanya boleh lewat login
+    /// biasa (mis. membuat token API baru)
+    pub fn require_session(&self) -> Result<(), ApiError> {
+        match self.api_token {
+            Some(_) => Err(ApiError::Forbidden("Endpoint ini tidak bisa dipakai dengan token API".to_string())),
+            None => Ok(()),
+        }
+    }
 }
 
 // Import crate jsonwebtoken untuk proses decode JWT
-use jsonwebtoken::{decode, DecodingKey, Validation, Algorithm};
+use jsonwebtoken::{decode, errors::ErrorKind, DecodingKey, Validation, Algorithm};
+
+/// Ambil token dari header Authorization (Bearer <token>) atau dari cookie bernama 'token'
+pub fn bearer_or_cookie_token(req: &HttpRequest) -> Option<String> {
+    req
+        .headers() // ambil semua header
+        .get("Authorization") // cari header Authorization
+        .and_then(|h| h.to_str().ok()) // konversi ke string jika ada
+        .and_then(|auth| {
+            // Jika header mulai dengan "Bearer ", ambil tokennya saja
+            // ambil substring setelah "Bearer ", jika tidak ada return None
+            auth.strip_prefix("Bearer ").map(|token| token.to_string())
+        })
+        .or_else(|| {
+            // Jika tidak ada di header, coba ambil dari cookie bernama "token"
+            req.cookie("token").map(|c| c.value().to_string())
+        })
+}
 
 // Implementasi trait FromRequest agar Claims bisa langsung diekstrak dari request secara otomatis
 impl FromRequest for Claims {
-    // DEBUG: log setiap kali extractor ini dipanggil
-    // (log ada di dalam from_request di bawah)
-
     // Tipe error yang akan dikembalikan jika gagal
     type Error = Error;
-    // Tipe future yang dikembalikan (langsung jadi, tidak async/await)
-    type Future = Ready<Result<Self, Self::Error>>;
+    // Tipe future yang dikembalikan (async karena session dicek ke database)
+    type Future = LocalBoxFuture<'static, Result<Self, Self::Error>>;
 
     // Fungsi utama extractor: mengambil data JWT dari request
     fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
-        // Print ke konsol path yang sedang diakses (untuk debug)
-        println!("[JWT Extractor] called for path: {}", req.path());
-        // Ambil token dari header Authorization (Bearer <token>) atau dari cookie bernama 'token'
-        let token_opt = req
-            .headers() // ambil semua header
-            .get("Authorization") // cari header Authorization
-            .and_then(|h| h.to_str().ok()) // konversi ke string jika ada
-            .and_then(|auth| {
-                // Jika header mulai dengan "Bearer ", ambil tokennya saja
-                if auth.starts_with("Bearer ") {
-                    Some(auth[7..].to_string()) // ambil substring setelah "Bearer "
-                } else {
-                    None // jika tidak, return None
+        let req = req.clone();
+        Box::pin(async move {
+            // Claims yang sudah dicek di request ini (mis. oleh RequirePermission) dipakai ulang
+            if let Some(claims) = req.extensions().get::<Claims>() {
+                return Ok(claims.clone());
+            }
+            // Jika token tidak ditemukan di header maupun cookie, return error unauthorized (401)
+            let token = bearer_or_cookie_token(&req).ok_or_else(|| ApiError::Unauthorized("No JWT token".to_string()))?;
+            let pool = req
+                .app_data::<web::Data<PgPool>>()
+                .ok_or_else(|| ApiError::internal("Database pool tidak tersedia"))?;
+
+            let claims = if token.starts_with(API_TOKEN_PREFIX) {
+                api_token_claims(pool.get_ref(), &req, &token).await?
+            } else {
+                let claims = decode_claims(&req, &token)?;
+                // Token hanya berlaku selama session-nya belum dicabut (logout, ganti password, dll.)
+                let active = session_is_active(pool.get_ref(), claims.session_id()?, claims.user_id()?)
+                    .await
+                    .map_err(ApiError::from)?;
+                if !active {
+                    return Err(ApiError::Unauthorized("Session has been revoked".to_string()).into());
                 }
-            })
-            .or_else(|| {
-                // Jika tidak ada di header, coba ambil dari cookie bernama "token"
-                req.cookie("token").map(|c| c.value().to_string())
-            });
+                claims
+            };
 
-        // Jika token ditemukan
-        if let Some(token) = token_opt {
-            // Print token ke konsol untuk debug
-            println!("[JWT Extractor] Token found: {}", &token);
-            // Ambil secret dari environment variable JWT_SECRET (harus di-set di .env atau environment)
-            let secret = std::env::var("JWT_SECRET").expect("JWT_SECRET harus di-set");
-            // Buat decoding key dari secret
-            let decoding_key = DecodingKey::from_secret(secret.as_bytes());
-            // Set algoritma validasi JWT ke HS256
-            let validation = Validation::new(Algorithm::HS256);
-            // Decode token menjadi struct Claims
-            match decode::<Claims>(&token, &decoding_key, &validation) {
-                Ok(data) => {
-                    // Jika sukses, print data ke konsol
-                    println!("[JWT Extractor] JWT decode success: sub={}, role={}", data.claims.sub, data.claims.role);
-                    // Return data.claims sebagai hasil extractor
-                    ready(Ok(data.claims))
-                },
-                Err(e) => {
-                    // Jika gagal decode (token invalid/expired), print error ke konsol
-                    println!("[JWT Extractor] JWT decode error: {:?}", e);
-                    // Return error unauthorized (401) dengan pesan JSON
-                    ready(Err(actix_web::error::ErrorUnauthorized(serde_json::json!({"message": "Invalid JWT"}))))
-                },
-            }
-        } else {
-            // Jika token tidak ditemukan di header maupun cookie
-            println!("[JWT Extractor] No JWT token found in header or cookie");
-            // Return error unauthorized (401) dengan pesan JSON
-            ready(Err(actix_web::error::ErrorUnauthorized(serde_json::json!({"message": "No JWT token"}))))
-        }
+            req.extensions_mut().insert(claims.clone());
+            Ok(claims)
+        })
+    }
+}
+
+/// Claims untuk request yang memakai token API; permission-nya dibatasi `scopes` token
+async fn api_token_claims(pool: &PgPool, req: &HttpRequest, token: &str) -> Result<Claims, ApiError> {
+    let ip_address = ClientInfo::from_request(req).ip_address;
+    let user = authenticate_api_token(pool, token, ip_address.as_deref())
+        .await?
+        .ok_or_else(|| ApiError::Unauthorized("Invalid or revoked API token".to_string()))?;
+    Ok(Claims {
+        sub: user.user_id.to_string(),
+        exp: user.expires_at.map_or(usize::MAX, |expires_at| expires_at.timestamp() as usize),
+        role: user.role_id.to_string(),
+        jti: user.grant.token_id.to_string(),
+        api_token: Some(user.grant),
+    })
+}
+
+/// Decode dan validasi JWT tanpa mengecek session
+fn decode_claims(req: &HttpRequest, token: &str) -> Result<Claims, ApiError> {
+    // Ambil secret dari Config yang dibaca saat startup
+    let config = req
+        .app_data::<web::Data<Config>>()
+        .ok_or_else(|| ApiError::internal("Config tidak tersedia"))?;
+    // Buat decoding key dari secret
+    let decoding_key = DecodingKey::from_secret(config.jwt.secret.as_bytes());
+    // Set algoritma validasi JWT ke HS256
+    let validation = Validation::new(Algorithm::HS256);
+    // Decode token menjadi struct Claims
+    match decode::<Claims>(token, &decoding_key, &validation) {
+        Ok(data) => Ok(data.claims),
+        // Token kedaluwarsa punya kode sendiri supaya frontend tahu harus memanggil /api/refresh
+        Err(e) if matches!(e.kind(), ErrorKind::ExpiredSignature) => Err(ApiError::Detailed {
+            status: actix_web::http::StatusCode::UNAUTHORIZED,
+            code: "token_expired",
+            message: "Access token expired".to_string(),
+            details: serde_json::Value::Null,
+        }),
+        // Jika gagal decode (token invalid, atau token lama tanpa jti), return error unauthorized (401)
+        Err(_) => Err(ApiError::Unauthorized("Invalid JWT".to_string())),
     }
 }
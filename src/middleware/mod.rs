@@ -1,3 +1,4 @@
 pub mod jwt_extractor;
-pub mod admin_guard;
 pub mod permission_guard;
+pub mod request_id;
+pub mod security_log;
@@ -1,64 +1,248 @@
+use crate::error::ApiError;
 use crate::middleware::jwt_extractor::Claims;
+use actix_web::body::EitherBody;
+use actix_web::dev::{forward_ready, Payload, Service, ServiceRequest, ServiceResponse, Transform};
+use actix_web::{web, Error, FromRequest, HttpMessage, HttpRequest};
+use futures::future::{ready, LocalBoxFuture, Ready};
 use sqlx::PgPool;
-use sqlx::Row;
+use std::collections::{HashMap, HashSet};
+use std::rc::Rc;
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
 use uuid::Uuid;
 
-/// Check if a user has a specific permission
-pub async fn has_permission(claims: &Claims, pool: &PgPool, permission_name: &str) -> bool {
-    // Parse user ID from claims
-    let user_id = match Uuid::parse_str(&claims.sub) {
-        Ok(id) => id,
-        Err(_) => {
-            return false;
+/// Nama permission milik satu role
+type RolePermissions = Arc<HashSet<String>>;
+
+/// Cache role -> nama permission di memori, dibagikan ke semua worker lewat
+/// `web::Data<PermissionCache>`. Entry kedaluwarsa setelah `ttl`, dan dibuang lebih
+/// awal saat permission role diubah lewat API. Perubahan dari luar proses (mis.
+/// `inman-admin grant`) baru terlihat setelah TTL habis.
+pub struct PermissionCache {
+    ttl: Duration,
+    roles: RwLock<HashMap<Uuid, (Instant, RolePermissions)>>,
+}
+
+impl PermissionCache {
+    /// `ttl` nol berarti tanpa cache: permission selalu dibaca dari database
+    pub fn new(ttl: Duration) -> Self {
+        PermissionCache { ttl, roles: RwLock::new(HashMap::new()) }
+    }
+
+    /// Nama permission milik role, dari cache jika masih berlaku
+    pub async fn role_permissions(&self, pool: &PgPool, role_id: Uuid) -> Result<RolePermissions, sqlx::Error> {
+        if let Some((loaded_at, permissions)) = self.roles.read().unwrap().get(&role_id) {
+            if loaded_at.elapsed() < self.ttl {
+                return Ok(permissions.clone());
+            }
         }
-    };
 
-    // Get user's role ID
-    let user_row = sqlx::query("SELECT role_id FROM users WHERE id = $1")
-        .bind(user_id)
-        .fetch_optional(pool)
-        .await;
-    
-    let user_role_id = match user_row {
-        Ok(Some(row)) => row.get::<Uuid, _>("role_id"),
-        _ => {
-            return false;
+        let permissions = Arc::new(load_role_permissions(pool, role_id).await?);
+        if !self.ttl.is_zero() {
+            self.roles.write().unwrap().insert(role_id, (Instant::now(), permissions.clone()));
         }
-    };
+        Ok(permissions)
+    }
 
-    // Check if the role has the required permission
-    let permission_check = sqlx::query(
-        "SELECT 1 FROM role_permissions rp 
-         JOIN permissions p ON rp.permission_id = p.id 
-         WHERE rp.role_id = $1 AND p.name = $2"
-    )
-    .bind(user_role_id)
-    .bind(permission_name)
-    .fetch_optional(pool)
-    .await;
+    /// Buang cache satu role, dipanggil setelah permission role diubah
+    pub fn invalidate_role(&self, role_id: Uuid) {
+        self.roles.write().unwrap().remove(&role_id);
+    }
 
-    match permission_check {
-        Ok(Some(_)) => true,
-        _ => false,
+    /// Buang seluruh cache, mis. setelah permission diganti nama atau dihapus
+    pub fn invalidate_all(&self) {
+        self.roles.write().unwrap().clear();
     }
 }
 
-/// Check if a user has any of the specified permissions
-pub async fn has_any_permission(claims: &Claims, pool: &PgPool, permission_names: &[&str]) -> bool {
-    for permission_name in permission_names {
-        if has_permission(claims, pool, permission_name).await {
-            return true;
+async fn load_role_permissions(pool: &PgPool, role_id: Uuid) -> Result<HashSet<String>, sqlx::Error> {
+    let names: Vec<String> = sqlx::query_scalar(
+        "SELECT p.name FROM role_permissions rp
+         JOIN permissions p ON rp.permission_id = p.id
+         WHERE rp.role_id = $1"
+    )
+    .bind(role_id)
+    .fetch_all(pool)
+    .await?;
+    Ok(names.into_iter().collect())
+}
+
+/// Permission milik user yang login, di-resolve sekali per request lalu disimpan di
+/// extensions request. Dipakai sebagai extractor di handler (`perms: UserPermissions`)
+/// dan oleh `RequirePermission`, jadi pengecekan berikutnya tidak query ulang.
+#[derive(Clone, Debug)]
+pub struct UserPermissions {
+    pub user_id: Uuid,
+    pub role_name: Option<String>,
+    permissions: RolePermissions,
+}
+
+impl UserPermissions {
+    /// Check if a user has a specific permission
+    pub fn has(&self, permission_name: &str) -> bool {
+        self.permissions.contains(permission_name)
+    }
+
+    /// Check if a user has any of the specified permissions
+    pub fn has_any(&self, permission_names: &[&str]) -> bool {
+        permission_names.iter().any(|name| self.has(name))
+    }
+
+    /// Check if a user has all of the specified permissions
+    #[allow(dead_code)]
+    pub fn has_all(&self, permission_names: &[&str]) -> bool {
+        permission_names.iter().all(|name| self.has(name))
+    }
+
+    /// Semua nama permission efektif, terurut
+    pub fn names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.permissions.iter().cloned().collect();
+        names.sort();
+        names
+    }
+
+    /// Admin: punya `admin_access`, atau (untuk kompatibilitas) role bernama `admin`
+    pub fn is_admin(&self) -> bool {
+        self.has("admin_access") || self.role_name.as_deref() == Some("admin")
+    }
+
+    async fn resolve(req: &HttpRequest) -> Result<UserPermissions, Error> {
+        let claims = Claims::from_request(req, &mut Payload::None).await?;
+        let user_id = claims.user_id()?;
+        let pool = req
+            .app_data::<web::Data<PgPool>>()
+            .ok_or_else(|| ApiError::internal("Database pool tidak tersedia"))?;
+
+        let role: Option<(Uuid, Option<String>)> = sqlx::query_as(
+            "SELECT u.role_id, r.name FROM users u LEFT JOIN user_roles r ON r.id = u.role_id WHERE u.id = $1"
+        )
+        .bind(user_id)
+        .fetch_optional(pool.get_ref())
+        .await
+        .map_err(ApiError::from)?;
+
+        // User yang sudah dihapus tetap punya JWT valid sampai kedaluwarsa; tanpa permission
+        let Some((role_id, role_name)) = role else {
+            return Ok(UserPermissions { user_id, role_name: None, permissions: Arc::default() });
+        };
+        let permissions = match req.app_data::<web::Data<PermissionCache>>() {
+            Some(cache) => cache.role_permissions(pool.get_ref(), role_id).await,
+            None => load_role_permissions(pool.get_ref(), role_id).await.map(Arc::new),
         }
+        .map_err(ApiError::from)?;
+
+        // Token API hanya memakai permission role yang ada di scopes-nya, dan tidak ikut
+        // dianggap admin hanya karena nama role-nya `admin`
+        if let Some(grant) = &claims.api_token {
+            let scoped = grant.scopes.iter().filter(|name| permissions.contains(*name)).cloned().collect();
+            return Ok(UserPermissions { user_id, role_name: None, permissions: Arc::new(scoped) });
+        }
+        Ok(UserPermissions { user_id, role_name, permissions })
     }
-    false
 }
 
-/// Check if a user has all of the specified permissions
-pub async fn has_all_permissions(claims: &Claims, pool: &PgPool, permission_names: &[&str]) -> bool {
-    for permission_name in permission_names {
-        if !has_permission(claims, pool, permission_name).await {
-            return false;
-        }
+#[doc(hidden)]
+impl UserPermissions {
+    /// Permission tetap untuk test handler tanpa database
+    pub fn for_test(user_id: Uuid, permissions: &[&str]) -> Self {
+        let permissions = permissions.iter().map(|name| name.to_string()).collect();
+        UserPermissions { user_id, role_name: None, permissions: Arc::new(permissions) }
+    }
+}
+
+impl FromRequest for UserPermissions {
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        let req = req.clone();
+        Box::pin(async move {
+            if let Some(permissions) = req.extensions().get::<UserPermissions>() {
+                return Ok(permissions.clone());
+            }
+            let permissions = UserPermissions::resolve(&req).await?;
+            req.extensions_mut().insert(permissions.clone());
+            Ok(permissions)
+        })
+    }
+}
+
+/// Guard deklaratif untuk route: request ditolak (401 tanpa JWT, 403 tanpa izin)
+/// sebelum handler dipanggil. Dipasang lewat argumen `wrap` pada macro route:
+///
+/// ```ignore
+/// #[patch("/{id}", wrap = "RequirePermission::new(\"edit_items\")")]
+/// ```
+#[derive(Clone)]
+pub struct RequirePermission {
+    permissions: Rc<[&'static str]>,
+}
+
+impl RequirePermission {
+    /// User harus punya izin ini
+    pub fn new(permission: &'static str) -> Self {
+        Self::any(&[permission])
+    }
+
+    /// User cukup punya salah satu dari izin ini
+    pub fn any(permissions: &[&'static str]) -> Self {
+        RequirePermission { permissions: permissions.into() }
+    }
+}
+
+impl<S, B> Transform<S, ServiceRequest> for RequirePermission
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Transform = RequirePermissionMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(RequirePermissionMiddleware {
+            service: Rc::new(service),
+            permissions: self.permissions.clone(),
+        }))
+    }
+}
+
+pub struct RequirePermissionMiddleware<S> {
+    service: Rc<S>,
+    permissions: Rc<[&'static str]>,
+}
+
+impl<S, B> Service<ServiceRequest> for RequirePermissionMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let service = self.service.clone();
+        let permissions = self.permissions.clone();
+        Box::pin(async move {
+            // Error langsung dijadikan response di sini (bukan dikembalikan sebagai Err)
+            // supaya body error masih membawa request ID
+            if let Err(err) = authorize(&req, &permissions).await {
+                return Ok(req.error_response(err).map_into_right_body());
+            }
+            service.call(req).await.map(ServiceResponse::map_into_left_body)
+        })
+    }
+}
+
+async fn authorize(req: &ServiceRequest, permissions: &[&str]) -> Result<(), Error> {
+    let user_permissions = UserPermissions::from_request(req.request(), &mut Payload::None).await?;
+    if !user_permissions.has_any(permissions) {
+        return Err(ApiError::Forbidden(format!("Requires permission: {}", permissions.join(" or "))).into());
     }
-    true
+    Ok(())
 }
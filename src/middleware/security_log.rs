@@ -0,0 +1,44 @@
+use actix_web::body::MessageBody;
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::http::StatusCode;
+use actix_web::middleware::Next;
+use actix_web::{web, Error, HttpMessage};
+use sqlx::PgPool;
+
+use crate::middleware::permission_guard::UserPermissions;
+use crate::services::security_log::SecurityEvent;
+use crate::services::sessions::ClientInfo;
+
+/// Catat response 403 untuk user yang sudah login sebagai `permission.denied`, baik
+/// dari `RequirePermission` maupun dari cek izin di handler. User dikenali dari
+/// `UserPermissions` yang sudah di-resolve selama request; 403 lain (mis. login akun
+/// nonaktif) tidak ikut dicatat.
+pub async fn log_permission_denials(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, Error> {
+    let res = next.call(req).await?;
+    if res.status() != StatusCode::FORBIDDEN {
+        return Ok(res);
+    }
+    let http_req = res.request();
+    let user_id = http_req.extensions().get::<UserPermissions>().map(|perms| perms.user_id);
+    if let (Some(user_id), Some(pool)) = (user_id, http_req.app_data::<web::Data<PgPool>>()) {
+        let client = ClientInfo::from_request(http_req);
+        let result = SecurityEvent::new("permission.denied")
+            .user(user_id)
+            .details(serde_json::json!({
+                "method": http_req.method().as_str(),
+                "path": http_req.path(),
+                "reason": res.response().error().map(|e| e.to_string()),
+            }))
+            .client(&client)
+            .record(pool.get_ref())
+            .await;
+        // Response 403 tetap dikirim walaupun pencatatan gagal
+        if let Err(e) = result {
+            eprintln!("[WARN] Gagal mencatat permission.denied untuk user {}: {}", user_id, e);
+        }
+    }
+    Ok(res)
+}
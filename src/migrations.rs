@@ -0,0 +1,153 @@
+use sqlx::migrate::{Migrate, MigrateError, Migrator};
+use sqlx::PgPool;
+use std::collections::HashMap;
+
+/// Semua migrasi di folder `migrations/`, di-embed ke dalam binary saat build
+pub static MIGRATOR: Migrator = sqlx::migrate!("./migrations");
+
+/// Migrasi awal yang dulu dijalankan manual. Isinya mengatur ulang izin bawaan role,
+/// jadi tidak boleh dijalankan lagi di database yang sudah berisi data.
+const BASELINE_VERSION: i64 = 20230424000000;
+
+pub async fn run(pool: &PgPool) -> Result<(), MigrateError> {
+    baseline_existing_schema(pool).await?;
+    MIGRATOR.run(pool).await
+}
+
+/// Database lama yang disiapkan manual belum punya riwayat di `_sqlx_migrations`.
+/// Tanpa ini `20230424000000_consolidated.sql` akan dijalankan ulang dan menghapus
+/// izin role yang sudah diubah lewat `/api/permissions`. Migrasi tersebut dicatat
+/// sebagai sudah diterapkan; migrasi setelahnya idempotent dan tetap dijalankan.
+async fn baseline_existing_schema(pool: &PgPool) -> Result<(), MigrateError> {
+    let mut conn = pool.acquire().await?;
+    let has_schema: bool = sqlx::query_scalar("SELECT to_regclass('role_permissions') IS NOT NULL")
+        .fetch_one(&mut *conn)
+        .await?;
+    if !has_schema {
+        return Ok(());
+    }
+    conn.ensure_migrations_table().await?;
+    if !conn.list_applied_migrations().await?.is_empty() {
+        return Ok(());
+    }
+
+    let baseline = MIGRATOR
+        .iter()
+        .find(|m| m.version == BASELINE_VERSION && !m.migration_type.is_down_migration())
+        .ok_or(MigrateError::VersionMissing(BASELINE_VERSION))?;
+    sqlx::query(
+        "INSERT INTO _sqlx_migrations (version, description, success, checksum, execution_time)
+         VALUES ($1, $2, TRUE, $3, 0)",
+    )
+    .bind(baseline.version)
+    .bind(&*baseline.description)
+    .bind(&*baseline.checksum)
+    .execute(&mut *conn)
+    .await?;
+    println!(
+        "[INFO] Database sudah berisi tabel tapi belum punya riwayat migrasi; {} ditandai sudah diterapkan",
+        baseline.version
+    );
+    Ok(())
+}
+
+/// Menampilkan status setiap migrasi: applied, pending, atau checksum berbeda
+pub async fn print_status(pool: &PgPool) -> Result<(), MigrateError> {
+    let mut conn = pool.acquire().await?;
+    conn.ensure_migrations_table().await?;
+    let applied: HashMap<i64, Vec<u8>> = conn
+        .list_applied_migrations()
+        .await?
+        .into_iter()
+        .map(|m| (m.version, m.checksum.into_owned()))
+        .collect();
+
+    for migration in MIGRATOR.iter().filter(|m| !m.migration_type.is_down_migration()) {
+        let reversible = MIGRATOR
+            .iter()
+            .any(|m| m.version == migration.version && m.migration_type.is_down_migration());
+        let status = match applied.get(&migration.version) {
+            Some(checksum) if *checksum == *migration.checksum => "applied",
+            Some(_) => "applied (checksum mismatch)",
+            None => "pending",
+        };
+        println!(
+            "{:<16} {:<28} {:<11} {}",
+            migration.version,
+            status,
+            if reversible { "reversible" } else { "" },
+            migration.description
+        );
+    }
+
+    // Versi yang tercatat di database tapi filenya sudah tidak ada
+    for version in applied.keys() {
+        if !MIGRATOR.iter().any(|m| m.version == *version) {
+            println!("{:<16} {:<28} {:<11} (missing from migrations/)", version, "applied", "");
+        }
+    }
+    Ok(())
+}
+
+/// Mengembalikan migrasi. Tanpa target, hanya migrasi terakhir yang di-revert.
+/// Dengan target, semua migrasi dengan versi lebih besar dari target di-revert.
+pub async fn revert(pool: &PgPool, target: Option<i64>) -> Result<(), Box<dyn std::error::Error>> {
+    let target = match target {
+        Some(target) => target,
+        None => {
+            let mut conn = pool.acquire().await?;
+            conn.ensure_migrations_table().await?;
+            let mut versions: Vec<i64> = conn
+                .list_applied_migrations()
+                .await?
+                .into_iter()
+                .map(|m| m.version)
+                .collect();
+            versions.sort_unstable();
+            let latest = match versions.pop() {
+                Some(version) => version,
+                None => {
+                    println!("No applied migrations to revert");
+                    return Ok(());
+                }
+            };
+            let reversible = MIGRATOR
+                .iter()
+                .any(|m| m.version == latest && m.migration_type.is_down_migration());
+            if !reversible {
+                return Err(format!("Migration {} has no down script and cannot be reverted", latest).into());
+            }
+            versions.pop().unwrap_or(0)
+        }
+    };
+    Ok(MIGRATOR.undo(pool, target).await?)
+}
+
+/// Subcommand `migrate status|run|revert [--target VERSION]`
+pub async fn cli(pool: &PgPool, args: &[String]) -> Result<(), String> {
+    match args.first().map(String::as_str) {
+        Some("status") | None => print_status(pool).await.map_err(|e| e.to_string()),
+        Some("run") => {
+            run(pool).await.map_err(|e| e.to_string())?;
+            println!("Migrations applied");
+            Ok(())
+        },
+        Some("revert") => {
+            let target = match args.get(1).map(String::as_str) {
+                Some("--target") => match args.get(2).and_then(|v| v.parse().ok()) {
+                    Some(target) => Some(target),
+                    None => return Err("--target requires a migration version".to_string()),
+                },
+                Some(other) => return Err(format!("Unknown argument: {}", other)),
+                None => None,
+            };
+            revert(pool, target).await.map_err(|e| e.to_string())?;
+            println!("Migrations reverted");
+            Ok(())
+        },
+        Some(other) => Err(format!(
+            "Unknown migrate command: {}\nUsage: rustrest migrate [status|run|revert [--target VERSION]]",
+            other
+        )),
+    }
+}
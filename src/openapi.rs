@@ -0,0 +1,70 @@
+//! Spesifikasi OpenAPI yang dibangkitkan dari anotasi `#[utoipa::path]` di setiap
+//! handler dan `ToSchema` pada DTO. Disajikan di `/api/openapi.json` dengan
+//! Swagger UI di `/api/docs/`.
+
+use actix_web::web;
+use utoipa::openapi::security::{ApiKey, ApiKeyValue, HttpAuthScheme, HttpBuilder, SecurityScheme};
+use utoipa::{Modify, OpenApi};
+use utoipa_swagger_ui::SwaggerUi;
+
+use crate::error::ErrorBody;
+use crate::routes;
+
+#[derive(OpenApi)]
+#[openapi(
+    info(title = "Inman API", description = "API inventaris dan peminjaman barang"),
+    nest(
+        (path = "/api/auth/oidc", api = routes::oidc::OidcApi),
+        (path = "/api/sessions", api = routes::sessions::SessionsApi),
+        (path = "/api/2fa", api = routes::two_factor::TwoFactorApi),
+        (path = "/api/tokens", api = routes::api_tokens::ApiTokensApi),
+        (path = "/api/users", api = routes::user::UserApi),
+        (path = "/api/audit-events", api = routes::audit::AuditApi),
+        (path = "/api/security-events", api = routes::security_events::SecurityEventsApi),
+        (path = "/api/items", api = routes::items::ItemsApi),
+        (path = "/api/lookup", api = routes::lookup::LookupApi),
+        (path = "/api/upload", api = routes::upload::UploadApi),
+        (path = "/api/permissions", api = routes::permissions::PermissionsApi),
+        (path = "/api/borrowings", api = routes::borrowings::BorrowingsApi),
+        (path = "/api/calendar", api = routes::calendar::CalendarApi),
+        (path = "/api/penalties", api = routes::penalties::PenaltiesApi),
+    ),
+    components(schemas(ErrorBody)),
+    modifiers(&RootRoutes, &SecuritySchemes),
+    security(("bearer_auth" = []), ("cookie_auth" = [])),
+)]
+pub struct ApiDoc;
+
+/// Endpoint tanpa scope (`/health`, `/api/login`, dll.). `nest` dengan path kosong
+/// menghasilkan `//`, jadi digabung langsung.
+struct RootRoutes;
+
+impl Modify for RootRoutes {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        openapi.merge(routes::health::HealthApi::openapi());
+        openapi.merge(routes::auth::AuthApi::openapi());
+        openapi.merge(routes::me::MeApi::openapi());
+    }
+}
+
+/// JWT bisa dikirim lewat header `Authorization: Bearer` atau cookie `token` dari `/api/login`;
+/// token API (`inman_pat_...`) lewat header `Authorization: Bearer`
+struct SecuritySchemes;
+
+impl Modify for SecuritySchemes {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        let components = openapi.components.get_or_insert_with(Default::default);
+        components.add_security_scheme(
+            "bearer_auth",
+            SecurityScheme::Http(HttpBuilder::new().scheme(HttpAuthScheme::Bearer).bearer_format("JWT").build()),
+        );
+        components.add_security_scheme(
+            "cookie_auth",
+            SecurityScheme::ApiKey(ApiKey::Cookie(ApiKeyValue::new("token"))),
+        );
+    }
+}
+
+pub fn openapi_config(cfg: &mut web::ServiceConfig) {
+    cfg.service(SwaggerUi::new("/api/docs/{_:.*}").url("/api/openapi.json", ApiDoc::openapi()));
+}
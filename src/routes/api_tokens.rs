@@ -0,0 +1,121 @@
+use actix_web::{delete, get, post, web, HttpRequest, HttpResponse};
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use sqlx::PgPool;
+use utoipa::{OpenApi, ToSchema};
+use uuid::Uuid;
+
+use crate::error::{ApiError, ErrorBody};
+use crate::middleware::jwt_extractor::Claims;
+use crate::middleware::permission_guard::UserPermissions;
+use crate::services::api_tokens::{self, list_api_tokens, revoke_api_token, ApiToken, CreatedApiToken};
+use crate::services::audit::AuditEvent;
+use crate::services::sessions::ClientInfo;
+
+#[derive(Deserialize, ToSchema)]
+pub struct NewApiToken {
+    /// Nama untuk mengenali token, mis. `script stok opname`
+    pub name: String,
+    /// Nama permission yang boleh dipakai token; harus dimiliki user
+    pub scopes: Vec<String>,
+    /// Kosong berarti token tidak kedaluwarsa
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+/// Daftar token API aktif milik user yang login
+#[utoipa::path(tag = "api-tokens", responses((status = 200, body = Vec<ApiToken>)))]
+#[get("")]
+pub async fn get_api_tokens(claims: Claims, pool: web::Data<PgPool>) -> Result<HttpResponse, ApiError> {
+    Ok(HttpResponse::Ok().json(list_api_tokens(pool.get_ref(), claims.user_id()?).await?))
+}
+
+/// Buat token API untuk script/integrasi. Token dikirim sebagai
+/// `Authorization: Bearer inman_pat_...` dan hanya ditampilkan sekali di response ini.
+/// Hanya bisa dipanggil dengan login biasa, bukan dengan token API.
+#[utoipa::path(
+    tag = "api-tokens",
+    request_body = NewApiToken,
+    responses(
+        (status = 200, body = CreatedApiToken),
+        (status = 400, description = "Nama kosong, scopes kosong, atau `expires_at` sudah lewat", body = ErrorBody),
+        (status = 403, description = "Scope yang tidak dimiliki user, atau request memakai token API", body = ErrorBody),
+    )
+)]
+#[post("")]
+pub async fn create_api_token(
+    req: HttpRequest,
+    claims: Claims,
+    perms: UserPermissions,
+    pool: web::Data<PgPool>,
+    form: web::Json<NewApiToken>,
+) -> Result<HttpResponse, ApiError> {
+    claims.require_session()?;
+    let name = form.name.trim();
+    if name.is_empty() {
+        return Err(ApiError::BadRequest("Nama token tidak boleh kosong".to_string()));
+    }
+    if form.scopes.is_empty() {
+        return Err(ApiError::BadRequest("Token butuh minimal satu scope".to_string()));
+    }
+    if form.expires_at.is_some_and(|expires_at| expires_at <= Utc::now()) {
+        return Err(ApiError::BadRequest("expires_at harus di masa depan".to_string()));
+    }
+    let missing: Vec<&str> = form.scopes.iter().filter(|scope| !perms.has(scope)).map(String::as_str).collect();
+    if !missing.is_empty() {
+        return Err(ApiError::Forbidden(format!("Kamu tidak punya permission: {}", missing.join(", "))));
+    }
+
+    let mut scopes = form.scopes.clone();
+    scopes.sort();
+    scopes.dedup();
+    let created = api_tokens::create_api_token(pool.get_ref(), perms.user_id, name, &scopes, form.expires_at).await?;
+    AuditEvent::new("api_token.created", "api_token", Some(created.api_token.id))
+        .actor(perms.user_id)
+        .after(serde_json::json!({
+            "name": created.api_token.name,
+            "scopes": created.api_token.scopes,
+            "expires_at": created.api_token.expires_at,
+        }))
+        .client(&ClientInfo::from_request(&req))
+        .record(pool.get_ref())
+        .await?;
+    Ok(HttpResponse::Ok().json(created))
+}
+
+/// Cabut token API milik user yang login. Bisa dipanggil dengan token itu sendiri.
+#[utoipa::path(
+    tag = "api-tokens",
+    responses(
+        (status = 200, body = serde_json::Value),
+        (status = 404, description = "Token tidak ditemukan atau sudah dicabut", body = ErrorBody),
+    )
+)]
+#[delete("/{id}")]
+pub async fn delete_api_token(
+    req: HttpRequest,
+    claims: Claims,
+    pool: web::Data<PgPool>,
+    path: web::Path<Uuid>,
+) -> Result<HttpResponse, ApiError> {
+    let id = path.into_inner();
+    let user_id = claims.user_id()?;
+    if !revoke_api_token(pool.get_ref(), user_id, id).await? {
+        return Err(ApiError::NotFound("API token not found".to_string()));
+    }
+    AuditEvent::new("api_token.revoked", "api_token", Some(id))
+        .actor(user_id)
+        .client(&ClientInfo::from_request(&req))
+        .record(pool.get_ref())
+        .await?;
+    Ok(HttpResponse::Ok().json(serde_json::json!({"success": true})))
+}
+
+pub fn api_tokens_config(cfg: &mut web::ServiceConfig) {
+    cfg.service(get_api_tokens)
+        .service(create_api_token)
+        .service(delete_api_token);
+}
+
+#[derive(OpenApi)]
+#[openapi(paths(get_api_tokens, create_api_token, delete_api_token))]
+pub struct ApiTokensApi;
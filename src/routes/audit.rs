@@ -0,0 +1,80 @@
+use actix_web::{get, web, HttpResponse};
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use sqlx::PgPool;
+use utoipa::{IntoParams, OpenApi};
+use uuid::Uuid;
+
+use crate::error::{ApiError, ErrorBody};
+use crate::middleware::permission_guard::UserPermissions;
+use crate::services::audit::{list_audit_events, AuditFilter, AuditLogEntry};
+
+/// Jumlah event per halaman jika `limit` tidak diisi
+const DEFAULT_LIMIT: i64 = 50;
+const MAX_LIMIT: i64 = 500;
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct AuditQuery {
+    /// User yang melakukan aksi
+    pub actor_id: Option<Uuid>,
+    /// Mis. `user`, `role`, `permission`, `category`
+    pub entity_type: Option<String>,
+    pub entity_id: Option<Uuid>,
+    /// Nama aksi persis (`role.permission_granted`), atau awalan jika diakhiri `.` (`user.`)
+    pub action: Option<String>,
+    /// Hanya event sejak waktu ini (RFC 3339)
+    pub from: Option<DateTime<Utc>>,
+    /// Hanya event sebelum waktu ini (RFC 3339)
+    pub to: Option<DateTime<Utc>>,
+    /// Default 50, maksimal 500
+    pub limit: Option<i64>,
+    pub offset: Option<i64>,
+}
+
+/// Jejak audit perubahan data (user, role, permission, lookup, token, dll.), terbaru
+/// lebih dulu. Hanya admin.
+#[utoipa::path(
+    tag = "audit",
+    params(AuditQuery),
+    responses(
+        (status = 200, body = Vec<AuditLogEntry>),
+        (status = 400, description = "`limit`/`offset` di luar batas", body = ErrorBody),
+        (status = 403, description = "Hanya admin", body = ErrorBody),
+    )
+)]
+#[get("")]
+pub async fn get_audit_events(
+    perms: UserPermissions,
+    pool: web::Data<PgPool>,
+    query: web::Query<AuditQuery>,
+) -> Result<HttpResponse, ApiError> {
+    if !perms.is_admin() {
+        return Err(ApiError::Forbidden("Hanya admin yang boleh akses".to_string()));
+    }
+    let query = query.into_inner();
+    let limit = query.limit.unwrap_or(DEFAULT_LIMIT);
+    let offset = query.offset.unwrap_or(0);
+    if !(1..=MAX_LIMIT).contains(&limit) || offset < 0 {
+        return Err(ApiError::BadRequest(format!("limit harus 1-{} dan offset tidak boleh negatif", MAX_LIMIT)));
+    }
+    let filter = AuditFilter {
+        actor_id: query.actor_id,
+        entity_type: query.entity_type,
+        entity_id: query.entity_id,
+        action: query.action,
+        from: query.from,
+        to: query.to,
+        limit,
+        offset,
+    };
+    Ok(HttpResponse::Ok().json(list_audit_events(pool.get_ref(), &filter).await?))
+}
+
+pub fn audit_config(cfg: &mut web::ServiceConfig) {
+    cfg.service(get_audit_events);
+}
+
+#[derive(OpenApi)]
+#[openapi(paths(get_audit_events))]
+pub struct AuditApi;
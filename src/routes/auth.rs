@@ -1,19 +1,31 @@
-use actix_web::{post, get, web, HttpResponse, Responder, cookie::{Cookie, SameSite}};
+use actix_web::{post, get, web, HttpRequest, HttpResponse, cookie::{Cookie, SameSite}};
 use serde::{Deserialize, Serialize};
-use jsonwebtoken::{encode, Header, EncodingKey};
-use chrono::{Utc, Duration};
 use sqlx::PgPool;
+use utoipa::{OpenApi, ToSchema};
+use std::sync::OnceLock;
 use uuid::Uuid;
 
+use crate::config::{Config, PasswordConfig};
+use crate::error::{ApiError, ErrorBody};
+use crate::middleware::jwt_extractor::Claims;
+use crate::routes::user::{find_user_id, set_password};
+use crate::services::audit::AuditEvent;
+use crate::services::invitations::accept_invitation;
+use crate::services::ldap::{LdapAuthenticator, LdapUser, LDAP_ISSUER};
+use crate::services::login_throttle::{check_login_allowed, record_login_failure, record_login_success, unlock_account};
+use crate::services::mailer::Mailer;
+use crate::services::password_reset::{consume_password_reset, create_password_reset, reset_recently_requested};
+use crate::services::passwords::{hash_password, needs_rehash, verify_password};
+use crate::services::security_log::{record_login_outcome, SecurityEvent};
+use crate::services::sessions::{ensure_user_active, refresh_session, revoke_session, revoke_session_by_refresh_token, start_session, ClientInfo, SessionTokens};
+use crate::services::two_factor::{
+    consume_login_challenge, create_login_challenge, enable_two_factor, ChallengeUser, find_login_challenge, start_enrollment,
+    two_factor_status, verify_second_factor, TwoFactorEnrollment, CHALLENGE_TTL_SECS,
+};
 
-// SECRET diambil dari env JWT_SECRET
-
-#[derive(Debug, Serialize, Deserialize)]
-pub struct Claims {
-    pub sub: String,
-    pub exp: usize,
-    pub role: String,
-}
+/// Cookie refresh token hanya dikirim ke endpoint di bawah `/api`
+const REFRESH_COOKIE: &str = "refresh_token";
+const REFRESH_COOKIE_PATH: &str = "/api";
 
 #[derive(Debug, sqlx::FromRow)]
 struct UserRow {
@@ -21,127 +33,655 @@ struct UserRow {
     name: String,
     password_hash: Option<String>,
     role_id: Uuid,
+    /// User login lewat LDAP, bukan password lokal
+    ldap_linked: bool,
 }
 
-#[derive(Deserialize)]
+/// `UserRow` berdasarkan nama (`$1`); `$2` adalah issuer LDAP
+const USER_ROW_BY_NAME: &str = "SELECT u.id, u.name, u.password_hash, u.role_id,
+        EXISTS (SELECT 1 FROM user_identities i WHERE i.user_id = u.id AND i.issuer = $2) AS ldap_linked
+    FROM users u
+    WHERE u.name = $1";
+
+#[derive(Deserialize, ToSchema)]
 pub struct LoginRequest {
     pub name: String,
     pub password: String,
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, ToSchema)]
 pub struct CheckUserRequest {
     pub name: String,
 }
 
+#[derive(Serialize, ToSchema)]
+pub struct CheckUserResponse {
+    pub name: String,
+    /// false berarti user belum membuat password dan harus memakai link undangan dari admin
+    pub password_exists: bool,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct RequestResetRequest {
+    /// Nama user atau email
+    pub name: String,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct ConfirmResetRequest {
+    /// Token dari link reset password
+    pub token: String,
+    pub password: String,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct AcceptInviteRequest {
+    /// Token dari link undangan
+    pub token: String,
+    pub password: String,
+}
+
+/// Cek apakah user sudah membuat password (dipakai halaman login).
+/// Nama yang tidak terdaftar dijawab sama seperti user yang sudah punya password,
+/// jadi endpoint ini tidak bisa dipakai untuk menebak username.
+#[utoipa::path(
+    tag = "auth",
+    security(()),
+    request_body = CheckUserRequest,
+    responses(
+        (status = 200, body = CheckUserResponse),
+        (status = 429, description = "Terlalu banyak percobaan login gagal", body = ErrorBody),
+    )
+)]
 #[post("/api/check-user")]
 pub async fn check_user(
+    req: HttpRequest,
     pool: web::Data<PgPool>,
     form: web::Json<CheckUserRequest>,
-) -> impl Responder {
-    let user = sqlx::query_as::<_, UserRow>(
-        "SELECT id, name, password_hash, role_id FROM users WHERE name = $1"
+) -> Result<HttpResponse, ApiError> {
+    check_login_allowed(pool.get_ref(), &form.name, ClientInfo::from_request(&req).ip_address.as_deref()).await?;
+
+    let user = sqlx::query_as::<_, UserRow>(USER_ROW_BY_NAME)
+        .bind(&form.name)
+        .bind(LDAP_ISSUER)
+        .fetch_optional(pool.get_ref())
+        .await?;
+
+    let password_exists = user.is_none_or(|user| user.ldap_linked || !user.password_hash.unwrap_or_default().is_empty());
+    Ok(HttpResponse::Ok().json(CheckUserResponse {
+        name: form.into_inner().name,
+        password_exists,
+    }))
+}
+
+/// Buat password pertama dengan token dari link undangan. Token hanya bisa dipakai sekali.
+#[utoipa::path(
+    tag = "auth",
+    security(()),
+    request_body = AcceptInviteRequest,
+    responses(
+        (status = 200, description = "Password dibuat, user bisa login", body = serde_json::Value),
+        (status = 400, description = "Token tidak valid/sudah dipakai/kedaluwarsa (`invitation_invalid`)", body = ErrorBody),
+        (status = 422, description = "Password tidak memenuhi kebijakan (`weak_password`); token masih bisa dipakai", body = ErrorBody),
     )
-    .bind(&form.name)
-    .fetch_optional(pool.get_ref())
-    .await
-    .unwrap();
-
-    if let Some(user) = user {
-        let password_exists = user.password_hash.as_ref().map(|h| !h.is_empty()).unwrap_or(false);
-        return HttpResponse::Ok().json(serde_json::json!({
-            "id": user.id,
-            "name": user.name,
-            "role_id": user.role_id,
-            "password_exists": password_exists
-        }));
-    } else {
-        HttpResponse::NotFound().body("User tidak ditemukan")
-    }
+)]
+#[post("/api/auth/accept-invite")]
+pub async fn accept_invite(
+    req: HttpRequest,
+    pool: web::Data<PgPool>,
+    config: web::Data<Config>,
+    form: web::Json<AcceptInviteRequest>,
+) -> Result<HttpResponse, ApiError> {
+    let mut tx = pool.begin().await?;
+    let user_id = accept_invitation(&mut *tx, &form.token).await?;
+    // Jika password ditolak, tx di-drop tanpa commit dan token tetap berlaku
+    set_password(&mut *tx, &config.password, user_id, &form.password).await?;
+    tx.commit().await?;
+    SecurityEvent::new("password.changed")
+        .user(user_id)
+        .details(serde_json::json!({ "via": "invitation" }))
+        .client(&ClientInfo::from_request(&req))
+        .record(pool.get_ref())
+        .await?;
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "redirect": true,
+        "message": "Password berhasil dibuat, silakan login!"
+    })))
 }
 
 
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
 pub struct LoginResponse {
+    /// Access token (JWT) berumur pendek
     pub token: String,
+    /// Refresh token untuk `/api/refresh`; hanya berlaku sekali pakai
+    pub refresh_token: String,
+    /// Masa berlaku access token dalam detik
+    pub expires_in: i64,
+    pub session_id: Uuid,
     pub user_id: Uuid, 
     pub username: String,
     pub role: String,
+    /// Hanya ada jika 2FA baru saja diaktifkan saat login; ditampilkan sekali
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub recovery_codes: Option<Vec<String>>,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct TwoFactorLoginRequest {
+    /// `challenge_token` dari response `two_factor_required`/`two_factor_setup_required`
+    pub challenge_token: String,
+    /// Kode 6 digit dari authenticator atau kode pemulihan
+    pub code: String,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct TwoFactorSetupRequest {
+    pub challenge_token: String,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct RefreshRequest {
+    pub refresh_token: String,
+}
+
+/// Cookie `token` dan `refresh_token` untuk session baru
+pub(crate) fn session_cookies(config: &Config, tokens: &SessionTokens) -> [Cookie<'static>; 2] {
+    let access_cookie = Cookie::build("token", tokens.access_token.clone())
+        .http_only(true)
+        .secure(true) // HARUS false untuk dev HTTP agar cookie terkirim
+        .same_site(SameSite::None)
+        .path("/")
+        .finish();
+    let refresh_cookie = Cookie::build(REFRESH_COOKIE, tokens.refresh_token.clone())
+        .http_only(true)
+        .secure(true)
+        .same_site(SameSite::None)
+        .path(REFRESH_COOKIE_PATH)
+        .max_age(actix_web::cookie::time::Duration::days(config.jwt.refresh_token_ttl_days))
+        .finish();
+    [access_cookie, refresh_cookie]
+}
+
+/// Response login/refresh: token di body dan juga sebagai cookie `token` + `refresh_token`
+fn session_response(
+    config: &Config,
+    tokens: SessionTokens,
+    user_id: Uuid,
+    username: String,
+    role_id: Uuid,
+    recovery_codes: Option<Vec<String>>,
+) -> HttpResponse {
+    let [access_cookie, refresh_cookie] = session_cookies(config, &tokens);
+    HttpResponse::Ok()
+        .cookie(access_cookie)
+        .cookie(refresh_cookie)
+        .json(LoginResponse {
+            token: tokens.access_token,
+            refresh_token: tokens.refresh_token,
+            expires_in: config.jwt.access_token_ttl_minutes * 60,
+            session_id: tokens.session_id,
+            user_id,
+            username,
+            role: role_id.to_string(),
+            recovery_codes,
+        })
+}
+
+/// Cookie kosong yang langsung kedaluwarsa, untuk menghapus cookie di browser
+pub(crate) fn expired_cookie(name: &str, path: &str) -> Cookie<'static> {
+    Cookie::build(name.to_string(), "")
+        .http_only(true)
+        .secure(true)
+        .same_site(SameSite::None)
+        .path(path.to_string())
+        .max_age(actix_web::cookie::time::Duration::new(-1, 0)) // Expired cookie
+        .finish()
 }
 
+/// Hapus cookie `token` dan `refresh_token`
+pub(crate) fn clear_session_cookies(mut response: HttpResponse) -> HttpResponse {
+    for cookie in [expired_cookie("token", "/"), expired_cookie(REFRESH_COOKIE, REFRESH_COOKIE_PATH)] {
+        if let Err(e) = response.add_cookie(&cookie) {
+            eprintln!("[WARN] Gagal menghapus cookie {}: {}", cookie.name(), e);
+        }
+    }
+    response
+}
+
+/// Login dengan nama dan password. Membuat session baru; access token juga dikirim
+/// sebagai cookie `token` dan refresh token sebagai cookie `refresh_token`.
+/// Jika LDAP dikonfigurasi, nama/password yang tidak cocok dengan password lokal
+/// dicoba ke server LDAP, dan role user disamakan dengan grup LDAP-nya.
+#[utoipa::path(
+    tag = "auth",
+    security(()),
+    request_body = LoginRequest,
+    responses(
+        (status = 200, body = LoginResponse),
+        (status = 401, description = "Username atau password salah, atau password benar tapi butuh 2FA \
+            (`two_factor_required`/`two_factor_setup_required` dengan `challenge_token`)", body = ErrorBody),
+        (status = 403, description = "Password benar tapi akun sudah dinonaktifkan (`account_inactive`), atau login LDAP \
+            berhasil tapi tidak ada grup yang dipetakan ke role (`ldap_no_role`) atau user belum dibuat (`ldap_user_not_found`)", body = ErrorBody),
+        (status = 409, description = "Nama akun LDAP sudah dipakai user lokal dengan email berbeda (`ldap_account_conflict`)", body = ErrorBody),
+        (status = 429, description = "Terlalu banyak percobaan login gagal; tunggu sesuai header `Retry-After`", body = ErrorBody),
+        (status = 503, description = "Server LDAP tidak bisa dihubungi (`ldap_unavailable`)", body = ErrorBody),
+    )
+)]
 #[post("/api/login")]
 pub async fn login(
+    req: HttpRequest,
     pool: web::Data<PgPool>,
+    config: web::Data<Config>,
+    ldap: web::Data<LdapAuthenticator>,
     form: web::Json<LoginRequest>,
-) -> impl Responder {
-    let user = sqlx::query_as::<_, UserRow>(
-        "SELECT id, name, password_hash, role_id FROM users WHERE name = $1"
+) -> Result<HttpResponse, ApiError> {
+    let client = ClientInfo::from_request(&req);
+    let result = password_login(pool.get_ref(), &config, &ldap, &client, &form).await;
+    // Dicari setelah login, supaya user yang baru dibuat lewat LDAP ikut tercatat
+    let user_id = find_user_id(pool.get_ref(), &form.name).await?;
+    record_login_outcome(pool.get_ref(), &client, user_id, Some(&form.name), "password", result.as_ref().map(|_| ())).await?;
+    result
+}
+
+async fn password_login(
+    pool: &PgPool,
+    config: &Config,
+    ldap: &LdapAuthenticator,
+    client: &ClientInfo,
+    form: &LoginRequest,
+) -> Result<HttpResponse, ApiError> {
+    check_login_allowed(pool, &form.name, client.ip_address.as_deref()).await?;
+
+    let user = sqlx::query_as::<_, UserRow>(USER_ROW_BY_NAME)
+        .bind(&form.name)
+        .bind(LDAP_ISSUER)
+        .fetch_optional(pool)
+        .await?;
+
+    // User yang terhubung ke LDAP hanya bisa login lewat LDAP
+    match user.filter(|user| !user.ldap_linked) {
+        Some(UserRow { id, name, password_hash: Some(hash), role_id, .. }) => {
+            if verify_password(&form.password, &hash) {
+                rehash_if_needed(pool, &config.password, id, &form.password, &hash).await;
+                return finish_login(pool, config, client, &form.name, id, name, role_id).await;
+            }
+        },
+        _ => {
+            // Samakan waktu respons dengan user yang punya password supaya username tidak bisa ditebak dari latensi
+            verify_password(&form.password, dummy_password_hash(&config.password));
+        },
+    }
+
+    if let Some(account) = ldap.authenticate(&form.name, &form.password).await? {
+        let user = ldap.resolve_user(pool, &account).await?;
+        record_ldap_login(pool, client, &user).await?;
+        return finish_login(pool, config, client, &form.name, user.user_id, user.name, user.role_id).await;
+    }
+
+    record_login_failure(pool, &config.login, &form.name, client.ip_address.as_deref()).await?;
+    Err(ApiError::Unauthorized("Username atau password salah".to_string()))
+}
+
+/// Password sudah terverifikasi: tolak user nonaktif, minta kode 2FA jika perlu,
+/// atau langsung buat session
+async fn finish_login(
+    pool: &PgPool,
+    config: &Config,
+    client: &ClientInfo,
+    login_name: &str,
+    user_id: Uuid,
+    username: String,
+    role_id: Uuid,
+) -> Result<HttpResponse, ApiError> {
+    ensure_user_active(pool, user_id).await?;
+    let two_factor = two_factor_status(pool, user_id).await?;
+    if two_factor.enabled || two_factor.required {
+        // Password benar, tapi session baru dibuat setelah kode 2FA diverifikasi di /api/login/2fa
+        let challenge_token = create_login_challenge(pool, user_id).await?;
+        let (code, message) = if two_factor.enabled {
+            ("two_factor_required", "Masukkan kode dari aplikasi authenticator")
+        } else {
+            ("two_factor_setup_required", "Role kamu mewajibkan 2FA, daftarkan aplikasi authenticator dulu")
+        };
+        return Err(ApiError::Detailed {
+            status: actix_web::http::StatusCode::UNAUTHORIZED,
+            code,
+            message: message.to_string(),
+            details: serde_json::json!({
+                "challenge_token": challenge_token,
+                "expires_in": CHALLENGE_TTL_SECS,
+            }),
+        });
+    }
+    record_login_success(pool, login_name).await?;
+    let tokens = start_session(pool, &config.jwt, user_id, role_id, client).await?;
+    Ok(session_response(config, tokens, user_id, username, role_id, None))
+}
+
+/// Catat user yang dibuat/dihubungkan lewat LDAP, password lokal yang dihapus saat
+/// akun dihubungkan, dan perubahan role dari grup LDAP
+async fn record_ldap_login(pool: &PgPool, client: &ClientInfo, user: &LdapUser) -> Result<(), ApiError> {
+    if user.provisioned || user.linked {
+        let action = if user.provisioned { "ldap.user_provisioned" } else { "ldap.identity_linked" };
+        AuditEvent::new(action, "user", Some(user.user_id))
+            .actor(user.user_id)
+            .client(client)
+            .record(pool)
+            .await?;
+    }
+    if user.password_cleared {
+        AuditEvent::new("ldap.local_password_cleared", "user", Some(user.user_id))
+            .actor(user.user_id)
+            .before(serde_json::json!({ "has_password": true }))
+            .after(serde_json::json!({ "has_password": false }))
+            .client(client)
+            .record(pool)
+            .await?;
+    }
+    if let Some(previous_role_id) = user.previous_role_id {
+        AuditEvent::new("ldap.role_synced", "user", Some(user.user_id))
+            .actor(user.user_id)
+            .before(serde_json::json!({ "role_id": previous_role_id }))
+            .after(serde_json::json!({ "role_id": user.role_id }))
+            .client(client)
+            .record(pool)
+            .await?;
+    }
+    Ok(())
+}
+
+/// Minta link reset password lewat email. Response selalu sama, ada atau tidak
+/// user/email-nya, supaya endpoint ini tidak bisa dipakai menebak akun.
+/// User tanpa email harus meminta link ke admin.
+#[utoipa::path(
+    tag = "auth",
+    security(()),
+    request_body = RequestResetRequest,
+    responses((status = 200, description = "Permintaan diterima", body = serde_json::Value))
+)]
+#[post("/api/auth/request-reset")]
+pub async fn request_reset(
+    req: HttpRequest,
+    pool: web::Data<PgPool>,
+    config: web::Data<Config>,
+    mailer: web::Data<Mailer>,
+    form: web::Json<RequestResetRequest>,
+) -> Result<HttpResponse, ApiError> {
+    let user: Option<(Uuid, String, String)> = sqlx::query_as(
+        "SELECT id, name, email FROM users
+         WHERE (name = $1 OR LOWER(email) = LOWER($1)) AND email IS NOT NULL AND email <> '' AND is_active
+         ORDER BY name = $1 DESC
+         LIMIT 1"
     )
-    .bind(&form.name)
+    .bind(form.name.trim())
     .fetch_optional(pool.get_ref())
-    .await
-    .unwrap();
-
-    if let Some(user) = user {
-        if let Some(ref hash) = user.password_hash {
-            if verify_password(&form.password, hash) {
-                let exp = (Utc::now() + Duration::hours(24)).timestamp() as usize;
-                // NOTE: role is now a UUID, so you may want to join to user_roles for label if needed
-                let claims = Claims {
-                    sub: user.id.to_string(),
-                    exp,
-                    role: user.role_id.to_string(),
-                };
-                let secret = std::env::var("JWT_SECRET").expect("JWT_SECRET harus di-set di .env");
-                let token = encode(
-                    &Header::default(),
-                    &claims,
-                    &EncodingKey::from_secret(secret.as_ref()),
-                ).expect("JWT encode error");
-                let cookie = Cookie::build("token", token.clone())
-                    .http_only(true)
-                    .secure(true) // HARUS false untuk dev HTTP agar cookie terkirim
-                    .same_site(SameSite::None)
-                    .path("/")
-                    .finish();
-                return HttpResponse::Ok()
-                    .cookie(cookie)
-                    .json(LoginResponse {
-                        token,
-                        user_id: user.id,
-                        username: user.name,
-                        role: user.role_id.to_string(),
-                    });
-            }
+    .await?;
+
+    if let Some((user_id, name, email)) = user {
+        if !reset_recently_requested(pool.get_ref(), user_id).await? {
+            let reset = create_password_reset(pool.get_ref(), &config, user_id, None).await?;
+            AuditEvent::new("password_reset.requested", "user", Some(user_id))
+                .client(&ClientInfo::from_request(&req))
+                .record(pool.get_ref())
+                .await?;
+
+            let body = format!(
+                "Halo {},\n\nBuka link berikut untuk membuat password baru:\n{}\n\nLink berlaku sampai {} dan hanya bisa dipakai sekali. \
+                 Abaikan email ini jika kamu tidak meminta reset password.\n",
+                name,
+                reset.url,
+                reset.expires_at.format("%Y-%m-%d %H:%M UTC"),
+            );
+            // Kirim di background supaya waktu respons tidak membocorkan akun mana yang punya email
+            let mailer = mailer.get_ref().clone();
+            actix_web::rt::spawn(async move {
+                if let Err(e) = mailer.send(&email, "Reset password Inman", body).await {
+                    eprintln!("[ERROR] Gagal mengirim email reset password ke user {}: {}", user_id, e);
+                }
+            });
         }
     }
-    HttpResponse::Unauthorized().json(serde_json::json!({ "error": "Username atau password salah" }))
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "success": true,
+        "message": "Jika akun ditemukan dan punya email, link reset password sudah dikirim"
+    })))
+}
+
+/// Buat password baru dengan token dari link reset. Token hanya bisa dipakai sekali;
+/// semua session user dicabut dan kunci login akunnya dibuka.
+#[utoipa::path(
+    tag = "auth",
+    security(()),
+    request_body = ConfirmResetRequest,
+    responses(
+        (status = 200, description = "Password direset, user bisa login", body = serde_json::Value),
+        (status = 400, description = "Token tidak valid/sudah dipakai/kedaluwarsa (`reset_token_invalid`)", body = ErrorBody),
+        (status = 422, description = "Password tidak memenuhi kebijakan (`weak_password`); token masih bisa dipakai", body = ErrorBody),
+    )
+)]
+#[post("/api/auth/confirm-reset")]
+pub async fn confirm_reset(
+    req: HttpRequest,
+    pool: web::Data<PgPool>,
+    config: web::Data<Config>,
+    form: web::Json<ConfirmResetRequest>,
+) -> Result<HttpResponse, ApiError> {
+    let mut tx = pool.begin().await?;
+    let user_id = consume_password_reset(&mut *tx, &form.token).await?;
+    // Jika password ditolak, tx di-drop tanpa commit dan token tetap berlaku
+    set_password(&mut *tx, &config.password, user_id, &form.password).await?;
+    let name: String = sqlx::query_scalar("SELECT name FROM users WHERE id = $1")
+        .bind(user_id)
+        .fetch_one(&mut *tx)
+        .await?;
+    unlock_account(&mut *tx, &name).await?;
+    let client = ClientInfo::from_request(&req);
+    AuditEvent::new("password_reset.completed", "user", Some(user_id))
+        .actor(user_id)
+        .client(&client)
+        .record(&mut *tx)
+        .await?;
+    tx.commit().await?;
+
+    SecurityEvent::new("password.changed")
+        .user(user_id)
+        .details(serde_json::json!({ "via": "password_reset" }))
+        .client(&client)
+        .record(pool.get_ref())
+        .await?;
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "success": true,
+        "message": "Password berhasil direset, silakan login!"
+    })))
+}
+
+/// Langkah kedua login untuk user dengan 2FA: tukar `challenge_token` dan kode
+/// authenticator (atau kode pemulihan) dengan session. Untuk `two_factor_setup_required`,
+/// panggil `/api/login/2fa/setup` dulu; kode pertama yang benar sekaligus mengaktifkan
+/// 2FA dan response berisi `recovery_codes`.
+#[utoipa::path(
+    tag = "auth",
+    security(()),
+    request_body = TwoFactorLoginRequest,
+    responses(
+        (status = 200, body = LoginResponse),
+        (status = 400, description = "2FA wajib tapi `/api/login/2fa/setup` belum dipanggil", body = ErrorBody),
+        (status = 401, description = "Kode salah, atau challenge tidak valid/kedaluwarsa (`challenge_invalid`)", body = ErrorBody),
+        (status = 429, description = "Terlalu banyak percobaan gagal", body = ErrorBody),
+    )
+)]
+#[post("/api/login/2fa")]
+pub async fn login_two_factor(
+    req: HttpRequest,
+    pool: web::Data<PgPool>,
+    config: web::Data<Config>,
+    form: web::Json<TwoFactorLoginRequest>,
+) -> Result<HttpResponse, ApiError> {
+    let user = find_login_challenge(pool.get_ref(), &form.challenge_token).await?;
+    let client = ClientInfo::from_request(&req);
+    let (user_id, name) = (user.user_id, user.name.clone());
+    let result = second_factor_login(pool.get_ref(), &config, &client, &form, user).await;
+    record_login_outcome(pool.get_ref(), &client, Some(user_id), Some(&name), "two_factor", result.as_ref().map(|_| ())).await?;
+    result
 }
 
-// Password verification menggunakan argon2
-use argon2::{Argon2, PasswordHash, PasswordVerifier};
+async fn second_factor_login(
+    pool: &PgPool,
+    config: &Config,
+    client: &ClientInfo,
+    form: &TwoFactorLoginRequest,
+    user: ChallengeUser,
+) -> Result<HttpResponse, ApiError> {
+    check_login_allowed(pool, &user.name, client.ip_address.as_deref()).await?;
 
-fn verify_password(password: &str, hash: &str) -> bool {
-    if let Ok(parsed_hash) = PasswordHash::new(hash) {
-        Argon2::default().verify_password(password.as_bytes(), &parsed_hash).is_ok()
+    let (verified, recovery_codes) = if two_factor_status(pool, user.user_id).await?.enabled {
+        (verify_second_factor(pool, user.user_id, &form.code).await?, None)
     } else {
-        false
+        // Pendaftaran wajib saat login
+        match enable_two_factor(pool, user.user_id, &form.code).await? {
+            Some(codes) => {
+                AuditEvent::new("two_factor.enabled", "user", Some(user.user_id))
+                    .actor(user.user_id)
+                    .client(client)
+                    .record(pool)
+                    .await?;
+                (true, Some(codes))
+            },
+            None => (false, None),
+        }
+    };
+    if !verified {
+        record_login_failure(pool, &config.login, &user.name, client.ip_address.as_deref()).await?;
+        return Err(ApiError::Unauthorized("Kode 2FA salah".to_string()));
+    }
+
+    consume_login_challenge(pool, &form.challenge_token).await?;
+    record_login_success(pool, &user.name).await?;
+    let tokens = start_session(pool, &config.jwt, user.user_id, user.role_id, client).await?;
+    Ok(session_response(config, tokens, user.user_id, user.name, user.role_id, recovery_codes))
+}
+
+/// Daftarkan authenticator di tengah login, untuk user yang role-nya mewajibkan 2FA
+/// tapi belum mendaftar (`two_factor_setup_required`)
+#[utoipa::path(
+    tag = "auth",
+    security(()),
+    request_body = TwoFactorSetupRequest,
+    responses(
+        (status = 200, body = TwoFactorEnrollment),
+        (status = 401, description = "Challenge tidak valid/kedaluwarsa (`challenge_invalid`)", body = ErrorBody),
+        (status = 409, description = "2FA sudah aktif", body = ErrorBody),
+    )
+)]
+#[post("/api/login/2fa/setup")]
+pub async fn login_two_factor_setup(
+    pool: web::Data<PgPool>,
+    form: web::Json<TwoFactorSetupRequest>,
+) -> Result<HttpResponse, ApiError> {
+    let user = find_login_challenge(pool.get_ref(), &form.challenge_token).await?;
+    Ok(HttpResponse::Ok().json(start_enrollment(pool.get_ref(), user.user_id, &user.name).await?))
+}
+
+/// Hash argon2 untuk password acak, diverifikasi saat username tidak ditemukan
+fn dummy_password_hash(config: &PasswordConfig) -> &'static str {
+    static HASH: OnceLock<String> = OnceLock::new();
+    HASH.get_or_init(|| hash_password(config, "inman-dummy-password").unwrap_or_default())
+}
+
+/// Ganti hash yang dibuat dengan parameter Argon2 lama. Session tidak dicabut karena
+/// password-nya tidak berubah; kegagalan hanya dicatat supaya login tetap jalan.
+async fn rehash_if_needed(pool: &PgPool, config: &PasswordConfig, user_id: Uuid, password: &str, hash: &str) {
+    if !needs_rehash(config, hash) {
+        return;
+    }
+    let result = match hash_password(config, password) {
+        Ok(new_hash) => sqlx::query("UPDATE users SET password_hash = $1 WHERE id = $2 AND password_hash = $3")
+            .bind(new_hash)
+            .bind(user_id)
+            .bind(hash)
+            .execute(pool)
+            .await
+            .map(|_| ())
+            .map_err(|e| e.to_string()),
+        Err(e) => Err(e.to_string()),
+    };
+    if let Err(e) = result {
+        eprintln!("[WARN] Gagal rehash password user {}: {}", user_id, e);
     }
 }
 
-// Helper untuk middleware validasi JWT bisa dibuat menyusul
+/// Tukar refresh token (dari body atau cookie `refresh_token`) dengan access token dan
+/// refresh token baru. Refresh token lama langsung tidak berlaku.
+#[utoipa::path(
+    tag = "auth",
+    security(()),
+    request_body(content = Option<RefreshRequest>),
+    responses(
+        (status = 200, body = LoginResponse),
+        (status = 401, description = "Refresh token tidak valid, kedaluwarsa, atau session sudah dicabut", body = ErrorBody),
+    )
+)]
+#[post("/api/refresh")]
+pub async fn refresh(
+    req: HttpRequest,
+    pool: web::Data<PgPool>,
+    config: web::Data<Config>,
+    form: Option<web::Json<RefreshRequest>>,
+) -> Result<HttpResponse, ApiError> {
+    let refresh_token = form
+        .map(|f| f.into_inner().refresh_token)
+        .or_else(|| req.cookie(REFRESH_COOKIE).map(|c| c.value().to_string()))
+        .ok_or_else(|| ApiError::Unauthorized("No refresh token".to_string()))?;
+    let client = ClientInfo::from_request(&req);
+    let (user, tokens) = refresh_session(pool.get_ref(), &config.jwt, &refresh_token, &client).await?;
+    SecurityEvent::new("token.refreshed")
+        .user(user.user_id)
+        .details(serde_json::json!({ "session_id": tokens.session_id }))
+        .client(&client)
+        .record(pool.get_ref())
+        .await?;
+    Ok(session_response(&config, tokens, user.user_id, user.name, user.role_id, None))
+}
 
+/// Logout: cabut session yang sedang dipakai (dari access token atau cookie
+/// `refresh_token`) dan hapus cookie-nya. Token API tidak punya session; cabut
+/// lewat `DELETE /api/tokens/{id}`.
+#[utoipa::path(
+    tag = "auth",
+    security(()),
+    responses(
+        (status = 200, description = "Session dicabut dan cookie dihapus", body = serde_json::Value),
+        (status = 403, description = "Request memakai token API", body = ErrorBody),
+    )
+)]
 #[get("/api/logout")]
-pub async fn logout() -> impl Responder {
-    let cookie = Cookie::build("token", "")
-        .http_only(true)
-        .secure(true)
-        .same_site(SameSite::None)
-        .path("/")
-        .max_age(actix_web::cookie::time::Duration::new(-1, 0)) // Expired cookie
-        .finish();
-    
-    HttpResponse::Ok()
-        .cookie(cookie)
-        .json(serde_json::json!({ "success": true, "message": "Logged out successfully" }))
+pub async fn logout(req: HttpRequest, pool: web::Data<PgPool>, claims: Option<Claims>) -> Result<HttpResponse, ApiError> {
+    let revoked = if let Some(claims) = claims {
+        claims.require_session()?;
+        let (user_id, session_id) = (claims.user_id()?, claims.session_id()?);
+        revoke_session(pool.get_ref(), user_id, session_id).await?.then_some((user_id, session_id))
+    } else if let Some(cookie) = req.cookie(REFRESH_COOKIE) {
+        revoke_session_by_refresh_token(pool.get_ref(), cookie.value()).await?
+    } else {
+        None
+    };
+    if let Some((user_id, session_id)) = revoked {
+        SecurityEvent::new("logout")
+            .user(user_id)
+            .details(serde_json::json!({ "session_id": session_id }))
+            .client(&ClientInfo::from_request(&req))
+            .record(pool.get_ref())
+            .await?;
+    }
+
+    Ok(clear_session_cookies(
+        HttpResponse::Ok().json(serde_json::json!({ "success": true, "message": "Logged out successfully" })),
+    ))
 }
+
+#[derive(OpenApi)]
+#[openapi(paths(check_user, accept_invite, request_reset, confirm_reset, login, login_two_factor, login_two_factor_setup, refresh, logout))]
+pub struct AuthApi;
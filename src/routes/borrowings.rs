@@ -1,13 +1,31 @@
-use actix_web::{get, post, patch, web, HttpResponse, Responder};
+use actix_multipart::Multipart;
+use actix_web::{get, post, patch, web, HttpResponse};
+use actix_web::http::StatusCode;
+use futures::TryStreamExt;
 use serde::{Deserialize, Serialize};
-use sqlx::{PgPool, Row, Executor, Postgres};
+use sqlx::{PgPool, Row};
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use utoipa::{OpenApi, ToSchema};
 use uuid::Uuid;
 use chrono::{DateTime, Utc};
 
+use crate::error::{ApiError, ErrorBody};
 use crate::middleware::jwt_extractor::Claims;
-use crate::middleware::permission_guard::has_permission;
+use crate::middleware::permission_guard::{RequirePermission, UserPermissions};
+use crate::config::Config;
+use crate::services::penalties::{borrower_standing, charge_late_return, BorrowerPenalty};
+use crate::services::drive_storage::{public_backend_url, read_private_file, upload_private_file, DriveClient, DriveConfig, PrivateFile};
+use crate::services::receipt_pdf::{render_receipt, ReceiptData, ReceiptKind, SignatureImage};
 
-#[derive(Debug, Serialize, Deserialize, sqlx::FromRow)]
+/// Izin minimal untuk mengakses peminjaman; pembatasan per peminjaman (milik sendiri
+/// vs semua) tetap dicek di handler
+const BORROWING_PERMISSIONS: &[&str] = &["borrow_items", "view_all_borrowings", "approve_borrowings", "manage_borrowings"];
+/// Maximum accepted size of a drawn signature PNG
+const MAX_SIGNATURE_BYTES: usize = 2 * 1024 * 1024;
+const PNG_MAGIC: &[u8] = b"\x89PNG\r\n\x1a\n";
+
+#[derive(Debug, Serialize, Deserialize, sqlx::FromRow, ToSchema)]
 pub struct ItemBorrowing {
     pub id: Uuid,
     pub item_id: Uuid,
@@ -21,7 +39,7 @@ pub struct ItemBorrowing {
     pub status: String,
 }
 
-#[derive(Debug, Serialize, Deserialize, sqlx::FromRow)]
+#[derive(Debug, Serialize, Deserialize, sqlx::FromRow, ToSchema)]
 pub struct ItemBorrowingWithDetails {
     pub id: Uuid,
     pub item_id: Uuid,
@@ -38,7 +56,7 @@ pub struct ItemBorrowingWithDetails {
     pub status: String,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, ToSchema)]
 pub struct NewItemBorrowing {
     pub item_id: Uuid,
     pub quantity: Option<i32>,
@@ -46,6 +64,69 @@ pub struct NewItemBorrowing {
     pub notes: Option<String>,
 }
 
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct ReturnItemBorrowing {
+    pub condition_id: Option<Uuid>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ReturnedBorrowing {
+    #[serde(flatten)]
+    pub borrowing: ItemBorrowing,
+    pub penalty: Option<BorrowerPenalty>,
+}
+
+#[derive(Debug, Serialize, sqlx::FromRow, ToSchema)]
+pub struct BorrowingSignature {
+    pub id: Uuid,
+    pub borrowing_id: Uuid,
+    pub stage: String,
+    pub signer_role: String,
+    pub signed_by: Option<Uuid>,
+    pub signer_name: Option<String>,
+    /// Endpoint gambar tanda tangan; butuh JWT dengan akses ke peminjaman ini
+    #[sqlx(skip)]
+    pub image_url: String,
+    pub created_at: DateTime<Utc>,
+    #[serde(skip)]
+    pub storage_backend: String,
+    #[serde(skip)]
+    pub file_id: String,
+}
+
+impl BorrowingSignature {
+    fn with_image_url(mut self, config: &DriveConfig) -> Self {
+        self.image_url = format!(
+            "{}/api/borrowings/{}/signatures/{}/image",
+            public_backend_url(config), self.borrowing_id, self.id
+        );
+        self
+    }
+    
+    fn private_file(&self) -> Result<PrivateFile, ApiError> {
+        let backend = self.storage_backend.parse().map_err(ApiError::Internal)?;
+        Ok(PrivateFile { backend, file_id: self.file_id.clone() })
+    }
+}
+
+/// Kolom `BorrowingSignature`, dengan `s` = borrowing_signatures dan `u` = penanda tangan
+const SIGNATURE_COLUMNS: &str = "s.id, s.borrowing_id, s.stage, s.signer_role, s.signed_by, u.name as signer_name,
+                s.created_at, s.storage_backend, s.file_id";
+
+/// Form multipart untuk `POST /{id}/signatures`
+#[derive(ToSchema)]
+#[allow(dead_code)]
+pub struct SignatureUpload {
+    /// checkout atau return
+    stage: String,
+    /// borrower atau staff
+    role: String,
+    /// Gambar tanda tangan PNG, maksimal 2 MB
+    #[schema(format = Binary, value_type = String)]
+    file: Vec<u8>,
+}
+
+#[allow(dead_code)]
 #[derive(Debug, Deserialize)]
 pub struct UpdateItemBorrowing {
     pub quantity: Option<i32>,
@@ -55,10 +136,12 @@ pub struct UpdateItemBorrowing {
     pub status: Option<String>,
 }
 
-#[get("")]
-pub async fn get_borrowings(claims: Claims, pool: web::Data<PgPool>) -> impl Responder {
+/// Semua peminjaman untuk pemegang izin view_all_borrowings, selain itu hanya milik sendiri
+#[utoipa::path(tag = "borrowings", responses((status = 200, body = Vec<ItemBorrowingWithDetails>), (status = 403, description = "Butuh salah satu izin peminjaman", body = ErrorBody)))]
+#[get("", wrap = "RequirePermission::any(BORROWING_PERMISSIONS)")]
+pub async fn get_borrowings(claims: Claims, perms: UserPermissions, pool: web::Data<PgPool>) -> Result<HttpResponse, ApiError> {
     // Check if user has permission to view all borrowings
-    let can_view_all = has_permission(&claims, pool.get_ref(), "view_all_borrowings").await;
+    let can_view_all = perms.has("view_all_borrowings");
     
     let query = if can_view_all {
         // Admin/staff can see all borrowings
@@ -107,20 +190,25 @@ pub async fn get_borrowings(claims: Claims, pool: web::Data<PgPool>) -> impl Res
         }
     })
     .fetch_all(pool.get_ref())
-    .await;
+    .await?;
     
-    match borrowings {
-        Ok(borrowings) => HttpResponse::Ok().json(borrowings),
-        Err(e) => HttpResponse::InternalServerError().json(serde_json::json!({"error": e.to_string()})),
-    }
+    Ok(HttpResponse::Ok().json(borrowings))
 }
 
-#[get("/{id}")]
-pub async fn get_borrowing_by_id(claims: Claims, pool: web::Data<PgPool>, path: web::Path<Uuid>) -> impl Responder {
+#[utoipa::path(
+    tag = "borrowings",
+    responses(
+        (status = 200, body = ItemBorrowingWithDetails),
+        (status = 404, body = ErrorBody),
+        (status = 403, description = "Butuh salah satu izin peminjaman", body = ErrorBody),
+    )
+)]
+#[get("/{id}", wrap = "RequirePermission::any(BORROWING_PERMISSIONS)")]
+pub async fn get_borrowing_by_id(claims: Claims, perms: UserPermissions, pool: web::Data<PgPool>, path: web::Path<Uuid>) -> Result<HttpResponse, ApiError> {
     let id = path.into_inner();
     
     // Check if user has permission to view all borrowings
-    let can_view_all = has_permission(&claims, pool.get_ref(), "view_all_borrowings").await;
+    let can_view_all = perms.has("view_all_borrowings");
     
     let query = if can_view_all {
         // Admin/staff can see any borrowing
@@ -171,22 +259,37 @@ pub async fn get_borrowing_by_id(claims: Claims, pool: web::Data<PgPool>, path:
         }
     })
     .fetch_optional(pool.get_ref())
-    .await;
+    .await?
+    .ok_or_else(|| ApiError::NotFound("Borrowing not found".to_string()))?;
     
-    match borrowing {
-        Ok(Some(borrowing)) => HttpResponse::Ok().json(borrowing),
-        Ok(None) => HttpResponse::NotFound().json(serde_json::json!({"error": "Borrowing not found"})),
-        Err(e) => HttpResponse::InternalServerError().json(serde_json::json!({"error": e.to_string()})),
-    }
+    Ok(HttpResponse::Ok().json(borrowing))
 }
 
-#[post("")]
-pub async fn create_borrowing(claims: Claims, pool: web::Data<PgPool>, form: web::Json<NewItemBorrowing>) -> impl Responder {
-    // Check if user has permission to borrow items
-    if !has_permission(&claims, pool.get_ref(), "borrow_items").await {
-        return HttpResponse::Forbidden().json(serde_json::json!({
-            "error": "You don't have permission to borrow items"
-        }));
+/// Ajukan peminjaman (status `pending`)
+#[utoipa::path(
+    tag = "borrowings",
+    request_body = NewItemBorrowing,
+    responses(
+        (status = 200, body = ItemBorrowing),
+        (status = 400, description = "Item tidak tersedia atau jumlah tidak valid", body = ErrorBody),
+        (status = 403, description = "Tidak punya izin borrow_items, atau `borrowing_blocked` beserta field `standing`", body = ErrorBody),
+        (status = 404, body = ErrorBody),
+    )
+)]
+#[post("", wrap = "RequirePermission::new(\"borrow_items\")")]
+pub async fn create_borrowing(claims: Claims, pool: web::Data<PgPool>, config: web::Data<Config>, form: web::Json<NewItemBorrowing>) -> Result<HttpResponse, ApiError> {
+    // Parse user ID from claims
+    let user_id = claims.user_id()?;
+    
+    // Block borrowers with unsettled penalties or too many late returns
+    let standing = borrower_standing(pool.get_ref(), &config.penalties, user_id).await?;
+    if standing.blocked {
+        return Err(ApiError::Detailed {
+            status: StatusCode::FORBIDDEN,
+            code: "borrowing_blocked",
+            message: "Borrowing is blocked for this account".to_string(),
+            details: serde_json::json!({ "standing": standing }),
+        });
     }
     
     // Check if the item exists and is available
@@ -198,47 +301,20 @@ pub async fn create_borrowing(claims: Claims, pool: web::Data<PgPool>, form: web
         form.item_id
     )
     .fetch_optional(pool.get_ref())
-    .await;
-    
-    let item = match item {
-        Ok(Some(item)) => item,
-        Ok(None) => {
-            return HttpResponse::NotFound().json(serde_json::json!({
-                "error": "Item not found"
-            }));
-        },
-        Err(e) => {
-            return HttpResponse::InternalServerError().json(serde_json::json!({
-                "error": e.to_string()
-            }));
-        }
-    };
+    .await?
+    .ok_or_else(|| ApiError::NotFound("Item not found".to_string()))?;
     
     // Check if item is active
     if item.status_name != "active" {
-        return HttpResponse::BadRequest().json(serde_json::json!({
-            "error": "Item is not available for borrowing"
-        }));
+        return Err(ApiError::BadRequest("Item is not available for borrowing".to_string()));
     }
     
     // Check if quantity is valid
     let quantity = form.quantity.unwrap_or(1);
     if quantity <= 0 || quantity > item.quantity {
-        return HttpResponse::BadRequest().json(serde_json::json!({
-            "error": "Invalid quantity"
-        }));
+        return Err(ApiError::BadRequest("Invalid quantity".to_string()));
     }
     
-    // Parse user ID from claims
-    let user_id = match Uuid::parse_str(&claims.sub) {
-        Ok(id) => id,
-        Err(_) => {
-            return HttpResponse::BadRequest().json(serde_json::json!({
-                "error": "Invalid user ID"
-            }));
-        }
-    };
-    
     // Create the borrowing record
     let borrowing = sqlx::query_as::<_, ItemBorrowing>(
         "INSERT INTO item_borrowings (item_id, borrower_id, quantity, expected_return_date, notes, status) 
@@ -252,49 +328,39 @@ pub async fn create_borrowing(claims: Claims, pool: web::Data<PgPool>, form: web
     .bind(form.notes.clone())
     .bind("pending")
     .fetch_one(pool.get_ref())
+    .await?;
+    
+    // Log the borrowing request
+    let _ = sqlx::query(
+        "INSERT INTO item_logs (item_id, action, note, by) 
+         VALUES ($1, $2, $3, $4)"
+    )
+    .bind(form.item_id)
+    .bind("borrowing_requested")
+    .bind(format!("Borrowing requested: {} units, expected return: {}", 
+                  quantity, form.expected_return_date))
+    .bind(user_id)
+    .execute(pool.get_ref())
     .await;
     
-    match borrowing {
-        Ok(borrowing) => {
-            // Log the borrowing request
-            let _ = sqlx::query(
-                "INSERT INTO item_logs (item_id, action, note, by) 
-                 VALUES ($1, $2, $3, $4)"
-            )
-            .bind(form.item_id)
-            .bind("borrowing_requested")
-            .bind(format!("Borrowing requested: {} units, expected return: {}", 
-                          quantity, form.expected_return_date))
-            .bind(user_id)
-            .execute(pool.get_ref())
-            .await;
-            
-            HttpResponse::Ok().json(borrowing)
-        },
-        Err(e) => HttpResponse::InternalServerError().json(serde_json::json!({"error": e.to_string()})),
-    }
+    Ok(HttpResponse::Ok().json(borrowing))
 }
 
-#[patch("/{id}/approve")]
-pub async fn approve_borrowing(claims: Claims, pool: web::Data<PgPool>, path: web::Path<Uuid>) -> impl Responder {
+#[utoipa::path(
+    tag = "borrowings",
+    responses(
+        (status = 200, body = ItemBorrowing),
+        (status = 400, description = "Peminjaman tidak berstatus pending", body = ErrorBody),
+        (status = 403, description = "Butuh izin approve_borrowings", body = ErrorBody),
+        (status = 404, body = ErrorBody),
+    )
+)]
+#[patch("/{id}/approve", wrap = "RequirePermission::new(\"approve_borrowings\")")]
+pub async fn approve_borrowing(claims: Claims, pool: web::Data<PgPool>, path: web::Path<Uuid>) -> Result<HttpResponse, ApiError> {
     let id = path.into_inner();
     
-    // Check if user has permission to approve borrowings
-    if !has_permission(&claims, pool.get_ref(), "approve_borrowings").await {
-        return HttpResponse::Forbidden().json(serde_json::json!({
-            "error": "You don't have permission to approve borrowings"
-        }));
-    }
-    
     // Parse user ID from claims
-    let user_id = match Uuid::parse_str(&claims.sub) {
-        Ok(id) => id,
-        Err(_) => {
-            return HttpResponse::BadRequest().json(serde_json::json!({
-                "error": "Invalid user ID"
-            }));
-        }
-    };
+    let user_id = claims.user_id()?;
     
     // Get the borrowing record
     let borrowing = sqlx::query_as::<_, ItemBorrowing>(
@@ -302,141 +368,81 @@ pub async fn approve_borrowing(claims: Claims, pool: web::Data<PgPool>, path: we
     )
     .bind(id)
     .fetch_optional(pool.get_ref())
-    .await;
-    
-    let borrowing = match borrowing {
-        Ok(Some(b)) => b,
-        Ok(None) => {
-            return HttpResponse::NotFound().json(serde_json::json!({
-                "error": "Borrowing not found"
-            }));
-        },
-        Err(e) => {
-            return HttpResponse::InternalServerError().json(serde_json::json!({
-                "error": e.to_string()
-            }));
-        }
-    };
+    .await?
+    .ok_or_else(|| ApiError::NotFound("Borrowing not found".to_string()))?;
     
     // Check if borrowing is already approved
     if borrowing.status != "pending" {
-        return HttpResponse::BadRequest().json(serde_json::json!({
-            "error": "Borrowing is not in pending status"
-        }));
+        return Err(ApiError::BadRequest("Borrowing is not in pending status".to_string()));
     }
     
     // Start a transaction
-    let mut tx = match pool.begin().await {
-        Ok(tx) => tx,
-        Err(e) => {
-            return HttpResponse::InternalServerError().json(serde_json::json!({
-                "error": format!("Failed to start transaction: {}", e)
-            }));
-        }
-    };
+    let mut tx = pool.begin().await?;
     
     // Update the borrowing status
     let updated_borrowing = sqlx::query_as::<_, ItemBorrowing>(
         "UPDATE item_borrowings 
-         SET status = 'approved', approved_by = $1 
+         SET status = 'approved', approved_by = $1,
+             checkout_condition_id = (SELECT condition_id FROM items WHERE id = item_borrowings.item_id)
          WHERE id = $2 
          RETURNING *"
     )
     .bind(user_id)
     .bind(id)
     .fetch_one(&mut *tx)
-    .await;
-    
-    let updated_borrowing = match updated_borrowing {
-        Ok(b) => b,
-        Err(e) => {
-            let _ = tx.rollback().await;
-            return HttpResponse::InternalServerError().json(serde_json::json!({
-                "error": format!("Failed to update borrowing: {}", e)
-            }));
-        }
-    };
+    .await?;
     
     // Update the item status to 'borrowed'
     let borrowed_status = sqlx::query!(
         "SELECT id FROM item_statuses WHERE name = 'borrowed'"
     )
     .fetch_one(&mut *tx)
-    .await;
+    .await?;
     
-    let borrowed_status_id = match borrowed_status {
-        Ok(status) => status.id,
-        Err(e) => {
-            let _ = tx.rollback().await;
-            return HttpResponse::InternalServerError().json(serde_json::json!({
-                "error": format!("Failed to get borrowed status: {}", e)
-            }));
-        }
-    };
-    
-    // Update the item status
-    let update_item = sqlx::query!(
+    sqlx::query!(
         "UPDATE items SET status_id = $1 WHERE id = $2",
-        borrowed_status_id,
+        borrowed_status.id,
         borrowing.item_id
     )
     .execute(&mut *tx)
-    .await;
+    .await?;
     
-    match update_item {
-        Ok(_) => {
-            // Log the approval
-            let log = sqlx::query(
-                "INSERT INTO item_logs (item_id, action, note, by) 
-                 VALUES ($1, $2, $3, $4)"
-            )
-            .bind(borrowing.item_id)
-            .bind("borrowing_approved")
-            .bind(format!("Borrowing approved for {} units", borrowing.quantity))
-            .bind(user_id)
-            .execute(&mut *tx)
-            .await;
-            
-            match log {
-                Ok(_) => {
-                    // Commit the transaction
-                    match tx.commit().await {
-                        Ok(_) => HttpResponse::Ok().json(updated_borrowing),
-                        Err(e) => HttpResponse::InternalServerError().json(serde_json::json!({
-                            "error": format!("Failed to commit transaction: {}", e)
-                        })),
-                    }
-                },
-                Err(e) => {
-                    let _ = tx.rollback().await;
-                    HttpResponse::InternalServerError().json(serde_json::json!({
-                        "error": format!("Failed to log approval: {}", e)
-                    }))
-                }
-            }
-        },
-        Err(e) => {
-            let _ = tx.rollback().await;
-            HttpResponse::InternalServerError().json(serde_json::json!({
-                "error": format!("Failed to update item status: {}", e)
-            }))
-        }
-    }
+    // Log the approval
+    sqlx::query(
+        "INSERT INTO item_logs (item_id, action, note, by) 
+         VALUES ($1, $2, $3, $4)"
+    )
+    .bind(borrowing.item_id)
+    .bind("borrowing_approved")
+    .bind(format!("Borrowing approved for {} units", borrowing.quantity))
+    .bind(user_id)
+    .execute(&mut *tx)
+    .await?;
+    
+    // Transaksi otomatis di-rollback jika salah satu langkah di atas gagal
+    tx.commit().await?;
+    Ok(HttpResponse::Ok().json(updated_borrowing))
 }
 
-#[patch("/{id}/return")]
-pub async fn return_borrowing(claims: Claims, pool: web::Data<PgPool>, path: web::Path<Uuid>) -> impl Responder {
+/// Kembalikan item; denda keterlambatan dihitung otomatis
+#[utoipa::path(
+    tag = "borrowings",
+    request_body(content = Option<ReturnItemBorrowing>),
+    responses(
+        (status = 200, body = ReturnedBorrowing),
+        (status = 400, description = "Peminjaman tidak berstatus approved", body = ErrorBody),
+        (status = 403, description = "Butuh salah satu izin peminjaman", body = ErrorBody),
+        (status = 404, body = ErrorBody),
+        (status = 409, description = "Peminjaman sudah dikembalikan oleh request lain", body = ErrorBody),
+    )
+)]
+#[patch("/{id}/return", wrap = "RequirePermission::any(BORROWING_PERMISSIONS)")]
+pub async fn return_borrowing(claims: Claims, perms: UserPermissions, pool: web::Data<PgPool>, path: web::Path<Uuid>, form: Option<web::Json<ReturnItemBorrowing>>) -> Result<HttpResponse, ApiError> {
     let id = path.into_inner();
+    let return_condition_id = form.as_ref().and_then(|f| f.condition_id);
     
     // Parse user ID from claims
-    let user_id = match Uuid::parse_str(&claims.sub) {
-        Ok(id) => id,
-        Err(_) => {
-            return HttpResponse::BadRequest().json(serde_json::json!({
-                "error": "Invalid user ID"
-            }));
-        }
-    };
+    let user_id = claims.user_id()?;
     
     // Get the borrowing record
     let borrowing = sqlx::query_as::<_, ItemBorrowing>(
@@ -444,68 +450,63 @@ pub async fn return_borrowing(claims: Claims, pool: web::Data<PgPool>, path: web
     )
     .bind(id)
     .fetch_optional(pool.get_ref())
-    .await;
-    
-    let borrowing = match borrowing {
-        Ok(Some(b)) => b,
-        Ok(None) => {
-            return HttpResponse::NotFound().json(serde_json::json!({
-                "error": "Borrowing not found"
-            }));
-        },
-        Err(e) => {
-            return HttpResponse::InternalServerError().json(serde_json::json!({
-                "error": e.to_string()
-            }));
-        }
-    };
+    .await?
+    .ok_or_else(|| ApiError::NotFound("Borrowing not found".to_string()))?;
     
     // Check if borrowing is approved
     if borrowing.status != "approved" {
-        return HttpResponse::BadRequest().json(serde_json::json!({
-            "error": "Borrowing is not in approved status"
-        }));
+        return Err(ApiError::BadRequest("Borrowing is not in approved status".to_string()));
     }
     
     // Check if user is the borrower or has permission to manage borrowings
     let is_borrower = borrowing.borrower_id.to_string() == claims.sub;
-    let can_manage = has_permission(&claims, pool.get_ref(), "manage_borrowings").await;
+    let can_manage = perms.has("manage_borrowings");
     
     if !is_borrower && !can_manage {
-        return HttpResponse::Forbidden().json(serde_json::json!({
-            "error": "You don't have permission to return this item"
-        }));
+        return Err(ApiError::Forbidden("You don't have permission to return this item".to_string()));
     }
     
     // Start a transaction
-    let mut tx = match pool.begin().await {
-        Ok(tx) => tx,
-        Err(e) => {
-            return HttpResponse::InternalServerError().json(serde_json::json!({
-                "error": format!("Failed to start transaction: {}", e)
-            }));
+    let mut tx = pool.begin().await?;
+    
+    // Record the condition reported at return on the item itself
+    if let Some(condition_id) = return_condition_id {
+        let update_condition = sqlx::query("UPDATE items SET condition_id = $1 WHERE id = $2")
+            .bind(condition_id)
+            .bind(borrowing.item_id)
+            .execute(&mut *tx)
+            .await;
+        if let Err(e) = update_condition {
+            let _ = tx.rollback().await;
+            return Err(ApiError::Internal(format!("Failed to update item condition: {}", e)));
         }
-    };
+    }
     
-    // Update the borrowing status
+    // Update the borrowing status; the status guard lets only one concurrent return
+    // through, so the late penalty and item log are written once
     let updated_borrowing = sqlx::query_as::<_, ItemBorrowing>(
         "UPDATE item_borrowings 
-         SET status = 'returned', actual_return_date = now() 
-         WHERE id = $1 
+         SET status = 'returned', actual_return_date = now(),
+             return_condition_id = (SELECT condition_id FROM items WHERE id = item_borrowings.item_id)
+         WHERE id = $1 AND status = 'approved'
          RETURNING *"
     )
     .bind(id)
-    .fetch_one(&mut *tx)
-    .await;
-    
-    let updated_borrowing = match updated_borrowing {
-        Ok(b) => b,
-        Err(e) => {
-            let _ = tx.rollback().await;
-            return HttpResponse::InternalServerError().json(serde_json::json!({
-                "error": format!("Failed to update borrowing: {}", e)
-            }));
-        }
+    .fetch_optional(&mut *tx)
+    .await?
+    .ok_or_else(|| ApiError::Conflict("Borrowing has already been returned".to_string()))?;
+    
+    // Charge a late-return penalty according to the matching penalty rule
+    let penalty = match updated_borrowing.actual_return_date {
+        Some(returned_at) => charge_late_return(
+            &mut tx,
+            updated_borrowing.id,
+            updated_borrowing.borrower_id,
+            updated_borrowing.item_id,
+            updated_borrowing.expected_return_date,
+            returned_at,
+        ).await?,
+        None => None,
     };
     
     // Update the item status back to 'active'
@@ -513,66 +514,456 @@ pub async fn return_borrowing(claims: Claims, pool: web::Data<PgPool>, path: web
         "SELECT id FROM item_statuses WHERE name = 'active'"
     )
     .fetch_one(&mut *tx)
-    .await;
-    
-    let available_status_id = match active_status {
-        Ok(status) => status.id,
-        Err(e) => {
-            let _ = tx.rollback().await;
-            return HttpResponse::InternalServerError().json(serde_json::json!({
-                "error": format!("Failed to get active status: {}", e)
-            }));
-        }
-    };
+    .await?;
     
-    // Update the item status
-    let update_item = sqlx::query!(
+    sqlx::query!(
         "UPDATE items SET status_id = $1 WHERE id = $2",
-        available_status_id,
+        active_status.id,
         borrowing.item_id
     )
     .execute(&mut *tx)
-    .await;
+    .await?;
     
-    match update_item {
-        Ok(_) => {
-            // Log the return
-            let log = sqlx::query(
-                "INSERT INTO item_logs (item_id, action, note, by) 
-                 VALUES ($1, $2, $3, $4)"
-            )
-            .bind(borrowing.item_id)
-            .bind("item_returned")
-            .bind(format!("Item returned: {} units", borrowing.quantity))
-            .bind(user_id)
-            .execute(&mut *tx)
-            .await;
-            
-            match log {
-                Ok(_) => {
-                    // Commit the transaction
-                    match tx.commit().await {
-                        Ok(_) => HttpResponse::Ok().json(updated_borrowing),
-                        Err(e) => HttpResponse::InternalServerError().json(serde_json::json!({
-                            "error": format!("Failed to commit transaction: {}", e)
-                        })),
-                    }
-                },
-                Err(e) => {
-                    let _ = tx.rollback().await;
-                    HttpResponse::InternalServerError().json(serde_json::json!({
-                        "error": format!("Failed to log return: {}", e)
-                    }))
-                }
-            }
+    // Log the return
+    sqlx::query(
+        "INSERT INTO item_logs (item_id, action, note, by) 
+         VALUES ($1, $2, $3, $4)"
+    )
+    .bind(borrowing.item_id)
+    .bind("item_returned")
+    .bind(format!("Item returned: {} units", borrowing.quantity))
+    .bind(user_id)
+    .execute(&mut *tx)
+    .await?;
+    
+    tx.commit().await?;
+    Ok(HttpResponse::Ok().json(ReturnedBorrowing { borrowing: updated_borrowing, penalty }))
+}
+
+#[derive(Debug, sqlx::FromRow)]
+struct BorrowingReceiptRow {
+    id: Uuid,
+    item_name: String,
+    borrower_id: Uuid,
+    borrower_name: String,
+    approver_name: Option<String>,
+    quantity: i32,
+    borrowed_at: DateTime<Utc>,
+    expected_return_date: DateTime<Utc>,
+    actual_return_date: Option<DateTime<Utc>>,
+    checkout_condition: Option<String>,
+    return_condition: Option<String>,
+    notes: Option<String>,
+    status: String,
+}
+
+async fn borrowing_receipt(
+    perms: UserPermissions,
+    pool: web::Data<PgPool>,
+    config: web::Data<Config>,
+    drive_config: web::Data<DriveConfig>,
+    drive_client: web::Data<Arc<Mutex<DriveClient>>>,
+    id: Uuid,
+    kind: ReceiptKind,
+) -> Result<HttpResponse, ApiError> {
+    let row = sqlx::query_as::<_, BorrowingReceiptRow>(
+        "SELECT b.id, i.name as item_name, b.borrower_id, u.name as borrower_name,
+                a.name as approver_name, b.quantity, b.borrowed_at, b.expected_return_date,
+                b.actual_return_date, cc.name as checkout_condition, rc.name as return_condition,
+                b.notes, b.status
+         FROM item_borrowings b
+         JOIN items i ON b.item_id = i.id
+         JOIN users u ON b.borrower_id = u.id
+         LEFT JOIN users a ON b.approved_by = a.id
+         LEFT JOIN conditions cc ON b.checkout_condition_id = cc.id
+         LEFT JOIN conditions rc ON b.return_condition_id = rc.id
+         WHERE b.id = $1"
+    )
+    .bind(id)
+    .fetch_optional(pool.get_ref())
+    .await?
+    .ok_or_else(|| ApiError::NotFound("Borrowing not found".to_string()))?;
+    
+    // Only the borrower or staff who can see all borrowings may print receipts
+    let is_borrower = row.borrower_id == perms.user_id;
+    if !is_borrower && !perms.has("view_all_borrowings") {
+        return Err(ApiError::NotFound("Borrowing not found".to_string()));
+    }
+    
+    match kind {
+        ReceiptKind::Handover if row.status == "pending" || row.status == "rejected" => {
+            return Err(ApiError::BadRequest("Borrowing has not been handed over yet".to_string()));
         },
-        Err(e) => {
-            let _ = tx.rollback().await;
-            HttpResponse::InternalServerError().json(serde_json::json!({
-                "error": format!("Failed to update item status: {}", e)
-            }))
+        ReceiptKind::Return if row.status != "returned" => {
+            return Err(ApiError::BadRequest("Borrowing has not been returned yet".to_string()));
+        },
+        _ => {}
+    }
+    
+    let stage = match kind {
+        ReceiptKind::Handover => "checkout",
+        ReceiptKind::Return => "return",
+    };
+    let (borrower_signature, staff_signature) =
+        load_receipt_signatures(pool.get_ref(), &drive_config, &drive_client, id, stage).await;
+    
+    let data = ReceiptData {
+        borrowing_id: row.id,
+        item_name: row.item_name,
+        quantity: row.quantity,
+        borrower_name: row.borrower_name,
+        approver_name: row.approver_name,
+        borrowed_at: row.borrowed_at,
+        expected_return_date: row.expected_return_date,
+        actual_return_date: row.actual_return_date,
+        checkout_condition: row.checkout_condition,
+        return_condition: row.return_condition,
+        notes: row.notes,
+        status: row.status,
+        borrower_signature,
+        staff_signature,
+    };
+    
+    // QR code points to the borrowing detail page in the frontend
+    let qr_content = format!("{}/borrowings/{}", config.cors.frontend_url.trim_end_matches('/'), id);
+    
+    let prefix = match kind {
+        ReceiptKind::Handover => "handover",
+        ReceiptKind::Return => "return",
+    };
+    
+    let bytes = render_receipt(kind, &data, &qr_content)
+        .map_err(|e| ApiError::Internal(format!("Failed to generate receipt: {}", e)))?;
+    Ok(HttpResponse::Ok()
+        .content_type("application/pdf")
+        .append_header((
+            "Content-Disposition",
+            format!("inline; filename=\"{}-receipt-{}.pdf\"", prefix, id),
+        ))
+        .body(bytes))
+}
+
+/// Fetches the borrower and staff signature images for a receipt stage.
+/// Signatures that cannot be downloaded are left out so the receipt is still generated.
+async fn load_receipt_signatures(
+    pool: &PgPool,
+    drive_config: &DriveConfig,
+    drive_client: &Mutex<DriveClient>,
+    borrowing_id: Uuid,
+    stage: &str,
+) -> (Option<SignatureImage>, Option<SignatureImage>) {
+    let signatures = sqlx::query_as::<_, BorrowingSignature>(&format!(
+        "SELECT {}
+         FROM borrowing_signatures s
+         LEFT JOIN users u ON s.signed_by = u.id
+         WHERE s.borrowing_id = $1 AND s.stage = $2",
+        SIGNATURE_COLUMNS
+    ))
+    .bind(borrowing_id)
+    .bind(stage)
+    .fetch_all(pool)
+    .await
+    .unwrap_or_else(|e| {
+        println!("[WARN] Failed to load signatures for borrowing {}: {}", borrowing_id, e);
+        Vec::new()
+    });
+    
+    let mut borrower = None;
+    let mut staff = None;
+    for signature in signatures {
+        let file = match signature.private_file() {
+            Ok(file) => file,
+            Err(e) => {
+                println!("[WARN] Failed to download signature {}: {}", signature.id, e);
+                continue;
+            }
+        };
+        let png = match read_private_file(drive_config, drive_client, &file).await {
+            Ok(png) => png,
+            Err(e) => {
+                println!("[WARN] Failed to download signature {}: {}", signature.id, e);
+                continue;
+            }
+        };
+        let image = SignatureImage { signer_name: signature.signer_name, png };
+        match signature.signer_role.as_str() {
+            "borrower" => borrower = Some(image),
+            _ => staff = Some(image),
         }
     }
+    (borrower, staff)
+}
+
+#[utoipa::path(
+    tag = "borrowings",
+    responses(
+        (status = 200, content_type = "application/pdf", body = Vec<u8>),
+        (status = 400, description = "Barang belum diserahkan", body = ErrorBody),
+        (status = 404, body = ErrorBody),
+        (status = 403, description = "Butuh salah satu izin peminjaman", body = ErrorBody),
+    )
+)]
+#[get("/{id}/receipt", wrap = "RequirePermission::any(BORROWING_PERMISSIONS)")]
+pub async fn get_borrowing_receipt(
+    perms: UserPermissions,
+    pool: web::Data<PgPool>,
+    config: web::Data<Config>,
+    drive_config: web::Data<DriveConfig>,
+    drive_client: web::Data<Arc<Mutex<DriveClient>>>,
+    path: web::Path<Uuid>,
+) -> Result<HttpResponse, ApiError> {
+    borrowing_receipt(perms, pool, config, drive_config, drive_client, path.into_inner(), ReceiptKind::Handover).await
+}
+
+#[utoipa::path(
+    tag = "borrowings",
+    responses(
+        (status = 200, content_type = "application/pdf", body = Vec<u8>),
+        (status = 400, description = "Barang belum dikembalikan", body = ErrorBody),
+        (status = 404, body = ErrorBody),
+        (status = 403, description = "Butuh salah satu izin peminjaman", body = ErrorBody),
+    )
+)]
+#[get("/{id}/return-receipt", wrap = "RequirePermission::any(BORROWING_PERMISSIONS)")]
+pub async fn get_return_receipt(
+    perms: UserPermissions,
+    pool: web::Data<PgPool>,
+    config: web::Data<Config>,
+    drive_config: web::Data<DriveConfig>,
+    drive_client: web::Data<Arc<Mutex<DriveClient>>>,
+    path: web::Path<Uuid>,
+) -> Result<HttpResponse, ApiError> {
+    borrowing_receipt(perms, pool, config, drive_config, drive_client, path.into_inner(), ReceiptKind::Return).await
+}
+
+#[utoipa::path(
+    tag = "borrowings",
+    responses(
+        (status = 200, body = Vec<BorrowingSignature>),
+        (status = 404, body = ErrorBody),
+        (status = 403, description = "Butuh salah satu izin peminjaman", body = ErrorBody),
+    )
+)]
+#[get("/{id}/signatures", wrap = "RequirePermission::any(BORROWING_PERMISSIONS)")]
+pub async fn get_borrowing_signatures(
+    claims: Claims, perms: UserPermissions,
+    pool: web::Data<PgPool>,
+    path: web::Path<Uuid>,
+    config: web::Data<DriveConfig>,
+) -> Result<HttpResponse, ApiError> {
+    let id = path.into_inner();
+    ensure_can_view_borrowing(pool.get_ref(), &claims, &perms, id).await?;
+    
+    let signatures: Vec<BorrowingSignature> = sqlx::query_as::<_, BorrowingSignature>(&format!(
+        "SELECT {}
+         FROM borrowing_signatures s
+         LEFT JOIN users u ON s.signed_by = u.id
+         WHERE s.borrowing_id = $1
+         ORDER BY s.created_at",
+        SIGNATURE_COLUMNS
+    ))
+    .bind(id)
+    .fetch_all(pool.get_ref())
+    .await?
+    .into_iter()
+    .map(|signature| signature.with_image_url(&config))
+    .collect();
+    
+    Ok(HttpResponse::Ok().json(signatures))
+}
+
+/// Peminjam hanya boleh melihat peminjamannya sendiri, kecuali punya izin view_all_borrowings
+async fn ensure_can_view_borrowing(pool: &PgPool, claims: &Claims, perms: &UserPermissions, id: Uuid) -> Result<(), ApiError> {
+    let borrower_id = sqlx::query_scalar::<_, Uuid>("SELECT borrower_id FROM item_borrowings WHERE id = $1")
+        .bind(id)
+        .fetch_optional(pool)
+        .await?
+        .ok_or_else(|| ApiError::NotFound("Borrowing not found".to_string()))?;
+    if borrower_id.to_string() != claims.sub && !perms.has("view_all_borrowings") {
+        return Err(ApiError::NotFound("Borrowing not found".to_string()));
+    }
+    Ok(())
+}
+
+/// Gambar tanda tangan (PNG). Tanda tangan tidak disimpan publik, jadi hanya bisa diambil
+/// lewat endpoint ini dengan akses yang sama seperti daftar tanda tangan.
+#[utoipa::path(
+    tag = "borrowings",
+    responses(
+        (status = 200, content_type = "image/png", body = Vec<u8>),
+        (status = 404, body = ErrorBody),
+        (status = 403, description = "Butuh salah satu izin peminjaman", body = ErrorBody),
+    )
+)]
+#[get("/{id}/signatures/{signature_id}/image", wrap = "RequirePermission::any(BORROWING_PERMISSIONS)")]
+pub async fn get_borrowing_signature_image(
+    claims: Claims, perms: UserPermissions,
+    pool: web::Data<PgPool>,
+    path: web::Path<(Uuid, Uuid)>,
+    config: web::Data<DriveConfig>,
+    client: web::Data<Arc<Mutex<DriveClient>>>,
+) -> Result<HttpResponse, ApiError> {
+    let (id, signature_id) = path.into_inner();
+    ensure_can_view_borrowing(pool.get_ref(), &claims, &perms, id).await?;
+    
+    let signature = sqlx::query_as::<_, BorrowingSignature>(&format!(
+        "SELECT {}
+         FROM borrowing_signatures s
+         LEFT JOIN users u ON s.signed_by = u.id
+         WHERE s.borrowing_id = $1 AND s.id = $2",
+        SIGNATURE_COLUMNS
+    ))
+    .bind(id)
+    .bind(signature_id)
+    .fetch_optional(pool.get_ref())
+    .await?
+    .ok_or_else(|| ApiError::NotFound("Signature not found".to_string()))?;
+    
+    let png = read_private_file(&config, &client, &signature.private_file()?).await?;
+    Ok(HttpResponse::Ok()
+        .content_type("image/png")
+        .append_header(("Cache-Control", "private, max-age=3600"))
+        .append_header(("X-Content-Type-Options", "nosniff"))
+        .body(png))
+}
+
+/// Uploads a drawn signature (PNG) for the checkout or return of a borrowing.
+/// Multipart fields: `stage` (checkout|return), `role` (borrower|staff) and `file`.
+/// Uploading again for the same stage and role replaces the previous signature.
+#[utoipa::path(
+    tag = "borrowings",
+    request_body(content_type = "multipart/form-data", content = SignatureUpload),
+    responses(
+        (status = 200, body = BorrowingSignature),
+        (status = 400, body = ErrorBody),
+        (status = 403, description = "Butuh salah satu izin peminjaman", body = ErrorBody),
+        (status = 404, body = ErrorBody),
+        (status = 413, body = ErrorBody),
+    )
+)]
+#[post("/{id}/signatures", wrap = "RequirePermission::any(BORROWING_PERMISSIONS)")]
+pub async fn upload_borrowing_signature(
+    claims: Claims, perms: UserPermissions,
+    pool: web::Data<PgPool>,
+    path: web::Path<Uuid>,
+    mut payload: Multipart,
+    config: web::Data<DriveConfig>,
+    client: web::Data<Arc<Mutex<DriveClient>>>,
+) -> Result<HttpResponse, ApiError> {
+    let id = path.into_inner();
+    let user_id = claims.user_id()?;
+    
+    let mut stage: Option<String> = None;
+    let mut role: Option<String> = None;
+    let mut file: Option<(Vec<u8>, String)> = None;
+    
+    while let Some(mut field) = match payload.try_next().await {
+        Ok(field) => field,
+        Err(e) => return Err(ApiError::BadRequest(format!("Error extracting field: {}", e))),
+    } {
+        let field_name = field.content_disposition().get_name().unwrap_or("").to_string();
+        let content_type = field.content_type().map(|t| t.to_string()).unwrap_or_default();
+        
+        let mut bytes = Vec::new();
+        while let Some(chunk) = match field.try_next().await {
+            Ok(chunk) => chunk,
+            Err(e) => return Err(ApiError::BadRequest(format!("Error reading field: {}", e))),
+        } {
+            if bytes.len() + chunk.len() > MAX_SIGNATURE_BYTES {
+                return Err(ApiError::PayloadTooLarge("Signature image must be at most 2 MB".to_string()));
+            }
+            bytes.extend_from_slice(&chunk);
+        }
+        
+        match field_name.as_str() {
+            "stage" => stage = Some(String::from_utf8_lossy(&bytes).trim().to_string()),
+            "role" => role = Some(String::from_utf8_lossy(&bytes).trim().to_string()),
+            "file" => file = Some((bytes, content_type)),
+            _ => {}
+        }
+    }
+    
+    let stage = match stage.as_deref() {
+        Some("checkout") | Some("return") => stage.unwrap(),
+        _ => return Err(ApiError::BadRequest("stage must be 'checkout' or 'return'".to_string())),
+    };
+    let role = match role.as_deref() {
+        Some("borrower") | Some("staff") => role.unwrap(),
+        _ => return Err(ApiError::BadRequest("role must be 'borrower' or 'staff'".to_string())),
+    };
+    let bytes = match file {
+        Some((bytes, content_type)) if content_type == "image/png" && bytes.starts_with(PNG_MAGIC) => bytes,
+        Some(_) => return Err(ApiError::BadRequest("Signature must be a PNG image".to_string())),
+        None => return Err(ApiError::BadRequest("Signature file is required".to_string())),
+    };
+    
+    let borrowing = sqlx::query("SELECT item_id, borrower_id, status FROM item_borrowings WHERE id = $1")
+        .bind(id)
+        .fetch_optional(pool.get_ref())
+        .await?
+        .ok_or_else(|| ApiError::NotFound("Borrowing not found".to_string()))?;
+    let (item_id, borrower_id, status) = (
+        borrowing.get::<Uuid, _>("item_id"),
+        borrowing.get::<Uuid, _>("borrower_id"),
+        borrowing.get::<String, _>("status"),
+    );
+    
+    // Borrowers sign for themselves; staff may also capture the borrower's signature on their own device
+    let can_manage = perms.has("manage_borrowings");
+    let signed_by = if role == "borrower" {
+        if borrower_id != user_id && !can_manage {
+            return Err(ApiError::Forbidden("Only the borrower can sign as borrower".to_string()));
+        }
+        borrower_id
+    } else {
+        if !can_manage && !perms.has("approve_borrowings") {
+            return Err(ApiError::Forbidden("You don't have permission to sign as staff".to_string()));
+        }
+        user_id
+    };
+    
+    if status == "rejected" || (stage == "return" && status == "pending") {
+        return Err(ApiError::BadRequest(format!("Cannot sign {} for a {} borrowing", stage, status)));
+    }
+    
+    // Tanda tangan disimpan privat, hanya bisa diambil lewat get_borrowing_signature_image
+    let filename = format!("signature-{}-{}-{}.png", id, stage, role);
+    let file = upload_private_file(&config, &client, &filename, bytes, "image/png").await?;
+    
+    let signature = sqlx::query_as::<_, BorrowingSignature>(&format!(
+        "WITH saved AS (
+             INSERT INTO borrowing_signatures (borrowing_id, stage, signer_role, signed_by, storage_backend, file_id)
+             VALUES ($1, $2, $3, $4, $5, $6)
+             ON CONFLICT (borrowing_id, stage, signer_role)
+             DO UPDATE SET signed_by = EXCLUDED.signed_by, storage_backend = EXCLUDED.storage_backend,
+                           file_id = EXCLUDED.file_id, created_at = NOW()
+             RETURNING *
+         )
+         SELECT {}
+         FROM saved s
+         LEFT JOIN users u ON s.signed_by = u.id",
+        SIGNATURE_COLUMNS
+    ))
+    .bind(id)
+    .bind(&stage)
+    .bind(&role)
+    .bind(signed_by)
+    .bind(file.backend.as_str())
+    .bind(&file.file_id)
+    .fetch_one(pool.get_ref())
+    .await?
+    .with_image_url(&config);
+    
+    let _ = sqlx::query(
+        "INSERT INTO item_logs (item_id, action, note, by) VALUES ($1, $2, $3, $4)"
+    )
+    .bind(item_id)
+    .bind("borrowing_signed")
+    .bind(format!("{} signature captured for {}", role, stage))
+    .bind(user_id)
+    .execute(pool.get_ref())
+    .await;
+    Ok(HttpResponse::Ok().json(signature))
 }
 
 pub fn borrowings_config(cfg: &mut web::ServiceConfig) {
@@ -580,5 +971,25 @@ pub fn borrowings_config(cfg: &mut web::ServiceConfig) {
         .service(get_borrowing_by_id)
         .service(create_borrowing)
         .service(approve_borrowing)
-        .service(return_borrowing);
+        .service(return_borrowing)
+        .service(get_borrowing_receipt)
+        .service(get_return_receipt)
+        .service(get_borrowing_signatures)
+        .service(get_borrowing_signature_image)
+        .service(upload_borrowing_signature);
 }
+
+#[derive(OpenApi)]
+#[openapi(paths(
+    get_borrowings,
+    get_borrowing_by_id,
+    create_borrowing,
+    approve_borrowing,
+    return_borrowing,
+    get_borrowing_receipt,
+    get_return_receipt,
+    get_borrowing_signatures,
+    get_borrowing_signature_image,
+    upload_borrowing_signature,
+))]
+pub struct BorrowingsApi;
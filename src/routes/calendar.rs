@@ -0,0 +1,253 @@
+use actix_web::{get, post, delete, web, HttpResponse};
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use utoipa::{OpenApi, ToSchema};
+use uuid::Uuid;
+
+use crate::config::Config;
+use crate::error::{ApiError, ErrorBody};
+use crate::middleware::jwt_extractor::Claims;
+use crate::middleware::permission_guard::UserPermissions;
+use crate::services::ical::{render_calendar, CalendarEvent};
+use crate::services::tokens::{generate_token, hash_token};
+
+/// Panjang awal token yang disimpan untuk ditampilkan di daftar feed
+const DISPLAY_PREFIX_LEN: usize = 8;
+
+#[derive(Debug, Serialize, sqlx::FromRow, ToSchema)]
+pub struct CalendarFeed {
+    pub id: Uuid,
+    /// Awal token, untuk mengenali feed tanpa menampilkan seluruhnya
+    pub token_prefix: String,
+    pub user_id: Option<Uuid>,
+    pub location_id: Option<Uuid>,
+    pub created_by: Option<Uuid>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Feed baru; `token` dan `url` hanya ditampilkan sekali
+#[derive(Debug, Serialize, ToSchema)]
+pub struct CalendarFeedResponse {
+    #[serde(flatten)]
+    pub feed: CalendarFeed,
+    pub token: String,
+    pub url: String,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct NewCalendarFeed {
+    pub location_id: Option<Uuid>,
+}
+
+#[derive(Debug, sqlx::FromRow)]
+struct FeedBorrowing {
+    id: Uuid,
+    item_name: String,
+    borrower_name: String,
+    location_name: Option<String>,
+    quantity: i32,
+    borrowed_at: DateTime<Utc>,
+    expected_return_date: DateTime<Utc>,
+    actual_return_date: Option<DateTime<Utc>>,
+    notes: Option<String>,
+    status: String,
+}
+
+fn feed_url(config: &Config, token: &str) -> String {
+    format!("{}/api/calendar/feeds/{}.ics", config.base_url().trim_end_matches('/'), token)
+}
+
+#[utoipa::path(tag = "calendar", responses((status = 200, body = Vec<CalendarFeed>)))]
+#[get("/feeds")]
+pub async fn get_calendar_feeds(claims: Claims, pool: web::Data<PgPool>) -> Result<HttpResponse, ApiError> {
+    let user_id = claims.user_id()?;
+    let feeds = sqlx::query_as::<_, CalendarFeed>(
+        "SELECT id, token_prefix, user_id, location_id, created_by, created_at
+         FROM calendar_feeds
+         WHERE created_by = $1 OR user_id = $1
+         ORDER BY created_at DESC"
+    )
+    .bind(user_id)
+    .fetch_all(pool.get_ref())
+    .await?;
+    Ok(HttpResponse::Ok().json(feeds))
+}
+
+#[utoipa::path(
+    tag = "calendar",
+    request_body = NewCalendarFeed,
+    responses(
+        (status = 200, body = CalendarFeedResponse),
+        (status = 403, description = "Feed per lokasi butuh izin view_all_borrowings, atau request memakai token API", body = ErrorBody),
+    )
+)]
+#[post("/feeds")]
+pub async fn create_calendar_feed(claims: Claims, perms: UserPermissions, pool: web::Data<PgPool>, config: web::Data<Config>, form: web::Json<NewCalendarFeed>) -> Result<HttpResponse, ApiError> {
+    // Token feed tidak kedaluwarsa, jadi tidak boleh dibuat dari token API yang bisa kedaluwarsa/dicabut
+    claims.require_session()?;
+    let user_id = claims.user_id()?;
+
+    // Feed per lokasi berisi peminjaman orang lain, jadi butuh izin melihat semua peminjaman
+    let owner_user_id = match form.location_id {
+        Some(_) => {
+            if !perms.has("view_all_borrowings") {
+                return Err(ApiError::Forbidden(
+                    "You don't have permission to subscribe to location calendars".to_string()
+                ));
+            }
+            None
+        },
+        None => Some(user_id),
+    };
+
+    let token = generate_token();
+    let feed = sqlx::query_as::<_, CalendarFeed>(
+        "INSERT INTO calendar_feeds (token_hash, token_prefix, user_id, location_id, created_by)
+         VALUES ($1, $2, $3, $4, $5)
+         RETURNING id, token_prefix, user_id, location_id, created_by, created_at"
+    )
+    .bind(hash_token(&token))
+    .bind(&token[..DISPLAY_PREFIX_LEN])
+    .bind(owner_user_id)
+    .bind(form.location_id)
+    .bind(user_id)
+    .fetch_one(pool.get_ref())
+    .await?;
+    Ok(HttpResponse::Ok().json(CalendarFeedResponse { url: feed_url(&config, &token), token, feed }))
+}
+
+#[utoipa::path(
+    tag = "calendar",
+    responses(
+        (status = 200, body = serde_json::Value),
+        (status = 404, body = ErrorBody),
+    )
+)]
+#[delete("/feeds/{id}")]
+pub async fn delete_calendar_feed(claims: Claims, perms: UserPermissions, pool: web::Data<PgPool>, path: web::Path<Uuid>) -> Result<HttpResponse, ApiError> {
+    let id = path.into_inner();
+    let user_id = claims.user_id()?;
+    let can_manage = perms.has("manage_borrowings");
+    sqlx::query(
+        "DELETE FROM calendar_feeds
+         WHERE id = $1 AND ($2 OR created_by = $3 OR user_id = $3)
+         RETURNING id"
+    )
+    .bind(id)
+    .bind(can_manage)
+    .bind(user_id)
+    .fetch_optional(pool.get_ref())
+    .await?
+    .ok_or_else(|| ApiError::NotFound("Calendar feed not found".to_string()))?;
+    Ok(HttpResponse::Ok().json(serde_json::json!({"success": true})))
+}
+
+/// Feed iCalendar publik, diautentikasi dengan token di URL (tanpa cookie JWT)
+#[utoipa::path(
+    tag = "calendar",
+    security(()),
+    responses(
+        (status = 200, content_type = "text/calendar", body = String),
+        (status = 404, body = ErrorBody),
+    )
+)]
+#[get("/feeds/{token}.ics")]
+pub async fn get_calendar_feed_ics(pool: web::Data<PgPool>, path: web::Path<String>) -> Result<HttpResponse, ApiError> {
+    let token = path.into_inner();
+    let feed = sqlx::query_as::<_, CalendarFeed>(
+        "SELECT f.id, f.token_prefix, f.user_id, f.location_id, f.created_by, f.created_at
+         FROM calendar_feeds f
+         LEFT JOIN users u ON u.id = f.user_id
+         WHERE f.token_hash = $1 AND (f.user_id IS NULL OR u.is_active)"
+    )
+    .bind(hash_token(&token))
+    .fetch_optional(pool.get_ref())
+    .await?
+    .ok_or_else(|| ApiError::NotFound("Calendar feed not found".to_string()))?;
+
+    let borrowings = sqlx::query_as::<_, FeedBorrowing>(
+        "SELECT b.id, i.name as item_name, u.name as borrower_name, l.name as location_name,
+                b.quantity, b.borrowed_at, b.expected_return_date, b.actual_return_date,
+                b.notes, b.status
+         FROM item_borrowings b
+         JOIN items i ON b.item_id = i.id
+         JOIN users u ON b.borrower_id = u.id
+         LEFT JOIN locations l ON i.location_id = l.id
+         WHERE b.status <> 'rejected'
+           AND (($1::uuid IS NOT NULL AND b.borrower_id = $1)
+             OR ($2::uuid IS NOT NULL AND i.location_id = $2))
+         ORDER BY b.borrowed_at DESC"
+    )
+    .bind(feed.user_id)
+    .bind(feed.location_id)
+    .fetch_all(pool.get_ref())
+    .await?;
+
+    let calendar_name = match (feed.user_id, &borrowings.first()) {
+        (Some(_), Some(b)) => format!("Peminjaman {}", b.borrower_name),
+        (None, Some(b)) => format!("Peminjaman {}", b.location_name.clone().unwrap_or_default()),
+        _ => "Peminjaman".to_string(),
+    };
+
+    let now = Utc::now();
+    let events: Vec<CalendarEvent> = borrowings
+        .iter()
+        .map(|b| {
+            let is_overdue = b.actual_return_date.is_none()
+                && (b.status == "approved" || b.status == "overdue")
+                && b.expected_return_date < now;
+            let end = b.actual_return_date.unwrap_or(b.expected_return_date);
+            let end = if end <= b.borrowed_at { b.borrowed_at + Duration::hours(1) } else { end };
+
+            let mut summary = format!("{} ({} unit)", b.item_name, b.quantity);
+            if is_overdue {
+                summary = format!("[OVERDUE] {}", summary);
+            }
+            let mut description = format!(
+                "Borrower: {}\nStatus: {}\nExpected return: {}",
+                b.borrower_name,
+                if is_overdue { "overdue" } else { b.status.as_str() },
+                b.expected_return_date.to_rfc3339()
+            );
+            if let Some(returned) = b.actual_return_date {
+                description.push_str(&format!("\nReturned: {}", returned.to_rfc3339()));
+            }
+            if let Some(notes) = &b.notes {
+                description.push_str(&format!("\nNotes: {}", notes));
+            }
+
+            let mut categories = vec!["BORROWING".to_string()];
+            if is_overdue {
+                categories.push("OVERDUE".to_string());
+            }
+
+            CalendarEvent {
+                uid: format!("{}@inman", b.id),
+                start: b.borrowed_at,
+                end,
+                summary,
+                description: Some(description),
+                location: b.location_name.clone(),
+                categories,
+                status: Some(if b.status == "pending" { "TENTATIVE" } else { "CONFIRMED" }),
+            }
+        })
+        .collect();
+
+    Ok(HttpResponse::Ok()
+        .content_type("text/calendar; charset=utf-8")
+        .append_header(("Cache-Control", "no-cache"))
+        .body(render_calendar(&calendar_name, &events)))
+}
+
+pub fn calendar_config(cfg: &mut web::ServiceConfig) {
+    cfg.service(get_calendar_feeds)
+        .service(create_calendar_feed)
+        .service(get_calendar_feed_ics)
+        .service(delete_calendar_feed);
+}
+
+#[derive(OpenApi)]
+#[openapi(paths(get_calendar_feeds, create_calendar_feed, delete_calendar_feed, get_calendar_feed_ics))]
+pub struct CalendarApi;
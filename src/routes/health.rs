@@ -0,0 +1,108 @@
+use actix_web::{get, web, HttpResponse, Responder};
+use sqlx::PgPool;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Mutex;
+use utoipa::OpenApi;
+
+use crate::config::{Config, StorageBackend};
+use crate::services::drive_storage::{get_access_token, DriveClient};
+
+const DB_CHECK_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// Liveness: proses berjalan dan bisa melayani request
+#[utoipa::path(tag = "health", security(()), responses((status = 200, description = "Proses berjalan", body = serde_json::Value)))]
+#[get("/health")]
+pub async fn health() -> impl Responder {
+    HttpResponse::Ok().json(serde_json::json!({"status": "ok"}))
+}
+
+/// Readiness: database bisa diakses. Status Google Drive hanya dilaporkan,
+/// karena endpoint selain upload tetap bisa melayani request.
+#[utoipa::path(
+    tag = "health",
+    security(()),
+    responses(
+        (status = 200, description = "Database bisa diakses", body = serde_json::Value),
+        (status = 503, description = "Database tidak bisa diakses", body = serde_json::Value),
+    )
+)]
+#[get("/ready")]
+pub async fn ready(
+    pool: web::Data<PgPool>,
+    config: web::Data<Config>,
+    client: web::Data<Arc<Mutex<DriveClient>>>,
+) -> impl Responder {
+    let database = match tokio::time::timeout(DB_CHECK_TIMEOUT, sqlx::query("SELECT 1").execute(pool.get_ref())).await {
+        Ok(Ok(_)) => serde_json::json!({"status": "ok"}),
+        Ok(Err(e)) => serde_json::json!({"status": "error", "error": e.to_string()}),
+        Err(_) => serde_json::json!({"status": "error", "error": "timed out"}),
+    };
+    let database_ok = database["status"] == "ok";
+
+    let drive = if config.storage.active_backend() == StorageBackend::Local {
+        serde_json::json!({"status": "disabled"})
+    } else {
+        let mut client_guard = client.lock().await;
+        let configured = !client_guard.credentials.client_email.is_empty()
+            && !client_guard.credentials.private_key.is_empty();
+        if !configured {
+            serde_json::json!({"status": "not_configured"})
+        } else {
+            // Token di-cache oleh DriveClient, jadi ini hanya memanggil Google saat token kedaluwarsa
+            match get_access_token(&mut client_guard).await {
+                Ok(_) => serde_json::json!({"status": "ok"}),
+                Err(e) => serde_json::json!({"status": "error", "error": e.to_string()}),
+            }
+        }
+    };
+
+    let body = serde_json::json!({
+        "status": if database_ok { "ready" } else { "not_ready" },
+        "database": database,
+        "drive": drive,
+    });
+    if database_ok {
+        HttpResponse::Ok().json(body)
+    } else {
+        HttpResponse::ServiceUnavailable().json(body)
+    }
+}
+
+/// Versi aplikasi, commit git, dan migrasi database terakhir yang diterapkan
+#[utoipa::path(tag = "health", security(()), responses((status = 200, body = serde_json::Value)))]
+#[get("/version")]
+pub async fn version(pool: web::Data<PgPool>, config: web::Data<Config>) -> impl Responder {
+    // Render menyediakan RENDER_GIT_COMMIT saat runtime; fallback ke commit saat build
+    let git_commit = config
+        .server
+        .git_commit
+        .clone()
+        .unwrap_or_else(|| env!("GIT_COMMIT").to_string());
+
+    let migration = sqlx::query_as::<_, (i64, String)>(
+        "SELECT version, description FROM _sqlx_migrations WHERE success ORDER BY version DESC LIMIT 1"
+    )
+    .fetch_optional(pool.get_ref())
+    .await
+    .ok()
+    .flatten()
+    .map(|(version, description)| serde_json::json!({"version": version, "description": description}));
+
+    HttpResponse::Ok().json(serde_json::json!({
+        "name": env!("CARGO_PKG_NAME"),
+        "version": env!("CARGO_PKG_VERSION"),
+        "git_commit": git_commit,
+        "migration": migration,
+    }))
+}
+
+#[derive(OpenApi)]
+#[openapi(paths(health, ready, version))]
+pub struct HealthApi;
+
+pub fn health_config(cfg: &mut web::ServiceConfig) {
+    cfg.service(health)
+        .service(ready)
+        .service(version);
+}
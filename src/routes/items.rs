@@ -1,4 +1,4 @@
-use actix_web::{get, post, delete, web, HttpResponse, Responder, patch};
+use actix_web::{get, post, delete, web, HttpResponse, patch};
 use actix_web::http::header::ContentType;
 use qrcode::QrCode;
 use image::Luma;
@@ -6,20 +6,26 @@ use image::EncodableLayout;
 use image::ImageEncoder;
 use std::io::Cursor;
 
+use crate::config::Config;
+use crate::error::{ApiError, ErrorBody};
 use crate::middleware::jwt_extractor::Claims;
+use crate::middleware::permission_guard::RequirePermission;
 use serde::{Deserialize, Serialize};
 use sqlx::FromRow;
+use utoipa::{OpenApi, ToSchema};
 use uuid::Uuid;
 use chrono::{DateTime, Utc};
 use sqlx::PgPool;
 
-#[derive(Debug, Serialize, Deserialize, FromRow)]
+#[derive(Debug, Serialize, Deserialize, FromRow, ToSchema)]
 pub struct ItemLog {
     pub id: Uuid,
     pub item_id: Uuid,
     pub item_name: Option<String>,
     pub action: String,
+    #[schema(value_type = Option<Object>)]
     pub before: Option<serde_json::Value>,
+    #[schema(value_type = Option<Object>)]
     pub after: Option<serde_json::Value>,
     pub note: Option<String>,
     pub by: Option<Uuid>,
@@ -27,8 +33,9 @@ pub struct ItemLog {
     pub created_at: DateTime<Utc>,
 }
 
-#[get("/item_logs/{item_id}")]
-pub async fn get_item_logs(pool: web::Data<PgPool>, path: web::Path<Uuid>) -> impl Responder {
+#[utoipa::path(tag = "items", responses((status = 200, body = Vec<ItemLog>), (status = 403, description = "Butuh izin view_items", body = ErrorBody)))]
+#[get("/item_logs/{item_id}", wrap = "RequirePermission::new(\"view_items\")")]
+pub async fn get_item_logs(pool: web::Data<PgPool>, path: web::Path<Uuid>) -> Result<HttpResponse, ApiError> {
     let item_id = path.into_inner();
     let logs = sqlx::query_as::<_, ItemLog>(
         r#"SELECT l.id, l.item_id, i.name as item_name, l.action, l.before, l.after, l.note, l.by, u.name as user_name, l.created_at
@@ -40,15 +47,13 @@ pub async fn get_item_logs(pool: web::Data<PgPool>, path: web::Path<Uuid>) -> im
     )
         .bind(item_id)
         .fetch_all(pool.get_ref())
-        .await;
-    match logs {
-        Ok(logs) => HttpResponse::Ok().json(logs),
-        Err(e) => HttpResponse::InternalServerError().json(serde_json::json!({"error": e.to_string()})),
-    }
+        .await?;
+    Ok(HttpResponse::Ok().json(logs))
 }
 
-#[get("/item_logs")]
-pub async fn get_all_item_logs(pool: web::Data<PgPool>) -> impl Responder {
+#[utoipa::path(tag = "items", responses((status = 200, body = Vec<ItemLog>), (status = 403, description = "Butuh izin view_items", body = ErrorBody)))]
+#[get("/item_logs", wrap = "RequirePermission::new(\"view_items\")")]
+pub async fn get_all_item_logs(pool: web::Data<PgPool>) -> Result<HttpResponse, ApiError> {
     let logs = sqlx::query_as::<_, ItemLog>(
         r#"SELECT l.id, l.item_id, i.name as item_name, l.action, l.before, l.after, l.note, l.by, u.name as user_name, l.created_at
         FROM item_logs l
@@ -57,14 +62,11 @@ pub async fn get_all_item_logs(pool: web::Data<PgPool>) -> impl Responder {
         ORDER BY l.created_at DESC"#
     )
     .fetch_all(pool.get_ref())
-    .await;
-    match logs {
-        Ok(logs) => HttpResponse::Ok().json(logs),
-        Err(e) => HttpResponse::InternalServerError().json(serde_json::json!({"error": e.to_string()})),
-    }
+    .await?;
+    Ok(HttpResponse::Ok().json(logs))
 }
 
-#[derive(Debug, Serialize, Deserialize, FromRow)]
+#[derive(Debug, Serialize, Deserialize, FromRow, ToSchema)]
 pub struct Item {
     pub id: Uuid,
     pub name: String,
@@ -82,41 +84,40 @@ pub struct Item {
 }
 
 
-#[get("")]
-pub async fn get_items(pool: web::Data<PgPool>) -> impl Responder {
+#[utoipa::path(tag = "items", responses((status = 200, body = Vec<Item>), (status = 403, description = "Butuh izin view_items", body = ErrorBody)))]
+#[get("", wrap = "RequirePermission::new(\"view_items\")")]
+pub async fn get_items(pool: web::Data<PgPool>) -> Result<HttpResponse, ApiError> {
     println!("[Handler] get_items dipanggil");
     let items = sqlx::query_as::<_, Item>("SELECT * FROM items ORDER BY created_at DESC")
         .fetch_all(pool.get_ref())
-        .await;
-    match items {
-        Ok(items) => HttpResponse::Ok().json(items),
-        Err(e) => HttpResponse::InternalServerError().json(serde_json::json!({"error": e.to_string()})),
-    }
+        .await?;
+    Ok(HttpResponse::Ok().json(items))
 }
 
-#[get("/{id}")]
-pub async fn get_item_by_id(pool: web::Data<PgPool>, path: web::Path<String>) -> impl Responder {
+#[utoipa::path(
+    tag = "items",
+    params(("id" = Uuid, Path)),
+    responses(
+        (status = 200, body = Item),
+        (status = 400, description = "ID bukan UUID", body = ErrorBody),
+        (status = 404, body = ErrorBody),
+        (status = 403, description = "Butuh izin view_items", body = ErrorBody),
+    )
+)]
+#[get("/{id}", wrap = "RequirePermission::new(\"view_items\")")]
+pub async fn get_item_by_id(pool: web::Data<PgPool>, path: web::Path<String>) -> Result<HttpResponse, ApiError> {
     let id_str = path.into_inner();
-    let id = match uuid::Uuid::parse_str(&id_str) {
-        Ok(uuid) => uuid,
-        Err(_) => {
-            return HttpResponse::BadRequest().json(serde_json::json!({
-                "error": "Invalid UUID format"
-            }));
-        }
-    };
+    let id = uuid::Uuid::parse_str(&id_str)
+        .map_err(|_| ApiError::BadRequest("Invalid UUID format".to_string()))?;
     let item = sqlx::query_as::<_, Item>("SELECT * FROM items WHERE id = $1")
         .bind(id)
         .fetch_optional(pool.get_ref())
-        .await;
-    match item {
-        Ok(Some(item)) => HttpResponse::Ok().json(item),
-        Ok(None) => HttpResponse::NotFound().json(serde_json::json!({"error": "Item not found"})),
-        Err(e) => HttpResponse::InternalServerError().json(serde_json::json!({"error": e.to_string()})),
-    }
+        .await?
+        .ok_or_else(|| ApiError::NotFound("Item not found".to_string()))?;
+    Ok(HttpResponse::Ok().json(item))
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, ToSchema)]
 pub struct NewItem {
     pub name: String,
     pub category_id: Uuid,
@@ -131,8 +132,18 @@ pub struct NewItem {
     pub value: Option<String>, // Nilai barang (opsional)
 }
 
-#[post("")]
-pub async fn create_item(claims: Claims, pool: web::Data<PgPool>, form: web::Json<NewItem>) -> impl Responder {
+/// Tambah item; `status_id` default ke status `active`
+#[utoipa::path(
+    tag = "items",
+    request_body = NewItem,
+    responses(
+        (status = 200, body = Item),
+        (status = 422, description = "Kategori, kondisi, lokasi, dll. tidak ada", body = ErrorBody),
+        (status = 403, description = "Butuh izin add_items", body = ErrorBody),
+    )
+)]
+#[post("", wrap = "RequirePermission::new(\"add_items\")")]
+pub async fn create_item(claims: Claims, pool: web::Data<PgPool>, form: web::Json<NewItem>) -> Result<HttpResponse, ApiError> {
     println!("DEBUG payload: {:?}", form);
 
     let id = uuid::Uuid::new_v4();
@@ -158,41 +169,36 @@ pub async fn create_item(claims: Claims, pool: web::Data<PgPool>, form: web::Jso
         }
     };
     
-    let q = sqlx::query_as::<_, Item>(
+    let item = sqlx::query_as::<_, Item>(
         "INSERT INTO items (id, name, category_id, quantity, condition_id, location_id, photo_url, source_id, donor_id, procurement_id, status_id, value, created_at) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13) RETURNING *"
     )
     .bind(id)
     .bind(&form.name)
-    .bind(&form.category_id)
+    .bind(form.category_id)
     .bind(form.quantity.unwrap_or(1))
-    .bind(&form.condition_id)
+    .bind(form.condition_id)
     .bind(form.location_id)
     .bind(&form.photo_url)
-    .bind(&form.source_id)
+    .bind(form.source_id)
     .bind(form.donor_id)
     .bind(form.procurement_id)
     .bind(status_id)
     .bind(&form.value)
     .bind(chrono::Utc::now())
     .fetch_one(pool.get_ref())
-    .await;
-    match q {
-        Ok(item) => {
-            // Insert log
-            let _ = sqlx::query("INSERT INTO item_logs (item_id, action, before, after, by) VALUES ($1, $2, $3, $4, $5)")
-                .bind(item.id)
-                .bind("create")
-                .bind(None::<serde_json::Value>)
-                .bind(Some(serde_json::to_value(&item).unwrap()))
-                .bind(uuid::Uuid::parse_str(&claims.sub).ok())
-                .execute(pool.get_ref()).await;
-            HttpResponse::Ok().json(item)
-        },
-        Err(e) => HttpResponse::InternalServerError().json(serde_json::json!({"error": e.to_string()})),
-    }
+    .await?;
+    // Insert log
+    let _ = sqlx::query("INSERT INTO item_logs (item_id, action, before, after, by) VALUES ($1, $2, $3, $4, $5)")
+        .bind(item.id)
+        .bind("create")
+        .bind(None::<serde_json::Value>)
+        .bind(Some(serde_json::to_value(&item).unwrap()))
+        .bind(uuid::Uuid::parse_str(&claims.sub).ok())
+        .execute(pool.get_ref()).await;
+    Ok(HttpResponse::Ok().json(item))
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, ToSchema)]
 pub struct UpdateItem {
     pub name: Option<String>,
     pub category_id: Option<Uuid>,
@@ -207,71 +213,81 @@ pub struct UpdateItem {
     pub value: Option<String>, // Nilai barang (opsional)
 }
 
-#[patch("/{id}")]
-pub async fn update_item(claims: Claims, pool: web::Data<PgPool>, path: web::Path<Uuid>, form: web::Json<UpdateItem>) -> impl Responder {
+/// Update item. `location_id`, `photo_url`, `donor_id`, `procurement_id` dan `value`
+/// selalu ditimpa, jadi kirim nilai lama jika tidak ingin mengubahnya.
+#[utoipa::path(
+    tag = "items",
+    request_body = UpdateItem,
+    responses(
+        (status = 200, body = Item),
+        (status = 404, body = ErrorBody),
+        (status = 403, description = "Butuh izin edit_items", body = ErrorBody),
+    )
+)]
+#[patch("/{id}", wrap = "RequirePermission::new(\"edit_items\")")]
+pub async fn update_item(claims: Claims, pool: web::Data<PgPool>, path: web::Path<Uuid>, form: web::Json<UpdateItem>) -> Result<HttpResponse, ApiError> {
     let id = path.into_inner();
     // Ambil data sebelum update dengan query eksplisit
-    let before = match sqlx::query_as::<_, Item>(
+    let before = sqlx::query_as::<_, Item>(
         "SELECT id, name, category_id, quantity, condition_id, location_id, photo_url, source_id, donor_id, procurement_id, status_id, value, created_at FROM items WHERE id = $1"
     )
         .bind(id)
         .fetch_optional(pool.get_ref())
-        .await {
-            Ok(Some(item)) => {
-                println!("[DEBUG] Item sebelum update: ID: {}, photo_url: {:?}", item.id, item.photo_url);
-                Some(item)
-            },
-            Ok(None) => return HttpResponse::NotFound().json(serde_json::json!({"error": "Item not found"})),
-            Err(e) => return HttpResponse::InternalServerError().json(serde_json::json!({"error": format!("Failed to fetch item: {}", e)}))
-        };
-    let q = sqlx::query_as::<_, Item>("UPDATE items SET name = COALESCE($1, name), category_id = COALESCE($2, category_id), quantity = COALESCE($3, quantity), condition_id = COALESCE($4, condition_id), location_id = $5, photo_url = $6, source_id = COALESCE($7, source_id), donor_id = $8, procurement_id = $9, status_id = COALESCE($10, status_id), value = $11 WHERE id = $12 RETURNING *")
+        .await?
+        .ok_or_else(|| ApiError::NotFound("Item not found".to_string()))?;
+    println!("[DEBUG] Item sebelum update: ID: {}, photo_url: {:?}", before.id, before.photo_url);
+    let item = sqlx::query_as::<_, Item>("UPDATE items SET name = COALESCE($1, name), category_id = COALESCE($2, category_id), quantity = COALESCE($3, quantity), condition_id = COALESCE($4, condition_id), location_id = $5, photo_url = $6, source_id = COALESCE($7, source_id), donor_id = $8, procurement_id = $9, status_id = COALESCE($10, status_id), value = $11 WHERE id = $12 RETURNING *")
     .bind(form.name.clone())
     .bind(form.category_id)
     .bind(form.quantity)
-    .bind(&form.condition_id)
+    .bind(form.condition_id)
     .bind(form.location_id)
     .bind(&form.photo_url)
-    .bind(&form.source_id)
+    .bind(form.source_id)
     .bind(form.donor_id)
     .bind(form.procurement_id)
     .bind(form.status_id)
     .bind(&form.value)
     .bind(id)
     .fetch_optional(pool.get_ref())
-    .await;
-    match q {
-        Ok(Some(item)) => {
-            // Debug: Cetak nilai after untuk debugging
-            println!("[DEBUG] Item setelah update: ID: {}, photo_url: {:?}", item.id, item.photo_url);
-            
-            let before_json = before.map(|b| serde_json::to_value(&b).unwrap());
-            let after_json = serde_json::to_value(&item).unwrap();
-            
-            println!("[DEBUG] Before JSON: {:?}", before_json);
-            println!("[DEBUG] After JSON: {:?}", after_json);
-            
-            // Insert log
-            let log_result = sqlx::query("INSERT INTO item_logs (item_id, action, before, after, by) VALUES ($1, $2, $3, $4, $5)")
-                .bind(item.id)
-                .bind("update")
-                .bind(before_json)
-                .bind(Some(after_json))
-                .bind(uuid::Uuid::parse_str(&claims.sub).ok())
-                .execute(pool.get_ref()).await;
-                
-            if let Err(e) = log_result {
-                println!("[ERROR] Failed to create log: {}", e);
-            }
-            
-            HttpResponse::Ok().json(item)
-        },
-        Ok(None) => HttpResponse::NotFound().json(serde_json::json!({"error": "Item not found"})),
-        Err(e) => HttpResponse::InternalServerError().json(serde_json::json!({"error": e.to_string()})),
+    .await?
+    .ok_or_else(|| ApiError::NotFound("Item not found".to_string()))?;
+    // Debug: Cetak nilai after untuk debugging
+    println!("[DEBUG] Item setelah update: ID: {}, photo_url: {:?}", item.id, item.photo_url);
+    
+    let before_json = serde_json::to_value(&before).unwrap();
+    let after_json = serde_json::to_value(&item).unwrap();
+    
+    println!("[DEBUG] Before JSON: {:?}", before_json);
+    println!("[DEBUG] After JSON: {:?}", after_json);
+    
+    // Insert log
+    let log_result = sqlx::query("INSERT INTO item_logs (item_id, action, before, after, by) VALUES ($1, $2, $3, $4, $5)")
+        .bind(item.id)
+        .bind("update")
+        .bind(Some(before_json))
+        .bind(Some(after_json))
+        .bind(uuid::Uuid::parse_str(&claims.sub).ok())
+        .execute(pool.get_ref()).await;
+        
+    if let Err(e) = log_result {
+        println!("[ERROR] Failed to create log: {}", e);
     }
+    
+    Ok(HttpResponse::Ok().json(item))
 }
 
-#[delete("/{id}")]
-pub async fn delete_item(claims: Claims, pool: web::Data<PgPool>, path: web::Path<Uuid>) -> impl Responder {
+#[utoipa::path(
+    tag = "items",
+    responses(
+        (status = 200, body = serde_json::Value),
+        (status = 404, body = ErrorBody),
+        (status = 422, description = "Item masih direferensikan, mis. oleh peminjaman", body = ErrorBody),
+        (status = 403, description = "Butuh izin delete_items", body = ErrorBody),
+    )
+)]
+#[delete("/{id}", wrap = "RequirePermission::new(\"delete_items\")")]
+pub async fn delete_item(claims: Claims, pool: web::Data<PgPool>, path: web::Path<Uuid>) -> Result<HttpResponse, ApiError> {
     let id = path.into_inner();
     // Ambil data sebelum delete
     let before = sqlx::query_as::<_, Item>("SELECT * FROM items WHERE id = $1")
@@ -280,61 +296,52 @@ pub async fn delete_item(claims: Claims, pool: web::Data<PgPool>, path: web::Pat
         .await
         .ok()
         .flatten();
-    let q = sqlx::query("DELETE FROM items WHERE id = $1 RETURNING id")
+    sqlx::query("DELETE FROM items WHERE id = $1 RETURNING id")
         .bind(id)
         .fetch_optional(pool.get_ref())
-        .await;
-    match q {
-        Ok(Some(_row)) => {
-            // Insert log
-            if let Some(b) = before {
-                let _ = sqlx::query("INSERT INTO item_logs (item_id, action, before, after, by) VALUES ($1, $2, $3, $4, $5)")
-                    .bind(b.id)
-                    .bind("delete")
-                    .bind(Some(serde_json::to_value(&b).unwrap()))
-                    .bind(None::<serde_json::Value>)
-                    .bind(uuid::Uuid::parse_str(&claims.sub).ok())
-                    .execute(pool.get_ref()).await;
-            }
-            HttpResponse::Ok().json(serde_json::json!({"success": true}))
-        },
-        Ok(None) => HttpResponse::NotFound().json(serde_json::json!({"error": "Item not found"})),
-        Err(e) => HttpResponse::InternalServerError().json(serde_json::json!({"error": e.to_string()})),
+        .await?
+        .ok_or_else(|| ApiError::NotFound("Item not found".to_string()))?;
+    // Insert log
+    if let Some(b) = before {
+        let _ = sqlx::query("INSERT INTO item_logs (item_id, action, before, after, by) VALUES ($1, $2, $3, $4, $5)")
+            .bind(b.id)
+            .bind("delete")
+            .bind(Some(serde_json::to_value(&b).unwrap()))
+            .bind(None::<serde_json::Value>)
+            .bind(uuid::Uuid::parse_str(&claims.sub).ok())
+            .execute(pool.get_ref()).await;
     }
+    Ok(HttpResponse::Ok().json(serde_json::json!({"success": true})))
 }
 
-#[get("/{id}/qrcode")]
+/// QR code PNG yang mengarah ke halaman detail item di frontend
+#[utoipa::path(
+    tag = "items",
+    params(("id" = Uuid, Path)),
+    responses((status = 200, content_type = "image/png", body = Vec<u8>), (status = 403, description = "Butuh izin view_items", body = ErrorBody))
+)]
+#[get("/{id}/qrcode", wrap = "RequirePermission::new(\"view_items\")")]
 pub async fn get_item_qrcode(
     path: web::Path<String>,
-    // req: actix_web::HttpRequest
-) -> impl Responder {
+    config: web::Data<Config>,
+) -> Result<HttpResponse, ApiError> {
     let id_str = path.into_inner();
-    // URL detail item, ambil dari env FRONTEND_URL
-    let frontend_url = std::env::var("FRONTEND_URL").unwrap_or_else(|_| "http://localhost:5173".to_string());
-    let url = format!("{}/items/{}", frontend_url.trim_end_matches('/'), id_str);
-    match QrCode::new(url) {
-        Ok(code) => {
-            let image = code.render::<Luma<u8>>().build();
-            let mut cursor = Cursor::new(Vec::new());
-            let encoder = image::codecs::png::PngEncoder::new(&mut cursor);
-            let encode_result = encoder.write_image(
-                image.as_bytes(),
-                image.width(),
-                image.height(),
-                image::ColorType::L8.into()
-            );
-            match encode_result {
-                Ok(_) => {
-                    let bytes = cursor.into_inner();
-                    HttpResponse::Ok()
-                        .content_type(ContentType::png())
-                        .body(bytes)
-                },
-                Err(e) => HttpResponse::InternalServerError().body(format!("QR encode error: {}", e)),
-            }
-        },
-        Err(e) => HttpResponse::InternalServerError().body(format!("QR gen error: {}", e)),
-    }
+    // URL detail item di frontend
+    let url = format!("{}/items/{}", config.cors.frontend_url.trim_end_matches('/'), id_str);
+    let code = QrCode::new(url).map_err(|e| ApiError::internal(format!("QR gen error: {}", e)))?;
+    let image = code.render::<Luma<u8>>().build();
+    let mut cursor = Cursor::new(Vec::new());
+    let encoder = image::codecs::png::PngEncoder::new(&mut cursor);
+    encoder.write_image(
+        image.as_bytes(),
+        image.width(),
+        image.height(),
+        image::ColorType::L8.into()
+    ).map_err(|e| ApiError::internal(format!("QR encode error: {}", e)))?;
+    let bytes = cursor.into_inner();
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::png())
+        .body(bytes))
 }
 
 pub fn items_config(cfg: &mut web::ServiceConfig) {
@@ -348,3 +355,16 @@ pub fn items_config(cfg: &mut web::ServiceConfig) {
         .service(delete_item)
         .service(get_item_qrcode);
 }
+
+#[derive(OpenApi)]
+#[openapi(paths(
+    get_items,
+    get_all_item_logs,
+    get_item_logs,
+    get_item_by_id,
+    create_item,
+    update_item,
+    delete_item,
+    get_item_qrcode,
+))]
+pub struct ItemsApi;
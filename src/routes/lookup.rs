@@ -1,86 +1,129 @@
-use actix_web::{get, post, patch, delete, web, HttpResponse, Responder};
+use actix_web::{get, post, patch, delete, web, HttpRequest, HttpResponse};
 
 use serde::{Deserialize, Serialize};
 use sqlx::PgPool;
+use utoipa::{OpenApi, ToSchema};
 use uuid::Uuid;
 
-use crate::middleware::admin_guard::is_admin;
-
+use crate::error::{ApiError, ErrorBody};
+use crate::middleware::permission_guard::{RequirePermission, UserPermissions};
+use crate::services::audit::record_change;
 
 // ----------------- Categories -----------------
-#[derive(Serialize, Deserialize, sqlx::FromRow)]
+#[derive(Serialize, Deserialize, sqlx::FromRow, ToSchema)]
 pub struct Category {
     pub id: Uuid,
     pub name: String,
     pub description: Option<String>,
 }
 
-#[get("")]
-pub async fn get_categories(_claims: crate::middleware::jwt_extractor::Claims, pool: web::Data<PgPool>) -> impl Responder {
+#[utoipa::path(
+    context_path = "/categories",
+    tag = "lookup",
+    responses(
+        (status = 200, body = Vec<Category>),
+        (status = 403, description = "Butuh izin view_items", body = ErrorBody),
+    ),
+)]
+#[get("", wrap = "RequirePermission::new(\"view_items\")")]
+pub async fn get_categories(pool: web::Data<PgPool>) -> Result<HttpResponse, ApiError> {
     let rows = sqlx::query_as::<_, Category>("SELECT id, name, description FROM categories ORDER BY name")
         .fetch_all(pool.get_ref())
-        .await;
-    match rows {
-        Ok(rows) => HttpResponse::Ok().json(rows),
-        Err(e) => HttpResponse::InternalServerError().json(serde_json::json!({"error": e.to_string()})),
-    }
+        .await?;
+    Ok(HttpResponse::Ok().json(rows))
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, ToSchema)]
 pub struct CategoryPayload {
     pub name: String,
     pub description: Option<String>,
 }
 
-#[post("")]
-pub async fn create_category(claims: crate::middleware::jwt_extractor::Claims, pool: web::Data<PgPool>, form: web::Json<CategoryPayload>) -> impl Responder {
-    if !is_admin(&claims, pool.get_ref()).await {
-        return HttpResponse::Forbidden().json(serde_json::json!({ "message": "Admin only" }));
-    }
+#[utoipa::path(
+    context_path = "/categories",
+    tag = "lookup",
+    request_body = CategoryPayload,
+    responses(
+        (status = 200, body = Category),
+        (status = 403, description = "Butuh izin manage_categories", body = ErrorBody),
+        (status = 409, description = "Nama sudah dipakai", body = ErrorBody),
+    ),
+)]
+#[post("", wrap = "RequirePermission::new(\"manage_categories\")")]
+pub async fn create_category(
+    req: HttpRequest,
+    perms: UserPermissions,
+    pool: web::Data<PgPool>,
+    form: web::Json<CategoryPayload>,
+) -> Result<HttpResponse, ApiError> {
     let row = sqlx::query_as::<_, Category>("INSERT INTO categories (name, description) VALUES ($1, $2) RETURNING id, name, description")
         .bind(&form.name)
         .bind(&form.description)
         .fetch_one(pool.get_ref())
-        .await;
-    match row {
-        Ok(row) => HttpResponse::Ok().json(row),
-        Err(e) => HttpResponse::InternalServerError().json(serde_json::json!({"error": e.to_string()})),
-    }
-}
-
-#[patch("/{id}")]
-pub async fn update_category(claims: crate::middleware::jwt_extractor::Claims, pool: web::Data<PgPool>, path: web::Path<Uuid>, form: web::Json<CategoryPayload>) -> impl Responder {
-    if !is_admin(&claims, pool.get_ref()).await {
-        return HttpResponse::Forbidden().json(serde_json::json!({ "message": "Admin only" }));
-    }
+        .await?;
+    record_change(pool.get_ref(), &req, perms.user_id, "category.created", row.id, None, Some(&row)).await?;
+    Ok(HttpResponse::Ok().json(row))
+}
+
+#[utoipa::path(
+    context_path = "/categories",
+    tag = "lookup",
+    request_body = CategoryPayload,
+    responses(
+        (status = 200, body = Category),
+        (status = 403, description = "Butuh izin manage_categories", body = ErrorBody),
+        (status = 404, body = ErrorBody),
+        (status = 409, description = "Nama sudah dipakai", body = ErrorBody),
+    ),
+)]
+#[patch("/{id}", wrap = "RequirePermission::new(\"manage_categories\")")]
+pub async fn update_category(
+    req: HttpRequest,
+    perms: UserPermissions,
+    pool: web::Data<PgPool>,
+    path: web::Path<Uuid>,
+    form: web::Json<CategoryPayload>,
+) -> Result<HttpResponse, ApiError> {
     let id = path.into_inner();
+    let before = sqlx::query_as::<_, Category>("SELECT id, name, description FROM categories WHERE id = $1")
+        .bind(id)
+        .fetch_one(pool.get_ref())
+        .await?;
     let row = sqlx::query_as::<_, Category>("UPDATE categories SET name = $1, description = $2 WHERE id = $3 RETURNING id, name, description")
         .bind(&form.name)
         .bind(&form.description)
         .bind(id)
         .fetch_one(pool.get_ref())
-        .await;
-    match row {
-        Ok(row) => HttpResponse::Ok().json(row),
-        Err(e) => HttpResponse::InternalServerError().json(serde_json::json!({"error": e.to_string()})),
-    }
-}
-
-#[delete("/{id}")]
-pub async fn delete_category(claims: crate::middleware::jwt_extractor::Claims, pool: web::Data<PgPool>, path: web::Path<Uuid>) -> impl Responder {
-    if !is_admin(&claims, pool.get_ref()).await {
-        return HttpResponse::Forbidden().json(serde_json::json!({ "message": "Admin only" }));
-    }
+        .await?;
+    record_change(pool.get_ref(), &req, perms.user_id, "category.updated", id, Some(&before), Some(&row)).await?;
+    Ok(HttpResponse::Ok().json(row))
+}
+
+#[utoipa::path(
+    context_path = "/categories",
+    tag = "lookup",
+    responses(
+        (status = 200, body = serde_json::Value),
+        (status = 403, description = "Butuh izin manage_categories", body = ErrorBody),
+        (status = 404, body = ErrorBody),
+        (status = 422, description = "Masih dipakai oleh data lain", body = ErrorBody),
+    ),
+)]
+#[delete("/{id}", wrap = "RequirePermission::new(\"manage_categories\")")]
+pub async fn delete_category(
+    req: HttpRequest,
+    perms: UserPermissions,
+    pool: web::Data<PgPool>,
+    path: web::Path<Uuid>,
+) -> Result<HttpResponse, ApiError> {
     let id = path.into_inner();
-    let row = sqlx::query("DELETE FROM categories WHERE id = $1 RETURNING id")
+    let before = sqlx::query_as::<_, Category>("DELETE FROM categories WHERE id = $1 RETURNING id, name, description")
         .bind(id)
         .fetch_optional(pool.get_ref())
-        .await;
-    match row {
-        Ok(Some(_)) => HttpResponse::Ok().json(serde_json::json!({"success": true})),
-        Ok(None) => HttpResponse::NotFound().json(serde_json::json!({"error": "Not found"})),
-        Err(e) => HttpResponse::InternalServerError().json(serde_json::json!({"error": e.to_string()})),
-    }
+        .await?
+        .ok_or_else(|| ApiError::NotFound("Not found".to_string()))?;
+    record_change(pool.get_ref(), &req, perms.user_id, "category.deleted", id, Some(&before), None).await?;
+    Ok(HttpResponse::Ok().json(serde_json::json!({"success": true})))
 }
 
 pub fn categories_config(cfg: &mut web::ServiceConfig) {
@@ -91,79 +134,120 @@ pub fn categories_config(cfg: &mut web::ServiceConfig) {
 }
 
 // ----------------- ItemSources -----------------
-#[derive(Serialize, Deserialize, sqlx::FromRow)]
+#[derive(Serialize, Deserialize, sqlx::FromRow, ToSchema)]
 pub struct ItemSource {
     pub id: Uuid,
     pub name: String,
     pub description: Option<String>,
 }
 
-#[get("")]
-pub async fn get_item_sources(_claims: crate::middleware::jwt_extractor::Claims, pool: web::Data<PgPool>) -> impl Responder {
+#[utoipa::path(
+    context_path = "/item_sources",
+    tag = "lookup",
+    responses(
+        (status = 200, body = Vec<ItemSource>),
+        (status = 403, description = "Butuh izin view_items", body = ErrorBody),
+    ),
+)]
+#[get("", wrap = "RequirePermission::new(\"view_items\")")]
+pub async fn get_item_sources(pool: web::Data<PgPool>) -> Result<HttpResponse, ApiError> {
     let rows = sqlx::query_as::<_, ItemSource>("SELECT id, name, description FROM item_sources ORDER BY name")
         .fetch_all(pool.get_ref())
-        .await;
-    match rows {
-        Ok(rows) => HttpResponse::Ok().json(rows),
-        Err(e) => HttpResponse::InternalServerError().json(serde_json::json!({"error": e.to_string()})),
-    }
+        .await?;
+    Ok(HttpResponse::Ok().json(rows))
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, ToSchema)]
 pub struct ItemSourcePayload {
     pub name: String,
     pub description: Option<String>,
 }
 
-#[post("")]
-pub async fn create_item_source(claims: crate::middleware::jwt_extractor::Claims, pool: web::Data<PgPool>, form: web::Json<ItemSourcePayload>) -> impl Responder {
-    if !is_admin(&claims, pool.get_ref()).await {
-        return HttpResponse::Forbidden().json(serde_json::json!({ "message": "Admin only" }));
-    }
+#[utoipa::path(
+    context_path = "/item_sources",
+    tag = "lookup",
+    request_body = ItemSourcePayload,
+    responses(
+        (status = 200, body = ItemSource),
+        (status = 403, description = "Butuh izin admin_access", body = ErrorBody),
+        (status = 409, description = "Nama sudah dipakai", body = ErrorBody),
+    ),
+)]
+#[post("", wrap = "RequirePermission::new(\"admin_access\")")]
+pub async fn create_item_source(
+    req: HttpRequest,
+    perms: UserPermissions,
+    pool: web::Data<PgPool>,
+    form: web::Json<ItemSourcePayload>,
+) -> Result<HttpResponse, ApiError> {
     let row = sqlx::query_as::<_, ItemSource>("INSERT INTO item_sources (name, description) VALUES ($1, $2) RETURNING id, name, description")
         .bind(&form.name)
         .bind(&form.description)
         .fetch_one(pool.get_ref())
-        .await;
-    match row {
-        Ok(row) => HttpResponse::Ok().json(row),
-        Err(e) => HttpResponse::InternalServerError().json(serde_json::json!({"error": e.to_string()})),
-    }
-}
-
-#[patch("/{id}")]
-pub async fn update_item_source(claims: crate::middleware::jwt_extractor::Claims, pool: web::Data<PgPool>, path: web::Path<Uuid>, form: web::Json<ItemSourcePayload>) -> impl Responder {
-    if !is_admin(&claims, pool.get_ref()).await {
-        return HttpResponse::Forbidden().json(serde_json::json!({ "message": "Admin only" }));
-    }
+        .await?;
+    record_change(pool.get_ref(), &req, perms.user_id, "item_source.created", row.id, None, Some(&row)).await?;
+    Ok(HttpResponse::Ok().json(row))
+}
+
+#[utoipa::path(
+    context_path = "/item_sources",
+    tag = "lookup",
+    request_body = ItemSourcePayload,
+    responses(
+        (status = 200, body = ItemSource),
+        (status = 403, description = "Butuh izin admin_access", body = ErrorBody),
+        (status = 404, body = ErrorBody),
+        (status = 409, description = "Nama sudah dipakai", body = ErrorBody),
+    ),
+)]
+#[patch("/{id}", wrap = "RequirePermission::new(\"admin_access\")")]
+pub async fn update_item_source(
+    req: HttpRequest,
+    perms: UserPermissions,
+    pool: web::Data<PgPool>,
+    path: web::Path<Uuid>,
+    form: web::Json<ItemSourcePayload>,
+) -> Result<HttpResponse, ApiError> {
     let id = path.into_inner();
+    let before = sqlx::query_as::<_, ItemSource>("SELECT id, name, description FROM item_sources WHERE id = $1")
+        .bind(id)
+        .fetch_one(pool.get_ref())
+        .await?;
     let row = sqlx::query_as::<_, ItemSource>("UPDATE item_sources SET name = $1, description = $2 WHERE id = $3 RETURNING id, name, description")
         .bind(&form.name)
         .bind(&form.description)
         .bind(id)
         .fetch_one(pool.get_ref())
-        .await;
-    match row {
-        Ok(row) => HttpResponse::Ok().json(row),
-        Err(e) => HttpResponse::InternalServerError().json(serde_json::json!({"error": e.to_string()})),
-    }
-}
-
-#[delete("/{id}")]
-pub async fn delete_item_source(claims: crate::middleware::jwt_extractor::Claims, pool: web::Data<PgPool>, path: web::Path<Uuid>) -> impl Responder {
-    if !is_admin(&claims, pool.get_ref()).await {
-        return HttpResponse::Forbidden().json(serde_json::json!({ "message": "Admin only" }));
-    }
+        .await?;
+    record_change(pool.get_ref(), &req, perms.user_id, "item_source.updated", id, Some(&before), Some(&row)).await?;
+    Ok(HttpResponse::Ok().json(row))
+}
+
+#[utoipa::path(
+    context_path = "/item_sources",
+    tag = "lookup",
+    responses(
+        (status = 200, body = serde_json::Value),
+        (status = 403, description = "Butuh izin admin_access", body = ErrorBody),
+        (status = 404, body = ErrorBody),
+        (status = 422, description = "Masih dipakai oleh data lain", body = ErrorBody),
+    ),
+)]
+#[delete("/{id}", wrap = "RequirePermission::new(\"admin_access\")")]
+pub async fn delete_item_source(
+    req: HttpRequest,
+    perms: UserPermissions,
+    pool: web::Data<PgPool>,
+    path: web::Path<Uuid>,
+) -> Result<HttpResponse, ApiError> {
     let id = path.into_inner();
-    let row = sqlx::query("DELETE FROM item_sources WHERE id = $1 RETURNING id")
+    let before = sqlx::query_as::<_, ItemSource>("DELETE FROM item_sources WHERE id = $1 RETURNING id, name, description")
         .bind(id)
         .fetch_optional(pool.get_ref())
-        .await;
-    match row {
-        Ok(Some(_)) => HttpResponse::Ok().json(serde_json::json!({"success": true})),
-        Ok(None) => HttpResponse::NotFound().json(serde_json::json!({"error": "Not found"})),
-        Err(e) => HttpResponse::InternalServerError().json(serde_json::json!({"error": e.to_string()})),
-    }
+        .await?
+        .ok_or_else(|| ApiError::NotFound("Not found".to_string()))?;
+    record_change(pool.get_ref(), &req, perms.user_id, "item_source.deleted", id, Some(&before), None).await?;
+    Ok(HttpResponse::Ok().json(serde_json::json!({"success": true})))
 }
 
 pub fn item_sources_config(cfg: &mut web::ServiceConfig) {
@@ -174,79 +258,120 @@ pub fn item_sources_config(cfg: &mut web::ServiceConfig) {
 }
 
 // ----------------- Conditions -----------------
-#[derive(Serialize, Deserialize, sqlx::FromRow)]
+#[derive(Serialize, Deserialize, sqlx::FromRow, ToSchema)]
 pub struct Condition {
     pub id: Uuid,
     pub name: String,
     pub description: Option<String>,
 }
 
-#[get("")]
-pub async fn get_conditions(_claims: crate::middleware::jwt_extractor::Claims, pool: web::Data<PgPool>) -> impl Responder {
+#[utoipa::path(
+    context_path = "/conditions",
+    tag = "lookup",
+    responses(
+        (status = 200, body = Vec<Condition>),
+        (status = 403, description = "Butuh izin view_items", body = ErrorBody),
+    ),
+)]
+#[get("", wrap = "RequirePermission::new(\"view_items\")")]
+pub async fn get_conditions(pool: web::Data<PgPool>) -> Result<HttpResponse, ApiError> {
     let rows = sqlx::query_as::<_, Condition>("SELECT id, name, description FROM conditions ORDER BY name")
         .fetch_all(pool.get_ref())
-        .await;
-    match rows {
-        Ok(rows) => HttpResponse::Ok().json(rows),
-        Err(e) => HttpResponse::InternalServerError().json(serde_json::json!({"error": e.to_string()})),
-    }
+        .await?;
+    Ok(HttpResponse::Ok().json(rows))
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, ToSchema)]
 pub struct ConditionPayload {
     pub name: String,
     pub description: Option<String>,
 }
 
-#[post("")]
-pub async fn create_condition(claims: crate::middleware::jwt_extractor::Claims, pool: web::Data<PgPool>, form: web::Json<ConditionPayload>) -> impl Responder {
-    if !is_admin(&claims, pool.get_ref()).await {
-        return HttpResponse::Forbidden().json(serde_json::json!({ "message": "Admin only" }));
-    }
+#[utoipa::path(
+    context_path = "/conditions",
+    tag = "lookup",
+    request_body = ConditionPayload,
+    responses(
+        (status = 200, body = Condition),
+        (status = 403, description = "Butuh izin admin_access", body = ErrorBody),
+        (status = 409, description = "Nama sudah dipakai", body = ErrorBody),
+    ),
+)]
+#[post("", wrap = "RequirePermission::new(\"admin_access\")")]
+pub async fn create_condition(
+    req: HttpRequest,
+    perms: UserPermissions,
+    pool: web::Data<PgPool>,
+    form: web::Json<ConditionPayload>,
+) -> Result<HttpResponse, ApiError> {
     let row = sqlx::query_as::<_, Condition>("INSERT INTO conditions (name, description) VALUES ($1, $2) RETURNING id, name, description")
         .bind(&form.name)
         .bind(&form.description)
         .fetch_one(pool.get_ref())
-        .await;
-    match row {
-        Ok(row) => HttpResponse::Ok().json(row),
-        Err(e) => HttpResponse::InternalServerError().json(serde_json::json!({"error": e.to_string()})),
-    }
-}
-
-#[patch("/{id}")]
-pub async fn update_condition(claims: crate::middleware::jwt_extractor::Claims, pool: web::Data<PgPool>, path: web::Path<Uuid>, form: web::Json<ConditionPayload>) -> impl Responder {
-    if !is_admin(&claims, pool.get_ref()).await {
-        return HttpResponse::Forbidden().json(serde_json::json!({ "message": "Admin only" }));
-    }
+        .await?;
+    record_change(pool.get_ref(), &req, perms.user_id, "condition.created", row.id, None, Some(&row)).await?;
+    Ok(HttpResponse::Ok().json(row))
+}
+
+#[utoipa::path(
+    context_path = "/conditions",
+    tag = "lookup",
+    request_body = ConditionPayload,
+    responses(
+        (status = 200, body = Condition),
+        (status = 403, description = "Butuh izin admin_access", body = ErrorBody),
+        (status = 404, body = ErrorBody),
+        (status = 409, description = "Nama sudah dipakai", body = ErrorBody),
+    ),
+)]
+#[patch("/{id}", wrap = "RequirePermission::new(\"admin_access\")")]
+pub async fn update_condition(
+    req: HttpRequest,
+    perms: UserPermissions,
+    pool: web::Data<PgPool>,
+    path: web::Path<Uuid>,
+    form: web::Json<ConditionPayload>,
+) -> Result<HttpResponse, ApiError> {
     let id = path.into_inner();
+    let before = sqlx::query_as::<_, Condition>("SELECT id, name, description FROM conditions WHERE id = $1")
+        .bind(id)
+        .fetch_one(pool.get_ref())
+        .await?;
     let row = sqlx::query_as::<_, Condition>("UPDATE conditions SET name = $1, description = $2 WHERE id = $3 RETURNING id, name, description")
         .bind(&form.name)
         .bind(&form.description)
         .bind(id)
         .fetch_one(pool.get_ref())
-        .await;
-    match row {
-        Ok(row) => HttpResponse::Ok().json(row),
-        Err(e) => HttpResponse::InternalServerError().json(serde_json::json!({"error": e.to_string()})),
-    }
-}
-
-#[delete("/{id}")]
-pub async fn delete_condition(claims: crate::middleware::jwt_extractor::Claims, pool: web::Data<PgPool>, path: web::Path<Uuid>) -> impl Responder {
-    if !is_admin(&claims, pool.get_ref()).await {
-        return HttpResponse::Forbidden().json(serde_json::json!({ "message": "Admin only" }));
-    }
+        .await?;
+    record_change(pool.get_ref(), &req, perms.user_id, "condition.updated", id, Some(&before), Some(&row)).await?;
+    Ok(HttpResponse::Ok().json(row))
+}
+
+#[utoipa::path(
+    context_path = "/conditions",
+    tag = "lookup",
+    responses(
+        (status = 200, body = serde_json::Value),
+        (status = 403, description = "Butuh izin admin_access", body = ErrorBody),
+        (status = 404, body = ErrorBody),
+        (status = 422, description = "Masih dipakai oleh data lain", body = ErrorBody),
+    ),
+)]
+#[delete("/{id}", wrap = "RequirePermission::new(\"admin_access\")")]
+pub async fn delete_condition(
+    req: HttpRequest,
+    perms: UserPermissions,
+    pool: web::Data<PgPool>,
+    path: web::Path<Uuid>,
+) -> Result<HttpResponse, ApiError> {
     let id = path.into_inner();
-    let row = sqlx::query("DELETE FROM conditions WHERE id = $1 RETURNING id")
+    let before = sqlx::query_as::<_, Condition>("DELETE FROM conditions WHERE id = $1 RETURNING id, name, description")
         .bind(id)
         .fetch_optional(pool.get_ref())
-        .await;
-    match row {
-        Ok(Some(_)) => HttpResponse::Ok().json(serde_json::json!({"success": true})),
-        Ok(None) => HttpResponse::NotFound().json(serde_json::json!({"error": "Not found"})),
-        Err(e) => HttpResponse::InternalServerError().json(serde_json::json!({"error": e.to_string()})),
-    }
+        .await?
+        .ok_or_else(|| ApiError::NotFound("Not found".to_string()))?;
+    record_change(pool.get_ref(), &req, perms.user_id, "condition.deleted", id, Some(&before), None).await?;
+    Ok(HttpResponse::Ok().json(serde_json::json!({"success": true})))
 }
 
 pub fn conditions_config(cfg: &mut web::ServiceConfig) {
@@ -257,70 +382,111 @@ pub fn conditions_config(cfg: &mut web::ServiceConfig) {
 }
 
 // ----------------- ProcurementStatuses -----------------
-#[derive(Serialize, Deserialize, sqlx::FromRow)]
+#[derive(Serialize, Deserialize, sqlx::FromRow, ToSchema)]
 pub struct ProcurementStatus {
     pub id: Uuid,
     pub name: String,
 }
 
-#[get("")]
-pub async fn get_procurement_statuses(_claims: crate::middleware::jwt_extractor::Claims, pool: web::Data<PgPool>) -> impl Responder {
+#[utoipa::path(
+    context_path = "/procurement_statuses",
+    tag = "lookup",
+    responses(
+        (status = 200, body = Vec<ProcurementStatus>),
+        (status = 403, description = "Butuh izin view_items", body = ErrorBody),
+    ),
+)]
+#[get("", wrap = "RequirePermission::new(\"view_items\")")]
+pub async fn get_procurement_statuses(pool: web::Data<PgPool>) -> Result<HttpResponse, ApiError> {
     let rows = sqlx::query_as::<_, ProcurementStatus>("SELECT id, name FROM procurement_statuses ORDER BY name")
         .fetch_all(pool.get_ref())
-        .await;
-    match rows {
-        Ok(rows) => HttpResponse::Ok().json(rows),
-        Err(e) => HttpResponse::InternalServerError().json(serde_json::json!({"error": e.to_string()})),
-    }
-}
-
-#[post("")]
-pub async fn create_procurement_status(claims: crate::middleware::jwt_extractor::Claims, pool: web::Data<PgPool>, form: web::Json<ProcurementStatus>) -> impl Responder {
-    if !is_admin(&claims, pool.get_ref()).await {
-        return HttpResponse::Forbidden().json(serde_json::json!({ "message": "Admin only" }));
-    }
+        .await?;
+    Ok(HttpResponse::Ok().json(rows))
+}
+
+#[utoipa::path(
+    context_path = "/procurement_statuses",
+    tag = "lookup",
+    request_body = ProcurementStatus,
+    responses(
+        (status = 200, body = ProcurementStatus),
+        (status = 403, description = "Butuh izin admin_access", body = ErrorBody),
+        (status = 409, description = "Nama sudah dipakai", body = ErrorBody),
+    ),
+)]
+#[post("", wrap = "RequirePermission::new(\"admin_access\")")]
+pub async fn create_procurement_status(
+    req: HttpRequest,
+    perms: UserPermissions,
+    pool: web::Data<PgPool>,
+    form: web::Json<ProcurementStatus>,
+) -> Result<HttpResponse, ApiError> {
     let row = sqlx::query_as::<_, ProcurementStatus>("INSERT INTO procurement_statuses (name) VALUES ($1) RETURNING id, name")
         .bind(&form.name)
         .fetch_one(pool.get_ref())
-        .await;
-    match row {
-        Ok(row) => HttpResponse::Ok().json(row),
-        Err(e) => HttpResponse::InternalServerError().json(serde_json::json!({"error": e.to_string()})),
-    }
-}
-
-#[patch("/{id}")]
-pub async fn update_procurement_status(claims: crate::middleware::jwt_extractor::Claims, pool: web::Data<PgPool>, path: web::Path<Uuid>, form: web::Json<ProcurementStatus>) -> impl Responder {
-    if !is_admin(&claims, pool.get_ref()).await {
-        return HttpResponse::Forbidden().json(serde_json::json!({ "message": "Admin only" }));
-    }
+        .await?;
+    record_change(pool.get_ref(), &req, perms.user_id, "procurement_status.created", row.id, None, Some(&row)).await?;
+    Ok(HttpResponse::Ok().json(row))
+}
+
+#[utoipa::path(
+    context_path = "/procurement_statuses",
+    tag = "lookup",
+    request_body = ProcurementStatus,
+    responses(
+        (status = 200, body = ProcurementStatus),
+        (status = 403, description = "Butuh izin admin_access", body = ErrorBody),
+        (status = 404, body = ErrorBody),
+        (status = 409, description = "Nama sudah dipakai", body = ErrorBody),
+    ),
+)]
+#[patch("/{id}", wrap = "RequirePermission::new(\"admin_access\")")]
+pub async fn update_procurement_status(
+    req: HttpRequest,
+    perms: UserPermissions,
+    pool: web::Data<PgPool>,
+    path: web::Path<Uuid>,
+    form: web::Json<ProcurementStatus>,
+) -> Result<HttpResponse, ApiError> {
     let id = path.into_inner();
+    let before = sqlx::query_as::<_, ProcurementStatus>("SELECT id, name FROM procurement_statuses WHERE id = $1")
+        .bind(id)
+        .fetch_one(pool.get_ref())
+        .await?;
     let row = sqlx::query_as::<_, ProcurementStatus>("UPDATE procurement_statuses SET name = $1 WHERE id = $2 RETURNING id, name")
         .bind(&form.name)
         .bind(id)
         .fetch_one(pool.get_ref())
-        .await;
-    match row {
-        Ok(row) => HttpResponse::Ok().json(row),
-        Err(e) => HttpResponse::InternalServerError().json(serde_json::json!({"error": e.to_string()})),
-    }
-}
-
-#[delete("/{id}")]
-pub async fn delete_procurement_status(claims: crate::middleware::jwt_extractor::Claims, pool: web::Data<PgPool>, path: web::Path<Uuid>) -> impl Responder {
-    if !is_admin(&claims, pool.get_ref()).await {
-        return HttpResponse::Forbidden().json(serde_json::json!({ "message": "Admin only" }));
-    }
+        .await?;
+    record_change(pool.get_ref(), &req, perms.user_id, "procurement_status.updated", id, Some(&before), Some(&row)).await?;
+    Ok(HttpResponse::Ok().json(row))
+}
+
+#[utoipa::path(
+    context_path = "/procurement_statuses",
+    tag = "lookup",
+    responses(
+        (status = 200, body = serde_json::Value),
+        (status = 403, description = "Butuh izin admin_access", body = ErrorBody),
+        (status = 404, body = ErrorBody),
+        (status = 422, description = "Masih dipakai oleh data lain", body = ErrorBody),
+    ),
+)]
+#[delete("/{id}", wrap = "RequirePermission::new(\"admin_access\")")]
+pub async fn delete_procurement_status(
+    req: HttpRequest,
+    perms: UserPermissions,
+    pool: web::Data<PgPool>,
+    path: web::Path<Uuid>,
+) -> Result<HttpResponse, ApiError> {
     let id = path.into_inner();
-    let row = sqlx::query("DELETE FROM procurement_statuses WHERE id = $1 RETURNING id")
+    let before = sqlx::query_as::<_, ProcurementStatus>("DELETE FROM procurement_statuses WHERE id = $1 RETURNING id, name")
         .bind(id)
         .fetch_optional(pool.get_ref())
-        .await;
-    match row {
-        Ok(Some(_)) => HttpResponse::Ok().json(serde_json::json!({"success": true})),
-        Ok(None) => HttpResponse::NotFound().json(serde_json::json!({"error": "Not found"})),
-        Err(e) => HttpResponse::InternalServerError().json(serde_json::json!({"error": e.to_string()})),
-    }
+        .await?
+        .ok_or_else(|| ApiError::NotFound("Not found".to_string()))?;
+    record_change(pool.get_ref(), &req, perms.user_id, "procurement_status.deleted", id, Some(&before), None).await?;
+    Ok(HttpResponse::Ok().json(serde_json::json!({"success": true})))
 }
 
 pub fn procurement_statuses_config(cfg: &mut web::ServiceConfig) {
@@ -331,70 +497,133 @@ pub fn procurement_statuses_config(cfg: &mut web::ServiceConfig) {
 }
 
 // ----------------- UserRoles -----------------
-#[derive(Serialize, Deserialize, sqlx::FromRow)]
+#[derive(Serialize, Deserialize, sqlx::FromRow, ToSchema)]
 pub struct UserRole {
     pub id: Uuid,
     pub name: String,
-}
-
-#[get("")]
-pub async fn get_user_roles(_claims: crate::middleware::jwt_extractor::Claims, pool: web::Data<PgPool>) -> impl Responder {
-    let rows = sqlx::query_as::<_, UserRole>("SELECT id, name FROM user_roles ORDER BY name")
-        .fetch_all(pool.get_ref())
-        .await;
-    match rows {
-        Ok(rows) => HttpResponse::Ok().json(rows),
-        Err(e) => HttpResponse::InternalServerError().json(serde_json::json!({"error": e.to_string()})),
-    }
-}
-
-#[post("")]
-pub async fn create_user_role(claims: crate::middleware::jwt_extractor::Claims, pool: web::Data<PgPool>, form: web::Json<UserRole>) -> impl Responder {
-    if !is_admin(&claims, pool.get_ref()).await {
-        return HttpResponse::Forbidden().json(serde_json::json!({ "message": "Admin only" }));
-    }
-    let row = sqlx::query_as::<_, UserRole>("INSERT INTO user_roles (name) VALUES ($1) RETURNING id, name")
-        .bind(&form.name)
-        .fetch_one(pool.get_ref())
-        .await;
-    match row {
-        Ok(row) => HttpResponse::Ok().json(row),
-        Err(e) => HttpResponse::InternalServerError().json(serde_json::json!({"error": e.to_string()})),
-    }
-}
-
-#[patch("/{id}")]
-pub async fn update_user_role(claims: crate::middleware::jwt_extractor::Claims, pool: web::Data<PgPool>, path: web::Path<Uuid>, form: web::Json<UserRole>) -> impl Responder {
-    if !is_admin(&claims, pool.get_ref()).await {
-        return HttpResponse::Forbidden().json(serde_json::json!({ "message": "Admin only" }));
-    }
+    /// Anggota role wajib memakai 2FA saat login
+    #[serde(default)]
+    pub require_two_factor: bool,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct UpdateUserRole {
+    pub name: Option<String>,
+    pub require_two_factor: Option<bool>,
+}
+
+pub async fn list_user_roles(pool: &PgPool) -> Result<Vec<UserRole>, sqlx::Error> {
+    sqlx::query_as::<_, UserRole>("SELECT id, name, require_two_factor FROM user_roles ORDER BY name")
+        .fetch_all(pool)
+        .await
+}
+
+#[utoipa::path(
+    context_path = "/user_roles",
+    tag = "lookup",
+    responses(
+        (status = 200, body = Vec<UserRole>),
+        (status = 403, description = "Butuh izin view_items", body = ErrorBody),
+    ),
+)]
+#[get("", wrap = "RequirePermission::new(\"view_items\")")]
+pub async fn get_user_roles(pool: web::Data<PgPool>) -> Result<HttpResponse, ApiError> {
+    let rows = list_user_roles(pool.get_ref()).await?;
+    Ok(HttpResponse::Ok().json(rows))
+}
+
+#[utoipa::path(
+    context_path = "/user_roles",
+    tag = "lookup",
+    request_body = UserRole,
+    responses(
+        (status = 200, body = UserRole),
+        (status = 403, description = "Butuh izin manage_roles", body = ErrorBody),
+        (status = 409, description = "Nama sudah dipakai", body = ErrorBody),
+    ),
+)]
+#[post("", wrap = "RequirePermission::new(\"manage_roles\")")]
+pub async fn create_user_role(
+    req: HttpRequest,
+    perms: UserPermissions,
+    pool: web::Data<PgPool>,
+    form: web::Json<UserRole>,
+) -> Result<HttpResponse, ApiError> {
+    let row = sqlx::query_as::<_, UserRole>(
+        "INSERT INTO user_roles (name, require_two_factor) VALUES ($1, $2) RETURNING id, name, require_two_factor"
+    )
+    .bind(&form.name)
+    .bind(form.require_two_factor)
+    .fetch_one(pool.get_ref())
+    .await?;
+    record_change(pool.get_ref(), &req, perms.user_id, "role.created", row.id, None, Some(&row)).await?;
+    Ok(HttpResponse::Ok().json(row))
+}
+
+#[utoipa::path(
+    context_path = "/user_roles",
+    tag = "lookup",
+    request_body = UpdateUserRole,
+    responses(
+        (status = 200, body = UserRole),
+        (status = 403, description = "Butuh izin manage_roles", body = ErrorBody),
+        (status = 404, body = ErrorBody),
+        (status = 409, description = "Nama sudah dipakai", body = ErrorBody),
+    ),
+)]
+#[patch("/{id}", wrap = "RequirePermission::new(\"manage_roles\")")]
+pub async fn update_user_role(
+    req: HttpRequest,
+    perms: UserPermissions,
+    pool: web::Data<PgPool>,
+    path: web::Path<Uuid>,
+    form: web::Json<UpdateUserRole>,
+) -> Result<HttpResponse, ApiError> {
     let id = path.into_inner();
-    let row = sqlx::query_as::<_, UserRole>("UPDATE user_roles SET name = $1 WHERE id = $2 RETURNING id, name")
-        .bind(&form.name)
+    let before = sqlx::query_as::<_, UserRole>("SELECT id, name, require_two_factor FROM user_roles WHERE id = $1")
         .bind(id)
         .fetch_one(pool.get_ref())
-        .await;
-    match row {
-        Ok(row) => HttpResponse::Ok().json(row),
-        Err(e) => HttpResponse::InternalServerError().json(serde_json::json!({"error": e.to_string()})),
-    }
-}
-
-#[delete("/{id}")]
-pub async fn delete_user_role(claims: crate::middleware::jwt_extractor::Claims, pool: web::Data<PgPool>, path: web::Path<Uuid>) -> impl Responder {
-    if !is_admin(&claims, pool.get_ref()).await {
-        return HttpResponse::Forbidden().json(serde_json::json!({ "message": "Admin only" }));
-    }
+        .await?;
+    let row = sqlx::query_as::<_, UserRole>(
+        "UPDATE user_roles
+         SET name = COALESCE($1, name), require_two_factor = COALESCE($2, require_two_factor)
+         WHERE id = $3
+         RETURNING id, name, require_two_factor"
+    )
+    .bind(&form.name)
+    .bind(form.require_two_factor)
+    .bind(id)
+    .fetch_one(pool.get_ref())
+    .await?;
+    record_change(pool.get_ref(), &req, perms.user_id, "role.updated", id, Some(&before), Some(&row)).await?;
+    Ok(HttpResponse::Ok().json(row))
+}
+
+#[utoipa::path(
+    context_path = "/user_roles",
+    tag = "lookup",
+    responses(
+        (status = 200, body = serde_json::Value),
+        (status = 403, description = "Butuh izin manage_roles", body = ErrorBody),
+        (status = 404, body = ErrorBody),
+        (status = 422, description = "Masih dipakai oleh data lain", body = ErrorBody),
+    ),
+)]
+#[delete("/{id}", wrap = "RequirePermission::new(\"manage_roles\")")]
+pub async fn delete_user_role(
+    req: HttpRequest,
+    perms: UserPermissions,
+    pool: web::Data<PgPool>,
+    path: web::Path<Uuid>,
+) -> Result<HttpResponse, ApiError> {
     let id = path.into_inner();
-    let row = sqlx::query("DELETE FROM user_roles WHERE id = $1 RETURNING id")
+    let before = sqlx::query_as::<_, UserRole>("DELETE FROM user_roles WHERE id = $1 RETURNING id, name, require_two_factor")
         .bind(id)
         .fetch_optional(pool.get_ref())
-        .await;
-    match row {
-        Ok(Some(_)) => HttpResponse::Ok().json(serde_json::json!({"success": true})),
-        Ok(None) => HttpResponse::NotFound().json(serde_json::json!({"error": "Not found"})),
-        Err(e) => HttpResponse::InternalServerError().json(serde_json::json!({"error": e.to_string()})),
-    }
+        .await?
+        .ok_or_else(|| ApiError::NotFound("Not found".to_string()))?;
+    record_change(pool.get_ref(), &req, perms.user_id, "role.deleted", id, Some(&before), None).await?;
+    Ok(HttpResponse::Ok().json(serde_json::json!({"success": true})))
 }
 
 pub fn user_roles_config(cfg: &mut web::ServiceConfig) {
@@ -405,79 +634,120 @@ pub fn user_roles_config(cfg: &mut web::ServiceConfig) {
 }
 
 // ----------------- Locations -----------------
-#[derive(Serialize, Deserialize, sqlx::FromRow)]
+#[derive(Serialize, Deserialize, sqlx::FromRow, ToSchema)]
 pub struct Location {
     pub id: Uuid,
     pub name: String,
     pub description: Option<String>,
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, ToSchema)]
 pub struct LocationPayload {
     pub name: String,
     pub description: Option<String>,
 }
 
-#[get("")]
-pub async fn get_locations(_claims: crate::middleware::jwt_extractor::Claims, pool: web::Data<PgPool>) -> impl Responder {
+#[utoipa::path(
+    context_path = "/locations",
+    tag = "lookup",
+    responses(
+        (status = 200, body = Vec<Location>),
+        (status = 403, description = "Butuh izin view_items", body = ErrorBody),
+    ),
+)]
+#[get("", wrap = "RequirePermission::new(\"view_items\")")]
+pub async fn get_locations(pool: web::Data<PgPool>) -> Result<HttpResponse, ApiError> {
     let rows = sqlx::query_as::<_, Location>("SELECT id, name, description FROM locations ORDER BY name")
         .fetch_all(pool.get_ref())
-        .await;
-    match rows {
-        Ok(rows) => HttpResponse::Ok().json(rows),
-        Err(e) => HttpResponse::InternalServerError().json(serde_json::json!({"error": e.to_string()})),
-    }
-}
-
-#[post("")]
-pub async fn create_location(claims: crate::middleware::jwt_extractor::Claims, pool: web::Data<PgPool>, form: web::Json<LocationPayload>) -> impl Responder {
-    if !is_admin(&claims, pool.get_ref()).await {
-        return HttpResponse::Forbidden().json(serde_json::json!({ "message": "Admin only" }));
-    }
+        .await?;
+    Ok(HttpResponse::Ok().json(rows))
+}
+
+#[utoipa::path(
+    context_path = "/locations",
+    tag = "lookup",
+    request_body = LocationPayload,
+    responses(
+        (status = 200, body = Location),
+        (status = 403, description = "Butuh izin manage_locations", body = ErrorBody),
+        (status = 409, description = "Nama sudah dipakai", body = ErrorBody),
+    ),
+)]
+#[post("", wrap = "RequirePermission::new(\"manage_locations\")")]
+pub async fn create_location(
+    req: HttpRequest,
+    perms: UserPermissions,
+    pool: web::Data<PgPool>,
+    form: web::Json<LocationPayload>,
+) -> Result<HttpResponse, ApiError> {
     let row = sqlx::query_as::<_, Location>("INSERT INTO locations (name, description) VALUES ($1, $2) RETURNING id, name, description")
         .bind(&form.name)
         .bind(&form.description)
         .fetch_one(pool.get_ref())
-        .await;
-    match row {
-        Ok(row) => HttpResponse::Ok().json(row),
-        Err(e) => HttpResponse::InternalServerError().json(serde_json::json!({"error": e.to_string()})),
-    }
-}
-
-#[patch("/{id}")]
-pub async fn update_location(claims: crate::middleware::jwt_extractor::Claims, pool: web::Data<PgPool>, path: web::Path<Uuid>, form: web::Json<LocationPayload>) -> impl Responder {
-    if !is_admin(&claims, pool.get_ref()).await {
-        return HttpResponse::Forbidden().json(serde_json::json!({ "message": "Admin only" }));
-    }
+        .await?;
+    record_change(pool.get_ref(), &req, perms.user_id, "location.created", row.id, None, Some(&row)).await?;
+    Ok(HttpResponse::Ok().json(row))
+}
+
+#[utoipa::path(
+    context_path = "/locations",
+    tag = "lookup",
+    request_body = LocationPayload,
+    responses(
+        (status = 200, body = Location),
+        (status = 403, description = "Butuh izin manage_locations", body = ErrorBody),
+        (status = 404, body = ErrorBody),
+        (status = 409, description = "Nama sudah dipakai", body = ErrorBody),
+    ),
+)]
+#[patch("/{id}", wrap = "RequirePermission::new(\"manage_locations\")")]
+pub async fn update_location(
+    req: HttpRequest,
+    perms: UserPermissions,
+    pool: web::Data<PgPool>,
+    path: web::Path<Uuid>,
+    form: web::Json<LocationPayload>,
+) -> Result<HttpResponse, ApiError> {
     let id = path.into_inner();
+    let before = sqlx::query_as::<_, Location>("SELECT id, name, description FROM locations WHERE id = $1")
+        .bind(id)
+        .fetch_one(pool.get_ref())
+        .await?;
     let row = sqlx::query_as::<_, Location>("UPDATE locations SET name = $1, description = $2 WHERE id = $3 RETURNING id, name, description")
         .bind(&form.name)
         .bind(&form.description)
         .bind(id)
         .fetch_one(pool.get_ref())
-        .await;
-    match row {
-        Ok(row) => HttpResponse::Ok().json(row),
-        Err(e) => HttpResponse::InternalServerError().json(serde_json::json!({"error": e.to_string()})),
-    }
-}
-
-#[delete("/{id}")]
-pub async fn delete_location(claims: crate::middleware::jwt_extractor::Claims, pool: web::Data<PgPool>, path: web::Path<Uuid>) -> impl Responder {
-    if !is_admin(&claims, pool.get_ref()).await {
-        return HttpResponse::Forbidden().json(serde_json::json!({ "message": "Admin only" }));
-    }
+        .await?;
+    record_change(pool.get_ref(), &req, perms.user_id, "location.updated", id, Some(&before), Some(&row)).await?;
+    Ok(HttpResponse::Ok().json(row))
+}
+
+#[utoipa::path(
+    context_path = "/locations",
+    tag = "lookup",
+    responses(
+        (status = 200, body = serde_json::Value),
+        (status = 403, description = "Butuh izin manage_locations", body = ErrorBody),
+        (status = 404, body = ErrorBody),
+        (status = 422, description = "Masih dipakai oleh data lain", body = ErrorBody),
+    ),
+)]
+#[delete("/{id}", wrap = "RequirePermission::new(\"manage_locations\")")]
+pub async fn delete_location(
+    req: HttpRequest,
+    perms: UserPermissions,
+    pool: web::Data<PgPool>,
+    path: web::Path<Uuid>,
+) -> Result<HttpResponse, ApiError> {
     let id = path.into_inner();
-    let row = sqlx::query("DELETE FROM locations WHERE id = $1 RETURNING id")
+    let before = sqlx::query_as::<_, Location>("DELETE FROM locations WHERE id = $1 RETURNING id, name, description")
         .bind(id)
         .fetch_optional(pool.get_ref())
-        .await;
-    match row {
-        Ok(Some(_)) => HttpResponse::Ok().json(serde_json::json!({"success": true})),
-        Ok(None) => HttpResponse::NotFound().json(serde_json::json!({"error": "Not found"})),
-        Err(e) => HttpResponse::InternalServerError().json(serde_json::json!({"error": e.to_string()})),
-    }
+        .await?
+        .ok_or_else(|| ApiError::NotFound("Not found".to_string()))?;
+    record_change(pool.get_ref(), &req, perms.user_id, "location.deleted", id, Some(&before), None).await?;
+    Ok(HttpResponse::Ok().json(serde_json::json!({"success": true})))
 }
 
 pub fn locations_config(cfg: &mut web::ServiceConfig) {
@@ -488,7 +758,7 @@ pub fn locations_config(cfg: &mut web::ServiceConfig) {
 }
 
 // ----------------- ItemStatuses -----------------
-#[derive(Serialize, Deserialize, sqlx::FromRow)]
+#[derive(Serialize, Deserialize, sqlx::FromRow, ToSchema)]
 pub struct ItemStatus {
     pub id: Uuid,
     pub name: String,
@@ -496,75 +766,116 @@ pub struct ItemStatus {
     pub color: Option<String>,
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, ToSchema)]
 pub struct ItemStatusPayload {
     pub name: String,
     pub description: Option<String>,
     pub color: Option<String>,
 }
 
-#[get("")]
-pub async fn get_item_statuses(_claims: crate::middleware::jwt_extractor::Claims, pool: web::Data<PgPool>) -> impl Responder {
+#[utoipa::path(
+    context_path = "/item_statuses",
+    tag = "lookup",
+    responses(
+        (status = 200, body = Vec<ItemStatus>),
+        (status = 403, description = "Butuh izin view_items", body = ErrorBody),
+    ),
+)]
+#[get("", wrap = "RequirePermission::new(\"view_items\")")]
+pub async fn get_item_statuses(pool: web::Data<PgPool>) -> Result<HttpResponse, ApiError> {
     let rows = sqlx::query_as::<_, ItemStatus>("SELECT id, name, description, color FROM item_statuses ORDER BY name")
         .fetch_all(pool.get_ref())
-        .await;
-    match rows {
-        Ok(rows) => HttpResponse::Ok().json(rows),
-        Err(e) => HttpResponse::InternalServerError().json(serde_json::json!({"error": e.to_string()})),
-    }
-}
-
-#[post("")]
-pub async fn create_item_status(claims: crate::middleware::jwt_extractor::Claims, pool: web::Data<PgPool>, form: web::Json<ItemStatusPayload>) -> impl Responder {
-    if !is_admin(&claims, pool.get_ref()).await {
-        return HttpResponse::Forbidden().json(serde_json::json!({ "message": "Admin only" }));
-    }
+        .await?;
+    Ok(HttpResponse::Ok().json(rows))
+}
+
+#[utoipa::path(
+    context_path = "/item_statuses",
+    tag = "lookup",
+    request_body = ItemStatusPayload,
+    responses(
+        (status = 200, body = ItemStatus),
+        (status = 403, description = "Butuh izin admin_access", body = ErrorBody),
+        (status = 409, description = "Nama sudah dipakai", body = ErrorBody),
+    ),
+)]
+#[post("", wrap = "RequirePermission::new(\"admin_access\")")]
+pub async fn create_item_status(
+    req: HttpRequest,
+    perms: UserPermissions,
+    pool: web::Data<PgPool>,
+    form: web::Json<ItemStatusPayload>,
+) -> Result<HttpResponse, ApiError> {
     let row = sqlx::query_as::<_, ItemStatus>("INSERT INTO item_statuses (name, description, color) VALUES ($1, $2, $3) RETURNING id, name, description, color")
         .bind(&form.name)
         .bind(&form.description)
         .bind(&form.color)
         .fetch_one(pool.get_ref())
-        .await;
-    match row {
-        Ok(row) => HttpResponse::Ok().json(row),
-        Err(e) => HttpResponse::InternalServerError().json(serde_json::json!({"error": e.to_string()})),
-    }
-}
-
-#[patch("/{id}")]
-pub async fn update_item_status(claims: crate::middleware::jwt_extractor::Claims, pool: web::Data<PgPool>, path: web::Path<Uuid>, form: web::Json<ItemStatusPayload>) -> impl Responder {
-    if !is_admin(&claims, pool.get_ref()).await {
-        return HttpResponse::Forbidden().json(serde_json::json!({ "message": "Admin only" }));
-    }
+        .await?;
+    record_change(pool.get_ref(), &req, perms.user_id, "item_status.created", row.id, None, Some(&row)).await?;
+    Ok(HttpResponse::Ok().json(row))
+}
+
+#[utoipa::path(
+    context_path = "/item_statuses",
+    tag = "lookup",
+    request_body = ItemStatusPayload,
+    responses(
+        (status = 200, body = ItemStatus),
+        (status = 403, description = "Butuh izin admin_access", body = ErrorBody),
+        (status = 404, body = ErrorBody),
+        (status = 409, description = "Nama sudah dipakai", body = ErrorBody),
+    ),
+)]
+#[patch("/{id}", wrap = "RequirePermission::new(\"admin_access\")")]
+pub async fn update_item_status(
+    req: HttpRequest,
+    perms: UserPermissions,
+    pool: web::Data<PgPool>,
+    path: web::Path<Uuid>,
+    form: web::Json<ItemStatusPayload>,
+) -> Result<HttpResponse, ApiError> {
     let id = path.into_inner();
+    let before = sqlx::query_as::<_, ItemStatus>("SELECT id, name, description, color FROM item_statuses WHERE id = $1")
+        .bind(id)
+        .fetch_one(pool.get_ref())
+        .await?;
     let row = sqlx::query_as::<_, ItemStatus>("UPDATE item_statuses SET name = $1, description = $2, color = $3 WHERE id = $4 RETURNING id, name, description, color")
         .bind(&form.name)
         .bind(&form.description)
         .bind(&form.color)
         .bind(id)
         .fetch_one(pool.get_ref())
-        .await;
-    match row {
-        Ok(row) => HttpResponse::Ok().json(row),
-        Err(e) => HttpResponse::InternalServerError().json(serde_json::json!({"error": e.to_string()})),
-    }
-}
-
-#[delete("/{id}")]
-pub async fn delete_item_status(claims: crate::middleware::jwt_extractor::Claims, pool: web::Data<PgPool>, path: web::Path<Uuid>) -> impl Responder {
-    if !is_admin(&claims, pool.get_ref()).await {
-        return HttpResponse::Forbidden().json(serde_json::json!({ "message": "Admin only" }));
-    }
+        .await?;
+    record_change(pool.get_ref(), &req, perms.user_id, "item_status.updated", id, Some(&before), Some(&row)).await?;
+    Ok(HttpResponse::Ok().json(row))
+}
+
+#[utoipa::path(
+    context_path = "/item_statuses",
+    tag = "lookup",
+    responses(
+        (status = 200, body = serde_json::Value),
+        (status = 403, description = "Butuh izin admin_access", body = ErrorBody),
+        (status = 404, body = ErrorBody),
+        (status = 422, description = "Masih dipakai oleh data lain", body = ErrorBody),
+    ),
+)]
+#[delete("/{id}", wrap = "RequirePermission::new(\"admin_access\")")]
+pub async fn delete_item_status(
+    req: HttpRequest,
+    perms: UserPermissions,
+    pool: web::Data<PgPool>,
+    path: web::Path<Uuid>,
+) -> Result<HttpResponse, ApiError> {
     let id = path.into_inner();
-    let row = sqlx::query("DELETE FROM item_statuses WHERE id = $1 RETURNING id")
+    let before = sqlx::query_as::<_, ItemStatus>("DELETE FROM item_statuses WHERE id = $1 RETURNING id, name, description, color")
         .bind(id)
         .fetch_optional(pool.get_ref())
-        .await;
-    match row {
-        Ok(Some(_)) => HttpResponse::Ok().json(serde_json::json!({"success": true})),
-        Ok(None) => HttpResponse::NotFound().json(serde_json::json!({"error": "Not found"})),
-        Err(e) => HttpResponse::InternalServerError().json(serde_json::json!({"error": e.to_string()})),
-    }
+        .await?
+        .ok_or_else(|| ApiError::NotFound("Not found".to_string()))?;
+    record_change(pool.get_ref(), &req, perms.user_id, "item_status.deleted", id, Some(&before), None).await?;
+    Ok(HttpResponse::Ok().json(serde_json::json!({"success": true})))
 }
 
 pub fn item_statuses_config(cfg: &mut web::ServiceConfig) {
@@ -574,6 +885,51 @@ pub fn item_statuses_config(cfg: &mut web::ServiceConfig) {
         .service(delete_item_status);
 }
 
+// -------- Default seed data --------
+// Sama dengan data awal di migrations/20230424000000_consolidated.sql
+
+pub const DEFAULT_CATEGORIES: &[&str] = &["electronics", "prayer", "furniture"];
+pub const DEFAULT_ITEM_SOURCES: &[&str] = &["existing", "donation", "procurement"];
+pub const DEFAULT_CONDITIONS: &[&str] = &["good", "damaged", "lost"];
+pub const DEFAULT_PROCUREMENT_STATUSES: &[&str] = &["pending", "approved", "rejected", "purchased"];
+pub const DEFAULT_ITEM_STATUSES: &[(&str, &str, &str)] = &[
+    ("active", "Item is available for use", "green"),
+    ("maintenance", "Item is under maintenance", "orange"),
+    ("borrowed", "Item is currently borrowed", "blue"),
+    ("reserved", "Item is reserved for future use", "purple"),
+    ("damaged", "Item is damaged but still usable", "yellow"),
+    ("unusable", "Item is damaged beyond use", "red"),
+];
+
+/// Isi table lookup dengan nilai default yang belum ada. Mengembalikan jumlah baris baru.
+pub async fn seed_default_lookups(pool: &PgPool) -> Result<u64, sqlx::Error> {
+    let mut inserted = 0;
+    for (table, names) in [
+        ("categories", DEFAULT_CATEGORIES),
+        ("item_sources", DEFAULT_ITEM_SOURCES),
+        ("conditions", DEFAULT_CONDITIONS),
+        ("procurement_statuses", DEFAULT_PROCUREMENT_STATUSES),
+    ] {
+        for name in names {
+            inserted += sqlx::query(&format!("INSERT INTO {} (name) VALUES ($1) ON CONFLICT (name) DO NOTHING", table))
+                .bind(name)
+                .execute(pool)
+                .await?
+                .rows_affected();
+        }
+    }
+    for (name, description, color) in DEFAULT_ITEM_STATUSES {
+        inserted += sqlx::query("INSERT INTO item_statuses (name, description, color) VALUES ($1, $2, $3) ON CONFLICT (name) DO NOTHING")
+            .bind(name)
+            .bind(description)
+            .bind(color)
+            .execute(pool)
+            .await?
+            .rows_affected();
+    }
+    Ok(inserted)
+}
+
 // -------- Register all configs --------
 pub fn lookup_config(cfg: &mut web::ServiceConfig) {
     use actix_web::web::scope;
@@ -586,4 +942,35 @@ pub fn lookup_config(cfg: &mut web::ServiceConfig) {
     cfg.service(scope("/item_statuses").configure(item_statuses_config));
 }
 
-
+#[derive(OpenApi)]
+#[openapi(paths(
+    get_categories,
+    create_category,
+    update_category,
+    delete_category,
+    get_item_sources,
+    create_item_source,
+    update_item_source,
+    delete_item_source,
+    get_conditions,
+    create_condition,
+    update_condition,
+    delete_condition,
+    get_procurement_statuses,
+    create_procurement_status,
+    update_procurement_status,
+    delete_procurement_status,
+    get_user_roles,
+    create_user_role,
+    update_user_role,
+    delete_user_role,
+    get_locations,
+    create_location,
+    update_location,
+    delete_location,
+    get_item_statuses,
+    create_item_status,
+    update_item_status,
+    delete_item_status,
+))]
+pub struct LookupApi;
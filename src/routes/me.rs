@@ -1,47 +1,54 @@
-use actix_web::{get, HttpRequest, HttpResponse, Responder};
-use crate::routes::auth::Claims;
+use actix_web::{get, web, HttpResponse};
+use crate::error::{ApiError, ErrorBody};
+use crate::middleware::permission_guard::UserPermissions;
 
-use jsonwebtoken::{decode, DecodingKey, Validation};
 use serde::Serialize;
+use sqlx::{FromRow, PgPool};
+use utoipa::{OpenApi, ToSchema};
 use uuid::Uuid;
-use sqlx::PgPool;
 
-#[derive(Serialize)]
+#[derive(Serialize, FromRow, ToSchema)]
 pub struct MeResponse {
     pub id: Uuid,
     pub name: String,
+    pub email: Option<String>,
+    pub phone_number: Option<String>,
+    pub avatar_url: Option<String>,
+    pub role_id: Uuid,
+    /// Nama role, mis. `admin` atau `borrower`
     pub role: String,
+    pub role_description: Option<String>,
+    /// Permission efektif dari role, untuk menentukan menu/tombol yang ditampilkan frontend
+    #[sqlx(skip)]
+    pub permissions: Vec<String>,
 }
 
+/// User yang sedang login beserta role dan permission-nya. Token dibaca dari header
+/// `Authorization: Bearer` atau cookie `token`.
+#[utoipa::path(
+    tag = "auth",
+    responses(
+        (status = 200, body = MeResponse),
+        (status = 401, body = ErrorBody),
+    )
+)]
 #[get("/api/me")]
-pub async fn me(req: HttpRequest, pool: actix_web::web::Data<PgPool>) -> impl Responder {
-    let secret = std::env::var("JWT_SECRET").expect("JWT_SECRET harus di-set di .env");
-    let token_cookie = req.cookie("token");
-    if token_cookie.is_none() {
-        return HttpResponse::Unauthorized().json(serde_json::json!({ "error": "No token" }));
-    }
-    let token_cookie = token_cookie.unwrap();
-    let token = token_cookie.value().to_owned();
-    let token_data = decode::<Claims>(&token, &DecodingKey::from_secret(secret.as_bytes()), &Validation::default());
-    if let Ok(data_token) = token_data {
-        let user_id = match Uuid::parse_str(&data_token.claims.sub) {
-            Ok(uuid) => uuid,
-            Err(_) => return HttpResponse::Unauthorized().json(serde_json::json!({ "error": "Invalid user id in token" })),
-        };
-        // Query DB untuk ambil nama dan role
-        let row = sqlx::query_as::<_, crate::routes::user::User>("SELECT id, name, email, phone_number, avatar_url, role_id, created_at FROM users WHERE id = $1")
-            .bind(user_id)
-            .fetch_one(pool.get_ref())
-            .await;
-        match row {
-            Ok(user) => HttpResponse::Ok().json(MeResponse {
-                id: user.id,
-                name: user.name,
-                role: user.role_id.to_string(),
-            }),
-            Err(_) => HttpResponse::Unauthorized().json(serde_json::json!({ "error": "User not found" })),
-        }
-    } else {
-        HttpResponse::Unauthorized().json(serde_json::json!({ "error": "Invalid token" }))
-    }
+pub async fn me(perms: UserPermissions, pool: web::Data<PgPool>) -> Result<HttpResponse, ApiError> {
+    let mut user = sqlx::query_as::<_, MeResponse>(
+        "SELECT u.id, u.name, u.email, u.phone_number, u.avatar_url, u.role_id,
+                r.name AS role, r.description AS role_description
+         FROM users u
+         JOIN user_roles r ON r.id = u.role_id
+         WHERE u.id = $1"
+    )
+    .bind(perms.user_id)
+    .fetch_optional(pool.get_ref())
+    .await?
+    .ok_or_else(|| ApiError::Unauthorized("User not found".to_string()))?;
+    user.permissions = perms.names();
+    Ok(HttpResponse::Ok().json(user))
 }
+
+#[derive(OpenApi)]
+#[openapi(paths(me))]
+pub struct MeApi;
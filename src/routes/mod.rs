@@ -6,3 +6,46 @@ pub mod lookup;
 pub mod upload;
 pub mod permissions;
 pub mod borrowings;
+pub mod calendar;
+pub mod penalties;
+pub mod health;
+pub mod sessions;
+pub mod two_factor;
+pub mod api_tokens;
+pub mod oidc;
+pub mod audit;
+pub mod security_events;
+
+use actix_web::web;
+
+/// Daftarkan semua endpoint API. Dipakai oleh `main.rs` dan test spesifikasi OpenAPI,
+/// jadi route baru cukup didaftarkan di sini.
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    cfg
+        // Health check untuk platform deploy (render.yaml: healthCheckPath /health)
+        .configure(health::health_config)
+        .service(auth::check_user)
+        .service(auth::accept_invite)
+        .service(auth::request_reset)
+        .service(auth::confirm_reset)
+        .service(auth::login)
+        .service(auth::login_two_factor)
+        .service(auth::login_two_factor_setup)
+        .service(auth::refresh)
+        .service(auth::logout)
+        .service(web::scope("/api/auth/oidc").configure(oidc::oidc_config))
+        .service(me::me)
+        .service(web::scope("/api/sessions").configure(sessions::sessions_config))
+        .service(web::scope("/api/2fa").configure(two_factor::two_factor_config))
+        .service(web::scope("/api/tokens").configure(api_tokens::api_tokens_config))
+        .service(web::scope("/api/users").configure(user::user_config))
+        .service(web::scope("/api/audit-events").configure(audit::audit_config))
+        .service(web::scope("/api/security-events").configure(security_events::security_events_config))
+        .service(web::scope("/api/items").configure(items::items_config))
+        .service(web::scope("/api/lookup").configure(lookup::lookup_config))
+        .service(web::scope("/api/upload").configure(upload::upload_config))
+        .service(web::scope("/api/permissions").configure(permissions::permissions_config))
+        .service(web::scope("/api/borrowings").configure(borrowings::borrowings_config))
+        .service(web::scope("/api/calendar").configure(calendar::calendar_config))
+        .service(web::scope("/api/penalties").configure(penalties::penalties_config));
+}
@@ -0,0 +1,194 @@
+use actix_web::http::header;
+use actix_web::{get, web, HttpRequest, HttpResponse};
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use utoipa::{IntoParams, OpenApi, ToSchema};
+
+use crate::config::Config;
+use crate::error::{ApiError, ErrorBody};
+use crate::routes::auth::session_cookies;
+use crate::services::audit::AuditEvent;
+use crate::services::oidc::{OidcClient, OidcLogin};
+use crate::services::security_log::{record_login_outcome, SecurityEvent};
+use crate::services::sessions::{ensure_user_active, start_session, ClientInfo};
+use crate::services::two_factor::{create_login_challenge, two_factor_status};
+
+#[derive(Serialize, ToSchema)]
+pub struct OidcInfo {
+    /// false jika `OIDC_ISSUER_URL` belum di-set; frontend menyembunyikan tombol SSO
+    pub enabled: bool,
+    /// Label tombol login SSO
+    pub display_name: String,
+    /// Arahkan browser (bukan fetch) ke URL ini untuk mulai login
+    pub login_url: String,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct OidcLoginQuery {
+    /// Path frontend tujuan setelah login berhasil, mis. `/items`
+    pub redirect: Option<String>,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct OidcCallbackQuery {
+    pub code: Option<String>,
+    pub state: Option<String>,
+    /// Diisi provider jika login dibatalkan/ditolak
+    pub error: Option<String>,
+}
+
+/// Redirect 302 ke halaman frontend
+fn redirect_to(url: String) -> HttpResponse {
+    HttpResponse::Found().insert_header((header::LOCATION, url)).finish()
+}
+
+/// Hanya path relatif di frontend, supaya parameter `redirect` tidak bisa dipakai
+/// mengarahkan user ke situs lain
+fn safe_redirect_path(path: Option<&str>) -> Option<&str> {
+    path.filter(|path| path.starts_with('/') && !path.starts_with("//") && !path.starts_with("/\\"))
+}
+
+/// Status login SSO untuk halaman login frontend
+#[utoipa::path(tag = "auth", security(()), responses((status = 200, body = OidcInfo)))]
+#[get("")]
+pub async fn get_oidc_info(oidc: web::Data<OidcClient>) -> Result<HttpResponse, ApiError> {
+    Ok(HttpResponse::Ok().json(OidcInfo {
+        enabled: oidc.is_configured(),
+        display_name: oidc.display_name().to_string(),
+        login_url: "/api/auth/oidc/login".to_string(),
+    }))
+}
+
+/// Mulai login SSO: redirect browser ke halaman login provider OIDC
+#[utoipa::path(
+    tag = "auth",
+    security(()),
+    params(OidcLoginQuery),
+    responses(
+        (status = 302, description = "Redirect ke provider OIDC"),
+        (status = 502, description = "Provider OIDC tidak bisa dihubungi (`oidc_provider_error`)", body = ErrorBody),
+        (status = 503, description = "Login SSO belum dikonfigurasi", body = ErrorBody),
+    )
+)]
+#[get("/login")]
+pub async fn oidc_login(
+    pool: web::Data<PgPool>,
+    oidc: web::Data<OidcClient>,
+    query: web::Query<OidcLoginQuery>,
+) -> Result<HttpResponse, ApiError> {
+    let redirect_path = safe_redirect_path(query.redirect.as_deref());
+    Ok(redirect_to(oidc.authorization_url(pool.get_ref(), redirect_path).await?))
+}
+
+/// Callback dari provider OIDC. Jika berhasil, session dibuat (cookie `token` dan
+/// `refresh_token`) lalu browser diarahkan ke frontend. User dengan 2FA diarahkan ke
+/// `{FRONTEND_URL}/login?two_factor=required|setup&challenge_token=...` untuk melanjutkan
+/// lewat `/api/login/2fa`; kegagalan diarahkan ke `{FRONTEND_URL}/login?oidc_error=<code>`.
+#[utoipa::path(
+    tag = "auth",
+    security(()),
+    params(OidcCallbackQuery),
+    responses((status = 302, description = "Redirect ke frontend"))
+)]
+#[get("/callback")]
+pub async fn oidc_callback(
+    req: HttpRequest,
+    pool: web::Data<PgPool>,
+    config: web::Data<Config>,
+    oidc: web::Data<OidcClient>,
+    query: web::Query<OidcCallbackQuery>,
+) -> HttpResponse {
+    let frontend_url = config.cors.frontend_url.trim_end_matches('/').to_string();
+    match complete_oidc_login(&req, pool.get_ref(), &config, &oidc, &query).await {
+        Ok(response) => response,
+        Err(e) => {
+            eprintln!("[WARN] Login OIDC gagal: {}", e);
+            let code = match &e {
+                ApiError::Detailed { code, .. } => code,
+                _ => "oidc_failed",
+            };
+            redirect_to(format!("{}/login?oidc_error={}", frontend_url, code))
+        },
+    }
+}
+
+async fn complete_oidc_login(
+    req: &HttpRequest,
+    pool: &PgPool,
+    config: &Config,
+    oidc: &OidcClient,
+    query: &OidcCallbackQuery,
+) -> Result<HttpResponse, ApiError> {
+    let frontend_url = config.cors.frontend_url.trim_end_matches('/');
+    if let Some(error) = &query.error {
+        return Err(ApiError::Detailed {
+            status: actix_web::http::StatusCode::UNAUTHORIZED,
+            code: "oidc_denied",
+            message: format!("Provider menolak login: {}", error),
+            details: serde_json::Value::Null,
+        });
+    }
+    let (Some(code), Some(state)) = (&query.code, &query.state) else {
+        return Err(ApiError::BadRequest("Parameter code/state tidak ada".to_string()));
+    };
+
+    let client = ClientInfo::from_request(req);
+    let OidcLogin { user, redirect_path } = match oidc.complete_login(pool, code, state).await {
+        Ok(login) => login,
+        Err(e) => {
+            record_login_outcome(pool, &client, None, None, "oidc", Err(&e)).await?;
+            return Err(e);
+        },
+    };
+    if user.provisioned || user.linked {
+        let action = if user.provisioned { "oidc.user_provisioned" } else { "oidc.identity_linked" };
+        AuditEvent::new(action, "user", Some(user.user_id))
+            .actor(user.user_id)
+            .client(&client)
+            .record(pool)
+            .await?;
+    }
+
+    if let Err(e) = ensure_user_active(pool, user.user_id).await {
+        record_login_outcome(pool, &client, Some(user.user_id), None, "oidc", Err(&e)).await?;
+        return Err(e);
+    }
+    // 2FA lokal tetap berlaku untuk login SSO
+    let two_factor = two_factor_status(pool, user.user_id).await?;
+    if two_factor.enabled || two_factor.required {
+        let challenge_token = create_login_challenge(pool, user.user_id).await?;
+        let step = if two_factor.enabled { "required" } else { "setup" };
+        let reason = if two_factor.enabled { "two_factor_required" } else { "two_factor_setup_required" };
+        SecurityEvent::new("login.second_factor_required")
+            .user(user.user_id)
+            .details(serde_json::json!({ "method": "oidc", "reason": reason }))
+            .client(&client)
+            .record(pool)
+            .await?;
+        return Ok(redirect_to(format!(
+            "{}/login?two_factor={}&challenge_token={}",
+            frontend_url, step, challenge_token
+        )));
+    }
+
+    let tokens = start_session(pool, &config.jwt, user.user_id, user.role_id, &client).await?;
+    record_login_outcome(pool, &client, Some(user.user_id), None, "oidc", Ok(())).await?;
+    let [access_cookie, refresh_cookie] = session_cookies(config, &tokens);
+    Ok(HttpResponse::Found()
+        .insert_header((header::LOCATION, format!("{}{}", frontend_url, redirect_path.as_deref().unwrap_or("/"))))
+        .cookie(access_cookie)
+        .cookie(refresh_cookie)
+        .finish())
+}
+
+pub fn oidc_config(cfg: &mut web::ServiceConfig) {
+    cfg.service(get_oidc_info)
+        .service(oidc_login)
+        .service(oidc_callback);
+}
+
+#[derive(OpenApi)]
+#[openapi(paths(get_oidc_info, oidc_login, oidc_callback))]
+pub struct OidcApi;
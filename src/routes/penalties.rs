@@ -0,0 +1,314 @@
+use actix_web::{get, post, patch, delete, web, HttpResponse};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use utoipa::{IntoParams, OpenApi, ToSchema};
+use uuid::Uuid;
+
+use crate::middleware::jwt_extractor::Claims;
+use crate::middleware::permission_guard::UserPermissions;
+use crate::config::Config;
+use crate::error::{conflict_on_unique, ApiError, ErrorBody};
+use crate::services::penalties::{borrower_standing, BorrowerPenalty, BorrowerStanding};
+
+#[derive(Debug, Serialize, sqlx::FromRow, ToSchema)]
+pub struct PenaltyRule {
+    pub id: Uuid,
+    pub category_id: Option<Uuid>,
+    pub category_name: Option<String>,
+    pub amount_per_day: i64,
+    pub grace_days: i32,
+    pub max_amount: Option<i64>,
+    pub is_active: bool,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct PenaltyRulePayload {
+    pub category_id: Option<Uuid>,
+    pub amount_per_day: i64,
+    pub grace_days: Option<i32>,
+    pub max_amount: Option<i64>,
+    pub is_active: Option<bool>,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct PenaltyQuery {
+    pub borrower_id: Option<Uuid>,
+    /// pending, settled, atau waived
+    pub status: Option<String>,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct ResolvePenalty {
+    pub note: Option<String>,
+}
+
+#[derive(Debug, Serialize, sqlx::FromRow, ToSchema)]
+pub struct PenaltyWithDetails {
+    #[serde(flatten)]
+    #[sqlx(flatten)]
+    pub penalty: BorrowerPenalty,
+    pub borrower_name: String,
+    pub item_name: String,
+    pub expected_return_date: DateTime<Utc>,
+    pub actual_return_date: Option<DateTime<Utc>>,
+}
+
+const RULE_COLUMNS: &str = "r.id, r.category_id, c.name as category_name, r.amount_per_day, r.grace_days,
+                            r.max_amount, r.is_active, r.created_at, r.updated_at";
+
+fn validate_rule(form: &PenaltyRulePayload) -> Result<(), ApiError> {
+    if form.amount_per_day < 0 || form.grace_days.unwrap_or(0) < 0 || form.max_amount.unwrap_or(0) < 0 {
+        return Err(ApiError::BadRequest(
+            "amount_per_day, grace_days and max_amount must not be negative".to_string()
+        ));
+    }
+    Ok(())
+}
+
+#[utoipa::path(tag = "penalties", responses((status = 200, body = Vec<PenaltyRule>)))]
+#[get("/rules")]
+pub async fn get_penalty_rules(_claims: Claims, pool: web::Data<PgPool>) -> Result<HttpResponse, ApiError> {
+    let rules = sqlx::query_as::<_, PenaltyRule>(&format!(
+        "SELECT {} FROM penalty_rules r
+         LEFT JOIN categories c ON r.category_id = c.id
+         ORDER BY r.category_id NULLS FIRST, c.name",
+        RULE_COLUMNS
+    ))
+    .fetch_all(pool.get_ref())
+    .await?;
+    Ok(HttpResponse::Ok().json(rules))
+}
+
+#[utoipa::path(
+    tag = "penalties",
+    request_body = PenaltyRulePayload,
+    responses(
+        (status = 200, body = PenaltyRule),
+        (status = 400, body = ErrorBody),
+        (status = 403, description = "Butuh izin manage_penalties", body = ErrorBody),
+        (status = 409, description = "Kategori sudah punya aturan denda", body = ErrorBody),
+    )
+)]
+#[post("/rules")]
+pub async fn create_penalty_rule(perms: UserPermissions, pool: web::Data<PgPool>, form: web::Json<PenaltyRulePayload>) -> Result<HttpResponse, ApiError> {
+    if !perms.has("manage_penalties") {
+        return Err(ApiError::Forbidden("You don't have permission to manage penalties".to_string()));
+    }
+    validate_rule(&form)?;
+    let rule = sqlx::query_as::<_, PenaltyRule>(&format!(
+        "WITH r AS (
+             INSERT INTO penalty_rules (category_id, amount_per_day, grace_days, max_amount, is_active)
+             VALUES ($1, $2, $3, $4, $5)
+             RETURNING *
+         )
+         SELECT {} FROM r LEFT JOIN categories c ON r.category_id = c.id",
+        RULE_COLUMNS
+    ))
+    .bind(form.category_id)
+    .bind(form.amount_per_day)
+    .bind(form.grace_days.unwrap_or(0))
+    .bind(form.max_amount)
+    .bind(form.is_active.unwrap_or(true))
+    .fetch_one(pool.get_ref())
+    .await
+    .map_err(conflict_on_unique("A penalty rule already exists for this category"))?;
+    Ok(HttpResponse::Ok().json(rule))
+}
+
+#[utoipa::path(
+    tag = "penalties",
+    request_body = PenaltyRulePayload,
+    responses(
+        (status = 200, body = PenaltyRule),
+        (status = 400, body = ErrorBody),
+        (status = 403, description = "Butuh izin manage_penalties", body = ErrorBody),
+        (status = 404, body = ErrorBody),
+        (status = 409, description = "Kategori sudah punya aturan denda", body = ErrorBody),
+    )
+)]
+#[patch("/rules/{id}")]
+pub async fn update_penalty_rule(perms: UserPermissions, pool: web::Data<PgPool>, path: web::Path<Uuid>, form: web::Json<PenaltyRulePayload>) -> Result<HttpResponse, ApiError> {
+    if !perms.has("manage_penalties") {
+        return Err(ApiError::Forbidden("You don't have permission to manage penalties".to_string()));
+    }
+    validate_rule(&form)?;
+    let rule = sqlx::query_as::<_, PenaltyRule>(&format!(
+        "WITH r AS (
+             UPDATE penalty_rules
+             SET category_id = $1, amount_per_day = $2, grace_days = $3, max_amount = $4,
+                 is_active = $5, updated_at = NOW()
+             WHERE id = $6
+             RETURNING *
+         )
+         SELECT {} FROM r LEFT JOIN categories c ON r.category_id = c.id",
+        RULE_COLUMNS
+    ))
+    .bind(form.category_id)
+    .bind(form.amount_per_day)
+    .bind(form.grace_days.unwrap_or(0))
+    .bind(form.max_amount)
+    .bind(form.is_active.unwrap_or(true))
+    .bind(path.into_inner())
+    .fetch_optional(pool.get_ref())
+    .await
+    .map_err(conflict_on_unique("A penalty rule already exists for this category"))?
+    .ok_or_else(|| ApiError::NotFound("Penalty rule not found".to_string()))?;
+    Ok(HttpResponse::Ok().json(rule))
+}
+
+#[utoipa::path(
+    tag = "penalties",
+    responses(
+        (status = 200, body = serde_json::Value),
+        (status = 403, description = "Butuh izin manage_penalties", body = ErrorBody),
+        (status = 404, body = ErrorBody),
+    )
+)]
+#[delete("/rules/{id}")]
+pub async fn delete_penalty_rule(perms: UserPermissions, pool: web::Data<PgPool>, path: web::Path<Uuid>) -> Result<HttpResponse, ApiError> {
+    if !perms.has("manage_penalties") {
+        return Err(ApiError::Forbidden("You don't have permission to manage penalties".to_string()));
+    }
+    let result = sqlx::query("DELETE FROM penalty_rules WHERE id = $1")
+        .bind(path.into_inner())
+        .execute(pool.get_ref())
+        .await?;
+    if result.rows_affected() == 0 {
+        return Err(ApiError::NotFound("Penalty rule not found".to_string()));
+    }
+    Ok(HttpResponse::Ok().json(serde_json::json!({"success": true})))
+}
+
+/// Daftar denda. Peminjam biasa hanya melihat denda miliknya sendiri.
+#[utoipa::path(tag = "penalties", params(PenaltyQuery), responses((status = 200, body = Vec<PenaltyWithDetails>)))]
+#[get("")]
+pub async fn get_penalties(claims: Claims, perms: UserPermissions, pool: web::Data<PgPool>, query: web::Query<PenaltyQuery>) -> Result<HttpResponse, ApiError> {
+    let user_id = claims.user_id()?;
+    let can_view_all = perms.has("manage_penalties");
+    let borrower_id = if can_view_all { query.borrower_id } else { Some(user_id) };
+
+    let penalties = sqlx::query_as::<_, PenaltyWithDetails>(
+        "SELECT p.*, u.name as borrower_name, i.name as item_name,
+                b.expected_return_date, b.actual_return_date
+         FROM borrower_penalties p
+         JOIN users u ON p.borrower_id = u.id
+         JOIN item_borrowings b ON p.borrowing_id = b.id
+         JOIN items i ON b.item_id = i.id
+         WHERE ($1::uuid IS NULL OR p.borrower_id = $1)
+           AND ($2::text IS NULL OR p.status = $2)
+         ORDER BY p.created_at DESC"
+    )
+    .bind(borrower_id)
+    .bind(query.status.clone())
+    .fetch_all(pool.get_ref())
+    .await?;
+    Ok(HttpResponse::Ok().json(penalties))
+}
+
+/// Status peminjam yang login: denda belum lunas, jumlah keterlambatan, dan apakah diblokir
+#[utoipa::path(tag = "penalties", responses((status = 200, body = BorrowerStanding)))]
+#[get("/standing")]
+pub async fn get_my_standing(claims: Claims, pool: web::Data<PgPool>, config: web::Data<Config>) -> Result<HttpResponse, ApiError> {
+    let user_id = claims.user_id()?;
+    let standing = borrower_standing(pool.get_ref(), &config.penalties, user_id).await?;
+    Ok(HttpResponse::Ok().json(standing))
+}
+
+#[utoipa::path(
+    tag = "penalties",
+    responses(
+        (status = 200, body = BorrowerStanding),
+        (status = 403, description = "Butuh izin manage_penalties", body = ErrorBody),
+    )
+)]
+#[get("/standing/{user_id}")]
+pub async fn get_borrower_standing(perms: UserPermissions, pool: web::Data<PgPool>, config: web::Data<Config>, path: web::Path<Uuid>) -> Result<HttpResponse, ApiError> {
+    if !perms.has("manage_penalties") {
+        return Err(ApiError::Forbidden("You don't have permission to view borrower standing".to_string()));
+    }
+    let standing = borrower_standing(pool.get_ref(), &config.penalties, path.into_inner()).await?;
+    Ok(HttpResponse::Ok().json(standing))
+}
+
+async fn resolve_penalty(perms: UserPermissions, pool: web::Data<PgPool>, id: Uuid, status: &str, note: Option<String>) -> Result<HttpResponse, ApiError> {
+    if !perms.has("manage_penalties") {
+        return Err(ApiError::Forbidden("You don't have permission to manage penalties".to_string()));
+    }
+    let user_id = perms.user_id;
+
+    // Hanya denda yang masih pending yang bisa dilunasi atau dihapus
+    let penalty = sqlx::query_as::<_, BorrowerPenalty>(
+        "UPDATE borrower_penalties
+         SET status = $1, resolved_by = $2, resolved_at = NOW(), resolution_note = $3
+         WHERE id = $4 AND status = 'pending'
+         RETURNING *"
+    )
+    .bind(status)
+    .bind(user_id)
+    .bind(note)
+    .bind(id)
+    .fetch_optional(pool.get_ref())
+    .await?
+    .ok_or_else(|| ApiError::NotFound("Pending penalty not found".to_string()))?;
+    Ok(HttpResponse::Ok().json(penalty))
+}
+
+#[utoipa::path(
+    tag = "penalties",
+    request_body(content = Option<ResolvePenalty>),
+    responses(
+        (status = 200, body = BorrowerPenalty),
+        (status = 403, description = "Butuh izin manage_penalties", body = ErrorBody),
+        (status = 404, description = "Denda pending tidak ditemukan", body = ErrorBody),
+    )
+)]
+#[patch("/{id}/settle")]
+pub async fn settle_penalty(perms: UserPermissions, pool: web::Data<PgPool>, path: web::Path<Uuid>, form: Option<web::Json<ResolvePenalty>>) -> Result<HttpResponse, ApiError> {
+    let note = form.and_then(|f| f.into_inner().note);
+    resolve_penalty(perms, pool, path.into_inner(), "settled", note).await
+}
+
+#[utoipa::path(
+    tag = "penalties",
+    request_body(content = Option<ResolvePenalty>),
+    responses(
+        (status = 200, body = BorrowerPenalty),
+        (status = 403, description = "Butuh izin manage_penalties", body = ErrorBody),
+        (status = 404, description = "Denda pending tidak ditemukan", body = ErrorBody),
+    )
+)]
+#[patch("/{id}/waive")]
+pub async fn waive_penalty(perms: UserPermissions, pool: web::Data<PgPool>, path: web::Path<Uuid>, form: Option<web::Json<ResolvePenalty>>) -> Result<HttpResponse, ApiError> {
+    let note = form.and_then(|f| f.into_inner().note);
+    resolve_penalty(perms, pool, path.into_inner(), "waived", note).await
+}
+
+pub fn penalties_config(cfg: &mut web::ServiceConfig) {
+    cfg.service(get_penalty_rules)
+        .service(create_penalty_rule)
+        .service(update_penalty_rule)
+        .service(delete_penalty_rule)
+        .service(get_my_standing)
+        .service(get_borrower_standing)
+        .service(get_penalties)
+        .service(settle_penalty)
+        .service(waive_penalty);
+}
+
+#[derive(OpenApi)]
+#[openapi(paths(
+    get_penalty_rules,
+    create_penalty_rule,
+    update_penalty_rule,
+    delete_penalty_rule,
+    get_penalties,
+    get_my_standing,
+    get_borrower_standing,
+    settle_penalty,
+    waive_penalty,
+))]
+pub struct PenaltiesApi;
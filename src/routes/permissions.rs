@@ -1,111 +1,157 @@
-use actix_web::{get, post, patch, delete, web, HttpResponse, Responder};
+use actix_web::{get, post, patch, delete, web, HttpRequest, HttpResponse};
 use serde::{Deserialize, Serialize};
-use sqlx::PgPool;
+use sqlx::{PgExecutor, PgPool};
+use utoipa::{OpenApi, ToSchema};
 use uuid::Uuid;
 
+use crate::error::{ApiError, ErrorBody};
 use crate::middleware::jwt_extractor::Claims;
-use crate::middleware::permission_guard::has_permission;
+use crate::middleware::permission_guard::{PermissionCache, UserPermissions};
+use crate::services::audit::{record_change, AuditEvent};
+use crate::services::sessions::ClientInfo;
 
 // ----------------- Permissions -----------------
-#[derive(Serialize, Deserialize, sqlx::FromRow)]
+#[derive(Serialize, Deserialize, sqlx::FromRow, ToSchema)]
 pub struct Permission {
     pub id: Uuid,
     pub name: String,
     pub description: Option<String>,
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, ToSchema)]
 pub struct PermissionPayload {
     pub name: String,
     pub description: Option<String>,
 }
 
+pub async fn list_permissions(pool: &PgPool) -> Result<Vec<Permission>, sqlx::Error> {
+    sqlx::query_as::<_, Permission>("SELECT id, name, description FROM permissions ORDER BY name")
+        .fetch_all(pool)
+        .await
+}
+
+pub async fn find_permission_id(pool: &PgPool, name: &str) -> Result<Option<Uuid>, sqlx::Error> {
+    sqlx::query_scalar::<_, Uuid>("SELECT id FROM permissions WHERE name = $1")
+        .bind(name)
+        .fetch_optional(pool)
+        .await
+}
+
+#[utoipa::path(tag = "permissions", responses((status = 200, body = Vec<Permission>)))]
 #[get("")]
-pub async fn get_permissions(_claims: Claims, pool: web::Data<PgPool>) -> impl Responder {
-    let rows = sqlx::query_as::<_, Permission>("SELECT id, name, description FROM permissions ORDER BY name")
-        .fetch_all(pool.get_ref())
-        .await;
-    match rows {
-        Ok(rows) => HttpResponse::Ok().json(rows),
-        Err(e) => HttpResponse::InternalServerError().json(serde_json::json!({"error": e.to_string()})),
-    }
+pub async fn get_permissions(_claims: Claims, pool: web::Data<PgPool>) -> Result<HttpResponse, ApiError> {
+    let rows = list_permissions(pool.get_ref()).await?;
+    Ok(HttpResponse::Ok().json(rows))
 }
 
+#[utoipa::path(
+    tag = "permissions",
+    request_body = PermissionPayload,
+    responses(
+        (status = 200, body = Permission),
+        (status = 403, description = "Butuh izin manage_permissions", body = ErrorBody),
+        (status = 409, description = "Nama sudah dipakai", body = ErrorBody),
+    )
+)]
 #[post("")]
-pub async fn create_permission(claims: Claims, pool: web::Data<PgPool>, form: web::Json<PermissionPayload>) -> impl Responder {
+pub async fn create_permission(req: HttpRequest, perms: UserPermissions, pool: web::Data<PgPool>, form: web::Json<PermissionPayload>) -> Result<HttpResponse, ApiError> {
     // Only users with manage_permissions permission can create permissions
-    if !has_permission(&claims, pool.get_ref(), "manage_permissions").await {
-        return HttpResponse::Forbidden().json(serde_json::json!({ "message": "Insufficient permissions" }));
+    if !perms.has("manage_permissions") {
+        return Err(ApiError::Forbidden("Insufficient permissions".to_string()));
     }
 
+    let mut tx = pool.begin().await?;
     let row = sqlx::query_as::<_, Permission>("INSERT INTO permissions (name, description) VALUES ($1, $2) RETURNING id, name, description")
         .bind(&form.name)
         .bind(&form.description)
-        .fetch_one(pool.get_ref())
-        .await;
-    match row {
-        Ok(row) => HttpResponse::Ok().json(row),
-        Err(e) => HttpResponse::InternalServerError().json(serde_json::json!({"error": e.to_string()})),
-    }
+        .fetch_one(&mut *tx)
+        .await?;
+    record_change(&mut *tx, &req, perms.user_id, "permission.created", row.id, None, Some(&row)).await?;
+    tx.commit().await?;
+    Ok(HttpResponse::Ok().json(row))
 }
 
+#[utoipa::path(
+    tag = "permissions",
+    request_body = PermissionPayload,
+    responses(
+        (status = 200, body = Permission),
+        (status = 403, description = "Butuh izin manage_permissions", body = ErrorBody),
+        (status = 404, body = ErrorBody),
+    )
+)]
 #[patch("/{id}")]
-pub async fn update_permission(claims: Claims, pool: web::Data<PgPool>, path: web::Path<Uuid>, form: web::Json<PermissionPayload>) -> impl Responder {
+pub async fn update_permission(req: HttpRequest, perms: UserPermissions, pool: web::Data<PgPool>, cache: web::Data<PermissionCache>, path: web::Path<Uuid>, form: web::Json<PermissionPayload>) -> Result<HttpResponse, ApiError> {
     // Only users with manage_permissions permission can update permissions
-    if !has_permission(&claims, pool.get_ref(), "manage_permissions").await {
-        return HttpResponse::Forbidden().json(serde_json::json!({ "message": "Insufficient permissions" }));
+    if !perms.has("manage_permissions") {
+        return Err(ApiError::Forbidden("Insufficient permissions".to_string()));
     }
 
     let id = path.into_inner();
+    let mut tx = pool.begin().await?;
+    let before = sqlx::query_as::<_, Permission>("SELECT id, name, description FROM permissions WHERE id = $1 FOR UPDATE")
+        .bind(id)
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or_else(|| ApiError::NotFound("Permission not found".to_string()))?;
     let row = sqlx::query_as::<_, Permission>("UPDATE permissions SET name = $1, description = $2 WHERE id = $3 RETURNING id, name, description")
         .bind(&form.name)
         .bind(&form.description)
         .bind(id)
-        .fetch_one(pool.get_ref())
-        .await;
-    match row {
-        Ok(row) => HttpResponse::Ok().json(row),
-        Err(e) => HttpResponse::InternalServerError().json(serde_json::json!({"error": e.to_string()})),
-    }
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or_else(|| ApiError::NotFound("Permission not found".to_string()))?;
+    record_change(&mut *tx, &req, perms.user_id, "permission.updated", id, Some(&before), Some(&row)).await?;
+    tx.commit().await?;
+    // Nama permission bisa berubah, jadi cache semua role tidak berlaku lagi
+    cache.invalidate_all();
+    Ok(HttpResponse::Ok().json(row))
 }
 
+#[utoipa::path(
+    tag = "permissions",
+    responses(
+        (status = 200, body = serde_json::Value),
+        (status = 403, description = "Butuh izin manage_permissions", body = ErrorBody),
+        (status = 404, body = ErrorBody),
+    )
+)]
 #[delete("/{id}")]
-pub async fn delete_permission(claims: Claims, pool: web::Data<PgPool>, path: web::Path<Uuid>) -> impl Responder {
+pub async fn delete_permission(req: HttpRequest, perms: UserPermissions, pool: web::Data<PgPool>, cache: web::Data<PermissionCache>, path: web::Path<Uuid>) -> Result<HttpResponse, ApiError> {
     // Only users with manage_permissions permission can delete permissions
-    if !has_permission(&claims, pool.get_ref(), "manage_permissions").await {
-        return HttpResponse::Forbidden().json(serde_json::json!({ "message": "Insufficient permissions" }));
+    if !perms.has("manage_permissions") {
+        return Err(ApiError::Forbidden("Insufficient permissions".to_string()));
     }
 
     let id = path.into_inner();
-    let row = sqlx::query("DELETE FROM permissions WHERE id = $1 RETURNING id")
+    let mut tx = pool.begin().await?;
+    let before = sqlx::query_as::<_, Permission>("DELETE FROM permissions WHERE id = $1 RETURNING id, name, description")
         .bind(id)
-        .fetch_optional(pool.get_ref())
-        .await;
-    match row {
-        Ok(Some(_)) => HttpResponse::Ok().json(serde_json::json!({"success": true})),
-        Ok(None) => HttpResponse::NotFound().json(serde_json::json!({"error": "Not found"})),
-        Err(e) => HttpResponse::InternalServerError().json(serde_json::json!({"error": e.to_string()})),
-    }
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or_else(|| ApiError::NotFound("Permission not found".to_string()))?;
+    record_change(&mut *tx, &req, perms.user_id, "permission.deleted", id, Some(&before), None).await?;
+    tx.commit().await?;
+    cache.invalidate_all();
+    Ok(HttpResponse::Ok().json(serde_json::json!({"success": true})))
 }
 
 // ----------------- Role Permissions -----------------
-#[derive(Serialize, Deserialize, sqlx::FromRow)]
+#[derive(Serialize, Deserialize, sqlx::FromRow, ToSchema)]
 pub struct RolePermission {
     pub id: Uuid,
     pub role_id: Uuid,
     pub permission_id: Uuid,
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, ToSchema)]
 pub struct RolePermissionPayload {
     pub role_id: Uuid,
     pub permission_id: Uuid,
 }
 
-#[get("/role/{role_id}")]
-pub async fn get_role_permissions(_claims: Claims, pool: web::Data<PgPool>, path: web::Path<Uuid>) -> impl Responder {
-    let role_id = path.into_inner();
-    let rows = sqlx::query_as::<_, Permission>(
+pub async fn list_role_permissions(pool: &PgPool, role_id: Uuid) -> Result<Vec<Permission>, sqlx::Error> {
+    sqlx::query_as::<_, Permission>(
         "SELECT p.id, p.name, p.description 
          FROM permissions p
          JOIN role_permissions rp ON p.id = rp.permission_id
@@ -113,75 +159,122 @@ pub async fn get_role_permissions(_claims: Claims, pool: web::Data<PgPool>, path
          ORDER BY p.name"
     )
     .bind(role_id)
-    .fetch_all(pool.get_ref())
-    .await;
-    match rows {
-        Ok(rows) => HttpResponse::Ok().json(rows),
-        Err(e) => HttpResponse::InternalServerError().json(serde_json::json!({"error": e.to_string()})),
-    }
+    .fetch_all(pool)
+    .await
 }
 
-#[post("/role")]
-pub async fn assign_permission_to_role(claims: Claims, pool: web::Data<PgPool>, form: web::Json<RolePermissionPayload>) -> impl Responder {
-    // Only users with manage_roles permission can assign permissions to roles
-    if !has_permission(&claims, pool.get_ref(), "manage_roles").await {
-        return HttpResponse::Forbidden().json(serde_json::json!({ "message": "Insufficient permissions" }));
-    }
+/// Assign permission ke role. Mengembalikan None jika permission sudah ter-assign.
+pub async fn grant_permission(db: impl PgExecutor<'_>, role_id: Uuid, permission_id: Uuid) -> Result<Option<RolePermission>, sqlx::Error> {
+    sqlx::query_as::<_, RolePermission>(
+        "INSERT INTO role_permissions (role_id, permission_id) VALUES ($1, $2)
+         ON CONFLICT (role_id, permission_id) DO NOTHING
+         RETURNING id, role_id, permission_id"
+    )
+    .bind(role_id)
+    .bind(permission_id)
+    .fetch_optional(db)
+    .await
+}
 
-    // Check if the role-permission mapping already exists
-    let existing = sqlx::query("SELECT 1 FROM role_permissions WHERE role_id = $1 AND permission_id = $2")
-        .bind(form.role_id)
-        .bind(form.permission_id)
-        .fetch_optional(pool.get_ref())
-        .await;
-    
-    match existing {
-        Ok(Some(_)) => {
-            return HttpResponse::BadRequest().json(serde_json::json!({
-                "error": "This permission is already assigned to the role"
-            }));
-        },
-        Err(e) => {
-            return HttpResponse::InternalServerError().json(serde_json::json!({
-                "error": e.to_string()
-            }));
-        },
-        _ => {}
-    }
+/// Hapus permission dari role. Mengembalikan false jika mapping tidak ditemukan.
+pub async fn revoke_permission(db: impl PgExecutor<'_>, role_id: Uuid, permission_id: Uuid) -> Result<bool, sqlx::Error> {
+    let row = sqlx::query("DELETE FROM role_permissions WHERE role_id = $1 AND permission_id = $2 RETURNING id")
+        .bind(role_id)
+        .bind(permission_id)
+        .fetch_optional(db)
+        .await?;
+    Ok(row.is_some())
+}
 
-    let row = sqlx::query_as::<_, RolePermission>(
-        "INSERT INTO role_permissions (role_id, permission_id) VALUES ($1, $2) RETURNING id, role_id, permission_id"
+/// Isi jejak audit `role.permission_granted`/`role.permission_revoked`: id beserta nama
+/// role dan permission, supaya tetap terbaca setelah salah satunya dihapus
+pub async fn role_permission_snapshot(db: impl PgExecutor<'_>, role_id: Uuid, permission_id: Uuid) -> Result<serde_json::Value, sqlx::Error> {
+    let (role, permission) = sqlx::query_as::<_, (Option<String>, Option<String>)>(
+        "SELECT (SELECT name FROM user_roles WHERE id = $1), (SELECT name FROM permissions WHERE id = $2)"
     )
-    .bind(form.role_id)
-    .bind(form.permission_id)
-    .fetch_one(pool.get_ref())
-    .await;
-    
-    match row {
-        Ok(row) => HttpResponse::Ok().json(row),
-        Err(e) => HttpResponse::InternalServerError().json(serde_json::json!({"error": e.to_string()})),
+    .bind(role_id)
+    .bind(permission_id)
+    .fetch_one(db)
+    .await?;
+    Ok(serde_json::json!({
+        "role_id": role_id,
+        "role": role,
+        "permission_id": permission_id,
+        "permission": permission,
+    }))
+}
+
+#[utoipa::path(tag = "permissions", responses((status = 200, body = Vec<Permission>)))]
+#[get("/role/{role_id}")]
+pub async fn get_role_permissions(_claims: Claims, pool: web::Data<PgPool>, path: web::Path<Uuid>) -> Result<HttpResponse, ApiError> {
+    let role_id = path.into_inner();
+    let rows = list_role_permissions(pool.get_ref(), role_id).await?;
+    Ok(HttpResponse::Ok().json(rows))
+}
+
+#[utoipa::path(
+    tag = "permissions",
+    request_body = RolePermissionPayload,
+    responses(
+        (status = 200, body = RolePermission),
+        (status = 403, description = "Butuh izin manage_roles", body = ErrorBody),
+        (status = 409, description = "Permission sudah ter-assign ke role", body = ErrorBody),
+        (status = 422, description = "Role atau permission tidak ada", body = ErrorBody),
+    )
+)]
+#[post("/role")]
+pub async fn assign_permission_to_role(req: HttpRequest, perms: UserPermissions, pool: web::Data<PgPool>, cache: web::Data<PermissionCache>, form: web::Json<RolePermissionPayload>) -> Result<HttpResponse, ApiError> {
+    // Only users with manage_roles permission can assign permissions to roles
+    if !perms.has("manage_roles") {
+        return Err(ApiError::Forbidden("Insufficient permissions".to_string()));
     }
+
+    let mut tx = pool.begin().await?;
+    let row = grant_permission(&mut *tx, form.role_id, form.permission_id)
+        .await?
+        .ok_or_else(|| ApiError::Conflict("This permission is already assigned to the role".to_string()))?;
+    AuditEvent::new("role.permission_granted", "role", Some(form.role_id))
+        .actor(perms.user_id)
+        .after(role_permission_snapshot(&mut *tx, form.role_id, form.permission_id).await?)
+        .client(&ClientInfo::from_request(&req))
+        .record(&mut *tx)
+        .await?;
+    tx.commit().await?;
+    cache.invalidate_role(form.role_id);
+    Ok(HttpResponse::Ok().json(row))
 }
 
+#[utoipa::path(
+    tag = "permissions",
+    responses(
+        (status = 200, body = serde_json::Value),
+        (status = 403, description = "Butuh izin manage_roles", body = ErrorBody),
+        (status = 404, body = ErrorBody),
+    )
+)]
 #[delete("/role/{role_id}/permission/{permission_id}")]
-pub async fn remove_permission_from_role(claims: Claims, pool: web::Data<PgPool>, path: web::Path<(Uuid, Uuid)>) -> impl Responder {
+pub async fn remove_permission_from_role(req: HttpRequest, perms: UserPermissions, pool: web::Data<PgPool>, cache: web::Data<PermissionCache>, path: web::Path<(Uuid, Uuid)>) -> Result<HttpResponse, ApiError> {
     // Only users with manage_roles permission can remove permissions from roles
-    if !has_permission(&claims, pool.get_ref(), "manage_roles").await {
-        return HttpResponse::Forbidden().json(serde_json::json!({ "message": "Insufficient permissions" }));
+    if !perms.has("manage_roles") {
+        return Err(ApiError::Forbidden("Insufficient permissions".to_string()));
     }
 
     let (role_id, permission_id) = path.into_inner();
-    let row = sqlx::query("DELETE FROM role_permissions WHERE role_id = $1 AND permission_id = $2 RETURNING id")
-        .bind(role_id)
-        .bind(permission_id)
-        .fetch_optional(pool.get_ref())
-        .await;
-    
-    match row {
-        Ok(Some(_)) => HttpResponse::Ok().json(serde_json::json!({"success": true})),
-        Ok(None) => HttpResponse::NotFound().json(serde_json::json!({"error": "Role-permission mapping not found"})),
-        Err(e) => HttpResponse::InternalServerError().json(serde_json::json!({"error": e.to_string()})),
+    let mut tx = pool.begin().await?;
+    // Diambil sebelum dicabut; kalau mapping tidak ada, event tidak dicatat
+    let before = role_permission_snapshot(&mut *tx, role_id, permission_id).await?;
+    if !revoke_permission(&mut *tx, role_id, permission_id).await? {
+        return Err(ApiError::NotFound("Role-permission mapping not found".to_string()));
     }
+    AuditEvent::new("role.permission_revoked", "role", Some(role_id))
+        .actor(perms.user_id)
+        .before(before)
+        .client(&ClientInfo::from_request(&req))
+        .record(&mut *tx)
+        .await?;
+    tx.commit().await?;
+    cache.invalidate_role(role_id);
+    Ok(HttpResponse::Ok().json(serde_json::json!({"success": true})))
 }
 
 pub fn permissions_config(cfg: &mut web::ServiceConfig) {
@@ -193,3 +286,15 @@ pub fn permissions_config(cfg: &mut web::ServiceConfig) {
         .service(assign_permission_to_role)
         .service(remove_permission_from_role);
 }
+
+#[derive(OpenApi)]
+#[openapi(paths(
+    get_permissions,
+    create_permission,
+    update_permission,
+    delete_permission,
+    get_role_permissions,
+    assign_permission_to_role,
+    remove_permission_from_role,
+))]
+pub struct PermissionsApi;
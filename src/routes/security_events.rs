@@ -0,0 +1,87 @@
+use actix_web::{get, web, HttpResponse};
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use sqlx::PgPool;
+use utoipa::{IntoParams, OpenApi};
+use uuid::Uuid;
+
+use crate::error::{ApiError, ErrorBody};
+use crate::middleware::permission_guard::UserPermissions;
+use crate::services::security_log::{list_security_events, SecurityEventFilter, SecurityLogEntry};
+
+/// Jumlah event per halaman jika `limit` tidak diisi
+const DEFAULT_LIMIT: i64 = 50;
+const MAX_LIMIT: i64 = 500;
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct SecurityEventQuery {
+    /// Jenis event persis (`login.failed`), atau awalan jika diakhiri `.` (`login.`)
+    pub event_type: Option<String>,
+    pub ip_address: Option<String>,
+    /// Hanya event sejak waktu ini (RFC 3339)
+    pub from: Option<DateTime<Utc>>,
+    /// Hanya event sebelum waktu ini (RFC 3339)
+    pub to: Option<DateTime<Utc>>,
+    /// Default 50, maksimal 500
+    pub limit: Option<i64>,
+    pub offset: Option<i64>,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct SecurityEventUserQuery {
+    /// Hanya event milik user ini
+    pub user_id: Option<Uuid>,
+}
+
+/// Filter untuk `list_security_events`; `limit`/`offset` di luar batas ditolak
+pub fn security_event_filter(user_id: Option<Uuid>, query: SecurityEventQuery) -> Result<SecurityEventFilter, ApiError> {
+    let limit = query.limit.unwrap_or(DEFAULT_LIMIT);
+    let offset = query.offset.unwrap_or(0);
+    if !(1..=MAX_LIMIT).contains(&limit) || offset < 0 {
+        return Err(ApiError::BadRequest(format!("limit harus 1-{} dan offset tidak boleh negatif", MAX_LIMIT)));
+    }
+    Ok(SecurityEventFilter {
+        user_id,
+        event_type: query.event_type,
+        ip_address: query.ip_address,
+        from: query.from,
+        to: query.to,
+        limit,
+        offset,
+    })
+}
+
+/// Log keamanan semua user (login, logout, refresh token, ganti password, akses
+/// ditolak), terbaru lebih dulu. Hanya admin.
+#[utoipa::path(
+    tag = "security",
+    params(SecurityEventUserQuery, SecurityEventQuery),
+    responses(
+        (status = 200, body = Vec<SecurityLogEntry>),
+        (status = 400, description = "`limit`/`offset` di luar batas", body = ErrorBody),
+        (status = 403, description = "Hanya admin", body = ErrorBody),
+    )
+)]
+#[get("")]
+pub async fn get_security_events(
+    perms: UserPermissions,
+    pool: web::Data<PgPool>,
+    user: web::Query<SecurityEventUserQuery>,
+    query: web::Query<SecurityEventQuery>,
+) -> Result<HttpResponse, ApiError> {
+    if !perms.is_admin() {
+        return Err(ApiError::Forbidden("Hanya admin yang boleh akses".to_string()));
+    }
+    let filter = security_event_filter(user.user_id, query.into_inner())?;
+    Ok(HttpResponse::Ok().json(list_security_events(pool.get_ref(), &filter).await?))
+}
+
+pub fn security_events_config(cfg: &mut web::ServiceConfig) {
+    cfg.service(get_security_events);
+}
+
+#[derive(OpenApi)]
+#[openapi(paths(get_security_events))]
+pub struct SecurityEventsApi;
@@ -0,0 +1,111 @@
+use actix_web::{delete, get, web, HttpRequest, HttpResponse};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use sqlx::PgPool;
+use utoipa::{OpenApi, ToSchema};
+use uuid::Uuid;
+
+use crate::error::{ApiError, ErrorBody};
+use crate::middleware::jwt_extractor::Claims;
+use crate::routes::auth::clear_session_cookies;
+use crate::services::security_log::SecurityEvent;
+use crate::services::sessions::{list_sessions, revoke_all_sessions, revoke_session, ClientInfo};
+
+/// Session login aktif (satu per perangkat/browser)
+#[derive(Serialize, ToSchema)]
+pub struct SessionInfo {
+    pub id: Uuid,
+    pub user_agent: Option<String>,
+    pub ip_address: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub last_used_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+    /// Session yang dipakai request ini
+    pub current: bool,
+}
+
+/// Daftar session aktif milik user yang login
+#[utoipa::path(tag = "sessions", responses((status = 200, body = Vec<SessionInfo>), (status = 403, description = "Request memakai token API", body = ErrorBody)))]
+#[get("")]
+pub async fn get_sessions(claims: Claims, pool: web::Data<PgPool>) -> Result<HttpResponse, ApiError> {
+    claims.require_session()?;
+    let current_id = claims.session_id()?;
+    let sessions: Vec<SessionInfo> = list_sessions(pool.get_ref(), claims.user_id()?)
+        .await?
+        .into_iter()
+        .map(|session| SessionInfo {
+            current: session.id == current_id,
+            id: session.id,
+            user_agent: session.user_agent,
+            ip_address: session.ip_address,
+            created_at: session.created_at,
+            last_used_at: session.last_used_at,
+            expires_at: session.expires_at,
+        })
+        .collect();
+    Ok(HttpResponse::Ok().json(sessions))
+}
+
+/// Cabut satu session (logout dari perangkat tersebut). Jika yang dicabut session
+/// saat ini, cookie ikut dihapus.
+#[utoipa::path(
+    tag = "sessions",
+    responses(
+        (status = 200, body = serde_json::Value),
+        (status = 404, description = "Session tidak ditemukan atau sudah dicabut", body = ErrorBody),
+        (status = 403, description = "Request memakai token API", body = ErrorBody),
+    )
+)]
+#[delete("/{id}")]
+pub async fn delete_session(
+    req: HttpRequest,
+    claims: Claims,
+    pool: web::Data<PgPool>,
+    path: web::Path<Uuid>,
+) -> Result<HttpResponse, ApiError> {
+    claims.require_session()?;
+    let id = path.into_inner();
+    let user_id = claims.user_id()?;
+    if !revoke_session(pool.get_ref(), user_id, id).await? {
+        return Err(ApiError::NotFound("Session not found".to_string()));
+    }
+    SecurityEvent::new("logout")
+        .user(user_id)
+        .details(serde_json::json!({ "session_id": id }))
+        .client(&ClientInfo::from_request(&req))
+        .record(pool.get_ref())
+        .await?;
+    let response = HttpResponse::Ok().json(serde_json::json!({"success": true}));
+    if id == claims.session_id()? {
+        return Ok(clear_session_cookies(response));
+    }
+    Ok(response)
+}
+
+/// Cabut semua session user, termasuk session saat ini (logout dari semua perangkat)
+#[utoipa::path(tag = "sessions", responses((status = 200, description = "Jumlah session yang dicabut", body = serde_json::Value), (status = 403, description = "Request memakai token API", body = ErrorBody)))]
+#[delete("")]
+pub async fn delete_all_sessions(req: HttpRequest, claims: Claims, pool: web::Data<PgPool>) -> Result<HttpResponse, ApiError> {
+    claims.require_session()?;
+    let user_id = claims.user_id()?;
+    let revoked = revoke_all_sessions(pool.get_ref(), user_id).await?;
+    SecurityEvent::new("logout")
+        .user(user_id)
+        .details(serde_json::json!({ "all_sessions": true, "revoked": revoked }))
+        .client(&ClientInfo::from_request(&req))
+        .record(pool.get_ref())
+        .await?;
+    Ok(clear_session_cookies(
+        HttpResponse::Ok().json(serde_json::json!({"success": true, "revoked": revoked})),
+    ))
+}
+
+pub fn sessions_config(cfg: &mut web::ServiceConfig) {
+    cfg.service(get_sessions)
+        .service(delete_all_sessions)
+        .service(delete_session);
+}
+
+#[derive(OpenApi)]
+#[openapi(paths(get_sessions, delete_session, delete_all_sessions))]
+pub struct SessionsApi;
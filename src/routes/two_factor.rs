@@ -0,0 +1,203 @@
+use actix_web::{get, post, web, HttpRequest, HttpResponse};
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use utoipa::{OpenApi, ToSchema};
+use uuid::Uuid;
+
+use crate::config::Config;
+use crate::error::{ApiError, ErrorBody};
+use crate::middleware::jwt_extractor::Claims;
+use crate::services::audit::AuditEvent;
+use crate::services::login_throttle::{check_login_allowed, record_login_failure};
+use crate::services::sessions::ClientInfo;
+use crate::services::two_factor::{self, start_enrollment, two_factor_status, verify_second_factor, TwoFactorEnrollment, TwoFactorStatus};
+
+#[derive(Deserialize, ToSchema)]
+pub struct TwoFactorCodeRequest {
+    /// Kode 6 digit dari aplikasi authenticator (atau kode pemulihan, kecuali saat mengaktifkan)
+    pub code: String,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct RecoveryCodesResponse {
+    /// Kode pemulihan sekali pakai; hanya ditampilkan sekali, simpan di tempat aman
+    pub recovery_codes: Vec<String>,
+}
+
+/// Percobaan kode 2FA. Kode salah dihitung seperti password salah (lihat
+/// `login_throttle`), supaya kode 6 digit tidak bisa ditebak dengan session yang dicuri.
+struct CodeAttempt {
+    name: String,
+    ip: Option<String>,
+}
+
+impl CodeAttempt {
+    async fn start(req: &HttpRequest, pool: &PgPool, user_id: Uuid) -> Result<Self, ApiError> {
+        let name: String = sqlx::query_scalar("SELECT name FROM users WHERE id = $1")
+            .bind(user_id)
+            .fetch_one(pool)
+            .await?;
+        let ip = ClientInfo::from_request(req).ip_address;
+        check_login_allowed(pool, &name, ip.as_deref()).await?;
+        Ok(CodeAttempt { name, ip })
+    }
+
+    async fn failed(self, pool: &PgPool, config: &Config) -> ApiError {
+        if let Err(e) = record_login_failure(pool, &config.login, &self.name, self.ip.as_deref()).await {
+            return e.into();
+        }
+        ApiError::BadRequest("Kode 2FA salah".to_string())
+    }
+}
+
+/// Status 2FA user yang login
+#[utoipa::path(tag = "two-factor", responses((status = 200, body = TwoFactorStatus), (status = 403, description = "Request memakai token API", body = ErrorBody)))]
+#[get("")]
+pub async fn get_two_factor(claims: Claims, pool: web::Data<PgPool>) -> Result<HttpResponse, ApiError> {
+    claims.require_session()?;
+    Ok(HttpResponse::Ok().json(two_factor_status(pool.get_ref(), claims.user_id()?).await?))
+}
+
+/// Mulai pendaftaran 2FA: buat secret baru dan QR code untuk dipindai aplikasi
+/// authenticator. 2FA baru aktif setelah dikonfirmasi lewat `/api/2fa/enable`.
+#[utoipa::path(
+    tag = "two-factor",
+    responses(
+        (status = 200, body = TwoFactorEnrollment),
+        (status = 409, description = "2FA sudah aktif", body = ErrorBody),
+        (status = 403, description = "Request memakai token API", body = ErrorBody),
+    )
+)]
+#[post("/setup")]
+pub async fn setup_two_factor(claims: Claims, pool: web::Data<PgPool>) -> Result<HttpResponse, ApiError> {
+    claims.require_session()?;
+    let user_id = claims.user_id()?;
+    let name: String = sqlx::query_scalar("SELECT name FROM users WHERE id = $1")
+        .bind(user_id)
+        .fetch_one(pool.get_ref())
+        .await?;
+    Ok(HttpResponse::Ok().json(start_enrollment(pool.get_ref(), user_id, &name).await?))
+}
+
+/// Aktifkan 2FA dengan kode dari authenticator; mengembalikan kode pemulihan
+#[utoipa::path(
+    tag = "two-factor",
+    request_body = TwoFactorCodeRequest,
+    responses(
+        (status = 200, body = RecoveryCodesResponse),
+        (status = 400, description = "Kode salah atau belum memanggil `/api/2fa/setup`", body = ErrorBody),
+        (status = 429, description = "Terlalu banyak kode salah", body = ErrorBody),
+        (status = 403, description = "Request memakai token API", body = ErrorBody),
+    )
+)]
+#[post("/enable")]
+pub async fn enable_two_factor(
+    req: HttpRequest,
+    claims: Claims,
+    pool: web::Data<PgPool>,
+    config: web::Data<Config>,
+    form: web::Json<TwoFactorCodeRequest>,
+) -> Result<HttpResponse, ApiError> {
+    claims.require_session()?;
+    let user_id = claims.user_id()?;
+    let attempt = CodeAttempt::start(&req, pool.get_ref(), user_id).await?;
+    let Some(recovery_codes) = two_factor::enable_two_factor(pool.get_ref(), user_id, &form.code).await? else {
+        return Err(attempt.failed(pool.get_ref(), &config).await);
+    };
+    AuditEvent::new("two_factor.enabled", "user", Some(user_id))
+        .actor(user_id)
+        .client(&ClientInfo::from_request(&req))
+        .record(pool.get_ref())
+        .await?;
+    Ok(HttpResponse::Ok().json(RecoveryCodesResponse { recovery_codes }))
+}
+
+/// Matikan 2FA. Butuh kode authenticator atau kode pemulihan, dan ditolak jika
+/// role user mewajibkan 2FA.
+#[utoipa::path(
+    tag = "two-factor",
+    request_body = TwoFactorCodeRequest,
+    responses(
+        (status = 200, body = serde_json::Value),
+        (status = 400, description = "Kode salah", body = ErrorBody),
+        (status = 429, description = "Terlalu banyak kode salah", body = ErrorBody),
+        (status = 403, description = "Role user mewajibkan 2FA, atau request memakai token API", body = ErrorBody),
+    )
+)]
+#[post("/disable")]
+pub async fn disable_two_factor(
+    req: HttpRequest,
+    claims: Claims,
+    pool: web::Data<PgPool>,
+    config: web::Data<Config>,
+    form: web::Json<TwoFactorCodeRequest>,
+) -> Result<HttpResponse, ApiError> {
+    claims.require_session()?;
+    let user_id = claims.user_id()?;
+    if two_factor_status(pool.get_ref(), user_id).await?.required {
+        return Err(ApiError::Forbidden("Role kamu mewajibkan 2FA".to_string()));
+    }
+    let attempt = CodeAttempt::start(&req, pool.get_ref(), user_id).await?;
+    if !verify_second_factor(pool.get_ref(), user_id, &form.code).await? {
+        return Err(attempt.failed(pool.get_ref(), &config).await);
+    }
+    two_factor::disable_two_factor(pool.get_ref(), user_id).await?;
+    AuditEvent::new("two_factor.disabled", "user", Some(user_id))
+        .actor(user_id)
+        .client(&ClientInfo::from_request(&req))
+        .record(pool.get_ref())
+        .await?;
+    Ok(HttpResponse::Ok().json(serde_json::json!({"success": true})))
+}
+
+/// Buat ulang kode pemulihan (kode lama tidak berlaku lagi)
+#[utoipa::path(
+    tag = "two-factor",
+    request_body = TwoFactorCodeRequest,
+    responses(
+        (status = 200, body = RecoveryCodesResponse),
+        (status = 400, description = "Kode salah", body = ErrorBody),
+        (status = 429, description = "Terlalu banyak kode salah", body = ErrorBody),
+        (status = 403, description = "Request memakai token API", body = ErrorBody),
+    )
+)]
+#[post("/recovery-codes")]
+pub async fn regenerate_recovery_codes(
+    req: HttpRequest,
+    claims: Claims,
+    pool: web::Data<PgPool>,
+    config: web::Data<Config>,
+    form: web::Json<TwoFactorCodeRequest>,
+) -> Result<HttpResponse, ApiError> {
+    claims.require_session()?;
+    let user_id = claims.user_id()?;
+    let attempt = CodeAttempt::start(&req, pool.get_ref(), user_id).await?;
+    if !verify_second_factor(pool.get_ref(), user_id, &form.code).await? {
+        return Err(attempt.failed(pool.get_ref(), &config).await);
+    }
+    let recovery_codes = two_factor::regenerate_recovery_codes(pool.get_ref(), user_id).await?;
+    AuditEvent::new("two_factor.recovery_codes_regenerated", "user", Some(user_id))
+        .actor(user_id)
+        .client(&ClientInfo::from_request(&req))
+        .record(pool.get_ref())
+        .await?;
+    Ok(HttpResponse::Ok().json(RecoveryCodesResponse { recovery_codes }))
+}
+
+pub fn two_factor_config(cfg: &mut web::ServiceConfig) {
+    cfg.service(get_two_factor)
+        .service(setup_two_factor)
+        .service(enable_two_factor)
+        .service(disable_two_factor)
+        .service(regenerate_recovery_codes);
+}
+
+#[derive(OpenApi)]
+#[openapi(paths(
+    get_two_factor,
+    setup_two_factor,
+    enable_two_factor,
+    disable_two_factor,
+    regenerate_recovery_codes,
+))]
+pub struct TwoFactorApi;
@@ -1,70 +1,103 @@
-use actix_web::{post, get, patch, web, Responder, HttpResponse, Error, HttpRequest};
+use actix_web::{post, get, patch, web, HttpResponse, HttpRequest};
+use actix_web::http::StatusCode;
+use crate::error::{ApiError, ErrorBody};
 use crate::middleware::jwt_extractor::Claims;
+use crate::middleware::permission_guard::RequirePermission;
+use crate::config::StorageBackend;
 use crate::services::drive_storage::{upload_file_handler, upload_file_with_item_id, DriveConfig, DriveClient};
 use actix_multipart::Multipart;
 use std::sync::Arc;
 use tokio::sync::Mutex;
 use reqwest::Client;
 use sqlx::PgPool;
+use utoipa::{OpenApi, ToSchema};
 use uuid::Uuid;
 use crate::routes::items::{UpdateItem, Item};
 
-#[post("")]
+/// Form multipart untuk upload gambar
+#[derive(ToSchema)]
+#[allow(dead_code)]
+pub struct ImageUpload {
+    #[schema(format = Binary, value_type = String)]
+    file: Vec<u8>,
+}
+
+/// Form multipart untuk `PATCH /{id}/update-with-image`
+#[derive(ToSchema)]
+#[allow(dead_code)]
+pub struct ItemWithImageUpload {
+    #[schema(format = Binary, value_type = Option<String>)]
+    file: Option<Vec<u8>>,
+    /// JSON `UpdateItem`
+    #[schema(value_type = Option<String>, rename = "itemData")]
+    item_data: Option<String>,
+    /// Override content type file
+    #[schema(rename = "contentType")]
+    content_type: Option<String>,
+}
+
+/// Upload gambar ke storage (Google Drive atau local) dan kembalikan URL-nya
+#[utoipa::path(
+    tag = "upload",
+    request_body(content_type = "multipart/form-data", content = ImageUpload),
+    responses(
+        (status = 200, description = "`{\"url\": \"...\"}`", body = serde_json::Value),
+        (status = 400, description = "Tidak ada file", body = ErrorBody),
+        (status = 413, body = ErrorBody),
+        (status = 422, description = "Tipe file tidak diizinkan", body = ErrorBody),
+        (status = 403, description = "Butuh salah satu izin: add_items, edit_items", body = ErrorBody),
+    )
+)]
+#[post("", wrap = "RequirePermission::any(&[\"add_items\", \"edit_items\"])")]
 pub async fn upload_image(
     req: HttpRequest,
     _claims: Claims, // Menggunakan underscore untuk menandakan variabel yang sengaja tidak digunakan
     payload: Multipart,
     config: web::Data<DriveConfig>,
     client: web::Data<Arc<Mutex<DriveClient>>>,
-) -> impl Responder {
+) -> Result<HttpResponse, ApiError> {
     println!("[INFO] Menerima permintaan upload gambar");
     println!("[DEBUG] Request dari: {:?}", req.peer_addr());
     println!("[DEBUG] Headers: {:?}", req.headers());
     println!("[DEBUG] Config: folder_id={}, public_base_url={}", config.folder_id, config.public_base_url);
     
-    // Cek apakah folder_id kosong
-    if config.folder_id.is_empty() {
-        println!("[ERROR] Google Drive folder ID tidak dikonfigurasi");
-        return HttpResponse::InternalServerError().json(serde_json::json!({
-            "error": "Google Drive folder ID tidak dikonfigurasi"
-        }));
-    }
-    
-    // Cek apakah credentials_json valid
-    if config.credentials_json == "{}" || config.credentials_json.is_empty() {
-        println!("[ERROR] Google Drive credentials tidak dikonfigurasi");
-        return HttpResponse::InternalServerError().json(serde_json::json!({
-            "error": "Google Drive credentials tidak dikonfigurasi"
-        }));
+    // Backend local tidak membutuhkan konfigurasi Google Drive
+    if config.backend == StorageBackend::GoogleDrive {
+        // Cek apakah folder_id kosong
+        if config.folder_id.is_empty() {
+            println!("[ERROR] Google Drive folder ID tidak dikonfigurasi");
+            return Err(ApiError::ServiceUnavailable("Google Drive folder ID tidak dikonfigurasi".to_string()));
+        }
+        
+        // Cek apakah credentials_json valid
+        if config.credentials_json == "{}" || config.credentials_json.is_empty() {
+            println!("[ERROR] Google Drive credentials tidak dikonfigurasi");
+            return Err(ApiError::ServiceUnavailable("Google Drive credentials tidak dikonfigurasi".to_string()));
+        }
     }
     
     println!("[DEBUG] Memulai proses upload file...");
     
-    match upload_file_handler(payload, config, client).await {
-        Ok(json) => {
-            println!("[INFO] Upload berhasil: {:?}", json);
-            HttpResponse::Ok()
-                .append_header(("Access-Control-Allow-Origin", "*"))
-                .append_header(("Access-Control-Allow-Methods", "POST, OPTIONS"))
-                .append_header(("Access-Control-Allow-Headers", "Content-Type, Authorization"))
-                .json(json)
-        },
-        Err(e) => {
-            let err_msg = format!("Error uploading file: {}", e);
-            println!("[ERROR] {}", err_msg);
-            HttpResponse::InternalServerError()
-                .append_header(("Access-Control-Allow-Origin", "*"))
-                .append_header(("Access-Control-Allow-Methods", "POST, OPTIONS"))
-                .append_header(("Access-Control-Allow-Headers", "Content-Type, Authorization"))
-                .json(serde_json::json!({
-                    "error": err_msg
-                }))
-        }
-    }
+    let json = upload_file_handler(payload, config, client).await?;
+    println!("[INFO] Upload berhasil: {:?}", json);
+    Ok(HttpResponse::Ok()
+        .append_header(("Access-Control-Allow-Origin", "*"))
+        .append_header(("Access-Control-Allow-Methods", "POST, OPTIONS"))
+        .append_header(("Access-Control-Allow-Headers", "Content-Type, Authorization"))
+        .json(json))
 }
 
-#[get("/proxy/drive/{file_id}")]
-pub async fn proxy_drive_file(path: web::Path<String>) -> Result<HttpResponse, Error> {
+/// Proxy file Google Drive agar bisa ditampilkan di browser
+#[utoipa::path(
+    tag = "upload",
+    responses(
+        (status = 200, content_type = "application/octet-stream", body = Vec<u8>),
+        (status = 404, body = ErrorBody),
+        (status = 403, description = "Butuh izin view_items", body = ErrorBody),
+    )
+)]
+#[get("/proxy/drive/{file_id}", wrap = "RequirePermission::new(\"view_items\")")]
+pub async fn proxy_drive_file(path: web::Path<String>) -> Result<HttpResponse, ApiError> {
     let file_id = path.into_inner();
     println!("[DEBUG] proxy_drive_file: Menerima request proxy untuk file ID: {}", file_id);
     
@@ -99,34 +132,48 @@ pub async fn proxy_drive_file(path: web::Path<String>) -> Result<HttpResponse, E
                     // Dapatkan bytes dari response
                     match response.bytes().await {
                         Ok(bytes) => {
-                            // Buat response dengan header yang sesuai
+                            // Endpoint ini butuh login, jadi jangan disimpan di cache bersama;
+                            // header CORS sudah diatur middleware Cors
                             let http_response = HttpResponse::Ok()
                                 .content_type(content_type)
-                                .append_header(("Cache-Control", "public, max-age=86400"))
-                                .append_header(("Access-Control-Allow-Origin", "*"))
+                                .append_header(("Cache-Control", "private, max-age=86400"))
                                 .body(bytes);
                             
                             Ok(http_response)
                         },
                         Err(e) => {
                             println!("[ERROR] proxy_drive_file: Gagal membaca bytes: {}", e);
-                            Ok(HttpResponse::InternalServerError().body(format!("Gagal membaca response: {}", e)))
+                            Err(ApiError::Internal(format!("Gagal membaca response: {}", e)))
                         }
                     }
                 } else {
                     // Jika response gagal, kembalikan error
                     println!("[ERROR] proxy_drive_file: Google Drive mengembalikan status error: {}", status);
-                    Ok(HttpResponse::build(status).body(format!("Google Drive error: {}", status)))
+                    Err(ApiError::Detailed {
+                        status: StatusCode::from_u16(status.as_u16()).unwrap_or(StatusCode::BAD_GATEWAY),
+                        code: "upstream_error",
+                        message: format!("Google Drive error: {}", status),
+                        details: serde_json::Value::Null,
+                    })
                 }
             },
             Err(e) => {
                 println!("[ERROR] proxy_drive_file: Gagal mengirim request ke Google Drive: {}", e);
-                Ok(HttpResponse::InternalServerError().body(format!("Gagal mengirim request ke Google Drive: {}", e)))
+                Err(ApiError::ServiceUnavailable(format!("Gagal mengirim request ke Google Drive: {}", e)))
             }
         }
 }
 
-#[patch("/{id}/upload-image")]
+#[utoipa::path(
+    tag = "upload",
+    request_body(content_type = "multipart/form-data", content = ImageUpload),
+    responses(
+        (status = 200, body = Item),
+        (status = 404, body = ErrorBody),
+        (status = 403, description = "Butuh izin edit_items", body = ErrorBody),
+    )
+)]
+#[patch("/{id}/upload-image", wrap = "RequirePermission::new(\"edit_items\")")]
 pub async fn upload_item_image(
     claims: Claims, // Hapus underscore agar bisa digunakan untuk log
     pool: web::Data<PgPool>,
@@ -134,7 +181,7 @@ pub async fn upload_item_image(
     payload: Multipart,
     config: web::Data<DriveConfig>,
     client: web::Data<Arc<Mutex<DriveClient>>>,
-) -> impl Responder {
+) -> Result<HttpResponse, ApiError> {
     let item_id = path.into_inner();
     println!("[INFO] upload_item_image: Uploading image for item ID: {}", item_id);
     
@@ -149,14 +196,14 @@ pub async fn upload_item_image(
                 println!("[DEBUG] Item sebelum update: ID: {}, photo_url: {:?}", item.id, item.photo_url);
                 Some(item)
             },
-            Ok(None) => return HttpResponse::NotFound().json(serde_json::json!({"error": "Item not found"})),
-            Err(e) => return HttpResponse::InternalServerError().json(serde_json::json!({"error": format!("Failed to fetch item: {}", e)}))
+            Ok(None) => return Err(ApiError::NotFound("Item not found".to_string())),
+            Err(e) => return Err(ApiError::Internal(format!("Failed to fetch item: {}", e)))
         };
     
     // Mulai transaksi
     let mut tx = match pool.begin().await {
         Ok(tx) => tx,
-        Err(e) => return HttpResponse::InternalServerError().json(serde_json::json!({"error": format!("Failed to start transaction: {}", e)}))
+        Err(e) => return Err(ApiError::Internal(format!("Failed to start transaction: {}", e)))
     };
     
     // Gunakan fungsi upload_file_with_item_id yang menerima item_id
@@ -213,34 +260,44 @@ pub async fn upload_item_image(
                     
                     if let Err(e) = log_result {
                         let _ = tx.rollback().await;
-                        return HttpResponse::InternalServerError().json(serde_json::json!({"error": format!("Failed to create log: {}", e)}));
+                        return Err(ApiError::Internal(format!("Failed to create log: {}", e)));
                     }
                     
                     // Commit transaksi
                     match tx.commit().await {
-                        Ok(_) => HttpResponse::Ok().json(item),
-                        Err(e) => HttpResponse::InternalServerError().json(serde_json::json!({"error": format!("Failed to commit transaction: {}", e)}))
+                        Ok(_) => Ok(HttpResponse::Ok().json(item)),
+                        Err(e) => Err(ApiError::Internal(format!("Failed to commit transaction: {}", e)))
                     }
                 },
                 Ok(None) => {
                     let _ = tx.rollback().await;
-                    HttpResponse::NotFound().json(serde_json::json!({"error": "Item not found"}))
+                    Err(ApiError::NotFound("Item not found".to_string()))
                 },
                 Err(e) => {
                     let _ = tx.rollback().await;
-                    HttpResponse::InternalServerError().json(serde_json::json!({"error": e.to_string()}))
+                    Err(e.into())
                 },
             }
         },
         Err(e) => {
-            HttpResponse::InternalServerError().json(serde_json::json!({"error": format!("Upload failed: {}", e)}))
+            Err(e.into())
         }
     }
 }
 
 use futures::TryStreamExt;
 
-#[patch("/{id}/update-with-image")]
+#[utoipa::path(
+    tag = "upload",
+    request_body(content_type = "multipart/form-data", content = ItemWithImageUpload),
+    responses(
+        (status = 200, body = Item),
+        (status = 400, body = ErrorBody),
+        (status = 404, body = ErrorBody),
+        (status = 403, description = "Butuh izin edit_items", body = ErrorBody),
+    )
+)]
+#[patch("/{id}/update-with-image", wrap = "RequirePermission::new(\"edit_items\")")]
 pub async fn update_item_with_image(
     claims: Claims,
     pool: web::Data<PgPool>,
@@ -248,7 +305,7 @@ pub async fn update_item_with_image(
     mut payload: Multipart,
     config: web::Data<DriveConfig>,
     client: web::Data<Arc<Mutex<DriveClient>>>,
-) -> impl Responder {
+) -> Result<HttpResponse, ApiError> {
     let item_id = path.into_inner();
     println!("[INFO] update_item_with_image: Updating item and uploading image for item ID: {}", item_id);
     
@@ -263,8 +320,8 @@ pub async fn update_item_with_image(
                 println!("[DEBUG] Item ditemukan dengan ID: {}, photo_url: {:?}", item.id, item.photo_url);
                 Some(item)
             },
-            Ok(None) => return HttpResponse::NotFound().json(serde_json::json!({"error": "Item not found"})),
-            Err(e) => return HttpResponse::InternalServerError().json(serde_json::json!({"error": format!("Failed to fetch item: {}", e)}))
+            Ok(None) => return Err(ApiError::NotFound("Item not found".to_string())),
+            Err(e) => return Err(ApiError::Internal(format!("Failed to fetch item: {}", e)))
         };
     
     // Ekstrak file dan data item dari payload
@@ -277,7 +334,7 @@ pub async fn update_item_with_image(
         Ok(field) => field,
         Err(e) => {
             println!("[ERROR] Error extracting field from multipart: {}", e);
-            return HttpResponse::BadRequest().json(serde_json::json!({"error": format!("Error extracting field: {}", e)}));
+            return Err(ApiError::BadRequest(format!("Error extracting field: {}", e)));
         }
     } {
         let content_disposition = field.content_disposition().clone();
@@ -289,10 +346,10 @@ pub async fn update_item_with_image(
             // Ekstrak file
             let filename = content_disposition
                 .get_filename()
-                .map(|f| sanitize_filename::sanitize(f))
+                .map(sanitize_filename::sanitize)
                 .unwrap_or_else(|| "unknown_file".to_string());
             
-            let content_type = field.content_type().clone()
+            let content_type = field.content_type()
                 .map(|t| t.to_string())
                 .unwrap_or_else(|| "application/octet-stream".to_string());
             
@@ -301,7 +358,7 @@ pub async fn update_item_with_image(
                 Ok(chunk) => chunk,
                 Err(e) => {
                     println!("[ERROR] Error reading file chunk: {}", e);
-                    return HttpResponse::InternalServerError().json(serde_json::json!({"error": format!("Error reading file: {}", e)}));
+                    return Err(ApiError::Internal(format!("Error reading file: {}", e)));
                 }
             } {
                 bytes.extend_from_slice(&chunk);
@@ -316,7 +373,7 @@ pub async fn update_item_with_image(
                 Ok(chunk) => chunk,
                 Err(e) => {
                     println!("[ERROR] Error reading itemData chunk: {}", e);
-                    return HttpResponse::InternalServerError().json(serde_json::json!({"error": format!("Error reading itemData: {}", e)}));
+                    return Err(ApiError::Internal(format!("Error reading itemData: {}", e)));
                 }
             } {
                 data.push_str(std::str::from_utf8(&chunk).unwrap_or(""));
@@ -331,7 +388,7 @@ pub async fn update_item_with_image(
                 Ok(chunk) => chunk,
                 Err(e) => {
                     println!("[ERROR] Error reading contentType chunk: {}", e);
-                    return HttpResponse::InternalServerError().json(serde_json::json!({"error": format!("Error reading contentType: {}", e)}));
+                    return Err(ApiError::Internal(format!("Error reading contentType: {}", e)));
                 }
             } {
                 content_type.push_str(std::str::from_utf8(&chunk).unwrap_or(""));
@@ -345,7 +402,7 @@ pub async fn update_item_with_image(
     // Mulai transaksi
     let mut tx = match pool.begin().await {
         Ok(tx) => tx,
-        Err(e) => return HttpResponse::InternalServerError().json(serde_json::json!({"error": format!("Failed to start transaction: {}", e)}))
+        Err(e) => return Err(ApiError::Internal(format!("Failed to start transaction: {}", e)))
     };
     
     // Parse item data jika ada
@@ -389,7 +446,7 @@ pub async fn update_item_with_image(
             },
             Err(e) => {
                 let _ = tx.rollback().await;
-                return HttpResponse::InternalServerError().json(serde_json::json!({"error": format!("Upload failed: {}", e)}));
+                return Err(e.into());
             }
         }
     }
@@ -397,78 +454,63 @@ pub async fn update_item_with_image(
     // Buat query dinamis berdasarkan field yang diupdate
     let mut param_index = 1;
     
-    // Buat query builder
-    let mut query_builder = sqlx::query_as::<_, Item>(
-        "UPDATE items SET "
-    );
-    
     // Gunakan pendekatan yang lebih sederhana untuk query dinamis
     let mut set_clauses = Vec::new();
     
-    if let Some(name) = &update.name {
+    if update.name.is_some() {
         set_clauses.push(format!("name = ${}", param_index));
-        query_builder = query_builder.bind(name);
         param_index += 1;
     }
     
-    if let Some(category_id) = &update.category_id {
+    if update.category_id.is_some() {
         set_clauses.push(format!("category_id = ${}", param_index));
-        query_builder = query_builder.bind(category_id);
         param_index += 1;
     }
     
-    if let Some(quantity) = &update.quantity {
+    if update.quantity.is_some() {
         set_clauses.push(format!("quantity = ${}", param_index));
-        query_builder = query_builder.bind(quantity);
         param_index += 1;
     }
     
-    if let Some(condition_id) = &update.condition_id {
+    if update.condition_id.is_some() {
         set_clauses.push(format!("condition_id = ${}", param_index));
-        query_builder = query_builder.bind(condition_id);
         param_index += 1;
     }
     
-    if let Some(location_id) = &update.location_id {
+    if update.location_id.is_some() {
         set_clauses.push(format!("location_id = ${}", param_index));
-        query_builder = query_builder.bind(location_id);
         param_index += 1;
     }
     
-    if let Some(photo_url) = &update.photo_url {
+    if update.photo_url.is_some() {
         set_clauses.push(format!("photo_url = ${}", param_index));
-        query_builder = query_builder.bind(photo_url);
         param_index += 1;
     }
     
-    if let Some(source_id) = &update.source_id {
+    if update.source_id.is_some() {
         set_clauses.push(format!("source_id = ${}", param_index));
-        query_builder = query_builder.bind(source_id);
         param_index += 1;
     }
     
-    if let Some(donor_id) = &update.donor_id {
+    if update.donor_id.is_some() {
         set_clauses.push(format!("donor_id = ${}", param_index));
-        query_builder = query_builder.bind(donor_id);
         param_index += 1;
     }
     
-    if let Some(procurement_id) = &update.procurement_id {
+    if update.procurement_id.is_some() {
         set_clauses.push(format!("procurement_id = ${}", param_index));
-        query_builder = query_builder.bind(procurement_id);
         param_index += 1;
     }
     
-    if let Some(status_id) = &update.status_id {
+    if update.status_id.is_some() {
         set_clauses.push(format!("status_id = ${}", param_index));
-        query_builder = query_builder.bind(status_id);
         param_index += 1;
     }
     
     // Jika tidak ada field yang diupdate, return error
     if set_clauses.is_empty() {
         let _ = tx.rollback().await;
-        return HttpResponse::BadRequest().json(serde_json::json!({"error": "No fields to update"}));
+        return Err(ApiError::BadRequest("No fields to update".to_string()));
     }
     
     // Buat query lengkap
@@ -556,22 +598,22 @@ pub async fn update_item_with_image(
             
             if let Err(e) = log_result {
                 let _ = tx.rollback().await;
-                return HttpResponse::InternalServerError().json(serde_json::json!({"error": format!("Failed to create log: {}", e)}));
+                return Err(ApiError::Internal(format!("Failed to create log: {}", e)));
             }
             
             // Commit transaksi
             match tx.commit().await {
-                Ok(_) => HttpResponse::Ok().json(item),
-                Err(e) => HttpResponse::InternalServerError().json(serde_json::json!({"error": format!("Failed to commit transaction: {}", e)}))
+                Ok(_) => Ok(HttpResponse::Ok().json(item)),
+                Err(e) => Err(ApiError::Internal(format!("Failed to commit transaction: {}", e)))
             }
         },
         Ok(None) => {
             let _ = tx.rollback().await;
-            HttpResponse::NotFound().json(serde_json::json!({"error": "Item not found"}))
+            Err(ApiError::NotFound("Item not found".to_string()))
         },
         Err(e) => {
             let _ = tx.rollback().await;
-            HttpResponse::InternalServerError().json(serde_json::json!({"error": e.to_string()}))
+            Err(e.into())
         },
     }
 }
@@ -582,3 +624,7 @@ pub fn upload_config(cfg: &mut web::ServiceConfig) {
     cfg.service(upload_item_image);
     cfg.service(update_item_with_image);
 }
+
+#[derive(OpenApi)]
+#[openapi(paths(upload_image, proxy_drive_file, upload_item_image, update_item_with_image))]
+pub struct UploadApi;
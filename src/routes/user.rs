@@ -1,15 +1,28 @@
-use actix_web::{get, post, patch, delete, web, HttpResponse, Responder};
-use argon2::password_hash::rand_core;
-use sqlx::{PgPool, FromRow};
+use actix_web::{get, post, patch, delete, web, HttpRequest, HttpResponse};
+use sqlx::{Acquire, FromRow, PgExecutor, PgPool, Postgres};
 use actix_web::web::Data;
 use serde::{Serialize, Deserialize};
+use utoipa::{IntoParams, OpenApi, ToSchema};
 use uuid::Uuid;
 
+use crate::error::{ApiError, ErrorBody};
+use crate::middleware::jwt_extractor::Claims;
+use crate::middleware::permission_guard::UserPermissions;
+use crate::config::{Config, PasswordConfig};
+use crate::services::api_tokens::{list_api_tokens, revoke_api_token, ApiToken};
+use crate::services::audit::{record_change, AuditEvent};
+use crate::services::invitations::{create_invitation, Invitation};
+use crate::services::ldap::LDAP_ISSUER;
+use crate::services::password_reset::{create_password_reset, PasswordReset};
+use crate::routes::security_events::{security_event_filter, SecurityEventQuery};
+use crate::services::security_log::{list_security_events, SecurityEvent, SecurityLogEntry};
+use crate::services::passwords::{check_password_policy, hash_password, verify_password};
+use crate::services::sessions::ClientInfo;
+use crate::services::two_factor::disable_two_factor;
+use crate::services::login_throttle::{check_login_allowed, record_login_failure, unlock_account};
+use crate::services::sessions::{account_inactive, revoke_all_sessions};
 
-
-use crate::middleware::admin_guard::is_admin;
-
-#[derive(Serialize, FromRow)]
+#[derive(Serialize, FromRow, ToSchema)]
 pub struct User {
     pub id: Uuid,
     pub name: String,
@@ -18,91 +31,350 @@ pub struct User {
     pub avatar_url: Option<String>,
     pub role_id: Uuid,
     pub created_at: chrono::DateTime<chrono::Utc>,
+    /// false jika user sudah dinonaktifkan; user nonaktif tidak bisa login
+    pub is_active: bool,
+    pub deactivated_at: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+const USER_COLUMNS: &str = "id, name, email, phone_number, avatar_url, role_id, created_at, is_active, deactivated_at";
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct UserQuery {
+    /// true untuk ikut menampilkan user yang sudah dinonaktifkan
+    pub include_inactive: Option<bool>,
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, ToSchema)]
 pub struct NewUser {
     pub name: String,
     pub role_id: Option<Uuid>,
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, ToSchema)]
 pub struct UpdateUser {
     pub name: Option<String>,
     pub email: Option<String>,
     pub phone_number: Option<String>,
     pub avatar_url: Option<String>,
+    /// Hanya admin yang boleh mengubah role
     pub role_id: Option<Uuid>,
     pub password: Option<String>,
-    pub from_login: Option<bool>,
+    /// Wajib saat user mengubah password akunnya sendiri
+    pub current_password: Option<String>,
 }
 
-#[get("")]
-pub async fn get_all_users(db: Data<PgPool>, claims: crate::middleware::jwt_extractor::Claims) -> impl Responder {
-    if !is_admin(&claims, db.get_ref()).await {
-        return HttpResponse::Forbidden().json(serde_json::json!({ "message": "Hanya admin yang boleh akses" }));
+/// User baru beserta undangan untuk membuat password pertamanya
+#[derive(Serialize, ToSchema)]
+pub struct CreatedUser {
+    #[serde(flatten)]
+    pub user: User,
+    pub invitation: Invitation,
+}
+
+/// Cari id role berdasarkan nama di table user_roles
+pub async fn find_role_id(db: &PgPool, role_name: &str) -> Result<Option<Uuid>, sqlx::Error> {
+    sqlx::query_scalar::<_, Uuid>("SELECT id FROM user_roles WHERE name = $1 LIMIT 1")
+        .bind(role_name)
+        .fetch_optional(db)
+        .await
+}
+
+pub async fn find_user_id(db: &PgPool, name: &str) -> Result<Option<Uuid>, sqlx::Error> {
+    sqlx::query_scalar::<_, Uuid>("SELECT id FROM users WHERE name = $1")
+        .bind(name)
+        .fetch_optional(db)
+        .await
+}
+
+async fn find_user_name(db: &PgPool, id: Uuid) -> Result<String, ApiError> {
+    sqlx::query_scalar::<_, String>("SELECT name FROM users WHERE id = $1")
+        .bind(id)
+        .fetch_optional(db)
+        .await?
+        .ok_or_else(|| ApiError::NotFound("User tidak ditemukan".to_string()))
+}
+
+async fn find_user(db: impl PgExecutor<'_>, id: Uuid) -> Result<User, ApiError> {
+    sqlx::query_as::<_, User>(&format!("SELECT {} FROM users WHERE id = $1", USER_COLUMNS))
+        .bind(id)
+        .fetch_optional(db)
+        .await?
+        .ok_or_else(|| ApiError::NotFound("User tidak ditemukan".to_string()))
+}
+
+/// Seperti `find_user_name`, tapi user nonaktif ditolak (`account_inactive`)
+async fn find_active_user_name(db: &PgPool, id: Uuid) -> Result<String, ApiError> {
+    let (name, is_active): (String, bool) = sqlx::query_as("SELECT name, is_active FROM users WHERE id = $1")
+        .bind(id)
+        .fetch_optional(db)
+        .await?
+        .ok_or_else(|| ApiError::NotFound("User tidak ditemukan".to_string()))?;
+    if !is_active {
+        return Err(account_inactive());
     }
-    let users = sqlx::query_as::<_, User>(
-        "SELECT id, name, email, phone_number, avatar_url, role_id, created_at FROM users"
+    Ok(name)
+}
+
+/// Cek `current_password` saat user mengganti password sendiri, supaya session yang
+/// dicuri tidak bisa dipakai mengambil alih akun. Password salah dihitung seperti
+/// login gagal (lihat `login_throttle`). User tanpa password lokal (undangan yang belum
+/// dipakai, SSO, LDAP) ditolak (`no_local_password`): password pertamanya dibuat lewat
+/// link undangan atau reset password, bukan lewat session yang bisa saja dicuri.
+async fn verify_current_password(
+    req: &HttpRequest,
+    db: &PgPool,
+    config: &Config,
+    id: Uuid,
+    current_password: Option<&str>,
+) -> Result<(), ApiError> {
+    let (name, password_hash): (String, Option<String>) =
+        sqlx::query_as("SELECT name, password_hash FROM users WHERE id = $1")
+            .bind(id)
+            .fetch_one(db)
+            .await?;
+    let Some(password_hash) = password_hash.filter(|hash| !hash.is_empty()) else {
+        return Err(ApiError::Detailed {
+            status: actix_web::http::StatusCode::CONFLICT,
+            code: "no_local_password",
+            message: "Akun ini belum punya password; buat password lewat link undangan atau reset password".to_string(),
+            details: serde_json::Value::Null,
+        });
+    };
+    let Some(current_password) = current_password else {
+        return Err(ApiError::BadRequest("current_password wajib diisi untuk mengganti password".to_string()));
+    };
+    let ip = ClientInfo::from_request(req).ip_address;
+    check_login_allowed(db, &name, ip.as_deref()).await?;
+    if !verify_password(current_password, &password_hash) {
+        record_login_failure(db, &config.login, &name, ip.as_deref()).await?;
+        return Err(ApiError::BadRequest("Password lama salah".to_string()));
+    }
+    Ok(())
+}
+
+pub async fn insert_user(db: impl PgExecutor<'_>, name: &str, role_id: Uuid) -> Result<User, sqlx::Error> {
+    sqlx::query_as::<_, User>(&format!("INSERT INTO users (name, role_id) VALUES ($1, $2) RETURNING {}", USER_COLUMNS))
+    .bind(name)
+    .bind(role_id)
+    .fetch_one(db)
+    .await
+}
+
+/// Set password user setelah dicek terhadap kebijakan password (error `weak_password`),
+/// mengembalikan false jika user tidak ditemukan. User LDAP ditolak (`ldap_managed_password`).
+/// Semua session user dicabut, jadi perangkat lain harus login ulang.
+pub async fn set_password<'c>(
+    db: impl Acquire<'c, Database = Postgres>,
+    config: &PasswordConfig,
+    id: Uuid,
+    password: &str,
+) -> Result<bool, Box<dyn std::error::Error>> {
+    let mut conn = db.acquire().await?;
+    let Some((name, ldap_linked)) = sqlx::query_as::<_, (String, bool)>(
+        "SELECT u.name, EXISTS (SELECT 1 FROM user_identities i WHERE i.user_id = u.id AND i.issuer = $2)
+         FROM users u WHERE u.id = $1"
     )
-    .fetch_all(db.get_ref())
-    .await;
+    .bind(id)
+    .bind(LDAP_ISSUER)
+    .fetch_optional(&mut *conn)
+    .await?
+    else {
+        return Ok(false);
+    };
+    if ldap_linked {
+        // Password lokal tidak dipakai untuk user LDAP
+        return Err(Box::new(ApiError::Detailed {
+            status: actix_web::http::StatusCode::CONFLICT,
+            code: "ldap_managed_password",
+            message: "Password user ini dikelola di LDAP".to_string(),
+            details: serde_json::Value::Null,
+        }));
+    }
+    check_password_policy(config, password, Some(&name))?;
+    let password_hash = hash_password(config, password).map_err(|e| e.to_string())?;
+    sqlx::query("UPDATE users SET password_hash = $1 WHERE id = $2")
+        .bind(password_hash)
+        .bind(id)
+        .execute(&mut *conn)
+        .await?;
+    revoke_all_sessions(&mut *conn, id).await?;
+    Ok(true)
+}
 
-    match users {
-        Ok(users) => HttpResponse::Ok().json(users),
-        Err(e) => {
-            eprintln!("DB error: {:?}", e);
-            HttpResponse::InternalServerError().json(serde_json::json!({ "message": format!("DB error: {:?}", e) }))
-        }
+/// Daftar user aktif; user nonaktif hanya ikut jika `include_inactive=true`
+#[utoipa::path(
+    tag = "users",
+    params(UserQuery),
+    responses(
+        (status = 200, body = Vec<User>),
+        (status = 403, description = "Hanya admin", body = ErrorBody),
+    )
+)]
+#[get("")]
+pub async fn get_all_users(
+    db: Data<PgPool>,
+    perms: UserPermissions,
+    query: web::Query<UserQuery>,
+) -> Result<HttpResponse, ApiError> {
+    if !perms.is_admin() {
+        return Err(ApiError::Forbidden("Hanya admin yang boleh akses".to_string()));
     }
+    let users = sqlx::query_as::<_, User>(&format!("SELECT {} FROM users WHERE is_active OR $1", USER_COLUMNS))
+        .bind(query.include_inactive.unwrap_or(false))
+        .fetch_all(db.get_ref())
+        .await?;
+    Ok(HttpResponse::Ok().json(users))
 }
 
+/// Buat user baru tanpa password; role default `staff`. Response berisi link undangan
+/// sekali pakai yang diteruskan admin ke user untuk membuat password.
+#[utoipa::path(
+    tag = "users",
+    request_body = NewUser,
+    responses(
+        (status = 200, body = CreatedUser),
+        (status = 403, description = "Hanya admin", body = ErrorBody),
+        (status = 409, description = "Nama sudah dipakai", body = ErrorBody),
+    )
+)]
 #[post("")]
-pub async fn create_user(db: Data<PgPool>, new_user: web::Json<NewUser>, claims: crate::middleware::jwt_extractor::Claims) -> impl Responder {
-    if !is_admin(&claims, db.get_ref()).await {
-        return HttpResponse::Forbidden().json(serde_json::json!({ "message": "Hanya admin yang boleh akses" }));
-    }
-    // Ambil role_id: jika tidak dikirim, cari id dari table user_roles (atau roles) dengan value/name 'staff'
-    let role_id = if let Some(rid) = new_user.role_id {
-        rid
-    } else {
-        // Cek table user_roles, jika tidak ada fallback ke roles
-        let row = match sqlx::query!("SELECT id FROM user_roles WHERE name = $1 LIMIT 1", "staff")
-            .fetch_optional(db.get_ref())
-            .await {
-            Ok(row) => row,
-            Err(e) => {
-                return HttpResponse::InternalServerError().json(serde_json::json!({"message": format!("DB error: {}", e)}));
-            }
-        };
-        if let Some(row) = row {
-            row.id
-        } else {
-            return HttpResponse::BadRequest().json(serde_json::json!({"message": "Role 'staff' tidak ditemukan di table user_roles"}));
-        }
+pub async fn create_user(
+    req: HttpRequest,
+    db: Data<PgPool>,
+    config: Data<Config>,
+    new_user: web::Json<NewUser>,
+    perms: UserPermissions,
+) -> Result<HttpResponse, ApiError> {
+    if !perms.is_admin() {
+        return Err(ApiError::Forbidden("Hanya admin yang boleh akses".to_string()));
+    }
+    // Ambil role_id: jika tidak dikirim, cari id dari table user_roles dengan name 'staff'
+    let role_id = match new_user.role_id {
+        Some(rid) => rid,
+        None => find_role_id(db.get_ref(), "staff")
+            .await?
+            .ok_or_else(|| ApiError::BadRequest("Role 'staff' tidak ditemukan di table user_roles".to_string()))?,
     };
-    let user = sqlx::query_as::<_, User>(
-        "INSERT INTO users (name, role_id) VALUES ($1, $2) RETURNING id, name, email, phone_number, avatar_url, role_id, created_at",
-    )
-    .bind(&new_user.name)
-    .bind(role_id)
-    .fetch_one(db.get_ref())
-    .await;
+    // User dan undangannya dibuat bersama: tanpa undangan, user tanpa password tidak bisa dipakai
+    let mut tx = db.begin().await?;
+    let user = insert_user(&mut *tx, &new_user.name, role_id).await?;
+    let invitation = create_invitation(&mut *tx, &config, user.id, Some(perms.user_id)).await?;
+    record_change(&mut *tx, &req, perms.user_id, "user.created", user.id, None, Some(&user)).await?;
+    tx.commit().await?;
+    Ok(HttpResponse::Ok().json(CreatedUser { user, invitation }))
+}
 
-match user {
-    Ok(user) => HttpResponse::Ok().json(user),
-    Err(e) => HttpResponse::InternalServerError().json(serde_json::json!({ "message": format!("DB error: {}", e) })),
+/// Buat ulang undangan user, mis. karena link sebelumnya kedaluwarsa.
+/// Link lama yang belum dipakai tidak berlaku lagi.
+#[utoipa::path(
+    tag = "users",
+    responses(
+        (status = 200, body = Invitation),
+        (status = 403, description = "Hanya admin", body = ErrorBody),
+        (status = 404, description = "User tidak ditemukan", body = ErrorBody),
+    )
+)]
+#[post("/{id}/invite")]
+pub async fn invite_user(
+    req: HttpRequest,
+    db: Data<PgPool>,
+    config: Data<Config>,
+    path: web::Path<Uuid>,
+    perms: UserPermissions,
+) -> Result<HttpResponse, ApiError> {
+    if !perms.is_admin() {
+        return Err(ApiError::Forbidden("Hanya admin yang boleh akses".to_string()));
+    }
+    let id = path.into_inner();
+    find_active_user_name(db.get_ref(), id).await?;
+    let mut tx = db.begin().await?;
+    let invitation = create_invitation(&mut *tx, &config, id, Some(perms.user_id)).await?;
+    AuditEvent::new("user.invited", "user", Some(id))
+        .actor(perms.user_id)
+        .after(serde_json::json!({ "expires_at": invitation.expires_at }))
+        .client(&ClientInfo::from_request(&req))
+        .record(&mut *tx)
+        .await?;
+    tx.commit().await?;
+    Ok(HttpResponse::Ok().json(invitation))
 }
+
+/// Buat link reset password untuk user, mis. user tanpa email yang lupa password.
+/// Admin meneruskan link-nya ke user; link lama yang belum dipakai tidak berlaku lagi.
+#[utoipa::path(
+    tag = "users",
+    responses(
+        (status = 200, body = PasswordReset),
+        (status = 403, description = "Hanya admin", body = ErrorBody),
+        (status = 404, description = "User tidak ditemukan", body = ErrorBody),
+    )
+)]
+#[post("/{id}/password-reset")]
+pub async fn issue_password_reset(
+    req: HttpRequest,
+    db: Data<PgPool>,
+    config: Data<Config>,
+    path: web::Path<Uuid>,
+    perms: UserPermissions,
+) -> Result<HttpResponse, ApiError> {
+    if !perms.is_admin() {
+        return Err(ApiError::Forbidden("Hanya admin yang boleh akses".to_string()));
+    }
+    let id = path.into_inner();
+    find_active_user_name(db.get_ref(), id).await?;
+    let mut tx = db.begin().await?;
+    let reset = create_password_reset(&mut *tx, &config, id, Some(perms.user_id)).await?;
+    AuditEvent::new("password_reset.issued", "user", Some(id))
+        .actor(perms.user_id)
+        .after(serde_json::json!({ "expires_at": reset.expires_at }))
+        .client(&ClientInfo::from_request(&req))
+        .record(&mut *tx)
+        .await?;
+    tx.commit().await?;
+    Ok(HttpResponse::Ok().json(reset))
 }
 
+/// Update profil dan/atau password user. User biasa hanya boleh mengubah akunnya
+/// sendiri (tanpa role); admin boleh mengubah semua user. Mengubah password sendiri
+/// butuh `current_password`, dan password baru harus lolos kebijakan password.
+#[utoipa::path(
+    tag = "users",
+    request_body = UpdateUser,
+    responses(
+        (status = 200, description = "User atau password berhasil diupdate", body = serde_json::Value),
+        (status = 400, description = "Tidak ada field yang diupdate, atau `current_password` kosong/salah", body = ErrorBody),
+        (status = 403, description = "Mengubah user lain atau role tanpa akses admin, atau mengganti password sendiri dengan token API", body = ErrorBody),
+        (status = 409, description = "Mengganti password sendiri untuk akun tanpa password lokal (`no_local_password`)", body = ErrorBody),
+        (status = 422, description = "Password baru tidak memenuhi kebijakan (`weak_password`)", body = ErrorBody),
+        (status = 429, description = "Terlalu banyak `current_password` salah", body = ErrorBody),
+    )
+)]
 #[patch("/{id}")]
 pub async fn update_user(
+    req: HttpRequest,
     db: Data<PgPool>,
+    config: Data<Config>,
     path: web::Path<Uuid>,
     update: web::Json<UpdateUser>,
-) -> impl Responder {
+    claims: Claims,
+    perms: UserPermissions,
+) -> Result<HttpResponse, ApiError> {
     use sqlx::QueryBuilder;
     let id = path.into_inner();
+    // Ganti password sendiri hanya lewat login biasa, bukan token API
+    if update.password.is_some() && id == perms.user_id {
+        claims.require_session()?;
+    }
+    if !perms.is_admin() {
+        if id != perms.user_id {
+            return Err(ApiError::Forbidden("Hanya boleh mengubah akun sendiri".to_string()));
+        }
+        if update.role_id.is_some() {
+            return Err(ApiError::Forbidden("Hanya admin yang boleh mengubah role".to_string()));
+        }
+    }
+    let before = find_user(db.get_ref(), id).await?;
     enum FieldValue<'a> {
         Name(&'a String),
         Email(&'a String),
@@ -126,41 +398,29 @@ pub async fn update_user(
     if let Some(role_id) = &update.role_id {
         sets.push(("role_id", FieldValue::RoleId(role_id)));
     }
-    let mut password_updated = false;
+    if sets.is_empty() && update.password.is_none() {
+        return Err(ApiError::BadRequest("No fields to update".to_string()));
+    }
+    // Dicek di luar transaksi: percobaan password lama yang salah tetap dihitung
+    if update.password.is_some() && id == perms.user_id {
+        verify_current_password(&req, db.get_ref(), &config, id, update.current_password.as_deref()).await?;
+    }
+    let client = ClientInfo::from_request(&req);
+    let mut tx = db.begin().await?;
     if let Some(new_password) = &update.password {
-        // hash password baru pakai argon2
-        use argon2::{Argon2, PasswordHasher};
-        use rand_core::OsRng;
-        let salt = argon2::password_hash::SaltString::generate(&mut OsRng);
-        let argon2 = Argon2::default();
-        let password_hash = argon2.hash_password(new_password.as_bytes(), &salt).unwrap().to_string();
-        let res = sqlx::query!(
-            "UPDATE users SET password_hash = $1 WHERE id = $2",
-            password_hash,
-            id
-        )
-        .execute(db.get_ref())
-        .await;
-        match res {
-            Ok(_) => password_updated = true,
-            Err(e) => return HttpResponse::InternalServerError().json(serde_json::json!({ "message": format!("DB error: {}", e) })),
-        }
+        set_password(&mut *tx, &config.password, id, new_password).await?;
+        // Hash password tidak pernah masuk jejak audit
+        AuditEvent::new("user.password_changed", "user", Some(id))
+            .actor(perms.user_id)
+            .client(&client)
+            .record(&mut *tx)
+            .await?;
     }
-    let from_login = update.from_login.unwrap_or(false);
     if sets.is_empty() {
         // Jika hanya update password saja, anggap sukses
-        if password_updated {
-            if from_login {
-                return HttpResponse::Ok().json(serde_json::json!({
-                    "redirect": true,
-                    "message": "Password berhasil dibuat, silakan login!"
-                }));
-            } else {
-                return HttpResponse::Ok().json(serde_json::json!({ "message": "Password berhasil diupdate!" }));
-            }
-        } else {
-            return HttpResponse::BadRequest().json(serde_json::json!({ "message": "No fields to update" }));
-        }
+        tx.commit().await?;
+        record_password_changed(db.get_ref(), &client, id, perms.user_id).await?;
+        return Ok(HttpResponse::Ok().json(serde_json::json!({ "message": "Password berhasil diupdate!" })));
     }
     let mut qb = QueryBuilder::new("UPDATE users SET ");
     for (i, (field, value)) in sets.iter().enumerate() {
@@ -179,44 +439,362 @@ pub async fn update_user(
     qb.push(" WHERE id = ").push_bind(id);
 
     let query = qb.build();
-    let result = query.execute(db.get_ref()).await;
-    match result {
-        Ok(_) => HttpResponse::Ok().json("User berhasil diupdate!"),
-        Err(e) => HttpResponse::InternalServerError().json(serde_json::json!({ "message": format!("DB error: {}", e) })),
+    query.execute(&mut *tx).await?;
+    let after = find_user(&mut *tx, id).await?;
+    record_change(&mut *tx, &req, perms.user_id, "user.updated", id, Some(&before), Some(&after)).await?;
+    tx.commit().await?;
+    if update.password.is_some() {
+        record_password_changed(db.get_ref(), &client, id, perms.user_id).await?;
     }
+    Ok(HttpResponse::Ok().json("User berhasil diupdate!"))
+}
+
+/// Catat ganti password di log keamanan user, setelah perubahannya tersimpan
+async fn record_password_changed(db: &PgPool, client: &ClientInfo, id: Uuid, changed_by: Uuid) -> Result<(), ApiError> {
+    SecurityEvent::new("password.changed")
+        .user(id)
+        .details(serde_json::json!({
+            "via": if id == changed_by { "self" } else { "admin" },
+            "changed_by": changed_by,
+        }))
+        .client(client)
+        .record(db)
+        .await?;
+    Ok(())
 }
 
 
+/// Nonaktifkan user. User tidak dihapus supaya namanya tetap ada di riwayat peminjaman
+/// dan log barang; semua session-nya dicabut, token API dan link undangan/reset
+/// password-nya tidak berlaku lagi, dan user tidak bisa login sampai diaktifkan kembali.
+#[utoipa::path(
+    tag = "users",
+    responses(
+        (status = 200, description = "`deactivated` false jika user sudah nonaktif", body = serde_json::Value),
+        (status = 400, description = "Menonaktifkan akun sendiri", body = ErrorBody),
+        (status = 403, description = "Hanya admin", body = ErrorBody),
+        (status = 404, description = "User tidak ditemukan", body = ErrorBody),
+    )
+)]
 #[delete("/{id}")]
-pub async fn delete_user(db: Data<PgPool>, path: web::Path<Uuid>, claims: crate::middleware::jwt_extractor::Claims) -> impl Responder {
-    if !is_admin(&claims, db.get_ref()).await {
-        return HttpResponse::Forbidden().json(serde_json::json!({ "message": "Hanya admin yang boleh akses" }));
+pub async fn delete_user(
+    req: HttpRequest,
+    db: Data<PgPool>,
+    path: web::Path<Uuid>,
+    perms: UserPermissions,
+) -> Result<HttpResponse, ApiError> {
+    if !perms.is_admin() {
+        return Err(ApiError::Forbidden("Hanya admin yang boleh akses".to_string()));
     }
     let id = path.into_inner();
-    let result = sqlx::query!("DELETE FROM users WHERE id = $1", id)
-        .execute(db.get_ref())
-        .await;
-    match result {
-        Ok(_) => HttpResponse::Ok().json("User berhasil dihapus!"),
-        Err(e) => HttpResponse::InternalServerError().json(serde_json::json!({ "message": format!("DB error: {}", e) })),
+    if id == perms.user_id {
+        return Err(ApiError::BadRequest("Tidak bisa menonaktifkan akun sendiri".to_string()));
+    }
+    find_user_name(db.get_ref(), id).await?;
+    let mut tx = db.begin().await?;
+    let deactivated = sqlx::query(
+        "UPDATE users SET is_active = FALSE, deactivated_at = NOW(), deactivated_by = $2 WHERE id = $1 AND is_active"
+    )
+    .bind(id)
+    .bind(perms.user_id)
+    .execute(&mut *tx)
+    .await?
+    .rows_affected()
+        > 0;
+    if deactivated {
+        revoke_all_sessions(&mut *tx, id).await?;
+        AuditEvent::new("user.deactivated", "user", Some(id))
+            .actor(perms.user_id)
+            .before(serde_json::json!({ "is_active": true }))
+            .after(serde_json::json!({ "is_active": false }))
+            .client(&ClientInfo::from_request(&req))
+            .record(&mut *tx)
+            .await?;
+    }
+    tx.commit().await?;
+    Ok(HttpResponse::Ok().json(serde_json::json!({ "success": true, "deactivated": deactivated })))
+}
+
+/// Aktifkan kembali user yang dinonaktifkan. Token API yang belum dicabut/kedaluwarsa
+/// berlaku lagi; session lama tetap dicabut, jadi user harus login ulang.
+#[utoipa::path(
+    tag = "users",
+    responses(
+        (status = 200, description = "`reactivated` false jika user memang masih aktif", body = serde_json::Value),
+        (status = 403, description = "Hanya admin", body = ErrorBody),
+        (status = 404, description = "User tidak ditemukan", body = ErrorBody),
+    )
+)]
+#[post("/{id}/reactivate")]
+pub async fn reactivate_user(
+    req: HttpRequest,
+    db: Data<PgPool>,
+    path: web::Path<Uuid>,
+    perms: UserPermissions,
+) -> Result<HttpResponse, ApiError> {
+    if !perms.is_admin() {
+        return Err(ApiError::Forbidden("Hanya admin yang boleh akses".to_string()));
+    }
+    let id = path.into_inner();
+    find_user_name(db.get_ref(), id).await?;
+    let mut tx = db.begin().await?;
+    let reactivated = sqlx::query(
+        "UPDATE users SET is_active = TRUE, deactivated_at = NULL, deactivated_by = NULL WHERE id = $1 AND NOT is_active"
+    )
+    .bind(id)
+    .execute(&mut *tx)
+    .await?
+    .rows_affected()
+        > 0;
+    if reactivated {
+        AuditEvent::new("user.reactivated", "user", Some(id))
+            .actor(perms.user_id)
+            .before(serde_json::json!({ "is_active": false }))
+            .after(serde_json::json!({ "is_active": true }))
+            .client(&ClientInfo::from_request(&req))
+            .record(&mut *tx)
+            .await?;
     }
+    tx.commit().await?;
+    Ok(HttpResponse::Ok().json(serde_json::json!({ "success": true, "reactivated": reactivated })))
 }
 
+/// Buka kunci login user yang terkunci karena terlalu banyak password salah
+#[utoipa::path(
+    tag = "users",
+    responses(
+        (status = 200, description = "`unlocked` false jika user tidak sedang terkunci", body = serde_json::Value),
+        (status = 403, description = "Hanya admin", body = ErrorBody),
+        (status = 404, description = "User tidak ditemukan", body = ErrorBody),
+    )
+)]
+#[post("/{id}/unlock")]
+pub async fn unlock_user(
+    req: HttpRequest,
+    db: Data<PgPool>,
+    path: web::Path<Uuid>,
+    perms: UserPermissions,
+) -> Result<HttpResponse, ApiError> {
+    if !perms.is_admin() {
+        return Err(ApiError::Forbidden("Hanya admin yang boleh akses".to_string()));
+    }
+    let id = path.into_inner();
+    let name = find_user_name(db.get_ref(), id).await?;
+    let mut tx = db.begin().await?;
+    let unlocked = unlock_account(&mut *tx, &name).await?;
+    if unlocked {
+        AuditEvent::new("user.unlocked", "user", Some(id))
+            .actor(perms.user_id)
+            .client(&ClientInfo::from_request(&req))
+            .record(&mut *tx)
+            .await?;
+    }
+    tx.commit().await?;
+    Ok(HttpResponse::Ok().json(serde_json::json!({ "success": true, "unlocked": unlocked })))
+}
 
-pub async fn protected_admin(db: Data<PgPool>, claims: crate::middleware::jwt_extractor::Claims) -> impl actix_web::Responder {
-    if !is_admin(&claims, db.get_ref()).await {
-        return actix_web::HttpResponse::Forbidden().json(serde_json::json!({ "message": "Hanya admin yang boleh akses endpoint ini" }));
+/// Matikan 2FA user yang kehilangan authenticator dan kode pemulihannya. Jika role-nya
+/// mewajibkan 2FA, user diminta mendaftar ulang saat login berikutnya.
+#[utoipa::path(
+    tag = "users",
+    responses(
+        (status = 200, description = "`reset` false jika user belum memakai 2FA", body = serde_json::Value),
+        (status = 403, description = "Hanya admin", body = ErrorBody),
+        (status = 404, description = "User tidak ditemukan", body = ErrorBody),
+    )
+)]
+#[delete("/{id}/two-factor")]
+pub async fn reset_two_factor(
+    req: HttpRequest,
+    db: Data<PgPool>,
+    path: web::Path<Uuid>,
+    perms: UserPermissions,
+) -> Result<HttpResponse, ApiError> {
+    if !perms.is_admin() {
+        return Err(ApiError::Forbidden("Hanya admin yang boleh akses".to_string()));
+    }
+    let id = path.into_inner();
+    find_user_name(db.get_ref(), id).await?;
+    let mut tx = db.begin().await?;
+    let reset = disable_two_factor(&mut *tx, id).await?;
+    if reset {
+        AuditEvent::new("two_factor.reset", "user", Some(id))
+            .actor(perms.user_id)
+            .client(&ClientInfo::from_request(&req))
+            .record(&mut *tx)
+            .await?;
     }
-    actix_web::HttpResponse::Ok().body("Hello admin!")
+    tx.commit().await?;
+    Ok(HttpResponse::Ok().json(serde_json::json!({ "success": true, "reset": reset })))
+}
+
+/// Daftar token API aktif milik user
+#[utoipa::path(
+    tag = "users",
+    responses(
+        (status = 200, body = Vec<ApiToken>),
+        (status = 403, description = "Hanya admin", body = ErrorBody),
+        (status = 404, description = "User tidak ditemukan", body = ErrorBody),
+    )
+)]
+#[get("/{id}/api-tokens")]
+pub async fn get_user_api_tokens(db: Data<PgPool>, path: web::Path<Uuid>, perms: UserPermissions) -> Result<HttpResponse, ApiError> {
+    if !perms.is_admin() {
+        return Err(ApiError::Forbidden("Hanya admin yang boleh akses".to_string()));
+    }
+    let id = path.into_inner();
+    find_user_name(db.get_ref(), id).await?;
+    Ok(HttpResponse::Ok().json(list_api_tokens(db.get_ref(), id).await?))
+}
+
+/// Cabut token API milik user, mis. token yang bocor
+#[utoipa::path(
+    tag = "users",
+    responses(
+        (status = 200, body = serde_json::Value),
+        (status = 403, description = "Hanya admin", body = ErrorBody),
+        (status = 404, description = "Token tidak ditemukan atau sudah dicabut", body = ErrorBody),
+    )
+)]
+#[delete("/{id}/api-tokens/{token_id}")]
+pub async fn revoke_user_api_token(
+    req: HttpRequest,
+    db: Data<PgPool>,
+    path: web::Path<(Uuid, Uuid)>,
+    perms: UserPermissions,
+) -> Result<HttpResponse, ApiError> {
+    if !perms.is_admin() {
+        return Err(ApiError::Forbidden("Hanya admin yang boleh akses".to_string()));
+    }
+    let (id, token_id) = path.into_inner();
+    let mut tx = db.begin().await?;
+    if !revoke_api_token(&mut *tx, id, token_id).await? {
+        return Err(ApiError::NotFound("API token not found".to_string()));
+    }
+    AuditEvent::new("api_token.revoked", "api_token", Some(token_id))
+        .actor(perms.user_id)
+        .before(serde_json::json!({ "user_id": id }))
+        .client(&ClientInfo::from_request(&req))
+        .record(&mut *tx)
+        .await?;
+    tx.commit().await?;
+    Ok(HttpResponse::Ok().json(serde_json::json!({ "success": true })))
+}
+
+/// Log keamanan satu user (login berhasil/gagal, logout, refresh token, ganti password,
+/// akses ditolak), terbaru lebih dulu. Percobaan login dengan nama user ini ikut tampil.
+#[utoipa::path(
+    tag = "users",
+    params(SecurityEventQuery),
+    responses(
+        (status = 200, body = Vec<SecurityLogEntry>),
+        (status = 400, description = "`limit`/`offset` di luar batas", body = ErrorBody),
+        (status = 403, description = "Hanya admin", body = ErrorBody),
+        (status = 404, description = "User tidak ditemukan", body = ErrorBody),
+    )
+)]
+#[get("/{id}/security-events")]
+pub async fn get_user_security_events(
+    db: Data<PgPool>,
+    path: web::Path<Uuid>,
+    query: web::Query<SecurityEventQuery>,
+    perms: UserPermissions,
+) -> Result<HttpResponse, ApiError> {
+    if !perms.is_admin() {
+        return Err(ApiError::Forbidden("Hanya admin yang boleh akses".to_string()));
+    }
+    let id = path.into_inner();
+    find_user_name(db.get_ref(), id).await?;
+    let filter = security_event_filter(Some(id), query.into_inner())?;
+    Ok(HttpResponse::Ok().json(list_security_events(db.get_ref(), &filter).await?))
+}
+
+#[utoipa::path(
+    get,
+    path = "/admin-only",
+    tag = "users",
+    responses(
+        (status = 200, body = String),
+        (status = 403, description = "Hanya admin", body = ErrorBody),
+    )
+)]
+pub async fn protected_admin(perms: UserPermissions) -> Result<HttpResponse, ApiError> {
+    if !perms.is_admin() {
+        return Err(ApiError::Forbidden("Hanya admin yang boleh akses endpoint ini".to_string()));
+    }
+    Ok(HttpResponse::Ok().body("Hello admin!"))
 }
 
 pub fn user_config(cfg: &mut web::ServiceConfig) {
     cfg
         .service(get_all_users)
         .service(create_user)
+        .service(invite_user)
+        .service(issue_password_reset)
         .service(update_user)
         .service(delete_user)
+        .service(reactivate_user)
+        .service(unlock_user)
+        .service(reset_two_factor)
+        .service(get_user_api_tokens)
+        .service(revoke_user_api_token)
+        .service(get_user_security_events)
         .service(
             actix_web::web::resource("/admin-only").route(actix_web::web::get().to(protected_admin))
         );
-}
\ No newline at end of file
+}
+#[derive(OpenApi)]
+#[openapi(paths(get_all_users, create_user, invite_user, issue_password_reset, update_user, delete_user, reactivate_user, unlock_user, reset_two_factor, get_user_api_tokens, revoke_user_api_token, get_user_security_events, protected_admin))]
+pub struct UserApi;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::api_tokens::ApiTokenGrant;
+    use crate::test_support::unreachable_pool;
+    use actix_web::dev::Service;
+    use actix_web::http::StatusCode;
+    use actix_web::test::{call_service, init_service, TestRequest};
+    use actix_web::{App, HttpMessage};
+
+    /// PATCH password milik sendiri dengan claims dan permission yang sudah ditentukan
+    async fn patch_own_password(api_token: Option<ApiTokenGrant>) -> StatusCode {
+        let user_id = Uuid::new_v4();
+        let claims = Claims {
+            sub: user_id.to_string(),
+            exp: usize::MAX,
+            role: Uuid::new_v4().to_string(),
+            jti: Uuid::new_v4().to_string(),
+            api_token,
+        };
+        let perms = UserPermissions::for_test(user_id, &["admin_access"]);
+        let app = init_service(
+            App::new()
+                .app_data(Data::new(unreachable_pool()))
+                .app_data(Data::new(Config::default()))
+                .wrap_fn(move |req, srv| {
+                    req.extensions_mut().insert(claims.clone());
+                    req.extensions_mut().insert(perms.clone());
+                    srv.call(req)
+                })
+                .service(web::scope("/api/users").configure(user_config)),
+        )
+        .await;
+        let req = TestRequest::patch()
+            .uri(&format!("/api/users/{}", user_id))
+            .set_json(serde_json::json!({ "password": "Password-baru-123", "current_password": "lama" }))
+            .to_request();
+        call_service(&app, req).await.status()
+    }
+
+    #[actix_web::test]
+    async fn own_password_change_rejects_api_token() {
+        let grant = ApiTokenGrant { token_id: Uuid::new_v4(), scopes: vec!["admin_access".to_string()] };
+        assert_eq!(patch_own_password(Some(grant)).await, StatusCode::FORBIDDEN);
+    }
+
+    #[actix_web::test]
+    async fn own_password_change_allows_login_session() {
+        assert_ne!(patch_own_password(None).await, StatusCode::FORBIDDEN);
+    }
+}
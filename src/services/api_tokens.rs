@@ -0,0 +1,158 @@
+//! Token API pribadi untuk script dan integrasi.
+//!
+//! User membuat token bernama dengan daftar permission (`scopes`) yang merupakan
+//! bagian dari permission-nya sendiri, dan opsional tanggal kedaluwarsa. Token dikirim
+//! seperti JWT (`Authorization: Bearer inman_pat_...`) dan diterima extractor `Claims`;
+//! permission efektifnya adalah irisan permission role user saat ini dengan `scopes`.
+//! Di database hanya disimpan hash token-nya.
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use sqlx::{PgExecutor, PgPool};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use crate::services::tokens::{generate_token, hash_token};
+
+/// Awalan token API, untuk membedakannya dari JWT
+pub const API_TOKEN_PREFIX: &str = "inman_pat_";
+
+/// Panjang awal token yang disimpan untuk ditampilkan di daftar token
+const DISPLAY_PREFIX_LEN: usize = API_TOKEN_PREFIX.len() + 6;
+
+/// `last_used_at` paling sering diperbarui sekali per interval ini, supaya setiap
+/// request tidak menulis ke database
+const LAST_USED_RESOLUTION_SECS: f64 = 60.0;
+
+#[derive(Debug, Serialize, sqlx::FromRow, ToSchema)]
+pub struct ApiToken {
+    pub id: Uuid,
+    pub name: String,
+    /// Awal token, untuk mengenali token tanpa menampilkan seluruhnya
+    pub token_prefix: String,
+    /// Nama permission yang boleh dipakai token
+    pub scopes: Vec<String>,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub last_used_at: Option<DateTime<Utc>>,
+    pub last_used_ip: Option<String>,
+}
+
+/// Token baru; nilai `token` hanya ditampilkan sekali
+#[derive(Debug, Serialize, ToSchema)]
+pub struct CreatedApiToken {
+    #[serde(flatten)]
+    pub api_token: ApiToken,
+    pub token: String,
+}
+
+/// Batasan request yang diautentikasi dengan token API, dibawa di `Claims`
+#[derive(Debug, Clone)]
+pub struct ApiTokenGrant {
+    pub token_id: Uuid,
+    pub scopes: Vec<String>,
+}
+
+/// Pemilik token API yang valid
+pub struct ApiTokenUser {
+    pub user_id: Uuid,
+    pub role_id: Uuid,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub grant: ApiTokenGrant,
+}
+
+/// Buat token API baru untuk user
+pub async fn create_api_token(
+    pool: &PgPool,
+    user_id: Uuid,
+    name: &str,
+    scopes: &[String],
+    expires_at: Option<DateTime<Utc>>,
+) -> Result<CreatedApiToken, sqlx::Error> {
+    let token = format!("{}{}", API_TOKEN_PREFIX, generate_token());
+    let api_token = sqlx::query_as::<_, ApiToken>(
+        "INSERT INTO api_tokens (user_id, name, token_hash, token_prefix, scopes, expires_at)
+         VALUES ($1, $2, $3, $4, $5, $6)
+         RETURNING id, name, token_prefix, scopes, created_at, expires_at, last_used_at, last_used_ip"
+    )
+    .bind(user_id)
+    .bind(name)
+    .bind(hash_token(&token))
+    .bind(&token[..DISPLAY_PREFIX_LEN])
+    .bind(scopes)
+    .bind(expires_at)
+    .fetch_one(pool)
+    .await?;
+    Ok(CreatedApiToken { api_token, token })
+}
+
+/// Token API aktif (belum dicabut/kedaluwarsa) milik user, terbaru lebih dulu
+pub async fn list_api_tokens(pool: &PgPool, user_id: Uuid) -> Result<Vec<ApiToken>, sqlx::Error> {
+    sqlx::query_as::<_, ApiToken>(
+        "SELECT id, name, token_prefix, scopes, created_at, expires_at, last_used_at, last_used_ip
+         FROM api_tokens
+         WHERE user_id = $1 AND revoked_at IS NULL AND (expires_at IS NULL OR expires_at > NOW())
+         ORDER BY created_at DESC"
+    )
+    .bind(user_id)
+    .fetch_all(pool)
+    .await
+}
+
+/// Cabut satu token milik user. Mengembalikan false jika tidak ditemukan.
+pub async fn revoke_api_token(db: impl PgExecutor<'_>, user_id: Uuid, token_id: Uuid) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query("UPDATE api_tokens SET revoked_at = NOW() WHERE id = $1 AND user_id = $2 AND revoked_at IS NULL")
+        .bind(token_id)
+        .bind(user_id)
+        .execute(db)
+        .await?;
+    Ok(result.rows_affected() > 0)
+}
+
+/// Cari pemilik token API dan catat pemakaiannya. Token yang tidak dikenal, dicabut,
+/// kedaluwarsa, atau milik user nonaktif menghasilkan `None`.
+pub async fn authenticate_api_token(
+    pool: &PgPool,
+    token: &str,
+    ip_address: Option<&str>,
+) -> Result<Option<ApiTokenUser>, sqlx::Error> {
+    #[derive(sqlx::FromRow)]
+    struct TokenRow {
+        id: Uuid,
+        user_id: Uuid,
+        role_id: Uuid,
+        scopes: Vec<String>,
+        expires_at: Option<DateTime<Utc>>,
+    }
+
+    let row = sqlx::query_as::<_, TokenRow>(
+        "SELECT t.id, t.user_id, u.role_id, t.scopes, t.expires_at
+         FROM api_tokens t
+         JOIN users u ON u.id = t.user_id
+         WHERE t.token_hash = $1 AND t.revoked_at IS NULL AND (t.expires_at IS NULL OR t.expires_at > NOW())
+           AND u.is_active"
+    )
+    .bind(hash_token(token))
+    .fetch_optional(pool)
+    .await?;
+    let Some(row) = row else {
+        return Ok(None);
+    };
+
+    sqlx::query(
+        "UPDATE api_tokens SET last_used_at = NOW(), last_used_ip = $2
+         WHERE id = $1 AND (last_used_at IS NULL OR last_used_at < NOW() - make_interval(secs => $3))"
+    )
+    .bind(row.id)
+    .bind(ip_address)
+    .bind(LAST_USED_RESOLUTION_SECS)
+    .execute(pool)
+    .await?;
+
+    Ok(Some(ApiTokenUser {
+        user_id: row.user_id,
+        role_id: row.role_id,
+        expires_at: row.expires_at,
+        grant: ApiTokenGrant { token_id: row.id, scopes: row.scopes },
+    }))
+}
@@ -0,0 +1,165 @@
+//! Jejak audit di table `audit_events`.
+//!
+//! Setiap event mencatat aksi (mis. `password_reset.completed`), entitas yang
+//! terkena, user yang melakukan (kosong jika anonim), kondisi sebelum/sesudah
+//! dalam JSON, serta IP dan user agent. Admin membacanya lewat `GET /api/audit-events`.
+
+use actix_web::HttpRequest;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use serde_json::Value;
+use sqlx::{PgExecutor, PgPool};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use crate::services::sessions::ClientInfo;
+
+/// Event yang sudah tercatat, untuk API query audit
+#[derive(Debug, Serialize, sqlx::FromRow, ToSchema)]
+pub struct AuditLogEntry {
+    pub id: Uuid,
+    pub action: String,
+    pub entity_type: String,
+    pub entity_id: Option<Uuid>,
+    pub actor_id: Option<Uuid>,
+    /// Nama actor saat ini; kosong untuk aksi anonim atau dari `inman-admin`
+    pub actor_name: Option<String>,
+    pub before: Option<Value>,
+    pub after: Option<Value>,
+    pub ip_address: Option<String>,
+    pub user_agent: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Filter query audit; field kosong tidak membatasi
+#[derive(Debug, Default)]
+pub struct AuditFilter {
+    pub actor_id: Option<Uuid>,
+    pub entity_type: Option<String>,
+    pub entity_id: Option<Uuid>,
+    /// Nama aksi persis, atau awalan jika diakhiri `.` (mis. `user.`)
+    pub action: Option<String>,
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+    pub limit: i64,
+    pub offset: i64,
+}
+
+pub struct AuditEvent<'a> {
+    pub action: &'static str,
+    pub entity_type: &'static str,
+    pub entity_id: Option<Uuid>,
+    pub actor_id: Option<Uuid>,
+    pub before: Option<Value>,
+    pub after: Option<Value>,
+    pub client: Option<&'a ClientInfo>,
+}
+
+impl<'a> AuditEvent<'a> {
+    pub fn new(action: &'static str, entity_type: &'static str, entity_id: Option<Uuid>) -> Self {
+        AuditEvent {
+            action,
+            entity_type,
+            entity_id,
+            actor_id: None,
+            before: None,
+            after: None,
+            client: None,
+        }
+    }
+
+    pub fn actor(mut self, actor_id: Uuid) -> Self {
+        self.actor_id = Some(actor_id);
+        self
+    }
+
+    pub fn before(mut self, before: Value) -> Self {
+        self.before = Some(before);
+        self
+    }
+
+    pub fn after(mut self, after: Value) -> Self {
+        self.after = Some(after);
+        self
+    }
+
+    pub fn client(mut self, client: &'a ClientInfo) -> Self {
+        self.client = Some(client);
+        self
+    }
+
+    /// Simpan event; untuk perubahan data, panggil dengan transaksi yang sama supaya
+    /// perubahan dan jejak auditnya tersimpan (atau batal) bersama
+    pub async fn record(self, db: impl PgExecutor<'_>) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            "INSERT INTO audit_events (actor_id, action, entity_type, entity_id, before, after, ip_address, user_agent)
+             VALUES ($1, $2, $3, $4, $5, $6, $7, $8)"
+        )
+        .bind(self.actor_id)
+        .bind(self.action)
+        .bind(self.entity_type)
+        .bind(self.entity_id)
+        .bind(self.before)
+        .bind(self.after)
+        .bind(self.client.and_then(|c| c.ip_address.as_deref()))
+        .bind(self.client.and_then(|c| c.user_agent.as_deref()))
+        .execute(db)
+        .await?;
+        Ok(())
+    }
+}
+
+/// Catat perubahan entitas oleh user yang login. `entity_type` adalah awalan `action`
+/// (mis. `category.updated` -> `category`), dan `before`/`after` berisi baris lengkapnya.
+pub async fn record_change<T: Serialize>(
+    db: impl PgExecutor<'_>,
+    req: &HttpRequest,
+    actor_id: Uuid,
+    action: &'static str,
+    entity_id: Uuid,
+    before: Option<&T>,
+    after: Option<&T>,
+) -> Result<(), sqlx::Error> {
+    let entity_type = action.split('.').next().unwrap_or(action);
+    let mut event = AuditEvent::new(action, entity_type, Some(entity_id)).actor(actor_id);
+    event.before = before.and_then(|value| serde_json::to_value(value).ok());
+    event.after = after.and_then(|value| serde_json::to_value(value).ok());
+    event.client(&ClientInfo::from_request(req)).record(db).await
+}
+
+/// Event audit terbaru lebih dulu, sesuai filter
+pub async fn list_audit_events(pool: &PgPool, filter: &AuditFilter) -> Result<Vec<AuditLogEntry>, sqlx::Error> {
+    let (action, action_prefix) = match filter.action.as_deref() {
+        // `_` di nama aksi (mis. `api_token.`) harus di-escape supaya tidak jadi wildcard LIKE
+        Some(prefix) if prefix.ends_with('.') => {
+            (None, Some(format!("{}%", prefix.replace('%', "\\%").replace('_', "\\_"))))
+        },
+        action => (action, None),
+    };
+    sqlx::query_as::<_, AuditLogEntry>(
+        "SELECT e.id, e.action, e.entity_type, e.entity_id, e.actor_id, u.name AS actor_name,
+                e.before, e.after, e.ip_address, e.user_agent, e.created_at
+         FROM audit_events e
+         LEFT JOIN users u ON u.id = e.actor_id
+         WHERE ($1::uuid IS NULL OR e.actor_id = $1)
+           AND ($2::text IS NULL OR e.entity_type = $2)
+           AND ($3::uuid IS NULL OR e.entity_id = $3)
+           AND ($4::text IS NULL OR e.action = $4)
+           AND ($5::text IS NULL OR e.action LIKE $5)
+           AND ($6::timestamptz IS NULL OR e.created_at >= $6)
+           AND ($7::timestamptz IS NULL OR e.created_at < $7)
+         ORDER BY e.created_at DESC, e.id
+         LIMIT $8 OFFSET $9"
+    )
+    .bind(filter.actor_id)
+    .bind(&filter.entity_type)
+    .bind(filter.entity_id)
+    .bind(action)
+    .bind(action_prefix)
+    .bind(filter.from)
+    .bind(filter.to)
+    .bind(filter.limit)
+    .bind(filter.offset)
+    .fetch_all(pool)
+    .await
+}
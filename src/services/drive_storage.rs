@@ -1,49 +1,51 @@
 use actix_multipart::Multipart;
-use actix_web::{web, Error};
+use actix_web::web;
 use futures::{StreamExt, TryStreamExt};
 use sanitize_filename::sanitize;
 use std::sync::Arc;
 use tokio::sync::Mutex;
 use uuid::Uuid;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use mime::Mime;
 use reqwest::{Client, multipart};
 use serde::{Deserialize, Serialize};
 use jsonwebtoken::{encode, Algorithm, EncodingKey, Header};
 use chrono::{Duration, Utc};
 
+use crate::config::{Config, StorageBackend};
+use crate::error::ApiError;
+
 /// Konfigurasi untuk Google Drive storage
 #[derive(Clone, Debug)]
 pub struct DriveConfig {
+    pub backend: StorageBackend,
     pub credentials_json: String,
     pub folder_id: String,
     pub max_file_size: usize,
     pub allowed_types: Vec<String>,
     pub public_base_url: String,
     pub base_url: String,
+    /// URL publik backend jika `base_url` masih localhost (PRODUCTION_URL / Render)
+    pub public_url: Option<String>,
+    /// Direktori penyimpanan untuk backend `local`
+    pub upload_dir: PathBuf,
+    /// Direktori file privat untuk backend `local`, tidak disajikan lewat `/uploads`
+    pub private_dir: PathBuf,
 }
 
-impl Default for DriveConfig {
-    fn default() -> Self {
+impl From<&Config> for DriveConfig {
+    fn from(config: &Config) -> Self {
         Self {
-            credentials_json: std::env::var("GOOGLE_CREDENTIALS_JSON")
-                .unwrap_or_else(|_| "{}".to_string()),
-            folder_id: std::env::var("GOOGLE_DRIVE_FOLDER_ID")
-                .unwrap_or_else(|_| "".to_string()),
-            max_file_size: std::env::var("MAX_FILE_SIZE")
-                .ok()
-                .and_then(|s| s.parse().ok())
-                .unwrap_or(5_000_000), // 5MB default
-            allowed_types: vec![
-                "image/jpeg".to_string(),
-                "image/png".to_string(),
-                "image/webp".to_string(),
-                "image/gif".to_string(),
-            ],
-            public_base_url: std::env::var("GOOGLE_DRIVE_PUBLIC_URL")
-                .unwrap_or_else(|_| "https://drive.google.com/uc?export=view&id=".to_string()),
-            base_url: std::env::var("BASE_URL")
-                .unwrap_or_else(|_| "http://localhost:8080".to_string()),
+            backend: config.storage.active_backend(),
+            credentials_json: config.storage.google_credentials_json.clone(),
+            folder_id: config.storage.drive_folder_id.clone(),
+            max_file_size: config.upload.max_file_size,
+            allowed_types: config.upload.allowed_types.clone(),
+            public_base_url: config.storage.drive_public_url.clone(),
+            base_url: config.base_url(),
+            public_url: config.server.public_url.clone(),
+            upload_dir: config.upload.dir.clone(),
+            private_dir: config.upload.private_dir.clone(),
         }
     }
 }
@@ -109,7 +111,7 @@ pub async fn create_drive_client(config: &DriveConfig) -> Result<DriveClient, Bo
 }
 
 /// Mendapatkan token akses untuk Google Drive API
-async fn get_access_token(client: &mut DriveClient) -> Result<String, Box<dyn std::error::Error>> {
+pub async fn get_access_token(client: &mut DriveClient) -> Result<String, Box<dyn std::error::Error>> {
     // Cek apakah token masih valid
     if let (Some(token), Some(expiry)) = (&client.access_token, client.token_expiry) {
         if expiry > Utc::now() + Duration::minutes(5) {
@@ -183,7 +185,7 @@ async fn get_access_token(client: &mut DriveClient) -> Result<String, Box<dyn st
                         Err(e3) => {
                             // Fallback 3: Coba format lain jika semua gagal
                             println!("[ERROR] Semua format private key gagal: {}, {}, {}", e, e2, e3);
-                            return Err(format!("InvalidKeyFormat: Tidak dapat memproses format private key").into());
+                            return Err("InvalidKeyFormat: Tidak dapat memproses format private key".to_string().into());
                         }
                     }
                 }
@@ -226,7 +228,7 @@ pub async fn ensure_folder_exists(client: &mut DriveClient, folder_id: &str) ->
     // Jika folder ID sudah ada, gunakan itu
     if !folder_id.is_empty() {
         // Verifikasi folder ID
-        let response = client.client.get(&format!("https://www.googleapis.com/drive/v3/files/{}", folder_id))
+        let response = client.client.get(format!("https://www.googleapis.com/drive/v3/files/{}", folder_id))
             .query(&[("fields", "id,name,mimeType")])
             .bearer_auth(&token)
             .send()
@@ -271,7 +273,7 @@ pub async fn ensure_folder_exists(client: &mut DriveClient, folder_id: &str) ->
         "type": "anyone"
     });
     
-    let perm_response = client.client.post(&format!("https://www.googleapis.com/drive/v3/files/{}/permissions", new_folder_id))
+    let perm_response = client.client.post(format!("https://www.googleapis.com/drive/v3/files/{}/permissions", new_folder_id))
         .bearer_auth(&token)
         .json(&permission)
         .send()
@@ -490,7 +492,6 @@ pub async fn upload_to_drive(
     Ok(file_id.to_string())
 }
 
-/// Menangani upload file gambar
 // Fungsi upload_image telah dihapus karena tidak digunakan lagi
 // Digantikan oleh fungsi upload_file_handler dan upload_to_drive_or_local
 
@@ -503,7 +504,7 @@ pub async fn upload_to_drive_or_local(
     println!("[DEBUG] upload_to_drive_or_local: Memulai proses upload");
     
     // Proses file upload
-    while let Ok(Some(mut field)) = payload.try_next().await {
+    if let Ok(Some(mut field)) = payload.try_next().await {
         // Dapatkan content disposition
         let content_disposition = field.content_disposition();
         
@@ -524,14 +525,14 @@ pub async fn upload_to_drive_or_local(
         println!("[DEBUG] upload_to_drive_or_local: Filename: {}", unique_filename);
         
         // Dapatkan content type
-        let content_type_opt = field.content_type().map(|ct| ct.clone());
+        let content_type_opt = field.content_type().cloned();
         println!("[DEBUG] upload_to_drive_or_local: Content type: {:?}", content_type_opt);
         
         // Validasi tipe file
         if let Some(content_type) = &content_type_opt {
             if !config.allowed_types.contains(&content_type.to_string()) {
                 println!("[WARN] upload_to_drive_or_local: Tipe file tidak diizinkan: {}", content_type);
-                return Err(format!("Tipe file tidak diizinkan: {}", content_type).into());
+                return Err(ApiError::Unprocessable(format!("Tipe file tidak diizinkan: {}", content_type)).into());
             }
         }
         
@@ -544,12 +545,16 @@ pub async fn upload_to_drive_or_local(
             // Cek ukuran file
             if data.len() > config.max_file_size {
                 println!("[WARN] upload_to_drive_or_local: Ukuran file melebihi batas: {} > {}", data.len(), config.max_file_size);
-                return Err(format!("Ukuran file melebihi batas: {} > {}", data.len(), config.max_file_size).into());
+                return Err(ApiError::PayloadTooLarge(format!("Ukuran file melebihi batas: {} > {}", data.len(), config.max_file_size)).into());
             }
         }
         
         println!("[DEBUG] upload_to_drive_or_local: File size: {} bytes", data.len());
         
+        if config.backend == StorageBackend::Local {
+            return upload_local(&config, &unique_filename, data).await;
+        }
+        
         // Prioritaskan upload ke Google Drive terlebih dahulu
         println!("[INFO] upload_to_drive_or_local: Mencoba upload ke Google Drive");
         match try_upload_to_google_drive(
@@ -574,7 +579,7 @@ pub async fn upload_to_drive_or_local(
         }
     }
     
-    Err("Tidak ada file yang diupload".into())
+    Err(ApiError::BadRequest("Tidak ada file yang diupload".to_string()).into())
 }
 
 /// Mencoba upload ke Google Drive
@@ -639,25 +644,18 @@ fn fix_private_key_format(private_key: &str) -> String {
     fixed_key
 }
 
+/// Base URL backend yang bisa diakses browser. Jika BASE_URL masih localhost
+/// tapi kita di production, gunakan URL production.
+pub fn public_backend_url(config: &DriveConfig) -> String {
+    match &config.public_url {
+        Some(url) if config.base_url.contains("localhost") => url.clone(),
+        _ => config.base_url.clone(),
+    }
+}
+
 /// Mendapatkan URL publik untuk file Google Drive
 pub fn get_public_url(config: &DriveConfig, file_id: &str) -> String {
-    // Deteksi environment: jika BASE_URL adalah localhost tapi kita di production, gunakan URL production
-    let base_url = if config.base_url.contains("localhost") {
-        // Cek jika ada environment variable untuk production URL
-        match std::env::var("PRODUCTION_URL") {
-            Ok(url) if !url.is_empty() => url,
-            _ => {
-                // Jika tidak ada PRODUCTION_URL, gunakan render.com URL jika kita di render
-                if std::env::var("RENDER").is_ok() {
-                    "https://inman-be.onrender.com".to_string()
-                } else {
-                    config.base_url.clone()
-                }
-            }
-        }
-    } else {
-        config.base_url.clone()
-    };
+    let base_url = public_backend_url(config);
     
     // Log URL yang digunakan
     println!("[INFO] Menggunakan base URL: {}", base_url);
@@ -666,26 +664,44 @@ pub fn get_public_url(config: &DriveConfig, file_id: &str) -> String {
     format!("{}/api/upload/proxy/drive/{}", base_url, file_id)
 }
 
-/// Upload file ke penyimpanan lokal (fallback)
+/// Upload file ke penyimpanan lokal (STORAGE_BACKEND=local), disajikan lewat `/uploads`
 async fn upload_local(
+    config: &DriveConfig,
     filename: &str,
     data: Vec<u8>,
-    _content_type: Option<Mime>,
 ) -> Result<String, Box<dyn std::error::Error>> {
-    println!("[DEBUG] upload_local: Mencoba upload ke penyimpanan lokal");
+    println!("[DEBUG] upload_local: Menyimpan {} ke {}", filename, config.upload_dir.display());
     
     // Buat direktori uploads jika belum ada
-    let upload_dir = "uploads";
-    if !Path::new(upload_dir).exists() {
-        std::fs::create_dir_all(upload_dir)?;
+    if !config.upload_dir.exists() {
+        std::fs::create_dir_all(&config.upload_dir)?;
     }
     
     // Simpan file
-    let file_path = format!("{}/{}", upload_dir, filename);
-    tokio::fs::write(&file_path, data).await?;
+    tokio::fs::write(config.upload_dir.join(filename), data).await?;
     
-    // Kembalikan URL relatif
-    Ok(format!("/uploads/{}", filename))
+    Ok(format!("{}/uploads/{}", public_backend_url(config), filename))
+}
+
+/// Drive client tanpa kredensial, dipakai saat Google Drive tidak tersedia atau tidak digunakan
+pub fn empty_drive_client() -> DriveClient {
+    DriveClient {
+        client: reqwest::Client::new(),
+        credentials: GoogleCredentials {
+            r#type: "".to_string(),
+            project_id: "".to_string(),
+            private_key_id: "".to_string(),
+            private_key: "".to_string(),
+            client_email: "".to_string(),
+            client_id: "".to_string(),
+            auth_uri: "".to_string(),
+            token_uri: "".to_string(),
+            auth_provider_x509_cert_url: "".to_string(),
+            client_x509_cert_url: "".to_string(),
+        },
+        access_token: None,
+        token_expiry: None,
+    }
 }
 
 /// Fungsi untuk upload file dengan item_id, membuat folder berdasarkan ID item jika belum ada
@@ -698,7 +714,7 @@ pub async fn upload_file_with_item_id(
     println!("[DEBUG] upload_file_with_item_id: Memulai proses upload untuk item {}", item_id);
     
     // Proses file upload
-    while let Ok(Some(mut field)) = payload.try_next().await {
+    if let Ok(Some(mut field)) = payload.try_next().await {
         // Dapatkan content disposition
         let content_disposition = field.content_disposition();
         
@@ -719,14 +735,14 @@ pub async fn upload_file_with_item_id(
         println!("[DEBUG] upload_file_with_item_id: Filename: {}", unique_filename);
         
         // Dapatkan content type
-        let content_type_opt = field.content_type().map(|ct| ct.clone());
+        let content_type_opt = field.content_type().cloned();
         println!("[DEBUG] upload_file_with_item_id: Content type: {:?}", content_type_opt);
         
         // Validasi tipe file
         if let Some(content_type) = &content_type_opt {
             if !config.allowed_types.contains(&content_type.to_string()) {
                 println!("[WARN] upload_file_with_item_id: Tipe file tidak diizinkan: {}", content_type);
-                return Err(format!("Tipe file tidak diizinkan: {}", content_type).into());
+                return Err(ApiError::Unprocessable(format!("Tipe file tidak diizinkan: {}", content_type)).into());
             }
         }
         
@@ -739,12 +755,16 @@ pub async fn upload_file_with_item_id(
             // Cek ukuran file
             if data.len() > config.max_file_size {
                 println!("[WARN] upload_file_with_item_id: Ukuran file melebihi batas: {} > {}", data.len(), config.max_file_size);
-                return Err(format!("Ukuran file melebihi batas: {} > {}", data.len(), config.max_file_size).into());
+                return Err(ApiError::PayloadTooLarge(format!("Ukuran file melebihi batas: {} > {}", data.len(), config.max_file_size)).into());
             }
         }
         
         println!("[DEBUG] upload_file_with_item_id: File size: {} bytes", data.len());
         
+        if config.backend == StorageBackend::Local {
+            return upload_local(&config, &unique_filename, data).await;
+        }
+        
         // Coba ambil client dari mutex
         let mut client_guard = client.lock().await;
         
@@ -876,7 +896,7 @@ pub async fn upload_file_with_item_id(
                     });
                     
                     println!("[DEBUG] Mengatur permission publik untuk folder {}", new_folder_id);
-                    let perm_response = match client_guard.client.post(&format!("https://www.googleapis.com/drive/v3/files/{}/permissions", new_folder_id))
+                    let perm_response = match client_guard.client.post(format!("https://www.googleapis.com/drive/v3/files/{}/permissions", new_folder_id))
                         .bearer_auth(&token)
                         .json(&permission)
                         .send()
@@ -935,7 +955,7 @@ pub async fn upload_file_with_item_id(
         return Ok(public_url);
     }
     
-    Err("Tidak ada file yang diupload".into())
+    Err(ApiError::BadRequest("Tidak ada file yang diupload".to_string()).into())
 }
 
 /// Handler untuk endpoint upload file
@@ -943,7 +963,7 @@ pub async fn upload_file_handler(
     payload: Multipart,
     config: web::Data<DriveConfig>,
     client: web::Data<Arc<Mutex<DriveClient>>>,
-) -> Result<web::Json<serde_json::Value>, Error> {
+) -> Result<web::Json<serde_json::Value>, ApiError> {
     println!("[DEBUG] upload_file_handler: Memulai proses upload");
     
     // Coba upload ke Google Drive terlebih dahulu
@@ -956,9 +976,7 @@ pub async fn upload_file_handler(
         },
         Err(e) => {
             println!("[ERROR] upload_file_handler: Error upload: {}", e);
-            Err(actix_web::error::ErrorInternalServerError(format!(
-                "Gagal upload file: {}", e
-            )))
+            Err(e.into())
         },
     }
 }
@@ -987,6 +1005,10 @@ pub async fn upload_file_with_item_id_field(
     let unique_filename = format!("{}-{}.{}", timestamp, uuid, file_ext);
     println!("[DEBUG] Generated unique filename: {}", unique_filename);
     
+    if config.backend == StorageBackend::Local {
+        return upload_local(&config, &unique_filename, bytes).await;
+    }
+    
     // Coba ambil client dari mutex
     let mut client_guard = client.lock().await;
     
@@ -1109,7 +1131,7 @@ pub async fn upload_file_with_item_id_field(
                 });
                 
                 println!("[DEBUG] Setting public permission for folder {}", new_folder_id);
-                let perm_response = match client_guard.client.post(&format!("https://www.googleapis.com/drive/v3/files/{}/permissions", new_folder_id))
+                let perm_response = match client_guard.client.post(format!("https://www.googleapis.com/drive/v3/files/{}/permissions", new_folder_id))
                     .bearer_auth(&token)
                     .json(&permission)
                     .send()
@@ -1153,7 +1175,7 @@ pub async fn upload_file_with_item_id_field(
     println!("[DEBUG] Uploading file to item folder {}", item_folder_id);
     
     // Prepare file metadata
-    let metadata = serde_json::json!({
+    let _metadata = serde_json::json!({
         "name": unique_filename,
         "parents": [item_folder_id]
     });
@@ -1185,7 +1207,7 @@ pub async fn upload_file_with_item_id_field(
     });
     
     // Buat URL untuk upload file
-    let upload_url = format!("https://www.googleapis.com/upload/drive/v3/files?uploadType=multipart&fields=id");
+    let upload_url = "https://www.googleapis.com/upload/drive/v3/files?uploadType=multipart&fields=id".to_string();
     
     // Buat multipart body secara manual
     let boundary = "----WebKitFormBoundary7MA4YWxkTrZu0gW";
@@ -1260,7 +1282,7 @@ pub async fn upload_file_with_item_id_field(
     });
     
     // Atur permission file
-    let perm_response = client_guard.client.post(&format!("https://www.googleapis.com/drive/v3/files/{}/permissions", file_id))
+    let perm_response = client_guard.client.post(format!("https://www.googleapis.com/drive/v3/files/{}/permissions", file_id))
         .bearer_auth(&token)
         .json(&permission)
         .send()
@@ -1293,3 +1315,96 @@ pub async fn upload_file_with_item_id_field(
     
     Ok(public_url)
 }
+
+/// File privat yang sudah disimpan: backend tempat file berada dan ID file-nya
+/// (file ID Google Drive atau nama file di `private_dir`)
+pub struct PrivateFile {
+    pub backend: StorageBackend,
+    pub file_id: String,
+}
+
+/// Simpan file yang tidak boleh diakses publik, mis. tanda tangan peminjaman.
+/// Di Google Drive file tidak diberi permission `anyone`, dan di backend `local` file
+/// disimpan di `private_dir`, bukan di bawah `/uploads`. Isi file hanya bisa diambil
+/// lewat `read_private_file` dari endpoint yang mengecek izin.
+pub async fn upload_private_file(
+    config: &DriveConfig,
+    client: &Mutex<DriveClient>,
+    filename: &str,
+    bytes: Vec<u8>,
+    content_type: &str,
+) -> Result<PrivateFile, Box<dyn std::error::Error>> {
+    let unique_filename = format!("{}-{}-{}", Utc::now().timestamp(), Uuid::new_v4(), sanitize(filename));
+    
+    if config.backend == StorageBackend::Local {
+        tokio::fs::create_dir_all(&config.private_dir).await?;
+        tokio::fs::write(config.private_dir.join(&unique_filename), bytes).await?;
+        return Ok(PrivateFile { backend: StorageBackend::Local, file_id: unique_filename });
+    }
+    
+    let mut client_guard = client.lock().await;
+    let token = get_access_token(&mut client_guard).await?;
+    
+    let metadata = serde_json::json!({
+        "name": unique_filename,
+        "parents": [config.folder_id]
+    });
+    let boundary = format!("inman-{}", Uuid::new_v4().simple());
+    let mut body = Vec::new();
+    body.extend_from_slice(format!("--{}\r\nContent-Type: application/json; charset=UTF-8\r\n\r\n", boundary).as_bytes());
+    body.extend_from_slice(metadata.to_string().as_bytes());
+    body.extend_from_slice(format!("\r\n--{}\r\nContent-Type: {}\r\n\r\n", boundary, content_type).as_bytes());
+    body.extend_from_slice(&bytes);
+    body.extend_from_slice(format!("\r\n--{}--", boundary).as_bytes());
+    
+    let response = client_guard.client
+        .post("https://www.googleapis.com/upload/drive/v3/files?uploadType=multipart&fields=id")
+        .header("Content-Type", format!("multipart/related; boundary={}", boundary))
+        .bearer_auth(&token)
+        .body(body)
+        .send()
+        .await?;
+    if !response.status().is_success() {
+        return Err(format!("Failed to upload file: {}", response.text().await?).into());
+    }
+    let result: serde_json::Value = response.json().await?;
+    let file_id = result["id"].as_str().ok_or("Failed to get file ID from response")?;
+    
+    Ok(PrivateFile { backend: StorageBackend::GoogleDrive, file_id: file_id.to_string() })
+}
+
+/// Ambil isi file yang disimpan dengan `upload_private_file`
+pub async fn read_private_file(
+    config: &DriveConfig,
+    client: &Mutex<DriveClient>,
+    file: &PrivateFile,
+) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    match file.backend {
+        StorageBackend::Local => {
+            // ID file berasal dari database, tapi tetap jangan sampai keluar dari private_dir
+            if file.file_id.is_empty() || sanitize(&file.file_id) != file.file_id {
+                return Err(format!("Invalid file id '{}'", file.file_id).into());
+            }
+            let path = config.private_dir.join(&file.file_id);
+            if !path.exists() && config.upload_dir.join(&file.file_id).exists() {
+                // File lama yang diupload sebelum ada private_dir masih di upload_dir
+                return Ok(tokio::fs::read(config.upload_dir.join(&file.file_id)).await?);
+            }
+            Ok(tokio::fs::read(path).await?)
+        },
+        StorageBackend::GoogleDrive => {
+            let mut client_guard = client.lock().await;
+            let token = get_access_token(&mut client_guard).await?;
+            let response = client_guard.client
+                .get(format!("https://www.googleapis.com/drive/v3/files/{}", file.file_id))
+                .query(&[("alt", "media")])
+                .bearer_auth(&token)
+                .send()
+                .await?;
+            if !response.status().is_success() {
+                return Err(format!("Google Drive returned status {}", response.status()).into());
+            }
+            Ok(response.bytes().await?.to_vec())
+        },
+    }
+}
@@ -0,0 +1,150 @@
+use chrono::{DateTime, Utc};
+
+/// Satu event (VEVENT) di dalam feed iCalendar
+pub struct CalendarEvent {
+    pub uid: String,
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+    pub summary: String,
+    pub description: Option<String>,
+    pub location: Option<String>,
+    pub categories: Vec<String>,
+    pub status: Option<&'static str>,
+}
+
+/// Membangun dokumen iCalendar (RFC 5545) sederhana
+pub fn render_calendar(name: &str, events: &[CalendarEvent]) -> String {
+    let now = format_datetime(Utc::now());
+    let mut lines = vec![
+        "BEGIN:VCALENDAR".to_string(),
+        "VERSION:2.0".to_string(),
+        "PRODID:-//Actisol//Inventory Borrowings//ID".to_string(),
+        "CALSCALE:GREGORIAN".to_string(),
+        "METHOD:PUBLISH".to_string(),
+        format!("X-WR-CALNAME:{}", escape_text(name)),
+    ];
+
+    for event in events {
+        lines.push("BEGIN:VEVENT".to_string());
+        lines.push(format!("UID:{}", event.uid));
+        lines.push(format!("DTSTAMP:{}", now));
+        lines.push(format!("DTSTART:{}", format_datetime(event.start)));
+        lines.push(format!("DTEND:{}", format_datetime(event.end)));
+        lines.push(format!("SUMMARY:{}", escape_text(&event.summary)));
+        if let Some(description) = &event.description {
+            lines.push(format!("DESCRIPTION:{}", escape_text(description)));
+        }
+        if let Some(location) = &event.location {
+            lines.push(format!("LOCATION:{}", escape_text(location)));
+        }
+        if !event.categories.is_empty() {
+            let categories: Vec<String> = event.categories.iter().map(|c| escape_text(c)).collect();
+            lines.push(format!("CATEGORIES:{}", categories.join(",")));
+        }
+        if let Some(status) = event.status {
+            lines.push(format!("STATUS:{}", status));
+        }
+        lines.push("END:VEVENT".to_string());
+    }
+
+    lines.push("END:VCALENDAR".to_string());
+
+    // iCalendar mewajibkan CRLF dan baris maksimal 75 oktet
+    lines
+        .iter()
+        .map(|line| fold_line(line))
+        .collect::<Vec<_>>()
+        .join("\r\n")
+        + "\r\n"
+}
+
+fn format_datetime(value: DateTime<Utc>) -> String {
+    value.format("%Y%m%dT%H%M%SZ").to_string()
+}
+
+fn escape_text(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace("\r\n", "\\n")
+        .replace('\n', "\\n")
+}
+
+fn fold_line(line: &str) -> String {
+    let mut folded = String::with_capacity(line.len());
+    let mut octets = 0;
+    for ch in line.chars() {
+        let len = ch.len_utf8();
+        if octets + len > 75 {
+            folded.push_str("\r\n ");
+            octets = 1;
+        }
+        folded.push(ch);
+        octets += len;
+    }
+    folded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn escape_text_escapes_special_characters() {
+        assert_eq!(escape_text(r"a\b;c,d"), r"a\\b\;c\,d");
+        assert_eq!(escape_text("baris 1\r\nbaris 2\nbaris 3"), r"baris 1\nbaris 2\nbaris 3");
+    }
+
+    #[test]
+    fn fold_line_keeps_short_lines() {
+        let line = "A".repeat(75);
+        assert_eq!(fold_line(&line), line);
+    }
+
+    #[test]
+    fn fold_line_splits_at_75_octets() {
+        let folded = fold_line(&"A".repeat(160));
+        let parts: Vec<&str> = folded.split("\r\n").collect();
+        assert_eq!(parts.len(), 3);
+        assert_eq!(parts[0].len(), 75);
+        // Baris lanjutan diawali spasi yang ikut dihitung dalam batas 75 oktet
+        assert!(parts[1].starts_with(' '));
+        assert_eq!(parts[1].len(), 75);
+        assert_eq!(parts[2], format!(" {}", "A".repeat(11)));
+        assert_eq!(folded.replace("\r\n ", ""), "A".repeat(160));
+    }
+
+    #[test]
+    fn fold_line_does_not_split_multibyte_characters() {
+        // "é" = 2 oktet, 74 + 2 melewati batas sehingga dipindah ke baris berikutnya
+        let folded = fold_line(&format!("{}é", "A".repeat(74)));
+        assert_eq!(folded, format!("{}\r\n é", "A".repeat(74)));
+        for part in fold_line(&"é".repeat(100)).split("\r\n") {
+            assert!(part.len() <= 75);
+        }
+    }
+
+    #[test]
+    fn render_calendar_uses_crlf_and_escapes_fields() {
+        let start = Utc.with_ymd_and_hms(2024, 1, 2, 3, 4, 5).unwrap();
+        let event = CalendarEvent {
+            uid: "borrowing-1@inventory".to_string(),
+            start,
+            end: start,
+            summary: "Proyektor, kabel; adaptor".to_string(),
+            description: None,
+            location: None,
+            categories: vec!["a,b".to_string()],
+            status: Some("CONFIRMED"),
+        };
+        let output = render_calendar("Peminjaman", &[event]);
+        assert!(output.starts_with("BEGIN:VCALENDAR\r\n"));
+        assert!(output.ends_with("END:VCALENDAR\r\n"));
+        assert!(output.contains("\r\nDTSTART:20240102T030405Z\r\n"));
+        assert!(output.contains("\r\nSUMMARY:Proyektor\\, kabel\\; adaptor\r\n"));
+        assert!(output.contains("\r\nCATEGORIES:a\\,b\r\n"));
+        assert!(!output.replace("\r\n", "").contains('\n'));
+    }
+}
@@ -0,0 +1,85 @@
+//! Undangan untuk user baru.
+//!
+//! User dibuat admin tanpa password. Bersamaan dengan itu dibuat token undangan
+//! sekali pakai yang dikirim admin ke user sebagai link
+//! `{FRONTEND_URL}/accept-invite?token=...`; user membuat password pertamanya lewat
+//! `POST /api/auth/accept-invite`. Di database hanya disimpan hash token-nya.
+
+use chrono::{DateTime, Duration, Utc};
+use serde::Serialize;
+use sqlx::{Acquire, PgExecutor, Postgres};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use crate::config::Config;
+use crate::error::ApiError;
+use crate::services::tokens::{generate_token, hash_token};
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct Invitation {
+    /// Token undangan; hanya ditampilkan sekali
+    pub token: String,
+    /// Link halaman frontend untuk membuat password
+    pub url: String,
+    pub expires_at: DateTime<Utc>,
+}
+
+/// Buat undangan baru untuk user. Undangan lama yang belum dipakai tidak berlaku lagi.
+pub async fn create_invitation<'c>(
+    db: impl Acquire<'c, Database = Postgres>,
+    config: &Config,
+    user_id: Uuid,
+    created_by: Option<Uuid>,
+) -> Result<Invitation, sqlx::Error> {
+    let mut conn = db.acquire().await?;
+    sqlx::query("DELETE FROM user_invitations WHERE user_id = $1 AND accepted_at IS NULL")
+        .bind(user_id)
+        .execute(&mut *conn)
+        .await?;
+
+    let token = generate_token();
+    let expires_at = Utc::now() + Duration::hours(config.invitations.ttl_hours);
+    sqlx::query(
+        "INSERT INTO user_invitations (user_id, token_hash, created_by, expires_at)
+         VALUES ($1, $2, $3, $4)"
+    )
+    .bind(user_id)
+    .bind(hash_token(&token))
+    .bind(created_by)
+    .bind(expires_at)
+    .execute(&mut *conn)
+    .await?;
+
+    Ok(Invitation {
+        url: format!("{}/accept-invite?token={}", config.cors.frontend_url.trim_end_matches('/'), token),
+        token,
+        expires_at,
+    })
+}
+
+fn invitation_invalid() -> ApiError {
+    ApiError::Detailed {
+        status: actix_web::http::StatusCode::BAD_REQUEST,
+        code: "invitation_invalid",
+        message: "Link undangan tidak valid, sudah dipakai, atau kedaluwarsa".to_string(),
+        details: serde_json::Value::Null,
+    }
+}
+
+/// Tandai undangan sudah dipakai dan kembalikan user pemiliknya. Token yang tidak
+/// dikenal, sudah dipakai, kedaluwarsa, atau milik user nonaktif ditolak. Dipanggil di
+/// dalam transaksi yang juga menyimpan password: baris undangan terkunci sampai commit,
+/// jadi request lain dengan token yang sama menunggu lalu ditolak, dan jika password
+/// ditolak transaksinya batal sehingga token masih bisa dipakai.
+pub async fn accept_invitation(db: impl PgExecutor<'_>, token: &str) -> Result<Uuid, ApiError> {
+    sqlx::query_scalar(
+        "UPDATE user_invitations SET accepted_at = NOW()
+         WHERE token_hash = $1 AND accepted_at IS NULL AND expires_at > NOW()
+           AND user_id IN (SELECT id FROM users WHERE is_active)
+         RETURNING user_id"
+    )
+    .bind(hash_token(token))
+    .fetch_optional(db)
+    .await?
+    .ok_or_else(invitation_invalid)
+}
@@ -0,0 +1,542 @@
+//! Login dengan akun LDAP/Active Directory.
+//!
+//! Jika `LDAP_URL` di-set, `POST /api/login` mencoba bind LDAP untuk user yang tidak
+//! punya password lokal yang cocok. DN user dicari dengan `user_filter` (memakai akun
+//! layanan `bind_dn` atau anonim), lalu password diverifikasi dengan bind sebagai DN
+//! tersebut. Akun LDAP dihubungkan ke user lewat `user_identities` (issuer `ldap`,
+//! subject nama login huruf kecil). Setiap login, role user disamakan dengan
+//! `group_roles` berdasarkan grup LDAP-nya, jadi role dikelola di direktori: user yang
+//! dikeluarkan dari semua grup yang dipetakan turun ke `default_role`, atau ditolak
+//! (`ldap_no_role`) jika `default_role` kosong.
+
+use actix_web::http::StatusCode;
+use ldap3::{ldap_escape, Ldap, LdapConnAsync, LdapConnSettings, LdapError, Scope, SearchEntry};
+use sqlx::PgPool;
+use std::time::Duration;
+use uuid::Uuid;
+
+use crate::config::{Config, LdapConfig};
+use crate::error::ApiError;
+use crate::routes::user::find_role_id;
+
+/// Nilai `user_identities.issuer` untuk akun LDAP
+pub const LDAP_ISSUER: &str = "ldap";
+
+/// Result code LDAP `invalidCredentials`
+const INVALID_CREDENTIALS: u32 = 49;
+
+/// Akun LDAP yang password-nya sudah terverifikasi
+#[derive(Debug)]
+pub struct LdapAccount {
+    pub dn: String,
+    pub username: String,
+    pub email: Option<String>,
+    /// DN grup user, sudah dinormalisasi
+    pub groups: Vec<String>,
+}
+
+/// User lokal untuk akun LDAP
+pub struct LdapUser {
+    pub user_id: Uuid,
+    pub name: String,
+    pub role_id: Uuid,
+    /// Role sebelum disinkronkan, jika berubah pada login ini
+    pub previous_role_id: Option<Uuid>,
+    pub provisioned: bool,
+    pub linked: bool,
+    /// Password lokal user dihapus karena akunnya baru dihubungkan ke LDAP
+    pub password_cleared: bool,
+}
+
+pub struct LdapAuthenticator {
+    config: LdapConfig,
+}
+
+fn ldap_error(status: StatusCode, code: &'static str, message: impl Into<String>) -> ApiError {
+    ApiError::Detailed { status, code, message: message.into(), details: serde_json::Value::Null }
+}
+
+fn unavailable(e: LdapError) -> ApiError {
+    eprintln!("[ERROR] Server LDAP gagal: {}", e);
+    ldap_error(StatusCode::SERVICE_UNAVAILABLE, "ldap_unavailable", "Server LDAP tidak bisa dihubungi, coba lagi nanti")
+}
+
+/// DN huruf kecil tanpa spasi di sekitar `,` dan `=`, supaya perbandingan DN tidak
+/// gagal karena penulisan yang berbeda
+fn normalize_dn(dn: &str) -> String {
+    dn.split(',')
+        .map(|rdn| rdn.split('=').map(str::trim).collect::<Vec<_>>().join("="))
+        .collect::<Vec<_>>()
+        .join(",")
+        .to_lowercase()
+}
+
+/// Nilai pertama atribut; nama atribut LDAP tidak case-sensitive
+fn attribute<'a>(entry: &'a SearchEntry, name: &str) -> Option<&'a Vec<String>> {
+    entry
+        .attrs
+        .iter()
+        .find(|(key, _)| key.eq_ignore_ascii_case(name))
+        .map(|(_, values)| values)
+}
+
+impl LdapAuthenticator {
+    pub fn new(config: &Config) -> Self {
+        LdapAuthenticator { config: config.ldap.clone() }
+    }
+
+    pub fn is_configured(&self) -> bool {
+        self.config.url.is_some()
+    }
+
+    fn timeout(&self) -> Duration {
+        Duration::from_secs(self.config.timeout_secs)
+    }
+
+    async fn connect(&self) -> Result<Ldap, LdapError> {
+        let url = self.config.url.as_deref().unwrap_or_default();
+        let settings = LdapConnSettings::new()
+            .set_conn_timeout(self.timeout())
+            .set_starttls(self.config.starttls);
+        let (conn, ldap) = LdapConnAsync::with_settings(settings, url).await?;
+        ldap3::drive!(conn);
+        Ok(ldap)
+    }
+
+    /// Bind dengan akun layanan, atau tetap anonim jika `bind_dn` tidak di-set
+    async fn bind_service(&self, ldap: &mut Ldap) -> Result<(), LdapError> {
+        if let Some(bind_dn) = &self.config.bind_dn {
+            let password = self.config.bind_password.as_deref().unwrap_or_default();
+            ldap.with_timeout(self.timeout()).simple_bind(bind_dn, password).await?.success()?;
+        }
+        Ok(())
+    }
+
+    /// Verifikasi nama dan password ke server LDAP. `None` jika user tidak ditemukan
+    /// (atau tidak unik) atau password salah.
+    pub async fn authenticate(&self, username: &str, password: &str) -> Result<Option<LdapAccount>, ApiError> {
+        // Bind dengan password kosong adalah bind anonim yang selalu berhasil
+        if !self.is_configured() || username.is_empty() || password.is_empty() {
+            return Ok(None);
+        }
+        let mut ldap = self.connect().await.map_err(unavailable)?;
+        let result = self.authenticate_with(&mut ldap, username, password).await;
+        let _ = ldap.unbind().await;
+        result.map_err(unavailable)
+    }
+
+    async fn authenticate_with(
+        &self,
+        ldap: &mut Ldap,
+        username: &str,
+        password: &str,
+    ) -> Result<Option<LdapAccount>, LdapError> {
+        self.bind_service(ldap).await?;
+        let filter = self.user_filter(username);
+        let (entries, _) = ldap
+            .with_timeout(self.timeout())
+            .search(&self.config.user_base_dn, Scope::Subtree, &filter, vec![self.config.email_attribute.as_str(), "memberOf"])
+            .await?
+            .success()?;
+        let mut entries = entries.into_iter();
+        let (Some(entry), None) = (entries.next(), entries.next()) else {
+            return Ok(None);
+        };
+        let entry = SearchEntry::construct(entry);
+
+        let bind = ldap.with_timeout(self.timeout()).simple_bind(&entry.dn, password).await?;
+        if bind.rc == INVALID_CREDENTIALS {
+            return Ok(None);
+        }
+        bind.success()?;
+
+        let groups = match &self.config.group_base_dn {
+            Some(group_base_dn) => {
+                // Cari grup dengan akun layanan, karena user biasa belum tentu boleh membaca grup
+                self.bind_service(ldap).await?;
+                let filter = self.group_filter(&entry.dn, username);
+                let (groups, _) = ldap
+                    .with_timeout(self.timeout())
+                    .search(group_base_dn, Scope::Subtree, &filter, vec!["1.1"])
+                    .await?
+                    .success()?;
+                groups.into_iter().map(|group| SearchEntry::construct(group).dn).collect()
+            },
+            None => attribute(&entry, "memberOf").cloned().unwrap_or_default(),
+        };
+
+        Ok(Some(LdapAccount {
+            username: username.to_string(),
+            email: attribute(&entry, &self.config.email_attribute)
+                .and_then(|values| values.first())
+                .filter(|email| !email.is_empty())
+                .cloned(),
+            groups: groups.iter().map(|group| normalize_dn(group)).collect(),
+            dn: entry.dn,
+        }))
+    }
+
+    /// Filter pencarian user dengan nama login yang sudah di-escape
+    fn user_filter(&self, username: &str) -> String {
+        self.config.user_filter.replace("{username}", &ldap_escape(username))
+    }
+
+    /// Filter pencarian grup dengan DN dan nama login yang sudah di-escape
+    fn group_filter(&self, dn: &str, username: &str) -> String {
+        self.config
+            .group_filter
+            .replace("{dn}", &ldap_escape(dn))
+            .replace("{username}", &ldap_escape(username))
+    }
+
+    /// Nama role dari `group_roles` pertama yang cocok dengan grup user
+    fn mapped_role(&self, account: &LdapAccount) -> Option<&str> {
+        self.config
+            .group_roles
+            .iter()
+            .find(|mapping| account.groups.contains(&normalize_dn(&mapping.group)))
+            .map(|mapping| mapping.role.as_str())
+    }
+
+    /// Nama role untuk grup user: `group_roles` pertama yang cocok, atau `default_role`
+    fn role_for(&self, account: &LdapAccount) -> Option<&str> {
+        self.mapped_role(account)
+            .or(Some(self.config.default_role.as_str()).filter(|role| !role.is_empty()))
+    }
+
+    /// Cari, hubungkan, atau buat user lokal untuk akun LDAP, lalu samakan role-nya
+    /// dengan grup LDAP (atau `default_role` jika tidak ada grup yang cocok)
+    pub async fn resolve_user(&self, pool: &PgPool, account: &LdapAccount) -> Result<LdapUser, ApiError> {
+        let role_name = self.role_for(account).ok_or_else(|| {
+            ldap_error(StatusCode::FORBIDDEN, "ldap_no_role", "Akun LDAP kamu tidak termasuk grup yang boleh memakai aplikasi ini")
+        })?;
+        let role_id = find_role_id(pool, role_name)
+            .await?
+            .ok_or_else(|| ApiError::internal(format!("Role LDAP '{}' tidak ditemukan", role_name)))?;
+        let subject = account.username.to_lowercase();
+
+        let linked: Option<(Uuid, String, Uuid)> = sqlx::query_as(
+            "UPDATE user_identities i SET last_login_at = NOW(), email = COALESCE($3, i.email)
+             FROM users u
+             WHERE u.id = i.user_id AND i.issuer = $1 AND i.subject = $2
+             RETURNING u.id, u.name, u.role_id"
+        )
+        .bind(LDAP_ISSUER)
+        .bind(&subject)
+        .bind(&account.email)
+        .fetch_optional(pool)
+        .await?;
+
+        let mut user = match linked {
+            Some((user_id, name, role_id)) => LdapUser {
+                user_id,
+                name,
+                role_id,
+                previous_role_id: None,
+                provisioned: false,
+                linked: false,
+                password_cleared: false,
+            },
+            None => {
+                let user = match self.link_existing_user(pool, account).await? {
+                    Some(user) => user,
+                    None => self.provision_user(pool, account, role_id).await?,
+                };
+                sqlx::query(
+                    "INSERT INTO user_identities (user_id, issuer, subject, email, last_login_at)
+                     VALUES ($1, $2, $3, $4, NOW())"
+                )
+                .bind(user.user_id)
+                .bind(LDAP_ISSUER)
+                .bind(&subject)
+                .bind(&account.email)
+                .execute(pool)
+                .await?;
+                user
+            },
+        };
+
+        if user.role_id != role_id {
+            sqlx::query("UPDATE users SET role_id = $2 WHERE id = $1")
+                .bind(user.user_id)
+                .bind(role_id)
+                .execute(pool)
+                .await?;
+            user.previous_role_id = Some(user.role_id);
+            user.role_id = role_id;
+        }
+        Ok(user)
+    }
+
+    /// User lokal dengan nama yang sama dihubungkan hanya jika email-nya sama dengan
+    /// email di direktori, supaya akun LDAP tidak bisa mengambil alih akun lokal orang
+    /// lain. Password lokalnya dihapus: setelah terhubung, user login lewat LDAP.
+    async fn link_existing_user(&self, pool: &PgPool, account: &LdapAccount) -> Result<Option<LdapUser>, ApiError> {
+        let existing: Option<(Uuid, String, Uuid, Option<String>, bool)> = sqlx::query_as(
+            "SELECT id, name, role_id, email, password_hash IS NOT NULL FROM users WHERE LOWER(name) = LOWER($1)"
+        )
+        .bind(&account.username)
+        .fetch_optional(pool)
+        .await?;
+        let Some((user_id, name, role_id, email, has_password)) = existing else {
+            return Ok(None);
+        };
+        let email_matches = match (&email, &account.email) {
+            (Some(local), Some(directory)) => local.eq_ignore_ascii_case(directory),
+            _ => false,
+        };
+        if !email_matches {
+            return Err(ldap_error(
+                StatusCode::CONFLICT,
+                "ldap_account_conflict",
+                "Nama ini sudah dipakai user lokal dengan email berbeda; minta admin menghubungkan akunnya",
+            ));
+        }
+        sqlx::query("UPDATE users SET password_hash = NULL WHERE id = $1")
+            .bind(user_id)
+            .execute(pool)
+            .await?;
+        Ok(Some(LdapUser {
+            user_id,
+            name,
+            role_id,
+            previous_role_id: None,
+            provisioned: false,
+            linked: true,
+            password_cleared: has_password,
+        }))
+    }
+
+    async fn provision_user(&self, pool: &PgPool, account: &LdapAccount, role_id: Uuid) -> Result<LdapUser, ApiError> {
+        if !self.config.auto_provision {
+            return Err(ldap_error(
+                StatusCode::FORBIDDEN,
+                "ldap_user_not_found",
+                "Belum ada user untuk akun LDAP ini; minta admin membuatkan akun",
+            ));
+        }
+        if account.username.chars().count() > 64 {
+            return Err(ApiError::BadRequest("Nama user maksimal 64 karakter".to_string()));
+        }
+        // Email yang sudah dipakai user lain tidak disimpan, supaya tidak bentrok dengan constraint UNIQUE
+        let email_taken: bool = match &account.email {
+            Some(email) => {
+                sqlx::query_scalar("SELECT EXISTS (SELECT 1 FROM users WHERE LOWER(email) = LOWER($1))")
+                    .bind(email)
+                    .fetch_one(pool)
+                    .await?
+            },
+            None => false,
+        };
+        let email = account.email.as_deref().filter(|_| !email_taken);
+        let user_id: Uuid = sqlx::query_scalar("INSERT INTO users (name, email, role_id) VALUES ($1, $2, $3) RETURNING id")
+            .bind(&account.username)
+            .bind(email)
+            .bind(role_id)
+            .fetch_one(pool)
+            .await?;
+        Ok(LdapUser {
+            user_id,
+            name: account.username.clone(),
+            role_id,
+            previous_role_id: None,
+            provisioned: true,
+            linked: false,
+            password_cleared: false,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::LdapGroupRole;
+
+    const ADMIN_GROUP: &str = "cn=inman-admin,ou=groups,dc=example,dc=org";
+    const STAFF_GROUP: &str = "cn=inman-staff,ou=groups,dc=example,dc=org";
+
+    fn authenticator(default_role: &str) -> LdapAuthenticator {
+        let mut config = Config::default();
+        config.ldap.url = Some("ldap://ldap.example.test".to_string());
+        config.ldap.default_role = default_role.to_string();
+        config.ldap.group_roles = vec![
+            LdapGroupRole { group: "CN=Inman-Admin, OU=Groups, DC=example, DC=org".to_string(), role: "admin".to_string() },
+            LdapGroupRole { group: STAFF_GROUP.to_string(), role: "staff".to_string() },
+        ];
+        LdapAuthenticator::new(&config)
+    }
+
+    /// Akun hasil `authenticate`, tanpa server LDAP
+    fn account(username: &str, email: Option<&str>, groups: &[&str]) -> LdapAccount {
+        LdapAccount {
+            dn: format!("uid={},ou=people,dc=example,dc=org", username),
+            username: username.to_string(),
+            email: email.map(str::to_string),
+            groups: groups.iter().map(|group| normalize_dn(group)).collect(),
+        }
+    }
+
+    #[test]
+    fn user_filter_escapes_username() {
+        let ldap = authenticator("borrower");
+        assert_eq!(ldap.user_filter("budi"), "(uid=budi)");
+        // Tanpa escape, nama ini akan cocok dengan semua user
+        assert_eq!(ldap.user_filter("*)(uid=*"), r"(uid=\2a\29\28uid=\2a)");
+    }
+
+    #[test]
+    fn group_filter_replaces_dn_and_username() {
+        let mut ldap = authenticator("borrower");
+        ldap.config.group_filter = "(&(member={dn})(!(cn={username})))".to_string();
+        assert_eq!(
+            ldap.group_filter("uid=budi (ops),dc=example,dc=org", "budi*"),
+            r"(&(member=uid=budi \28ops\29,dc=example,dc=org)(!(cn=budi\2a)))"
+        );
+    }
+
+    #[test]
+    fn normalize_dn_ignores_case_and_spacing() {
+        assert_eq!(normalize_dn("CN=Inman-Admin , OU = Groups,DC=example, DC=org"), ADMIN_GROUP);
+    }
+
+    #[test]
+    fn mapped_role_uses_first_matching_group() {
+        let ldap = authenticator("borrower");
+        let both = account("budi", None, &[STAFF_GROUP, "CN=INMAN-ADMIN,OU=GROUPS,DC=EXAMPLE,DC=ORG"]);
+        // Urutan `group_roles` yang menentukan, bukan urutan grup user
+        assert_eq!(ldap.mapped_role(&both), Some("admin"));
+        assert_eq!(ldap.mapped_role(&account("budi", None, &[STAFF_GROUP])), Some("staff"));
+        assert_eq!(ldap.mapped_role(&account("budi", None, &["cn=lain,dc=example,dc=org"])), None);
+    }
+
+    #[test]
+    fn role_for_falls_back_to_default_role() {
+        let unmapped = account("budi", None, &[]);
+        assert_eq!(authenticator("borrower").role_for(&unmapped), Some("borrower"));
+        assert_eq!(authenticator("").role_for(&unmapped), None);
+        assert_eq!(authenticator("").role_for(&account("budi", None, &[STAFF_GROUP])), Some("staff"));
+    }
+
+    /// Butuh database yang sudah dimigrasi:
+    /// `DATABASE_URL=postgres://... cargo test -- --ignored`
+    async fn test_pool() -> PgPool {
+        let url = std::env::var("DATABASE_URL").expect("DATABASE_URL belum di-set");
+        let pool = PgPool::connect(&url).await.unwrap();
+        crate::migrations::run(&pool).await.unwrap();
+        pool
+    }
+
+    async fn role_id(pool: &PgPool, name: &str) -> Uuid {
+        find_role_id(pool, name).await.unwrap().unwrap()
+    }
+
+    async fn create_user(pool: &PgPool, name: &str, email: &str, role: &str) -> Uuid {
+        sqlx::query_scalar(
+            "INSERT INTO users (name, email, role_id, password_hash) VALUES ($1, $2, $3, 'hash-lokal') RETURNING id"
+        )
+        .bind(name)
+        .bind(email)
+        .bind(role_id(pool, role).await)
+        .fetch_one(pool)
+        .await
+        .unwrap()
+    }
+
+    async fn user_state(pool: &PgPool, user_id: Uuid) -> (Uuid, bool) {
+        sqlx::query_as("SELECT role_id, password_hash IS NOT NULL FROM users WHERE id = $1")
+            .bind(user_id)
+            .fetch_one(pool)
+            .await
+            .unwrap()
+    }
+
+    async fn cleanup(pool: &PgPool, user_id: Uuid) {
+        sqlx::query("DELETE FROM user_identities WHERE user_id = $1").bind(user_id).execute(pool).await.unwrap();
+        sqlx::query("DELETE FROM users WHERE id = $1").bind(user_id).execute(pool).await.unwrap();
+    }
+
+    fn unique_name() -> String {
+        format!("ldap-test-{}", Uuid::new_v4().simple())
+    }
+
+    #[actix_web::test]
+    #[ignore = "butuh DATABASE_URL"]
+    async fn user_removed_from_mapped_groups_is_demoted() {
+        let pool = test_pool().await;
+        let name = unique_name();
+        let email = format!("{}@example.test", name);
+        let user_id = create_user(&pool, &name, &email, "staff").await;
+        let ldap = authenticator("borrower");
+
+        // Login pertama menghubungkan akun sebagai admin, lalu user dikeluarkan dari grup admin
+        let first = ldap.resolve_user(&pool, &account(&name, Some(&email), &[ADMIN_GROUP])).await;
+        let second = ldap.resolve_user(&pool, &account(&name, Some(&email), &[])).await;
+        let (role, has_password) = user_state(&pool, user_id).await;
+        let (staff, admin, borrower) =
+            (role_id(&pool, "staff").await, role_id(&pool, "admin").await, role_id(&pool, "borrower").await);
+        cleanup(&pool, user_id).await;
+
+        let first = first.unwrap();
+        assert!(first.linked);
+        assert!(first.password_cleared);
+        assert_eq!((first.role_id, first.previous_role_id), (admin, Some(staff)));
+        let second = second.unwrap();
+        assert!(!second.linked && !second.password_cleared);
+        assert_eq!((second.role_id, second.previous_role_id), (borrower, Some(admin)));
+        assert_eq!(role, borrower);
+        assert!(!has_password);
+    }
+
+    #[actix_web::test]
+    #[ignore = "butuh DATABASE_URL"]
+    async fn user_removed_from_mapped_groups_is_rejected_without_default_role() {
+        let pool = test_pool().await;
+        let name = unique_name();
+        let email = format!("{}@example.test", name);
+        let user_id = create_user(&pool, &name, &email, "admin").await;
+        let ldap = authenticator("");
+
+        let first = ldap.resolve_user(&pool, &account(&name, Some(&email), &[ADMIN_GROUP])).await;
+        let second = ldap.resolve_user(&pool, &account(&name, Some(&email), &[])).await;
+        let (role, _) = user_state(&pool, user_id).await;
+        let admin = role_id(&pool, "admin").await;
+        cleanup(&pool, user_id).await;
+
+        assert!(first.is_ok());
+        assert!(matches!(second, Err(ApiError::Detailed { code: "ldap_no_role", .. })));
+        // Role tidak berubah, tapi user tidak bisa login lewat LDAP
+        assert_eq!(role, admin);
+    }
+
+    #[actix_web::test]
+    #[ignore = "butuh DATABASE_URL"]
+    async fn mapped_group_syncs_role() {
+        let pool = test_pool().await;
+        let name = unique_name();
+        let email = format!("{}@example.test", name);
+        let user_id = create_user(&pool, &name, &email, "admin").await;
+        let ldap = authenticator("borrower");
+
+        let user = ldap.resolve_user(&pool, &account(&name, Some(&email), &[STAFF_GROUP])).await;
+        let (role, _) = user_state(&pool, user_id).await;
+        let (admin, staff) = (role_id(&pool, "admin").await, role_id(&pool, "staff").await);
+        cleanup(&pool, user_id).await;
+
+        let user = user.unwrap();
+        assert_eq!((user.role_id, user.previous_role_id), (staff, Some(admin)));
+        assert_eq!(role, staff);
+    }
+
+    #[actix_web::test]
+    #[ignore = "butuh DATABASE_URL"]
+    async fn new_unmapped_user_gets_default_role() {
+        let pool = test_pool().await;
+        let name = unique_name();
+        let user = authenticator("borrower").resolve_user(&pool, &account(&name, None, &[])).await.unwrap();
+        let borrower = role_id(&pool, "borrower").await;
+        cleanup(&pool, user.user_id).await;
+
+        assert!(user.provisioned && !user.password_cleared);
+        assert_eq!((user.role_id, user.previous_role_id), (borrower, None));
+    }
+}
@@ -0,0 +1,167 @@
+//! Perlindungan brute-force untuk login.
+//!
+//! Login gagal dihitung per akun dan per IP. Akun di sini adalah nama yang dicoba,
+//! ada atau tidak di `users`, supaya kunci tidak membocorkan username yang valid.
+//! Setelah `free_attempts` kegagalan, login berikutnya harus menunggu 1, 2, 4, ...
+//! detik; setelah `max_failures` akun dikunci selama `lockout_minutes`. IP mendapat
+//! jeda setelah `max_failures` dan dikunci setelah `ip_max_failures`. Hitungan
+//! direset jika tidak ada kegagalan selama `lockout_minutes`, dan hitungan akun
+//! direset saat login berhasil atau dibuka admin.
+
+use sqlx::{PgExecutor, PgPool};
+
+use crate::config::LoginConfig;
+use crate::error::ApiError;
+
+const ACCOUNT: &str = "account";
+const IP: &str = "ip";
+
+/// Tolak dengan 429 jika akun atau IP sedang dalam masa jeda/kunci
+pub async fn check_login_allowed(pool: &PgPool, name: &str, ip: Option<&str>) -> Result<(), ApiError> {
+    let remaining: Option<f64> = sqlx::query_scalar(
+        "SELECT EXTRACT(EPOCH FROM MAX(locked_until) - NOW())::FLOAT8
+         FROM login_throttles
+         WHERE ((scope = $1 AND key = $2) OR (scope = $3 AND key = $4))
+           AND locked_until > NOW()"
+    )
+    .bind(ACCOUNT)
+    .bind(name)
+    .bind(IP)
+    .bind(ip)
+    .fetch_one(pool)
+    .await?;
+
+    match remaining {
+        Some(secs) => Err(ApiError::TooManyRequests {
+            message: "Terlalu banyak percobaan login gagal, coba lagi nanti".to_string(),
+            retry_after_secs: secs.ceil().max(1.0) as u64,
+        }),
+        None => Ok(()),
+    }
+}
+
+/// Catat login gagal untuk akun dan IP, lalu pasang jeda/kunci jika perlu
+pub async fn record_login_failure(
+    pool: &PgPool,
+    config: &LoginConfig,
+    name: &str,
+    ip: Option<&str>,
+) -> Result<(), sqlx::Error> {
+    let mut counters = vec![(ACCOUNT, name, config.free_attempts, config.max_failures)];
+    if let Some(ip) = ip {
+        counters.push((IP, ip, config.max_failures, config.ip_max_failures));
+    }
+
+    for (scope, key, free_attempts, max_failures) in counters {
+        let failures: i32 = sqlx::query_scalar(
+            "INSERT INTO login_throttles (scope, key, failures, last_failure_at)
+             VALUES ($1, $2, 1, NOW())
+             ON CONFLICT (scope, key) DO UPDATE SET
+                 failures = CASE
+                     WHEN login_throttles.last_failure_at < NOW() - make_interval(mins => $3) THEN 1
+                     ELSE login_throttles.failures + 1
+                 END,
+                 last_failure_at = NOW()
+             RETURNING failures"
+        )
+        .bind(scope)
+        .bind(key)
+        .bind(config.lockout_minutes as i32)
+        .fetch_one(pool)
+        .await?;
+
+        if let Some(delay_secs) = lock_delay_secs(failures as u32, free_attempts, max_failures, config.lockout_minutes) {
+            sqlx::query("UPDATE login_throttles SET locked_until = NOW() + make_interval(secs => $3) WHERE scope = $1 AND key = $2")
+                .bind(scope)
+                .bind(key)
+                .bind(delay_secs as f64)
+                .execute(pool)
+                .await?;
+            if failures as u32 == max_failures {
+                eprintln!("[WARN] Login dikunci {} menit untuk {} '{}'", config.lockout_minutes, scope, key);
+            }
+        }
+    }
+
+    // Buang hitungan yang sudah kedaluwarsa supaya tabel tidak terus membesar
+    sqlx::query(
+        "DELETE FROM login_throttles
+         WHERE last_failure_at < NOW() - make_interval(mins => $1)
+           AND (locked_until IS NULL OR locked_until < NOW())"
+    )
+    .bind(config.lockout_minutes as i32)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// Reset hitungan akun setelah login berhasil. Hitungan IP tetap, supaya satu akun
+/// yang valid tidak bisa dipakai untuk mereset jeda IP.
+pub async fn record_login_success(pool: &PgPool, name: &str) -> Result<(), sqlx::Error> {
+    unlock_account(pool, name).await.map(|_| ())
+}
+
+/// Buka kunci akun (admin). Mengembalikan false jika akun tidak sedang dihitung.
+pub async fn unlock_account(db: impl PgExecutor<'_>, name: &str) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query("DELETE FROM login_throttles WHERE scope = $1 AND key = $2")
+        .bind(ACCOUNT)
+        .bind(name)
+        .execute(db)
+        .await?;
+    Ok(result.rows_affected() > 0)
+}
+
+/// Buka kunci IP (admin)
+pub async fn unlock_ip(pool: &PgPool, ip: &str) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query("DELETE FROM login_throttles WHERE scope = $1 AND key = $2")
+        .bind(IP)
+        .bind(ip)
+        .execute(pool)
+        .await?;
+    Ok(result.rows_affected() > 0)
+}
+
+/// Jeda setelah kegagalan ke-`failures`: tidak ada selama `free_attempts`, lalu
+/// berlipat dua tiap kegagalan, dan penuh `lockout_minutes` di `max_failures`
+fn lock_delay_secs(failures: u32, free_attempts: u32, max_failures: u32, lockout_minutes: i64) -> Option<i64> {
+    let lockout_secs = lockout_minutes * 60;
+    if failures >= max_failures {
+        Some(lockout_secs)
+    } else if failures > free_attempts {
+        let exponent = (failures - free_attempts - 1).min(30);
+        Some((1i64 << exponent).min(lockout_secs))
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn no_delay_during_free_attempts() {
+        for failures in 0..=3 {
+            assert_eq!(lock_delay_secs(failures, 3, 10, 15), None);
+        }
+    }
+
+    #[test]
+    fn delay_doubles_after_free_attempts() {
+        let delays: Vec<_> = (4..=9).map(|failures| lock_delay_secs(failures, 3, 10, 15)).collect();
+        assert_eq!(delays, vec![Some(1), Some(2), Some(4), Some(8), Some(16), Some(32)]);
+    }
+
+    #[test]
+    fn full_lockout_at_max_failures() {
+        assert_eq!(lock_delay_secs(10, 3, 10, 15), Some(900));
+        assert_eq!(lock_delay_secs(25, 3, 10, 15), Some(900));
+    }
+
+    #[test]
+    fn delay_never_exceeds_lockout() {
+        assert_eq!(lock_delay_secs(40, 0, 100, 1), Some(60));
+        // Eksponen dibatasi, jadi kegagalan sangat banyak tidak overflow
+        assert_eq!(lock_delay_secs(u32::MAX - 1, 0, u32::MAX, 60), Some(3600));
+    }
+}
@@ -0,0 +1,43 @@
+//! Pengiriman email lewat SMTP (`SMTP_URL`). Jika SMTP belum dikonfigurasi, email
+//! tidak dikirim dan hanya dicatat di log, jadi fitur yang bergantung pada email
+//! harus punya jalur lain (mis. admin membuat link reset password).
+
+use lettre::message::Mailbox;
+use lettre::{AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor};
+
+use crate::config::MailConfig;
+
+#[derive(Clone)]
+pub struct Mailer {
+    transport: Option<(AsyncSmtpTransport<Tokio1Executor>, Mailbox)>,
+}
+
+impl Mailer {
+    /// `MailConfig` sudah divalidasi saat startup, jadi URL/alamat yang tidak valid dianggap belum dikonfigurasi
+    pub fn new(config: &MailConfig) -> Self {
+        let transport = config.smtp_url.as_deref().and_then(|url| {
+            let transport = AsyncSmtpTransport::<Tokio1Executor>::from_url(url).ok()?.build();
+            Some((transport, config.from.parse().ok()?))
+        });
+        Mailer { transport }
+    }
+
+    pub fn is_configured(&self) -> bool {
+        self.transport.is_some()
+    }
+
+    /// Kirim email teks biasa
+    pub async fn send(&self, to: &str, subject: &str, body: String) -> Result<(), Box<dyn std::error::Error>> {
+        let Some((transport, from)) = &self.transport else {
+            eprintln!("[WARN] SMTP_URL belum di-set, email '{}' ke {} tidak dikirim", subject, to);
+            return Ok(());
+        };
+        let message = Message::builder()
+            .from(from.clone())
+            .to(to.parse()?)
+            .subject(subject)
+            .body(body)?;
+        transport.send(message).await?;
+        Ok(())
+    }
+}
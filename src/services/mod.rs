@@ -1 +1,17 @@
 pub mod drive_storage;
+pub mod ical;
+pub mod receipt_pdf;
+pub mod penalties;
+pub mod sessions;
+pub mod login_throttle;
+pub mod tokens;
+pub mod invitations;
+pub mod audit;
+pub mod mailer;
+pub mod password_reset;
+pub mod passwords;
+pub mod two_factor;
+pub mod api_tokens;
+pub mod oidc;
+pub mod ldap;
+pub mod security_log;
@@ -0,0 +1,658 @@
+//! Login SSO lewat OpenID Connect (authorization code flow dengan PKCE).
+//!
+//! `GET /api/auth/oidc/login` menyimpan `state`, `nonce`, dan PKCE verifier di
+//! `oidc_login_states` lalu mengarahkan browser ke provider. Di callback, code ditukar
+//! dengan ID token yang divalidasi terhadap JWKS provider (tanda tangan, issuer,
+//! audience, kedaluwarsa, nonce). Akun provider dihubungkan ke user lewat
+//! `user_identities` (issuer + `sub`); login pertama mencocokkan email yang sudah
+//! diverifikasi provider dengan `users.email`, atau membuat user baru dengan
+//! `default_role` jika `auto_provision` aktif.
+
+use actix_web::http::StatusCode;
+use argon2::password_hash::rand_core::{OsRng, RngCore};
+use base64::Engine;
+use chrono::{Duration, Utc};
+use jsonwebtoken::jwk::{Jwk, JwkSet};
+use jsonwebtoken::{decode, decode_header, Algorithm, DecodingKey, Validation};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use sqlx::PgPool;
+use std::sync::RwLock;
+use std::time::Instant;
+use uuid::Uuid;
+
+use crate::config::{Config, OidcConfig};
+use crate::error::ApiError;
+use crate::routes::user::{find_role_id, find_user_id};
+use crate::services::tokens::{generate_token, hash_token};
+
+/// Masa berlaku login yang sedang berjalan (dari redirect ke provider sampai callback)
+const STATE_TTL_MINUTES: i64 = 10;
+
+/// Metadata discovery dan JWKS di-cache selama ini; JWKS diambil ulang lebih awal
+/// jika ID token memakai `kid` yang belum dikenal (rotasi kunci)
+const PROVIDER_CACHE_TTL: std::time::Duration = std::time::Duration::from_secs(3600);
+
+#[derive(Clone, Deserialize)]
+struct ProviderMetadata {
+    issuer: String,
+    authorization_endpoint: String,
+    token_endpoint: String,
+    jwks_uri: String,
+}
+
+#[derive(Clone)]
+struct Provider {
+    metadata: ProviderMetadata,
+    jwks: JwkSet,
+    fetched_at: Instant,
+}
+
+#[derive(Deserialize)]
+struct TokenResponse {
+    id_token: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct IdTokenClaims {
+    sub: String,
+    nonce: Option<String>,
+    email: Option<String>,
+    /// Sebagian provider mengirim `"true"` sebagai string
+    email_verified: Option<serde_json::Value>,
+    preferred_username: Option<String>,
+}
+
+impl IdTokenClaims {
+    fn verified_email(&self) -> Option<&str> {
+        let verified = match &self.email_verified {
+            Some(serde_json::Value::Bool(verified)) => *verified,
+            Some(serde_json::Value::String(verified)) => verified == "true",
+            _ => false,
+        };
+        self.email.as_deref().filter(|_| verified)
+    }
+}
+
+/// User yang berhasil login lewat OIDC
+pub struct OidcUser {
+    pub user_id: Uuid,
+    pub name: String,
+    pub role_id: Uuid,
+    /// User baru dibuat di login ini
+    pub provisioned: bool,
+    /// Akun provider baru dihubungkan ke user yang sudah ada di login ini
+    pub linked: bool,
+}
+
+/// Hasil callback OIDC
+pub struct OidcLogin {
+    pub user: OidcUser,
+    /// Path frontend yang diminta saat login dimulai
+    pub redirect_path: Option<String>,
+}
+
+/// Client OIDC, dibagikan ke handler lewat `web::Data<OidcClient>`. Jika
+/// `OIDC_ISSUER_URL` belum di-set, semua endpoint OIDC menjawab 503.
+pub struct OidcClient {
+    config: OidcConfig,
+    redirect_url: String,
+    http: reqwest::Client,
+    provider: RwLock<Option<Provider>>,
+}
+
+fn oidc_error(status: StatusCode, code: &'static str, message: impl Into<String>) -> ApiError {
+    ApiError::Detailed { status, code, message: message.into(), details: serde_json::Value::Null }
+}
+
+fn provider_error(message: impl std::fmt::Display) -> ApiError {
+    oidc_error(StatusCode::BAD_GATEWAY, "oidc_provider_error", format!("Provider OIDC gagal: {}", message))
+}
+
+fn id_token_invalid(message: impl std::fmt::Display) -> ApiError {
+    oidc_error(StatusCode::UNAUTHORIZED, "oidc_id_token_invalid", format!("ID token tidak valid: {}", message))
+}
+
+/// PKCE code challenge (S256) dari verifier
+fn code_challenge(verifier: &str) -> String {
+    base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(Sha256::digest(verifier.as_bytes()))
+}
+
+/// Kunci untuk `kid`; provider dengan satu kunci boleh tidak mengirim `kid`
+fn find_key<'a>(jwks: &'a JwkSet, kid: Option<&str>) -> Option<&'a Jwk> {
+    match kid {
+        Some(kid) => jwks.find(kid),
+        None if jwks.keys.len() == 1 => jwks.keys.first(),
+        None => None,
+    }
+}
+
+/// Nama user dari klaim provider, hanya huruf, angka, `.`, `_`, dan `-`
+fn username_base(claims: &IdTokenClaims) -> String {
+    let source = claims
+        .preferred_username
+        .as_deref()
+        .or_else(|| claims.email.as_deref().and_then(|email| email.split('@').next()))
+        .unwrap_or_default();
+    let name: String = source
+        .chars()
+        .filter(|c| c.is_alphanumeric() || matches!(c, '.' | '_' | '-'))
+        .take(56)
+        .collect();
+    if name.is_empty() { "user".to_string() } else { name }
+}
+
+impl OidcClient {
+    pub fn new(config: &Config) -> Self {
+        OidcClient {
+            config: config.oidc.clone(),
+            redirect_url: config.oidc_redirect_url(),
+            http: reqwest::Client::builder()
+                .timeout(std::time::Duration::from_secs(10))
+                .build()
+                .unwrap_or_default(),
+            provider: RwLock::new(None),
+        }
+    }
+
+    pub fn is_configured(&self) -> bool {
+        self.config.issuer_url.is_some()
+    }
+
+    pub fn display_name(&self) -> &str {
+        &self.config.display_name
+    }
+
+    fn issuer_url(&self) -> Result<&str, ApiError> {
+        self.config
+            .issuer_url
+            .as_deref()
+            .map(|url| url.trim_end_matches('/'))
+            .ok_or_else(|| ApiError::ServiceUnavailable("Login SSO belum dikonfigurasi".to_string()))
+    }
+
+    /// Metadata discovery dan JWKS provider, dari cache jika masih berlaku
+    async fn provider(&self, refresh: bool) -> Result<Provider, ApiError> {
+        if !refresh {
+            if let Some(provider) = self.provider.read().unwrap().as_ref() {
+                if provider.fetched_at.elapsed() < PROVIDER_CACHE_TTL {
+                    return Ok(provider.clone());
+                }
+            }
+        }
+
+        let issuer_url = self.issuer_url()?;
+        let metadata: ProviderMetadata = self
+            .http
+            .get(format!("{}/.well-known/openid-configuration", issuer_url))
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(provider_error)?
+            .json()
+            .await
+            .map_err(provider_error)?;
+        if metadata.issuer.trim_end_matches('/') != issuer_url {
+            return Err(provider_error(format!("issuer discovery '{}' tidak sama dengan OIDC_ISSUER_URL", metadata.issuer)));
+        }
+        let jwks: JwkSet = self
+            .http
+            .get(&metadata.jwks_uri)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(provider_error)?
+            .json()
+            .await
+            .map_err(provider_error)?;
+
+        let provider = Provider { metadata, jwks, fetched_at: Instant::now() };
+        *self.provider.write().unwrap() = Some(provider.clone());
+        Ok(provider)
+    }
+
+    /// Mulai login: simpan state lalu kembalikan URL authorization provider
+    pub async fn authorization_url(&self, pool: &PgPool, redirect_path: Option<&str>) -> Result<String, ApiError> {
+        let provider = self.provider(false).await?;
+
+        sqlx::query("DELETE FROM oidc_login_states WHERE expires_at < NOW()")
+            .execute(pool)
+            .await?;
+        let state = generate_token();
+        let nonce = generate_token();
+        let code_verifier = generate_token();
+        sqlx::query(
+            "INSERT INTO oidc_login_states (state_hash, nonce, code_verifier, redirect_path, expires_at)
+             VALUES ($1, $2, $3, $4, $5)"
+        )
+        .bind(hash_token(&state))
+        .bind(&nonce)
+        .bind(&code_verifier)
+        .bind(redirect_path)
+        .bind(Utc::now() + Duration::minutes(STATE_TTL_MINUTES))
+        .execute(pool)
+        .await?;
+
+        let url = reqwest::Url::parse_with_params(
+            &provider.metadata.authorization_endpoint,
+            &[
+                ("response_type", "code"),
+                ("client_id", self.config.client_id.as_str()),
+                ("redirect_uri", self.redirect_url.as_str()),
+                ("scope", self.config.scopes.join(" ").as_str()),
+                ("state", state.as_str()),
+                ("nonce", nonce.as_str()),
+                ("code_challenge", code_challenge(&code_verifier).as_str()),
+                ("code_challenge_method", "S256"),
+            ],
+        )
+        .map_err(provider_error)?;
+        Ok(url.to_string())
+    }
+
+    /// Selesaikan login di callback: pakai state, tukar code dengan ID token,
+    /// validasi ID token, lalu cari/hubungkan/buat user-nya
+    pub async fn complete_login(&self, pool: &PgPool, code: &str, state: &str) -> Result<OidcLogin, ApiError> {
+        let (nonce, code_verifier, redirect_path): (String, String, Option<String>) = sqlx::query_as(
+            "DELETE FROM oidc_login_states
+             WHERE state_hash = $1 AND expires_at > NOW()
+             RETURNING nonce, code_verifier, redirect_path"
+        )
+        .bind(hash_token(state))
+        .fetch_optional(pool)
+        .await?
+        .ok_or_else(|| oidc_error(StatusCode::BAD_REQUEST, "oidc_state_invalid", "Login SSO tidak valid atau kedaluwarsa, ulangi login"))?;
+
+        let provider = self.provider(false).await?;
+        let id_token = self.exchange_code(&provider, code, &code_verifier).await?;
+        let claims = self.validate_id_token(provider, &id_token, &nonce).await?;
+        let user = self.resolve_user(pool, &claims).await?;
+        Ok(OidcLogin { user, redirect_path })
+    }
+
+    async fn exchange_code(&self, provider: &Provider, code: &str, code_verifier: &str) -> Result<String, ApiError> {
+        let mut form = vec![
+            ("grant_type", "authorization_code"),
+            ("code", code),
+            ("redirect_uri", self.redirect_url.as_str()),
+            ("code_verifier", code_verifier),
+        ];
+        let mut request = self.http.post(&provider.metadata.token_endpoint);
+        match &self.config.client_secret {
+            Some(secret) => request = request.basic_auth(&self.config.client_id, Some(secret)),
+            None => form.push(("client_id", self.config.client_id.as_str())),
+        }
+        let response = request.form(&form).send().await.map_err(provider_error)?;
+        if !response.status().is_success() {
+            let status = response.status();
+            let body = response.text().await.unwrap_or_default();
+            return Err(provider_error(format!("token endpoint menjawab {}: {}", status, body)));
+        }
+        response
+            .json::<TokenResponse>()
+            .await
+            .map_err(provider_error)?
+            .id_token
+            .ok_or_else(|| provider_error("response token tidak berisi id_token"))
+    }
+
+    async fn validate_id_token(&self, mut provider: Provider, id_token: &str, nonce: &str) -> Result<IdTokenClaims, ApiError> {
+        let header = decode_header(id_token).map_err(id_token_invalid)?;
+        if matches!(header.alg, Algorithm::HS256 | Algorithm::HS384 | Algorithm::HS512) {
+            return Err(id_token_invalid("algoritma HMAC tidak didukung"));
+        }
+        if find_key(&provider.jwks, header.kid.as_deref()).is_none() {
+            provider = self.provider(true).await?;
+        }
+        let jwk = find_key(&provider.jwks, header.kid.as_deref()).ok_or_else(|| id_token_invalid("kunci tidak ditemukan di JWKS"))?;
+        let key = DecodingKey::from_jwk(jwk).map_err(id_token_invalid)?;
+
+        let mut validation = Validation::new(header.alg);
+        validation.set_issuer(&[&provider.metadata.issuer]);
+        validation.set_audience(&[&self.config.client_id]);
+        validation.set_required_spec_claims(&["exp", "iss", "aud", "sub"]);
+        let claims = decode::<IdTokenClaims>(id_token, &key, &validation).map_err(id_token_invalid)?.claims;
+        if claims.nonce.as_deref() != Some(nonce) {
+            return Err(id_token_invalid("nonce tidak cocok"));
+        }
+        Ok(claims)
+    }
+
+    async fn resolve_user(&self, pool: &PgPool, claims: &IdTokenClaims) -> Result<OidcUser, ApiError> {
+        let issuer = self.issuer_url()?;
+        let linked: Option<(Uuid, String, Uuid)> = sqlx::query_as(
+            "UPDATE user_identities i SET last_login_at = NOW(), email = COALESCE($3, i.email)
+             FROM users u
+             WHERE u.id = i.user_id AND i.issuer = $1 AND i.subject = $2
+             RETURNING u.id, u.name, u.role_id"
+        )
+        .bind(issuer)
+        .bind(&claims.sub)
+        .bind(&claims.email)
+        .fetch_optional(pool)
+        .await?;
+        if let Some((user_id, name, role_id)) = linked {
+            return Ok(OidcUser { user_id, name, role_id, provisioned: false, linked: false });
+        }
+
+        // `users.email` tidak unik: jika lebih dari satu user memakai email yang sama,
+        // identity tidak dihubungkan otomatis ke salah satunya
+        let existing: Vec<(Uuid, String, Uuid)> = match claims.verified_email() {
+            Some(email) => {
+                sqlx::query_as("SELECT id, name, role_id FROM users WHERE LOWER(email) = LOWER($1) LIMIT 2")
+                    .bind(email)
+                    .fetch_all(pool)
+                    .await?
+            },
+            None => Vec::new(),
+        };
+        let user = match existing.as_slice() {
+            [] => self.provision_user(pool, claims).await?,
+            [(user_id, name, role_id)] => {
+                OidcUser { user_id: *user_id, name: name.clone(), role_id: *role_id, provisioned: false, linked: true }
+            },
+            _ => {
+                return Err(oidc_error(
+                    StatusCode::CONFLICT,
+                    "oidc_email_ambiguous",
+                    "Email ini dipakai lebih dari satu user; minta admin merapikan email user sebelum login SSO",
+                ));
+            },
+        };
+
+        sqlx::query(
+            "INSERT INTO user_identities (user_id, issuer, subject, email, last_login_at)
+             VALUES ($1, $2, $3, $4, NOW())"
+        )
+        .bind(user.user_id)
+        .bind(issuer)
+        .bind(&claims.sub)
+        .bind(&claims.email)
+        .execute(pool)
+        .await?;
+        Ok(user)
+    }
+
+    async fn provision_user(&self, pool: &PgPool, claims: &IdTokenClaims) -> Result<OidcUser, ApiError> {
+        if !self.config.auto_provision {
+            return Err(oidc_error(
+                StatusCode::FORBIDDEN,
+                "oidc_user_not_found",
+                "Belum ada user dengan email ini; minta admin membuatkan akun",
+            ));
+        }
+        // Email yang belum diverifikasi provider tidak disimpan, supaya tidak bisa dipakai
+        // mengklaim alamat milik orang lain
+        let email = claims.verified_email();
+        if let Some(email) = claims.email.as_deref().filter(|_| email.is_none()) {
+            let taken: bool = sqlx::query_scalar("SELECT EXISTS (SELECT 1 FROM users WHERE LOWER(email) = LOWER($1))")
+                .bind(email)
+                .fetch_one(pool)
+                .await?;
+            if taken {
+                return Err(oidc_error(
+                    StatusCode::FORBIDDEN,
+                    "oidc_email_unverified",
+                    "Email akun SSO belum diverifikasi provider, tidak bisa dihubungkan ke user yang sudah ada",
+                ));
+            }
+        }
+        let role_id = find_role_id(pool, &self.config.default_role)
+            .await?
+            .ok_or_else(|| ApiError::internal(format!("OIDC_DEFAULT_ROLE '{}' tidak ditemukan", self.config.default_role)))?;
+
+        let base = username_base(claims);
+        let mut name = base.clone();
+        while find_user_id(pool, &name).await?.is_some() {
+            name = format!("{}-{}", base, OsRng.next_u32() % 10_000);
+        }
+        let user_id: Uuid = sqlx::query_scalar("INSERT INTO users (name, email, role_id) VALUES ($1, $2, $3) RETURNING id")
+            .bind(&name)
+            .bind(email)
+            .bind(role_id)
+            .fetch_one(pool)
+            .await?;
+        Ok(OidcUser { user_id, name, role_id, provisioned: true, linked: false })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use jsonwebtoken::{encode, EncodingKey, Header};
+    use serde_json::json;
+
+    const ISSUER: &str = "https://sso.example.test/realms/inman";
+    const CLIENT_ID: &str = "inman";
+    const NONCE: &str = "nonce-login";
+    const JWKS: &str = include_str!("../../tests/fixtures/oidc/jwks.json");
+    const SIGNING_KEY: &[u8] = include_bytes!("../../tests/fixtures/oidc/signing_key.pem");
+    /// Kunci lain dengan `kid` yang sama, untuk tanda tangan yang tidak cocok
+    const OTHER_KEY: &[u8] = include_bytes!("../../tests/fixtures/oidc/other_key.pem");
+
+    fn client(auto_provision: bool) -> OidcClient {
+        let mut config = Config::default();
+        config.oidc.issuer_url = Some(ISSUER.to_string());
+        config.oidc.client_id = CLIENT_ID.to_string();
+        config.oidc.auto_provision = auto_provision;
+        OidcClient::new(&config)
+    }
+
+    fn provider() -> Provider {
+        Provider {
+            metadata: ProviderMetadata {
+                issuer: ISSUER.to_string(),
+                authorization_endpoint: format!("{}/auth", ISSUER),
+                token_endpoint: format!("{}/token", ISSUER),
+                jwks_uri: format!("{}/certs", ISSUER),
+            },
+            jwks: serde_json::from_str(JWKS).unwrap(),
+            fetched_at: Instant::now(),
+        }
+    }
+
+    fn claims() -> serde_json::Value {
+        json!({
+            "sub": "subject-1",
+            "iss": ISSUER,
+            "aud": CLIENT_ID,
+            "exp": (Utc::now() + Duration::minutes(5)).timestamp(),
+            "nonce": NONCE,
+            "email": "sso@example.test",
+            "email_verified": true,
+        })
+    }
+
+    fn sign(claims: &serde_json::Value, key: &[u8]) -> String {
+        let mut header = Header::new(Algorithm::RS256);
+        header.kid = Some("test-key".to_string());
+        encode(&header, claims, &EncodingKey::from_rsa_pem(key).unwrap()).unwrap()
+    }
+
+    async fn validate(id_token: &str) -> Result<IdTokenClaims, ApiError> {
+        client(true).validate_id_token(provider(), id_token, NONCE).await
+    }
+
+    fn assert_invalid(result: Result<IdTokenClaims, ApiError>, reason: &str) {
+        match result {
+            Err(ApiError::Detailed { status, code, message, .. }) => {
+                assert_eq!(status, StatusCode::UNAUTHORIZED, "{}", message);
+                assert_eq!(code, "oidc_id_token_invalid", "{}", message);
+                assert!(message.contains(reason), "{}", message);
+            },
+            Err(other) => panic!("error lain: {}", other),
+            Ok(claims) => panic!("ID token diterima untuk sub {}", claims.sub),
+        }
+    }
+
+    #[actix_web::test]
+    async fn accepts_token_signed_by_provider_key() {
+        let claims = validate(&sign(&claims(), SIGNING_KEY)).await.unwrap();
+        assert_eq!(claims.sub, "subject-1");
+        assert_eq!(claims.verified_email(), Some("sso@example.test"));
+    }
+
+    #[actix_web::test]
+    async fn rejects_bad_signature() {
+        assert_invalid(validate(&sign(&claims(), OTHER_KEY)).await, "InvalidSignature");
+    }
+
+    #[actix_web::test]
+    async fn rejects_wrong_audience() {
+        let mut claims = claims();
+        claims["aud"] = json!("aplikasi-lain");
+        assert_invalid(validate(&sign(&claims, SIGNING_KEY)).await, "InvalidAudience");
+    }
+
+    #[actix_web::test]
+    async fn rejects_wrong_issuer() {
+        let mut claims = claims();
+        claims["iss"] = json!("https://evil.example.test");
+        assert_invalid(validate(&sign(&claims, SIGNING_KEY)).await, "InvalidIssuer");
+    }
+
+    #[actix_web::test]
+    async fn rejects_expired_token() {
+        let mut claims = claims();
+        claims["exp"] = json!((Utc::now() - Duration::hours(1)).timestamp());
+        assert_invalid(validate(&sign(&claims, SIGNING_KEY)).await, "ExpiredSignature");
+    }
+
+    #[actix_web::test]
+    async fn rejects_hmac_algorithm() {
+        // HS256 dengan client_id sebagai secret: serangan klasik pada verifier yang menerima semua algoritma
+        let mut header = Header::new(Algorithm::HS256);
+        header.kid = Some("test-key".to_string());
+        let id_token = encode(&header, &claims(), &EncodingKey::from_secret(CLIENT_ID.as_bytes())).unwrap();
+        assert_invalid(validate(&id_token).await, "HMAC");
+    }
+
+    #[actix_web::test]
+    async fn rejects_nonce_mismatch() {
+        let mut claims = claims();
+        claims["nonce"] = json!("nonce-login-lain");
+        assert_invalid(validate(&sign(&claims, SIGNING_KEY)).await, "nonce tidak cocok");
+
+        claims.as_object_mut().unwrap().remove("nonce");
+        assert_invalid(validate(&sign(&claims, SIGNING_KEY)).await, "nonce tidak cocok");
+    }
+
+    #[test]
+    fn verified_email_requires_email_verified() {
+        let email = |verified: serde_json::Value| {
+            let claims: IdTokenClaims =
+                serde_json::from_value(json!({ "sub": "s", "email": "a@example.test", "email_verified": verified })).unwrap();
+            claims.verified_email().map(str::to_string)
+        };
+        assert_eq!(email(json!(true)).as_deref(), Some("a@example.test"));
+        assert_eq!(email(json!("true")).as_deref(), Some("a@example.test"));
+        assert_eq!(email(json!(false)), None);
+        assert_eq!(email(json!("false")), None);
+        assert_eq!(email(json!(null)), None);
+    }
+
+    /// Butuh database yang sudah dimigrasi:
+    /// `DATABASE_URL=postgres://... cargo test -- --ignored`
+    async fn test_pool() -> PgPool {
+        let url = std::env::var("DATABASE_URL").expect("DATABASE_URL belum di-set");
+        let pool = PgPool::connect(&url).await.unwrap();
+        crate::migrations::run(&pool).await.unwrap();
+        pool
+    }
+
+    async fn create_user(pool: &PgPool, email: &str) -> Uuid {
+        let role_id = find_role_id(pool, "borrower").await.unwrap().unwrap();
+        sqlx::query_scalar("INSERT INTO users (name, email, role_id) VALUES ($1, $2, $3) RETURNING id")
+            .bind(format!("oidc-test-{}", Uuid::new_v4().simple()))
+            .bind(email)
+            .bind(role_id)
+            .fetch_one(pool)
+            .await
+            .unwrap()
+    }
+
+    async fn identity_user(pool: &PgPool, subject: &str) -> Option<Uuid> {
+        sqlx::query_scalar("SELECT user_id FROM user_identities WHERE issuer = $1 AND subject = $2")
+            .bind(ISSUER)
+            .bind(subject)
+            .fetch_optional(pool)
+            .await
+            .unwrap()
+    }
+
+    async fn cleanup(pool: &PgPool, user_id: Uuid) {
+        sqlx::query("DELETE FROM user_identities WHERE user_id = $1").bind(user_id).execute(pool).await.unwrap();
+        sqlx::query("DELETE FROM users WHERE id = $1").bind(user_id).execute(pool).await.unwrap();
+    }
+
+    fn id_claims(subject: &str, email: &str, verified: bool) -> IdTokenClaims {
+        serde_json::from_value(json!({ "sub": subject, "email": email, "email_verified": verified })).unwrap()
+    }
+
+    #[actix_web::test]
+    #[ignore = "butuh DATABASE_URL"]
+    async fn unverified_email_does_not_link_existing_user() {
+        let pool = test_pool().await;
+        let email = format!("{}@example.test", Uuid::new_v4().simple());
+        let user_id = create_user(&pool, &email).await;
+        let subject = Uuid::new_v4().to_string();
+        // Email ditulis dengan huruf besar: pencocokan email tidak peka huruf besar/kecil
+        let claims = id_claims(&subject, &email.to_uppercase(), false);
+
+        let provisioning = client(true).resolve_user(&pool, &claims).await;
+        let without_provisioning = client(false).resolve_user(&pool, &claims).await;
+        let linked = identity_user(&pool, &subject).await;
+        cleanup(&pool, user_id).await;
+
+        assert!(matches!(provisioning, Err(ApiError::Detailed { code: "oidc_email_unverified", .. })));
+        assert!(matches!(without_provisioning, Err(ApiError::Detailed { code: "oidc_user_not_found", .. })));
+        assert_eq!(linked, None);
+    }
+
+    #[actix_web::test]
+    #[ignore = "butuh DATABASE_URL"]
+    async fn verified_email_links_existing_user_once() {
+        let pool = test_pool().await;
+        let email = format!("{}@example.test", Uuid::new_v4().simple());
+        let user_id = create_user(&pool, &email).await;
+        let subject = Uuid::new_v4().to_string();
+        let client = client(false);
+
+        let first = client.resolve_user(&pool, &id_claims(&subject, &email, true)).await;
+        // Login berikutnya memakai identity yang sudah terhubung, walau email tidak lagi terverifikasi
+        let second = client.resolve_user(&pool, &id_claims(&subject, "baru@example.test", false)).await;
+        let linked = identity_user(&pool, &subject).await;
+        cleanup(&pool, user_id).await;
+
+        let first = first.unwrap();
+        assert_eq!((first.user_id, first.linked, first.provisioned), (user_id, true, false));
+        let second = second.unwrap();
+        assert_eq!((second.user_id, second.linked, second.provisioned), (user_id, false, false));
+        assert_eq!(linked, Some(user_id));
+    }
+
+    #[actix_web::test]
+    #[ignore = "butuh DATABASE_URL"]
+    async fn shared_email_does_not_link_any_user() {
+        let pool = test_pool().await;
+        let email = format!("{}@example.test", Uuid::new_v4().simple());
+        let first_user = create_user(&pool, &email).await;
+        let second_user = create_user(&pool, &email.to_uppercase()).await;
+        let subject = Uuid::new_v4().to_string();
+
+        let result = client(true).resolve_user(&pool, &id_claims(&subject, &email, true)).await;
+        let linked = identity_user(&pool, &subject).await;
+        let users: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM users WHERE LOWER(email) = LOWER($1)")
+            .bind(&email)
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        cleanup(&pool, first_user).await;
+        cleanup(&pool, second_user).await;
+
+        assert!(matches!(result, Err(ApiError::Detailed { code: "oidc_email_ambiguous", .. })));
+        assert_eq!(linked, None);
+        // Tidak ada user baru yang dibuat walau auto-provisioning aktif
+        assert_eq!(users, 2);
+    }
+}
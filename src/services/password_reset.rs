@@ -0,0 +1,103 @@
+//! Reset password dengan token sekali pakai.
+//!
+//! User meminta reset lewat nama atau email dan menerima link
+//! `{FRONTEND_URL}/reset-password?token=...` lewat email. User tanpa email (atau jika
+//! SMTP belum dikonfigurasi) meminta admin, yang bisa membuat link yang sama lewat
+//! API atau `inman-admin reset-link`. Di database hanya disimpan hash token-nya.
+
+use chrono::{DateTime, Duration, Utc};
+use serde::Serialize;
+use sqlx::{Acquire, PgExecutor, PgPool, Postgres};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use crate::config::Config;
+use crate::error::ApiError;
+use crate::services::tokens::{generate_token, hash_token};
+
+/// Jeda minimum antar permintaan reset untuk user yang sama, supaya endpoint
+/// permintaan reset tidak bisa dipakai membanjiri inbox user
+const REQUEST_COOLDOWN_SECS: i64 = 60;
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct PasswordReset {
+    /// Token reset; hanya ditampilkan sekali
+    pub token: String,
+    /// Link halaman frontend untuk membuat password baru
+    pub url: String,
+    pub expires_at: DateTime<Utc>,
+}
+
+/// Buat link reset baru untuk user. Link lama yang belum dipakai tidak berlaku lagi.
+pub async fn create_password_reset<'c>(
+    db: impl Acquire<'c, Database = Postgres>,
+    config: &Config,
+    user_id: Uuid,
+    created_by: Option<Uuid>,
+) -> Result<PasswordReset, sqlx::Error> {
+    let mut conn = db.acquire().await?;
+    sqlx::query("DELETE FROM password_resets WHERE user_id = $1 AND used_at IS NULL")
+        .bind(user_id)
+        .execute(&mut *conn)
+        .await?;
+
+    let token = generate_token();
+    let expires_at = Utc::now() + Duration::minutes(config.password_reset.ttl_minutes);
+    sqlx::query(
+        "INSERT INTO password_resets (user_id, token_hash, created_by, expires_at)
+         VALUES ($1, $2, $3, $4)"
+    )
+    .bind(user_id)
+    .bind(hash_token(&token))
+    .bind(created_by)
+    .bind(expires_at)
+    .execute(&mut *conn)
+    .await?;
+
+    Ok(PasswordReset {
+        url: format!("{}/reset-password?token={}", config.cors.frontend_url.trim_end_matches('/'), token),
+        token,
+        expires_at,
+    })
+}
+
+/// true jika user baru saja meminta reset dan permintaan berikutnya harus diabaikan
+pub async fn reset_recently_requested(pool: &PgPool, user_id: Uuid) -> Result<bool, sqlx::Error> {
+    sqlx::query_scalar(
+        "SELECT EXISTS (
+            SELECT 1 FROM password_resets
+            WHERE user_id = $1 AND used_at IS NULL AND created_at > NOW() - make_interval(secs => $2)
+         )"
+    )
+    .bind(user_id)
+    .bind(REQUEST_COOLDOWN_SECS as f64)
+    .fetch_one(pool)
+    .await
+}
+
+fn reset_token_invalid() -> ApiError {
+    ApiError::Detailed {
+        status: actix_web::http::StatusCode::BAD_REQUEST,
+        code: "reset_token_invalid",
+        message: "Link reset password tidak valid, sudah dipakai, atau kedaluwarsa".to_string(),
+        details: serde_json::Value::Null,
+    }
+}
+
+/// Tandai token reset sudah dipakai dan kembalikan user pemiliknya. Token yang tidak
+/// dikenal, sudah dipakai, kedaluwarsa, atau milik user nonaktif ditolak. Jalankan di
+/// dalam transaksi bersama penggantian password: baris token terkunci sampai commit,
+/// jadi request kedua dengan token yang sama menunggu lalu ditolak, dan jika password
+/// ditolak transaksi di-rollback sehingga token masih bisa dipakai.
+pub async fn consume_password_reset(db: impl PgExecutor<'_>, token: &str) -> Result<Uuid, ApiError> {
+    sqlx::query_scalar(
+        "UPDATE password_resets SET used_at = NOW()
+         WHERE token_hash = $1 AND used_at IS NULL AND expires_at > NOW()
+           AND user_id IN (SELECT id FROM users WHERE is_active)
+         RETURNING user_id"
+    )
+    .bind(hash_token(token))
+    .fetch_optional(db)
+    .await?
+    .ok_or_else(reset_token_invalid)
+}
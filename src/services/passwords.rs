@@ -0,0 +1,220 @@
+//! Hash dan kebijakan password.
+//!
+//! Password di-hash dengan Argon2id memakai parameter dari `[password]`. Jika
+//! parameternya diubah, hash lama tetap bisa diverifikasi dan diganti dengan hash
+//! baru saat user berhasil login (`needs_rehash`). Password baru diperiksa terhadap
+//! panjang minimum, jumlah jenis karakter, dan daftar password umum
+//! (`assets/common-passwords.txt`, ditambah `blocklist_file` jika di-set).
+
+use argon2::password_hash::{rand_core::OsRng, PasswordHash, PasswordHasher, PasswordVerifier, SaltString};
+use argon2::{Algorithm, Argon2, Params, Version};
+use std::collections::HashSet;
+use std::sync::OnceLock;
+
+use crate::config::{PasswordConfig, MAX_PASSWORD_LENGTH};
+use crate::error::ApiError;
+
+/// Daftar password umum bawaan, huruf kecil
+const COMMON_PASSWORDS: &str = include_str!("../../assets/common-passwords.txt");
+
+fn argon2(config: &PasswordConfig) -> Result<Argon2<'static>, argon2::Error> {
+    let params = Params::new(config.argon2_memory_kib, config.argon2_iterations, config.argon2_parallelism, None)?;
+    Ok(Argon2::new(Algorithm::Argon2id, Version::V0x13, params))
+}
+
+/// Hash password dengan Argon2id sesuai konfigurasi
+pub fn hash_password(config: &PasswordConfig, password: &str) -> Result<String, argon2::password_hash::Error> {
+    let salt = SaltString::generate(&mut OsRng);
+    Ok(argon2(config)?.hash_password(password.as_bytes(), &salt)?.to_string())
+}
+
+/// Cocokkan password dengan hash. Parameter Argon2 dibaca dari hash-nya, jadi hash
+/// lama tetap valid setelah konfigurasi diubah.
+pub fn verify_password(password: &str, hash: &str) -> bool {
+    match PasswordHash::new(hash) {
+        Ok(parsed_hash) => Argon2::default().verify_password(password.as_bytes(), &parsed_hash).is_ok(),
+        Err(_) => false,
+    }
+}
+
+/// true jika hash dibuat dengan algoritma atau parameter yang berbeda dari konfigurasi sekarang
+pub fn needs_rehash(config: &PasswordConfig, hash: &str) -> bool {
+    let Ok(parsed_hash) = PasswordHash::new(hash) else {
+        return true;
+    };
+    let Ok(params) = Params::try_from(&parsed_hash) else {
+        return true;
+    };
+    parsed_hash.algorithm != Algorithm::Argon2id.ident()
+        || parsed_hash.version != Some(Version::V0x13.into())
+        || params.m_cost() != config.argon2_memory_kib
+        || params.t_cost() != config.argon2_iterations
+        || params.p_cost() != config.argon2_parallelism
+}
+
+fn common_passwords() -> &'static HashSet<&'static str> {
+    static LIST: OnceLock<HashSet<&'static str>> = OnceLock::new();
+    LIST.get_or_init(|| COMMON_PASSWORDS.lines().map(str::trim).filter(|line| !line.is_empty()).collect())
+}
+
+fn in_blocklist_file(config: &PasswordConfig, password: &str) -> bool {
+    let Some(path) = &config.blocklist_file else {
+        return false;
+    };
+    match std::fs::read_to_string(path) {
+        Ok(content) => content.lines().any(|line| line.trim().eq_ignore_ascii_case(password)),
+        Err(e) => {
+            eprintln!("[WARN] Gagal membaca PASSWORD_BLOCKLIST_FILE {}: {}", path.display(), e);
+            false
+        },
+    }
+}
+
+/// Periksa password baru terhadap kebijakan password. `username` dipakai untuk
+/// menolak password yang memuat nama user. Semua pelanggaran dikembalikan sekaligus
+/// di `details.problems` dengan code `weak_password`.
+pub fn check_password_policy(config: &PasswordConfig, password: &str, username: Option<&str>) -> Result<(), ApiError> {
+    let mut problems = Vec::new();
+    let length = password.chars().count();
+    if length < config.min_length {
+        problems.push(format!("Password minimal {} karakter", config.min_length));
+    }
+    if length > MAX_PASSWORD_LENGTH {
+        problems.push(format!("Password maksimal {} karakter", MAX_PASSWORD_LENGTH));
+    }
+
+    let classes = [
+        password.chars().any(|c| c.is_lowercase()),
+        password.chars().any(|c| c.is_uppercase()),
+        password.chars().any(|c| c.is_numeric()),
+        password.chars().any(|c| !c.is_alphanumeric()),
+    ];
+    if classes.iter().filter(|present| **present).count() < config.min_character_classes {
+        problems.push(format!(
+            "Password harus memuat minimal {} dari: huruf kecil, huruf besar, angka, simbol",
+            config.min_character_classes
+        ));
+    }
+
+    let lowercase = password.to_lowercase();
+    if common_passwords().contains(lowercase.as_str()) || in_blocklist_file(config, password) {
+        problems.push("Password terlalu umum dan mudah ditebak".to_string());
+    }
+    if let Some(username) = username.map(str::to_lowercase) {
+        if username.chars().count() >= 3 && lowercase.contains(&username) {
+            problems.push("Password tidak boleh memuat nama user".to_string());
+        }
+    }
+
+    if problems.is_empty() {
+        return Ok(());
+    }
+    Err(ApiError::Detailed {
+        status: actix_web::http::StatusCode::UNPROCESSABLE_ENTITY,
+        code: "weak_password",
+        message: problems.join("; "),
+        details: serde_json::json!({ "problems": problems }),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Parameter Argon2 kecil supaya test cepat
+    fn config() -> PasswordConfig {
+        PasswordConfig {
+            argon2_memory_kib: 64,
+            argon2_iterations: 1,
+            ..PasswordConfig::default()
+        }
+    }
+
+    fn problems(result: Result<(), ApiError>) -> Vec<String> {
+        match result {
+            Ok(()) => Vec::new(),
+            Err(ApiError::Detailed { code: "weak_password", details, .. }) => {
+                serde_json::from_value(details["problems"].clone()).unwrap()
+            },
+            Err(other) => panic!("unexpected error: {}", other),
+        }
+    }
+
+    #[test]
+    fn policy_accepts_strong_password() {
+        assert!(check_password_policy(&config(), "Lampu-Meja-42", Some("budi")).is_ok());
+    }
+
+    #[test]
+    fn policy_reports_every_problem_at_once() {
+        let found = problems(check_password_policy(&config(), "abc", None));
+        assert_eq!(found.len(), 2, "{:?}", found);
+        assert!(found[0].contains("minimal 8"));
+        assert!(found[1].contains("minimal 2"));
+    }
+
+    #[test]
+    fn policy_rejects_too_long_password() {
+        let password = format!("Aa1{}", "x".repeat(MAX_PASSWORD_LENGTH));
+        assert!(problems(check_password_policy(&config(), &password, None))[0].contains("maksimal"));
+    }
+
+    #[test]
+    fn policy_counts_character_classes() {
+        let config = PasswordConfig { min_character_classes: 3, ..config() };
+        assert_eq!(problems(check_password_policy(&config, "lowercase99!", None)), Vec::<String>::new());
+        assert_eq!(problems(check_password_policy(&config, "lowercase99", None)).len(), 1);
+        assert_eq!(problems(check_password_policy(&config, "onlylowercase", None)).len(), 1);
+        assert!(check_password_policy(&config, "Mixed-case-words", None).is_ok());
+    }
+
+    #[test]
+    fn policy_rejects_common_passwords_case_insensitively() {
+        let found = problems(check_password_policy(&config(), "Password123", None));
+        assert_eq!(found, vec!["Password terlalu umum dan mudah ditebak".to_string()]);
+    }
+
+    #[test]
+    fn policy_rejects_password_containing_username() {
+        let found = problems(check_password_policy(&config(), "Xx-Budi-2024", Some("BUDI")));
+        assert_eq!(found, vec!["Password tidak boleh memuat nama user".to_string()]);
+        // Nama yang terlalu pendek tidak dicek
+        assert!(check_password_policy(&config(), "Xx-ab-2024!", Some("ab")).is_ok());
+    }
+
+    #[test]
+    fn policy_uses_blocklist_file() {
+        let path = std::env::temp_dir().join(format!("inman-blocklist-{}.txt", std::process::id()));
+        std::fs::write(&path, "Gedung-Kantor-1\n").unwrap();
+        let config = PasswordConfig { blocklist_file: Some(path.clone()), ..config() };
+        let found = problems(check_password_policy(&config, "gedung-kantor-1", None));
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(found, vec!["Password terlalu umum dan mudah ditebak".to_string()]);
+    }
+
+    #[test]
+    fn hash_roundtrip_and_rehash_detection() {
+        let config = config();
+        let hash = hash_password(&config, "Lampu-Meja-42").unwrap();
+        assert!(verify_password("Lampu-Meja-42", &hash));
+        assert!(!verify_password("lampu-meja-42", &hash));
+        assert!(!needs_rehash(&config, &hash));
+
+        let stronger = PasswordConfig { argon2_iterations: 2, ..config.clone() };
+        assert!(needs_rehash(&stronger, &hash));
+        let more_memory = PasswordConfig { argon2_memory_kib: 128, ..config.clone() };
+        assert!(needs_rehash(&more_memory, &hash));
+    }
+
+    #[test]
+    fn needs_rehash_for_other_algorithms_and_garbage() {
+        let config = config();
+        let argon2i = Argon2::new(Algorithm::Argon2i, Version::V0x13, Params::new(64, 1, 1, None).unwrap())
+            .hash_password(b"secret", &SaltString::generate(&mut OsRng))
+            .unwrap()
+            .to_string();
+        assert!(needs_rehash(&config, &argon2i));
+        assert!(needs_rehash(&config, "not-a-hash"));
+        assert!(!verify_password("secret", "not-a-hash"));
+    }
+}
@@ -0,0 +1,211 @@
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use sqlx::{PgPool, Postgres, Transaction};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use crate::config::PenaltyConfig;
+
+/// Denda keterlambatan yang tercatat untuk satu peminjaman
+#[derive(Debug, Serialize, sqlx::FromRow, ToSchema)]
+pub struct BorrowerPenalty {
+    pub id: Uuid,
+    pub borrowing_id: Uuid,
+    pub borrower_id: Uuid,
+    pub rule_id: Option<Uuid>,
+    pub days_late: i32,
+    pub amount: i64,
+    pub status: String,
+    pub resolved_by: Option<Uuid>,
+    pub resolved_at: Option<DateTime<Utc>>,
+    pub resolution_note: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Ringkasan status peminjam, dipakai untuk memblokir peminjaman baru
+#[derive(Debug, Serialize, ToSchema)]
+pub struct BorrowerStanding {
+    pub unsettled_count: i64,
+    pub unsettled_amount: i64,
+    pub late_returns: i64,
+    pub max_late_returns: i64,
+    pub blocked: bool,
+    pub reasons: Vec<String>,
+}
+
+#[derive(sqlx::FromRow)]
+struct MatchedRule {
+    id: Uuid,
+    amount_per_day: i64,
+    grace_days: i32,
+    max_amount: Option<i64>,
+}
+
+/// Jumlah hari terlambat, dibulatkan ke atas (terlambat 1 jam = 1 hari)
+pub fn days_late(expected: DateTime<Utc>, actual: DateTime<Utc>) -> i32 {
+    let seconds = (actual - expected).num_seconds();
+    if seconds <= 0 {
+        return 0;
+    }
+    ((seconds + 86_399) / 86_400) as i32
+}
+
+/// Besar denda setelah masa tenggang, dibatasi max_amount jika ada
+pub fn penalty_amount(days_late: i32, amount_per_day: i64, grace_days: i32, max_amount: Option<i64>) -> i64 {
+    let charged_days = (days_late - grace_days).max(0) as i64;
+    let amount = charged_days.saturating_mul(amount_per_day);
+    match max_amount {
+        Some(max) => amount.min(max),
+        None => amount,
+    }
+}
+
+/// Mencatat denda untuk peminjaman yang dikembalikan terlambat.
+/// Aturan kategori item didahulukan, lalu aturan default (category_id NULL).
+/// Tidak mencatat apa pun jika tidak terlambat, tidak ada aturan aktif, atau denda 0.
+pub async fn charge_late_return(
+    tx: &mut Transaction<'_, Postgres>,
+    borrowing_id: Uuid,
+    borrower_id: Uuid,
+    item_id: Uuid,
+    expected_return_date: DateTime<Utc>,
+    actual_return_date: DateTime<Utc>,
+) -> Result<Option<BorrowerPenalty>, sqlx::Error> {
+    let days = days_late(expected_return_date, actual_return_date);
+    if days == 0 {
+        return Ok(None);
+    }
+
+    let rule = sqlx::query_as::<_, MatchedRule>(
+        "SELECT r.id, r.amount_per_day, r.grace_days, r.max_amount
+         FROM penalty_rules r
+         WHERE r.is_active
+           AND (r.category_id = (SELECT category_id FROM items WHERE id = $1) OR r.category_id IS NULL)
+         ORDER BY r.category_id NULLS LAST
+         LIMIT 1"
+    )
+    .bind(item_id)
+    .fetch_optional(&mut **tx)
+    .await?;
+
+    let rule = match rule {
+        Some(rule) => rule,
+        None => return Ok(None),
+    };
+
+    let amount = penalty_amount(days, rule.amount_per_day, rule.grace_days, rule.max_amount);
+    if amount == 0 {
+        return Ok(None);
+    }
+
+    let penalty = sqlx::query_as::<_, BorrowerPenalty>(
+        "INSERT INTO borrower_penalties (borrowing_id, borrower_id, rule_id, days_late, amount)
+         VALUES ($1, $2, $3, $4, $5)
+         ON CONFLICT (borrowing_id) DO NOTHING
+         RETURNING *"
+    )
+    .bind(borrowing_id)
+    .bind(borrower_id)
+    .bind(rule.id)
+    .bind(days)
+    .bind(amount)
+    .fetch_optional(&mut **tx)
+    .await?;
+
+    Ok(penalty)
+}
+
+/// Menghitung status peminjam: denda yang belum lunas dan jumlah pengembalian terlambat.
+/// Keterlambatan yang dendanya dihapus (waived) tidak ikut dihitung.
+pub async fn borrower_standing(
+    pool: &PgPool,
+    config: &PenaltyConfig,
+    borrower_id: Uuid,
+) -> Result<BorrowerStanding, sqlx::Error> {
+    let (unsettled_count, unsettled_amount): (i64, i64) = sqlx::query_as(
+        "SELECT COUNT(*), COALESCE(SUM(amount), 0)::BIGINT
+         FROM borrower_penalties
+         WHERE borrower_id = $1 AND status = 'pending'"
+    )
+    .bind(borrower_id)
+    .fetch_one(pool)
+    .await?;
+
+    let late_returns: i64 = sqlx::query_scalar(
+        "SELECT COUNT(*)
+         FROM item_borrowings b
+         LEFT JOIN borrower_penalties p ON p.borrowing_id = b.id
+         WHERE b.borrower_id = $1
+           AND b.actual_return_date > b.expected_return_date
+           AND b.actual_return_date > NOW() - ($2 * INTERVAL '1 day')
+           AND (p.status IS NULL OR p.status <> 'waived')"
+    )
+    .bind(borrower_id)
+    .bind(config.late_return_window_days as f64)
+    .fetch_one(pool)
+    .await?;
+
+    let max_late_returns = config.max_late_returns;
+    let mut reasons = Vec::new();
+    if unsettled_count > 0 {
+        reasons.push(format!("{} unsettled penalties totaling {}", unsettled_count, unsettled_amount));
+    }
+    if max_late_returns > 0 && late_returns >= max_late_returns {
+        reasons.push(format!("{} late returns (limit {})", late_returns, max_late_returns));
+    }
+
+    Ok(BorrowerStanding {
+        unsettled_count,
+        unsettled_amount,
+        late_returns,
+        max_late_returns,
+        blocked: !reasons.is_empty(),
+        reasons,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{Duration, TimeZone};
+
+    fn due() -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2024, 5, 1, 12, 0, 0).unwrap()
+    }
+
+    #[test]
+    fn days_late_is_zero_when_on_time_or_early() {
+        assert_eq!(days_late(due(), due()), 0);
+        assert_eq!(days_late(due(), due() - Duration::days(2)), 0);
+    }
+
+    #[test]
+    fn days_late_rounds_partial_days_up() {
+        assert_eq!(days_late(due(), due() + Duration::seconds(1)), 1);
+        assert_eq!(days_late(due(), due() + Duration::hours(1)), 1);
+        assert_eq!(days_late(due(), due() + Duration::days(1)), 1);
+        assert_eq!(days_late(due(), due() + Duration::days(1) + Duration::minutes(1)), 2);
+        assert_eq!(days_late(due(), due() + Duration::days(10)), 10);
+    }
+
+    #[test]
+    fn penalty_amount_skips_grace_days() {
+        assert_eq!(penalty_amount(2, 5_000, 2, None), 0);
+        assert_eq!(penalty_amount(1, 5_000, 2, None), 0);
+        assert_eq!(penalty_amount(3, 5_000, 2, None), 5_000);
+        assert_eq!(penalty_amount(3, 5_000, 0, None), 15_000);
+    }
+
+    #[test]
+    fn penalty_amount_is_capped_by_max_amount() {
+        assert_eq!(penalty_amount(10, 5_000, 0, Some(20_000)), 20_000);
+        assert_eq!(penalty_amount(3, 5_000, 0, Some(20_000)), 15_000);
+        assert_eq!(penalty_amount(0, 5_000, 0, Some(20_000)), 0);
+    }
+
+    #[test]
+    fn penalty_amount_saturates_instead_of_overflowing() {
+        assert_eq!(penalty_amount(i32::MAX, i64::MAX, 0, None), i64::MAX);
+        assert_eq!(penalty_amount(i32::MAX, i64::MAX, 0, Some(1_000_000)), 1_000_000);
+    }
+}
@@ -0,0 +1,399 @@
+use chrono::{DateTime, Utc};
+use image::Luma;
+use printpdf::{
+    ColorBits, ColorSpace, Image, ImageTransform, ImageXObject, IndirectFontRef, Line, Mm,
+    PdfDocument, PdfDocumentReference, PdfLayerReference, Point, Px,
+};
+use qrcode::QrCode;
+
+/// Font yang dibundel bersama aplikasi agar karakter non-ASCII tetap tampil
+const FONT_BYTES: &[u8] = include_bytes!("../../assets/fonts/DejaVuSans.ttf");
+
+const PAGE_WIDTH: f32 = 210.0;
+const PAGE_HEIGHT: f32 = 297.0;
+const MARGIN: f32 = 20.0;
+const SIGNATURE_HEIGHT: f32 = 28.0;
+/// Tinggi blok tanda tangan, dari label di atas sampai nama di bawah garis
+const SIGNATURE_BLOCK_HEIGHT: f32 = 45.0;
+/// Posisi baris pertama di halaman lanjutan
+const CONTINUATION_TOP: f32 = PAGE_HEIGHT - MARGIN - 16.0;
+
+/// Jenis tanda terima yang dibuat
+#[derive(Clone, Copy, PartialEq)]
+pub enum ReceiptKind {
+    Handover,
+    Return,
+}
+
+/// Data peminjaman yang dicetak pada tanda terima
+pub struct ReceiptData {
+    pub borrowing_id: uuid::Uuid,
+    pub item_name: String,
+    pub quantity: i32,
+    pub borrower_name: String,
+    pub approver_name: Option<String>,
+    pub borrowed_at: DateTime<Utc>,
+    pub expected_return_date: DateTime<Utc>,
+    pub actual_return_date: Option<DateTime<Utc>>,
+    pub checkout_condition: Option<String>,
+    pub return_condition: Option<String>,
+    pub notes: Option<String>,
+    pub status: String,
+    pub borrower_signature: Option<SignatureImage>,
+    pub staff_signature: Option<SignatureImage>,
+}
+
+/// Gambar tanda tangan (PNG) yang ditempel di atas garis tanda tangan
+pub struct SignatureImage {
+    pub signer_name: Option<String>,
+    pub png: Vec<u8>,
+}
+
+/// Membuat PDF tanda terima serah terima / pengembalian barang
+pub fn render_receipt(
+    kind: ReceiptKind,
+    data: &ReceiptData,
+    qr_content: &str,
+) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    let title = match kind {
+        ReceiptKind::Handover => "Tanda Terima Serah Terima Barang",
+        ReceiptKind::Return => "Tanda Terima Pengembalian Barang",
+    };
+
+    let (doc, page, layer) = PdfDocument::new(title, Mm(PAGE_WIDTH), Mm(PAGE_HEIGHT), "Receipt");
+    let font = doc.add_external_font(FONT_BYTES)?;
+    let mut layer = doc.get_page(page).get_layer(layer);
+
+    // Header
+    layer.use_text(title, 16.0, Mm(MARGIN), Mm(PAGE_HEIGHT - MARGIN - 6.0), &font);
+    layer.use_text(
+        format!("No. {}", data.borrowing_id),
+        9.0,
+        Mm(MARGIN),
+        Mm(PAGE_HEIGHT - MARGIN - 13.0),
+        &font,
+    );
+    layer.use_text(
+        format!("Dicetak: {}", format_datetime(Utc::now())),
+        9.0,
+        Mm(MARGIN),
+        Mm(PAGE_HEIGHT - MARGIN - 18.0),
+        &font,
+    );
+
+    // QR peminjaman di pojok kanan atas
+    add_qr_code(&layer, qr_content, PAGE_WIDTH - MARGIN - 35.0, PAGE_HEIGHT - MARGIN - 35.0, 35.0)?;
+
+    draw_line(&layer, MARGIN, PAGE_HEIGHT - MARGIN - 40.0, PAGE_WIDTH - MARGIN);
+
+    // Detail peminjaman
+    let mut rows: Vec<(&str, String)> = vec![
+        ("Barang", data.item_name.clone()),
+        ("Jumlah", format!("{} unit", data.quantity)),
+        ("Peminjam", data.borrower_name.clone()),
+        ("Disetujui oleh", data.approver_name.clone().unwrap_or_else(|| "-".to_string())),
+        ("Tanggal pinjam", format_datetime(data.borrowed_at)),
+        ("Batas pengembalian", format_datetime(data.expected_return_date)),
+        ("Kondisi saat serah terima", data.checkout_condition.clone().unwrap_or_else(|| "-".to_string())),
+    ];
+    if kind == ReceiptKind::Return {
+        rows.push((
+            "Tanggal kembali",
+            data.actual_return_date.map(format_datetime).unwrap_or_else(|| "-".to_string()),
+        ));
+        rows.push(("Kondisi saat kembali", data.return_condition.clone().unwrap_or_else(|| "-".to_string())));
+        let late = data
+            .actual_return_date
+            .map(|returned| returned > data.expected_return_date)
+            .unwrap_or(false);
+        rows.push(("Keterlambatan", if late { "Terlambat".to_string() } else { "Tepat waktu".to_string() }));
+    }
+    rows.push(("Status", data.status.clone()));
+    rows.push(("Catatan", data.notes.clone().unwrap_or_else(|| "-".to_string())));
+
+    // Isian panjang (mis. catatan) dilanjutkan ke halaman berikutnya, supaya tidak
+    // terpotong di tepi bawah halaman
+    let mut y = PAGE_HEIGHT - MARGIN - 50.0;
+    for (label, value) in rows {
+        if y < MARGIN {
+            (layer, y) = continuation_page(&doc, &font, title, data);
+        }
+        layer.use_text(label, 10.0, Mm(MARGIN), Mm(y), &font);
+        for (i, line) in wrap_text(&value, 60).iter().enumerate() {
+            if i > 0 {
+                y -= 5.0;
+                if y < MARGIN {
+                    (layer, y) = continuation_page(&doc, &font, title, data);
+                    layer.use_text(format!("{} (lanjutan)", label), 10.0, Mm(MARGIN), Mm(y), &font);
+                }
+            }
+            layer.use_text(format!(": {}", line), 10.0, Mm(MARGIN + 55.0), Mm(y), &font);
+        }
+        y -= 8.0;
+    }
+
+    // Kolom tanda tangan peminjam dan petugas, tidak pernah terpisah dari halamannya
+    if y - SIGNATURE_BLOCK_HEIGHT < MARGIN {
+        (layer, y) = continuation_page(&doc, &font, title, data);
+    }
+    let signature_y = y - 40.0;
+    let column_width = (PAGE_WIDTH - 2.0 * MARGIN) / 2.0;
+    draw_signature_box(
+        &layer,
+        &font,
+        "Peminjam",
+        &data.borrower_name,
+        data.borrower_signature.as_ref(),
+        MARGIN,
+        signature_y,
+        column_width - 10.0,
+    );
+    // Nama petugas diambil dari penanda tangan jika ada, karena petugas pengembalian bisa berbeda dari penyetuju
+    let staff_name = data
+        .staff_signature
+        .as_ref()
+        .and_then(|signature| signature.signer_name.as_deref())
+        .or(data.approver_name.as_deref())
+        .unwrap_or("");
+    draw_signature_box(
+        &layer,
+        &font,
+        "Petugas",
+        staff_name,
+        data.staff_signature.as_ref(),
+        MARGIN + column_width,
+        signature_y,
+        column_width - 10.0,
+    );
+
+    Ok(doc.save_to_bytes()?)
+}
+
+/// Tambah halaman baru dengan judul singkat; mengembalikan layer dan posisi baris pertamanya
+fn continuation_page(
+    doc: &PdfDocumentReference,
+    font: &IndirectFontRef,
+    title: &str,
+    data: &ReceiptData,
+) -> (PdfLayerReference, f32) {
+    let (page, layer) = doc.add_page(Mm(PAGE_WIDTH), Mm(PAGE_HEIGHT), "Receipt");
+    let layer = doc.get_page(page).get_layer(layer);
+    layer.use_text(
+        format!("{} (lanjutan) - No. {}", title, data.borrowing_id),
+        9.0,
+        Mm(MARGIN),
+        Mm(PAGE_HEIGHT - MARGIN - 6.0),
+        font,
+    );
+    (layer, CONTINUATION_TOP)
+}
+
+#[allow(clippy::too_many_arguments)]
+fn draw_signature_box(
+    layer: &PdfLayerReference,
+    font: &IndirectFontRef,
+    label: &str,
+    name: &str,
+    signature: Option<&SignatureImage>,
+    x: f32,
+    y: f32,
+    width: f32,
+) {
+    layer.use_text(label, 10.0, Mm(x), Mm(y + 32.0), font);
+    if let Some(signature) = signature {
+        // Tanda tangan yang rusak tidak boleh menggagalkan pembuatan tanda terima
+        if let Err(e) = add_signature_image(layer, &signature.png, x, y + 1.0, width, SIGNATURE_HEIGHT) {
+            println!("[WARN] Failed to embed signature image: {}", e);
+        }
+    }
+    draw_line(layer, x, y, x + width);
+    layer.use_text(name, 9.0, Mm(x), Mm(y - 5.0), font);
+}
+
+/// Menempelkan PNG tanda tangan, diperkecil agar muat di area max_width x max_height
+fn add_signature_image(
+    layer: &PdfLayerReference,
+    png: &[u8],
+    x: f32,
+    y: f32,
+    max_width: f32,
+    max_height: f32,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let decoded = image::load_from_memory_with_format(png, image::ImageFormat::Png)?.to_rgba8();
+    let (width, height) = decoded.dimensions();
+    if width == 0 || height == 0 {
+        return Err("Empty signature image".into());
+    }
+
+    // PDF tidak mendukung alpha langsung, jadi latar transparan diratakan ke putih
+    let mut rgb = Vec::with_capacity((width * height * 3) as usize);
+    for pixel in decoded.pixels() {
+        let alpha = pixel[3] as u32;
+        for channel in 0..3 {
+            let value = (pixel[channel] as u32 * alpha + 255 * (255 - alpha)) / 255;
+            rgb.push(value as u8);
+        }
+    }
+
+    let image = Image::from(ImageXObject {
+        width: Px(width as usize),
+        height: Px(height as usize),
+        color_space: ColorSpace::Rgb,
+        bits_per_component: ColorBits::Bit8,
+        interpolate: true,
+        image_data: rgb,
+        image_filter: None,
+        smask: None,
+        clipping_bbox: None,
+    });
+
+    let target_width = max_width.min(max_height * width as f32 / height as f32);
+    let dpi = width as f32 / (target_width / 25.4);
+    image.add_to_layer(
+        layer.clone(),
+        ImageTransform {
+            translate_x: Some(Mm(x)),
+            translate_y: Some(Mm(y)),
+            dpi: Some(dpi),
+            ..Default::default()
+        },
+    );
+    Ok(())
+}
+
+fn draw_line(layer: &PdfLayerReference, x1: f32, y: f32, x2: f32) {
+    let line = Line {
+        points: vec![
+            (Point::new(Mm(x1), Mm(y)), false),
+            (Point::new(Mm(x2), Mm(y)), false),
+        ],
+        is_closed: false,
+    };
+    layer.set_outline_thickness(0.5);
+    layer.add_line(line);
+}
+
+fn add_qr_code(
+    layer: &PdfLayerReference,
+    content: &str,
+    x: f32,
+    y: f32,
+    size_mm: f32,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let code = QrCode::new(content)?;
+    let qr_image = code.render::<Luma<u8>>().build();
+    let width = qr_image.width();
+    let height = qr_image.height();
+
+    let image = Image::from(ImageXObject {
+        width: Px(width as usize),
+        height: Px(height as usize),
+        color_space: ColorSpace::Greyscale,
+        bits_per_component: ColorBits::Bit8,
+        interpolate: false,
+        image_data: qr_image.into_raw(),
+        image_filter: None,
+        smask: None,
+        clipping_bbox: None,
+    });
+
+    // DPI diatur supaya lebar gambar sama dengan size_mm
+    let dpi = width as f32 / (size_mm / 25.4);
+    image.add_to_layer(
+        layer.clone(),
+        ImageTransform {
+            translate_x: Some(Mm(x)),
+            translate_y: Some(Mm(y)),
+            dpi: Some(dpi),
+            ..Default::default()
+        },
+    );
+    Ok(())
+}
+
+fn format_datetime(value: DateTime<Utc>) -> String {
+    value.format("%d-%m-%Y %H:%M UTC").to_string()
+}
+
+fn wrap_text(value: &str, max_chars: usize) -> Vec<String> {
+    let mut lines = Vec::new();
+    let mut current = String::new();
+    // Kata yang lebih panjang dari satu baris (mis. URL) dipotong supaya tidak melewati tepi kanan
+    let chunks = value.split_whitespace().flat_map(|word| {
+        let chars: Vec<char> = word.chars().collect();
+        chars.chunks(max_chars).map(|chunk| chunk.iter().collect::<String>()).collect::<Vec<_>>()
+    });
+    for word in chunks {
+        if !current.is_empty() && current.chars().count() + word.chars().count() + 1 > max_chars {
+            lines.push(std::mem::take(&mut current));
+        }
+        if !current.is_empty() {
+            current.push(' ');
+        }
+        current.push_str(&word);
+    }
+    if !current.is_empty() || lines.is_empty() {
+        lines.push(current);
+    }
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn receipt(notes: Option<String>) -> ReceiptData {
+        let borrowed_at = Utc.with_ymd_and_hms(2025, 1, 6, 8, 0, 0).unwrap();
+        ReceiptData {
+            borrowing_id: uuid::Uuid::nil(),
+            item_name: "Proyektor Epson".to_string(),
+            quantity: 1,
+            borrower_name: "budi".to_string(),
+            approver_name: Some("admin".to_string()),
+            borrowed_at,
+            expected_return_date: borrowed_at + chrono::Duration::days(3),
+            actual_return_date: Some(borrowed_at + chrono::Duration::days(4)),
+            checkout_condition: Some("Baik".to_string()),
+            return_condition: Some("Baik".to_string()),
+            notes,
+            status: "returned".to_string(),
+            borrower_signature: None,
+            staff_signature: None,
+        }
+    }
+
+    /// Jumlah halaman: objek `/Type/Page` tanpa `/Type/Pages`
+    fn page_count(pdf: &[u8]) -> usize {
+        let text = String::from_utf8_lossy(pdf);
+        text.matches("/Type/Page").count() - text.matches("/Type/Pages").count()
+    }
+
+    #[test]
+    fn short_receipt_fits_on_one_page() {
+        let pdf = render_receipt(ReceiptKind::Return, &receipt(Some("Lensa agak berdebu".to_string())), "qr").unwrap();
+        assert!(pdf.starts_with(b"%PDF"));
+        assert_eq!(page_count(&pdf), 1);
+    }
+
+    #[test]
+    fn long_notes_continue_on_next_page() {
+        let notes = "Catatan panjang tentang kondisi barang saat dikembalikan. ".repeat(150);
+        let pdf = render_receipt(ReceiptKind::Return, &receipt(Some(notes)), "qr").unwrap();
+        assert!(page_count(&pdf) >= 2, "halaman: {}", page_count(&pdf));
+    }
+
+    #[test]
+    fn wrap_text_splits_words_longer_than_a_line() {
+        let url = "https://example.test/".repeat(5);
+        let lines = wrap_text(&format!("lihat {}", url), 30);
+        assert!(lines.iter().all(|line| line.chars().count() <= 30), "{:?}", lines);
+        assert_eq!(lines.concat().replace(' ', ""), format!("lihat{}", url));
+    }
+
+    #[test]
+    fn wrap_text_keeps_empty_value_as_one_line() {
+        assert_eq!(wrap_text("", 60), vec![String::new()]);
+        assert_eq!(wrap_text("satu dua tiga", 8), vec!["satu dua", "tiga"]);
+    }
+}
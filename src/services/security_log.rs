@@ -0,0 +1,161 @@
+//! Log keamanan di table `security_events`.
+//!
+//! Berbeda dengan `audit_events` yang mencatat perubahan data, log ini mencatat siapa
+//! login dari mana: login berhasil/gagal, logout, refresh token, ganti password, dan
+//! request yang ditolak karena kurang izin. Admin membacanya lewat
+//! `GET /api/security-events` atau `GET /api/users/{id}/security-events`.
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use serde_json::Value;
+use sqlx::PgPool;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use crate::error::ApiError;
+use crate::services::sessions::ClientInfo;
+
+/// Event yang sudah tercatat, untuk API log keamanan
+#[derive(Debug, Serialize, sqlx::FromRow, ToSchema)]
+pub struct SecurityLogEntry {
+    pub id: Uuid,
+    /// Mis. `login.succeeded`, `login.failed`, `logout`, `token.refreshed`,
+    /// `password.changed`, `permission.denied`
+    pub event_type: String,
+    pub user_id: Option<Uuid>,
+    /// Nama user saat ini
+    pub user_name: Option<String>,
+    /// Nama yang diketik saat login, termasuk nama yang tidak terdaftar
+    pub username: Option<String>,
+    pub details: Option<Value>,
+    pub ip_address: Option<String>,
+    pub user_agent: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Filter query log keamanan; field kosong tidak membatasi
+#[derive(Debug, Default)]
+pub struct SecurityEventFilter {
+    pub user_id: Option<Uuid>,
+    /// Jenis event persis, atau awalan jika diakhiri `.` (mis. `login.`)
+    pub event_type: Option<String>,
+    pub ip_address: Option<String>,
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+    pub limit: i64,
+    pub offset: i64,
+}
+
+pub struct SecurityEvent<'a> {
+    pub event_type: &'static str,
+    pub user_id: Option<Uuid>,
+    pub username: Option<&'a str>,
+    pub details: Option<Value>,
+    pub client: Option<&'a ClientInfo>,
+}
+
+impl<'a> SecurityEvent<'a> {
+    pub fn new(event_type: &'static str) -> Self {
+        SecurityEvent {
+            event_type,
+            user_id: None,
+            username: None,
+            details: None,
+            client: None,
+        }
+    }
+
+    pub fn user(mut self, user_id: Uuid) -> Self {
+        self.user_id = Some(user_id);
+        self
+    }
+
+    pub fn username(mut self, username: &'a str) -> Self {
+        self.username = Some(username);
+        self
+    }
+
+    pub fn details(mut self, details: Value) -> Self {
+        self.details = Some(details);
+        self
+    }
+
+    pub fn client(mut self, client: &'a ClientInfo) -> Self {
+        self.client = Some(client);
+        self
+    }
+
+    pub async fn record(self, pool: &PgPool) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            "INSERT INTO security_events (event_type, user_id, username, details, ip_address, user_agent)
+             VALUES ($1, $2, $3, $4, $5, $6)"
+        )
+        .bind(self.event_type)
+        .bind(self.user_id)
+        .bind(self.username)
+        .bind(self.details)
+        .bind(self.client.and_then(|c| c.ip_address.as_deref()))
+        .bind(self.client.and_then(|c| c.user_agent.as_deref()))
+        .execute(pool)
+        .await?;
+        Ok(())
+    }
+}
+
+/// Catat hasil percobaan login: `login.succeeded`, `login.second_factor_required` jika
+/// password benar tapi masih butuh kode 2FA, atau `login.failed` dengan kode error-nya
+/// di `details.reason`. `method` adalah cara login, mis. `password` atau `two_factor`.
+pub async fn record_login_outcome(
+    pool: &PgPool,
+    client: &ClientInfo,
+    user_id: Option<Uuid>,
+    login_name: Option<&str>,
+    method: &'static str,
+    outcome: Result<(), &ApiError>,
+) -> Result<(), sqlx::Error> {
+    let (event_type, details) = match outcome {
+        Ok(()) => ("login.succeeded", serde_json::json!({ "method": method })),
+        Err(e) if matches!(e.code(), "two_factor_required" | "two_factor_setup_required") => {
+            ("login.second_factor_required", serde_json::json!({ "method": method, "reason": e.code() }))
+        },
+        Err(e) => ("login.failed", serde_json::json!({ "method": method, "reason": e.code() })),
+    };
+    let mut event = SecurityEvent::new(event_type).details(details).client(client);
+    event.user_id = user_id;
+    event.username = login_name;
+    event.record(pool).await
+}
+
+/// Event log keamanan terbaru lebih dulu, sesuai filter
+pub async fn list_security_events(pool: &PgPool, filter: &SecurityEventFilter) -> Result<Vec<SecurityLogEntry>, sqlx::Error> {
+    let (event_type, event_type_prefix) = match filter.event_type.as_deref() {
+        Some(prefix) if prefix.ends_with('.') => {
+            (None, Some(format!("{}%", prefix.replace('%', "\\%").replace('_', "\\_"))))
+        },
+        event_type => (event_type, None),
+    };
+    sqlx::query_as::<_, SecurityLogEntry>(
+        "SELECT e.id, e.event_type, e.user_id, u.name AS user_name, e.username, e.details,
+                e.ip_address, e.user_agent, e.created_at
+         FROM security_events e
+         LEFT JOIN users u ON u.id = e.user_id
+         WHERE ($1::uuid IS NULL OR e.user_id = $1)
+           AND ($2::text IS NULL OR e.event_type = $2)
+           AND ($3::text IS NULL OR e.event_type LIKE $3)
+           AND ($4::text IS NULL OR e.ip_address = $4)
+           AND ($5::timestamptz IS NULL OR e.created_at >= $5)
+           AND ($6::timestamptz IS NULL OR e.created_at < $6)
+         ORDER BY e.created_at DESC, e.id
+         LIMIT $7 OFFSET $8"
+    )
+    .bind(filter.user_id)
+    .bind(event_type)
+    .bind(event_type_prefix)
+    .bind(&filter.ip_address)
+    .bind(filter.from)
+    .bind(filter.to)
+    .bind(filter.limit)
+    .bind(filter.offset)
+    .fetch_all(pool)
+    .await
+}
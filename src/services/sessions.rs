@@ -0,0 +1,332 @@
+//! Session login di sisi server.
+//!
+//! Login membuat satu baris `sessions` per perangkat. Client memegang access token
+//! (JWT berumur pendek, ID session ada di claim `jti`) dan refresh token acak yang
+//! di database hanya disimpan hash SHA-256-nya. Setiap refresh merotasi refresh token;
+//! refresh token lama yang dipakai lagi dianggap bocor dan session-nya langsung dicabut.
+
+use actix_web::{web, HttpRequest};
+use chrono::{DateTime, Duration, Utc};
+use jsonwebtoken::{encode, EncodingKey, Header};
+use serde::Serialize;
+use sqlx::{PgExecutor, PgPool};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use std::net::IpAddr;
+
+use crate::config::{Config, JwtConfig, TrustedProxy};
+use crate::error::ApiError;
+use crate::middleware::jwt_extractor::Claims;
+use crate::services::tokens::{generate_token, hash_token};
+
+/// Pasangan token untuk satu session
+pub struct SessionTokens {
+    pub session_id: Uuid,
+    pub access_token: String,
+    pub refresh_token: String,
+}
+
+/// Perangkat yang login, ditampilkan di daftar session
+pub struct ClientInfo {
+    pub user_agent: Option<String>,
+    pub ip_address: Option<String>,
+}
+
+impl ClientInfo {
+    pub fn from_request(req: &HttpRequest) -> Self {
+        ClientInfo {
+            user_agent: req
+                .headers()
+                .get("User-Agent")
+                .and_then(|v| v.to_str().ok())
+                .map(str::to_string),
+            ip_address: client_ip(req).map(|ip| ip.to_string()),
+        }
+    }
+}
+
+/// IP client. `X-Forwarded-For` bisa diisi sembarang oleh client, jadi hanya dipakai
+/// jika koneksi datang dari `TRUSTED_PROXIES`.
+fn client_ip(req: &HttpRequest) -> Option<IpAddr> {
+    let peer = req.peer_addr()?.ip();
+    let trusted = req
+        .app_data::<web::Data<Config>>()
+        .map(|config| config.server.trusted_proxies.as_slice())
+        .unwrap_or_default();
+    let forwarded_for: Vec<&str> = req
+        .headers()
+        .get_all("X-Forwarded-For")
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .collect();
+    Some(resolve_client_ip(peer, &forwarded_for, trusted))
+}
+
+/// Telusuri `X-Forwarded-For` dari kanan (entri terakhir ditambahkan proxy terdekat);
+/// alamat pertama yang bukan proxy tepercaya adalah client.
+fn resolve_client_ip(peer: IpAddr, forwarded_for: &[&str], trusted: &[TrustedProxy]) -> IpAddr {
+    let mut client = peer;
+    for entry in forwarded_for.iter().rev() {
+        if !trusted.iter().any(|proxy| proxy.contains(client)) {
+            break;
+        }
+        match entry.trim().parse() {
+            Ok(ip) => client = ip,
+            Err(_) => break,
+        }
+    }
+    client
+}
+
+#[derive(Debug, Serialize, sqlx::FromRow, ToSchema)]
+pub struct Session {
+    pub id: Uuid,
+    pub user_agent: Option<String>,
+    pub ip_address: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub last_used_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+}
+
+/// User pemilik session yang berhasil di-refresh
+pub struct SessionUser {
+    pub user_id: Uuid,
+    pub name: String,
+    pub role_id: Uuid,
+}
+
+/// Error untuk user yang sudah dinonaktifkan admin
+pub fn account_inactive() -> ApiError {
+    ApiError::Detailed {
+        status: actix_web::http::StatusCode::FORBIDDEN,
+        code: "account_inactive",
+        message: "Akun ini sudah dinonaktifkan, hubungi admin".to_string(),
+        details: serde_json::Value::Null,
+    }
+}
+
+/// Tolak user yang sudah dinonaktifkan (atau tidak ada lagi)
+pub async fn ensure_user_active(pool: &PgPool, user_id: Uuid) -> Result<(), ApiError> {
+    let is_active: Option<bool> = sqlx::query_scalar("SELECT is_active FROM users WHERE id = $1")
+        .bind(user_id)
+        .fetch_optional(pool)
+        .await?;
+    if is_active != Some(true) {
+        return Err(account_inactive());
+    }
+    Ok(())
+}
+
+/// Buat session baru setelah login berhasil. User nonaktif ditolak (`account_inactive`).
+pub async fn start_session(
+    pool: &PgPool,
+    jwt: &JwtConfig,
+    user_id: Uuid,
+    role_id: Uuid,
+    client: &ClientInfo,
+) -> Result<SessionTokens, ApiError> {
+    ensure_user_active(pool, user_id).await?;
+
+    // Bersihkan session lama user ini yang sudah tidak berlaku
+    sqlx::query("DELETE FROM sessions WHERE user_id = $1 AND (expires_at < NOW() OR revoked_at IS NOT NULL)")
+        .bind(user_id)
+        .execute(pool)
+        .await?;
+
+    let refresh_token = generate_token();
+    let session_id: Uuid = sqlx::query_scalar(
+        "INSERT INTO sessions (user_id, refresh_token_hash, user_agent, ip_address, expires_at)
+         VALUES ($1, $2, $3, $4, $5)
+         RETURNING id"
+    )
+    .bind(user_id)
+    .bind(hash_token(&refresh_token))
+    .bind(&client.user_agent)
+    .bind(&client.ip_address)
+    .bind(refresh_expiry(jwt))
+    .fetch_one(pool)
+    .await?;
+
+    Ok(SessionTokens {
+        session_id,
+        access_token: access_token(jwt, user_id, role_id, session_id)?,
+        refresh_token,
+    })
+}
+
+/// Tukar refresh token dengan pasangan token baru (refresh token lama tidak berlaku lagi)
+pub async fn refresh_session(
+    pool: &PgPool,
+    jwt: &JwtConfig,
+    refresh_token: &str,
+    client: &ClientInfo,
+) -> Result<(SessionUser, SessionTokens), ApiError> {
+    let old_hash = hash_token(refresh_token);
+    let new_refresh_token = generate_token();
+    let row: Option<(Uuid, Uuid, String, Uuid)> = sqlx::query_as(
+        "UPDATE sessions s
+         SET refresh_token_hash = $2,
+             previous_refresh_token_hash = s.refresh_token_hash,
+             last_used_at = NOW(),
+             expires_at = $3,
+             user_agent = COALESCE($4, s.user_agent),
+             ip_address = COALESCE($5, s.ip_address)
+         FROM users u
+         WHERE u.id = s.user_id
+           AND u.is_active
+           AND s.refresh_token_hash = $1
+           AND s.revoked_at IS NULL
+           AND s.expires_at > NOW()
+         RETURNING s.id, s.user_id, u.name, u.role_id"
+    )
+    .bind(&old_hash)
+    .bind(hash_token(&new_refresh_token))
+    .bind(refresh_expiry(jwt))
+    .bind(&client.user_agent)
+    .bind(&client.ip_address)
+    .fetch_optional(pool)
+    .await?;
+
+    let Some((session_id, user_id, name, role_id)) = row else {
+        // Refresh token yang sudah dirotasi dipakai lagi: kemungkinan dicuri, cabut session-nya
+        let reused: Option<Uuid> = sqlx::query_scalar(
+            "UPDATE sessions SET revoked_at = NOW()
+             WHERE previous_refresh_token_hash = $1 AND revoked_at IS NULL
+             RETURNING id"
+        )
+        .bind(&old_hash)
+        .fetch_optional(pool)
+        .await?;
+        if let Some(session_id) = reused {
+            eprintln!("[WARN] Refresh token lama dipakai ulang, session {} dicabut", session_id);
+        }
+        return Err(ApiError::Unauthorized("Invalid or expired refresh token".to_string()));
+    };
+
+    let tokens = SessionTokens {
+        session_id,
+        access_token: access_token(jwt, user_id, role_id, session_id)?,
+        refresh_token: new_refresh_token,
+    };
+    Ok((SessionUser { user_id, name, role_id }, tokens))
+}
+
+/// Session masih aktif (belum dicabut/kedaluwarsa) dan milik user tersebut
+pub async fn session_is_active(pool: &PgPool, session_id: Uuid, user_id: Uuid) -> Result<bool, sqlx::Error> {
+    sqlx::query_scalar(
+        "SELECT EXISTS (
+            SELECT 1 FROM sessions
+            WHERE id = $1 AND user_id = $2 AND revoked_at IS NULL AND expires_at > NOW()
+         )"
+    )
+    .bind(session_id)
+    .bind(user_id)
+    .fetch_one(pool)
+    .await
+}
+
+/// Session aktif milik user, terbaru dipakai lebih dulu
+pub async fn list_sessions(pool: &PgPool, user_id: Uuid) -> Result<Vec<Session>, sqlx::Error> {
+    sqlx::query_as::<_, Session>(
+        "SELECT id, user_agent, ip_address, created_at, last_used_at, expires_at
+         FROM sessions
+         WHERE user_id = $1 AND revoked_at IS NULL AND expires_at > NOW()
+         ORDER BY last_used_at DESC"
+    )
+    .bind(user_id)
+    .fetch_all(pool)
+    .await
+}
+
+/// Cabut satu session milik user. Mengembalikan false jika tidak ditemukan.
+pub async fn revoke_session(pool: &PgPool, user_id: Uuid, session_id: Uuid) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query("UPDATE sessions SET revoked_at = NOW() WHERE id = $1 AND user_id = $2 AND revoked_at IS NULL")
+        .bind(session_id)
+        .bind(user_id)
+        .execute(pool)
+        .await?;
+    Ok(result.rows_affected() > 0)
+}
+
+/// Cabut session pemilik refresh token (dipakai logout tanpa access token yang valid).
+/// Mengembalikan `(user_id, session_id)` session yang dicabut.
+pub async fn revoke_session_by_refresh_token(pool: &PgPool, refresh_token: &str) -> Result<Option<(Uuid, Uuid)>, sqlx::Error> {
+    sqlx::query_as(
+        "UPDATE sessions SET revoked_at = NOW() WHERE refresh_token_hash = $1 AND revoked_at IS NULL
+         RETURNING user_id, id"
+    )
+    .bind(hash_token(refresh_token))
+    .fetch_optional(pool)
+    .await
+}
+
+/// Cabut semua session user, mis. saat logout dari semua perangkat atau ganti password
+pub async fn revoke_all_sessions(db: impl PgExecutor<'_>, user_id: Uuid) -> Result<u64, sqlx::Error> {
+    let result = sqlx::query("UPDATE sessions SET revoked_at = NOW() WHERE user_id = $1 AND revoked_at IS NULL")
+        .bind(user_id)
+        .execute(db)
+        .await?;
+    Ok(result.rows_affected())
+}
+
+/// Access token JWT untuk session
+pub fn access_token(jwt: &JwtConfig, user_id: Uuid, role_id: Uuid, session_id: Uuid) -> Result<String, ApiError> {
+    let claims = Claims {
+        sub: user_id.to_string(),
+        exp: (Utc::now() + Duration::minutes(jwt.access_token_ttl_minutes)).timestamp() as usize,
+        role: role_id.to_string(),
+        jti: session_id.to_string(),
+        api_token: None,
+    };
+    encode(&Header::default(), &claims, &EncodingKey::from_secret(jwt.secret.as_bytes())).map_err(ApiError::internal)
+}
+
+fn refresh_expiry(jwt: &JwtConfig) -> DateTime<Utc> {
+    Utc::now() + Duration::days(jwt.refresh_token_ttl_days)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ip(value: &str) -> IpAddr {
+        value.parse().unwrap()
+    }
+
+    fn proxies(values: &[&str]) -> Vec<TrustedProxy> {
+        values.iter().map(|value| value.parse().unwrap()).collect()
+    }
+
+    #[test]
+    fn forwarded_for_is_ignored_without_trusted_proxies() {
+        assert_eq!(resolve_client_ip(ip("203.0.113.7"), &["198.51.100.1"], &[]), ip("203.0.113.7"));
+    }
+
+    #[test]
+    fn forwarded_for_is_ignored_from_untrusted_peer() {
+        let trusted = proxies(&["10.0.0.0/8"]);
+        assert_eq!(resolve_client_ip(ip("203.0.113.7"), &["198.51.100.1"], &trusted), ip("203.0.113.7"));
+    }
+
+    #[test]
+    fn trusted_proxy_forwards_client_ip() {
+        let trusted = proxies(&["10.0.0.0/8"]);
+        assert_eq!(resolve_client_ip(ip("10.1.2.3"), &["198.51.100.1"], &trusted), ip("198.51.100.1"));
+    }
+
+    #[test]
+    fn spoofed_entries_left_of_the_client_are_ignored() {
+        // Client mengirim X-Forwarded-For palsu, proxy menambahkan IP aslinya di kanan
+        let trusted = proxies(&["10.0.0.0/8"]);
+        let forwarded_for = ["1.1.1.1", " 198.51.100.1", "10.0.0.5"];
+        assert_eq!(resolve_client_ip(ip("10.1.2.3"), &forwarded_for, &trusted), ip("198.51.100.1"));
+    }
+
+    #[test]
+    fn invalid_forwarded_entry_stops_at_last_trusted_hop() {
+        let trusted = proxies(&["10.0.0.0/8"]);
+        assert_eq!(resolve_client_ip(ip("10.1.2.3"), &["unknown"], &trusted), ip("10.1.2.3"));
+        assert_eq!(resolve_client_ip(ip("10.1.2.3"), &[], &trusted), ip("10.1.2.3"));
+    }
+}
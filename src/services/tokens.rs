@@ -0,0 +1,17 @@
+//! Token acak untuk session, undangan, dan sejenisnya. Token dikirim ke client apa
+//! adanya, sedangkan database hanya menyimpan hash SHA-256-nya.
+
+use argon2::password_hash::rand_core::{OsRng, RngCore};
+use sha2::{Digest, Sha256};
+
+/// 32 byte acak dalam bentuk hex
+pub fn generate_token() -> String {
+    let mut bytes = [0u8; 32];
+    OsRng.fill_bytes(&mut bytes);
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Hash SHA-256 (hex) yang disimpan di database
+pub fn hash_token(token: &str) -> String {
+    Sha256::digest(token.as_bytes()).iter().map(|b| format!("{:02x}", b)).collect()
+}
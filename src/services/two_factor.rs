@@ -0,0 +1,344 @@
+//! Two-factor authentication dengan TOTP (RFC 6238, kompatibel dengan Google
+//! Authenticator, Authy, dll.).
+//!
+//! Pendaftaran dua langkah: `start_enrollment` membuat secret (belum aktif) yang
+//! dipindai user, lalu `enable_two_factor` mengaktifkannya setelah user memasukkan
+//! kode yang benar dan mengembalikan kode pemulihan sekali pakai. Saat login, user
+//! dengan 2FA aktif (atau yang role-nya mewajibkan 2FA) mendapat login challenge
+//! yang ditukar dengan session setelah kode dari authenticator atau kode pemulihan
+//! diverifikasi.
+
+use std::io::Cursor;
+
+use argon2::password_hash::rand_core::{OsRng, RngCore};
+use base64::Engine;
+use chrono::{DateTime, Duration, Utc};
+use image::{ImageEncoder, Luma};
+use qrcode::QrCode;
+use serde::Serialize;
+use sqlx::{Acquire, PgPool, Postgres};
+use totp_rs::{Algorithm, Secret, TOTP};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use crate::error::ApiError;
+use crate::services::tokens::{generate_token, hash_token};
+
+const ISSUER: &str = "Inman";
+const DIGITS: usize = 6;
+const STEP_SECS: u64 = 30;
+/// Kode dari satu time step sebelum/sesudah tetap diterima (jam perangkat tidak selalu pas)
+const SKEW_STEPS: u64 = 1;
+const RECOVERY_CODE_COUNT: usize = 10;
+/// Masa berlaku login challenge (detik)
+pub const CHALLENGE_TTL_SECS: i64 = 300;
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct TwoFactorStatus {
+    pub enabled: bool,
+    pub enabled_at: Option<DateTime<Utc>>,
+    /// Role user mewajibkan 2FA
+    pub required: bool,
+    pub recovery_codes_remaining: i64,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct TwoFactorEnrollment {
+    /// Secret base32, untuk dimasukkan manual jika QR code tidak bisa dipindai
+    pub secret: String,
+    pub otpauth_url: String,
+    /// QR code PNG dari `otpauth_url` sebagai data URL (`data:image/png;base64,...`)
+    pub qr_code: String,
+}
+
+/// User pemilik login challenge
+pub struct ChallengeUser {
+    pub user_id: Uuid,
+    pub name: String,
+    pub role_id: Uuid,
+}
+
+pub async fn two_factor_status(pool: &PgPool, user_id: Uuid) -> Result<TwoFactorStatus, sqlx::Error> {
+    let (enabled_at, required, recovery_codes_remaining): (Option<DateTime<Utc>>, Option<bool>, i64) = sqlx::query_as(
+        "SELECT t.enabled_at,
+                r.require_two_factor,
+                (SELECT COUNT(*) FROM user_recovery_codes c WHERE c.user_id = u.id AND c.used_at IS NULL)
+         FROM users u
+         LEFT JOIN user_roles r ON r.id = u.role_id
+         LEFT JOIN user_totp t ON t.user_id = u.id
+         WHERE u.id = $1"
+    )
+    .bind(user_id)
+    .fetch_one(pool)
+    .await?;
+
+    Ok(TwoFactorStatus {
+        enabled: enabled_at.is_some(),
+        enabled_at,
+        required: required.unwrap_or(false),
+        recovery_codes_remaining,
+    })
+}
+
+/// Buat secret baru yang belum aktif. Pendaftaran sebelumnya yang belum dikonfirmasi diganti.
+pub async fn start_enrollment(pool: &PgPool, user_id: Uuid, account_name: &str) -> Result<TwoFactorEnrollment, ApiError> {
+    let mut bytes = [0u8; 20];
+    OsRng.fill_bytes(&mut bytes);
+    let secret = totp(&bytes).get_secret_base32();
+
+    let result = sqlx::query(
+        "INSERT INTO user_totp (user_id, secret) VALUES ($1, $2)
+         ON CONFLICT (user_id) DO UPDATE
+         SET secret = EXCLUDED.secret, last_used_step = NULL, created_at = NOW()
+         WHERE user_totp.enabled_at IS NULL"
+    )
+    .bind(user_id)
+    .bind(&secret)
+    .execute(pool)
+    .await?;
+    if result.rows_affected() == 0 {
+        return Err(ApiError::Conflict("2FA sudah aktif".to_string()));
+    }
+
+    let otpauth_url = format!(
+        "otpauth://totp/{issuer}:{account}?secret={secret}&issuer={issuer}&algorithm=SHA1&digits={DIGITS}&period={STEP_SECS}",
+        issuer = ISSUER,
+        account = percent_encode(account_name),
+        secret = secret,
+    );
+    Ok(TwoFactorEnrollment {
+        qr_code: qr_code_data_url(&otpauth_url)?,
+        otpauth_url,
+        secret,
+    })
+}
+
+/// Aktifkan 2FA jika `code` cocok dengan secret yang sedang didaftarkan.
+/// Mengembalikan kode pemulihan baru, atau `None` jika kodenya salah.
+pub async fn enable_two_factor(pool: &PgPool, user_id: Uuid, code: &str) -> Result<Option<Vec<String>>, ApiError> {
+    let secret: String = sqlx::query_scalar("SELECT secret FROM user_totp WHERE user_id = $1 AND enabled_at IS NULL")
+        .bind(user_id)
+        .fetch_optional(pool)
+        .await?
+        .ok_or_else(|| ApiError::BadRequest("Belum ada pendaftaran 2FA yang menunggu konfirmasi".to_string()))?;
+    let Some(step) = matching_step(&secret, code) else {
+        return Ok(None);
+    };
+
+    sqlx::query("UPDATE user_totp SET enabled_at = NOW(), last_used_step = $2 WHERE user_id = $1")
+        .bind(user_id)
+        .bind(step)
+        .execute(pool)
+        .await?;
+    Ok(Some(regenerate_recovery_codes(pool, user_id).await?))
+}
+
+/// Verifikasi kode dari authenticator (6 digit) atau kode pemulihan untuk user dengan
+/// 2FA aktif. Kode authenticator tidak bisa dipakai dua kali, kode pemulihan hangus setelah dipakai.
+pub async fn verify_second_factor(pool: &PgPool, user_id: Uuid, code: &str) -> Result<bool, sqlx::Error> {
+    let code: String = code.chars().filter(|c| !c.is_whitespace() && *c != '-').collect::<String>().to_lowercase();
+
+    if code.len() == DIGITS && code.chars().all(|c| c.is_ascii_digit()) {
+        let secret: Option<String> =
+            sqlx::query_scalar("SELECT secret FROM user_totp WHERE user_id = $1 AND enabled_at IS NOT NULL")
+                .bind(user_id)
+                .fetch_optional(pool)
+                .await?;
+        let Some(step) = secret.and_then(|secret| matching_step(&secret, &code)) else {
+            return Ok(false);
+        };
+        // Kondisi last_used_step sekaligus mencegah dua request memakai kode yang sama
+        let result = sqlx::query(
+            "UPDATE user_totp SET last_used_step = $2
+             WHERE user_id = $1 AND (last_used_step IS NULL OR last_used_step < $2)"
+        )
+        .bind(user_id)
+        .bind(step)
+        .execute(pool)
+        .await?;
+        return Ok(result.rows_affected() > 0);
+    }
+
+    let result = sqlx::query(
+        "UPDATE user_recovery_codes SET used_at = NOW()
+         WHERE user_id = $1 AND code_hash = $2 AND used_at IS NULL
+           AND EXISTS (SELECT 1 FROM user_totp WHERE user_id = $1 AND enabled_at IS NOT NULL)"
+    )
+    .bind(user_id)
+    .bind(hash_token(&code))
+    .execute(pool)
+    .await?;
+    Ok(result.rows_affected() > 0)
+}
+
+/// Ganti semua kode pemulihan user dengan yang baru. Kode hanya ditampilkan sekali.
+pub async fn regenerate_recovery_codes(pool: &PgPool, user_id: Uuid) -> Result<Vec<String>, sqlx::Error> {
+    let mut tx = pool.begin().await?;
+    sqlx::query("DELETE FROM user_recovery_codes WHERE user_id = $1")
+        .bind(user_id)
+        .execute(&mut *tx)
+        .await?;
+
+    let mut codes = Vec::with_capacity(RECOVERY_CODE_COUNT);
+    for _ in 0..RECOVERY_CODE_COUNT {
+        let raw = &generate_token()[..10];
+        sqlx::query("INSERT INTO user_recovery_codes (user_id, code_hash) VALUES ($1, $2)")
+            .bind(user_id)
+            .bind(hash_token(raw))
+            .execute(&mut *tx)
+            .await?;
+        codes.push(format!("{}-{}", &raw[..5], &raw[5..]));
+    }
+    tx.commit().await?;
+    Ok(codes)
+}
+
+/// Matikan 2FA dan hapus kode pemulihan. Mengembalikan false jika 2FA belum pernah didaftarkan.
+pub async fn disable_two_factor<'c>(db: impl Acquire<'c, Database = Postgres>, user_id: Uuid) -> Result<bool, sqlx::Error> {
+    let mut conn = db.acquire().await?;
+    sqlx::query("DELETE FROM user_recovery_codes WHERE user_id = $1")
+        .bind(user_id)
+        .execute(&mut *conn)
+        .await?;
+    let result = sqlx::query("DELETE FROM user_totp WHERE user_id = $1")
+        .bind(user_id)
+        .execute(&mut *conn)
+        .await?;
+    Ok(result.rows_affected() > 0)
+}
+
+/// Buat login challenge untuk user yang password-nya sudah benar
+pub async fn create_login_challenge(pool: &PgPool, user_id: Uuid) -> Result<String, sqlx::Error> {
+    sqlx::query("DELETE FROM login_challenges WHERE user_id = $1 AND expires_at < NOW()")
+        .bind(user_id)
+        .execute(pool)
+        .await?;
+
+    let token = generate_token();
+    sqlx::query("INSERT INTO login_challenges (user_id, token_hash, expires_at) VALUES ($1, $2, $3)")
+        .bind(user_id)
+        .bind(hash_token(&token))
+        .bind(Utc::now() + Duration::seconds(CHALLENGE_TTL_SECS))
+        .execute(pool)
+        .await?;
+    Ok(token)
+}
+
+/// User pemilik login challenge yang masih berlaku
+pub async fn find_login_challenge(pool: &PgPool, token: &str) -> Result<ChallengeUser, ApiError> {
+    let row: Option<(Uuid, String, Uuid)> = sqlx::query_as(
+        "SELECT u.id, u.name, u.role_id
+         FROM login_challenges c
+         JOIN users u ON u.id = c.user_id
+         WHERE c.token_hash = $1 AND c.expires_at > NOW() AND u.is_active"
+    )
+    .bind(hash_token(token))
+    .fetch_optional(pool)
+    .await?;
+
+    let (user_id, name, role_id) = row.ok_or_else(|| ApiError::Detailed {
+        status: actix_web::http::StatusCode::UNAUTHORIZED,
+        code: "challenge_invalid",
+        message: "Login challenge tidak valid atau kedaluwarsa, silakan login ulang".to_string(),
+        details: serde_json::Value::Null,
+    })?;
+    Ok(ChallengeUser { user_id, name, role_id })
+}
+
+/// Hapus login challenge setelah login selesai
+pub async fn consume_login_challenge(pool: &PgPool, token: &str) -> Result<(), sqlx::Error> {
+    sqlx::query("DELETE FROM login_challenges WHERE token_hash = $1")
+        .bind(hash_token(token))
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
+fn totp(secret: &[u8]) -> TOTP {
+    TOTP::new_unchecked(Algorithm::SHA1, DIGITS, SKEW_STEPS as u8, STEP_SECS, secret.to_vec())
+}
+
+/// Time step yang kodenya cocok dengan `code`, dalam rentang `SKEW_STEPS`
+fn matching_step(secret: &str, code: &str) -> Option<i64> {
+    matching_step_at(secret, code, Utc::now().timestamp() as u64 / STEP_SECS)
+}
+
+fn matching_step_at(secret: &str, code: &str, current: u64) -> Option<i64> {
+    let secret = Secret::Encoded(secret.to_string()).to_bytes().ok()?;
+    let totp = totp(&secret);
+    (current - SKEW_STEPS..=current + SKEW_STEPS)
+        .find(|step| totp.generate(step * STEP_SECS) == code.trim())
+        .map(|step| step as i64)
+}
+
+fn qr_code_data_url(content: &str) -> Result<String, ApiError> {
+    let code = QrCode::new(content).map_err(|e| ApiError::internal(format!("QR gen error: {}", e)))?;
+    let image = code.render::<Luma<u8>>().build();
+    let mut cursor = Cursor::new(Vec::new());
+    image::codecs::png::PngEncoder::new(&mut cursor)
+        .write_image(image.as_raw(), image.width(), image.height(), image::ColorType::L8.into())
+        .map_err(|e| ApiError::internal(format!("QR encode error: {}", e)))?;
+    Ok(format!(
+        "data:image/png;base64,{}",
+        base64::engine::general_purpose::STANDARD.encode(cursor.into_inner())
+    ))
+}
+
+fn percent_encode(value: &str) -> String {
+    value
+        .bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => (b as char).to_string(),
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CURRENT_STEP: u64 = 57_000_000;
+
+    fn secret() -> String {
+        Secret::Raw(b"12345678901234567890".to_vec()).to_encoded().to_string()
+    }
+
+    fn code_at(step: u64) -> String {
+        let secret = Secret::Encoded(secret()).to_bytes().unwrap();
+        totp(&secret).generate(step * STEP_SECS)
+    }
+
+    #[test]
+    fn matching_step_accepts_codes_within_skew() {
+        let secret = secret();
+        for step in [CURRENT_STEP - 1, CURRENT_STEP, CURRENT_STEP + 1] {
+            assert_eq!(matching_step_at(&secret, &code_at(step), CURRENT_STEP), Some(step as i64));
+        }
+        assert_eq!(matching_step_at(&secret, &format!(" {} ", code_at(CURRENT_STEP)), CURRENT_STEP), Some(CURRENT_STEP as i64));
+    }
+
+    #[test]
+    fn matching_step_rejects_codes_outside_skew_or_wrong() {
+        let secret = secret();
+        assert_eq!(matching_step_at(&secret, &code_at(CURRENT_STEP - 2), CURRENT_STEP), None);
+        assert_eq!(matching_step_at(&secret, &code_at(CURRENT_STEP + 2), CURRENT_STEP), None);
+        assert_eq!(matching_step_at(&secret, "not-a-code", CURRENT_STEP), None);
+        assert_eq!(matching_step_at("!!invalid base32!!", &code_at(CURRENT_STEP), CURRENT_STEP), None);
+    }
+
+    #[test]
+    fn replayed_code_maps_to_an_already_used_step() {
+        // Login menyimpan step yang dipakai di last_used_step dan hanya menerima step
+        // yang lebih besar, jadi kode yang sama (atau kode lebih lama) tidak bisa dipakai lagi
+        let secret = secret();
+        let code = code_at(CURRENT_STEP);
+        let used = matching_step_at(&secret, &code, CURRENT_STEP).unwrap();
+        let replayed = matching_step_at(&secret, &code, CURRENT_STEP + 1).unwrap();
+        assert_eq!(replayed, used);
+        let older = matching_step_at(&secret, &code_at(CURRENT_STEP - 1), CURRENT_STEP).unwrap();
+        assert!(older < used);
+        let newer = matching_step_at(&secret, &code_at(CURRENT_STEP + 1), CURRENT_STEP + 1).unwrap();
+        assert!(newer > used);
+    }
+}
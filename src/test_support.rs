@@ -0,0 +1,15 @@
+//! Fixture bersama untuk test handler tanpa database, dipakai oleh unit test
+//! di `src/` maupun integration test di `tests/`. Bukan bagian dari API publik.
+
+use std::time::Duration;
+
+use sqlx::postgres::PgPoolOptions;
+use sqlx::PgPool;
+
+/// Pool yang tidak pernah tersambung: handler yang lolos pengecekan akan gagal di query
+pub fn unreachable_pool() -> PgPool {
+    PgPoolOptions::new()
+        .acquire_timeout(Duration::from_millis(200))
+        .connect_lazy("postgres://unused@127.0.0.1:1/unused")
+        .expect("URL database test valid")
+}
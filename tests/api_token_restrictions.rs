@@ -0,0 +1,94 @@
+//! Endpoint pengelolaan akun (2FA, session, feed kalender, logout) hanya boleh dipakai dari login biasa, bukan token API.
+
+use actix_web::dev::Service;
+use actix_web::http::{Method, StatusCode};
+use actix_web::test::{call_service, init_service, read_body_json, TestRequest};
+use actix_web::{web, App, HttpMessage};
+use rustrest::config::Config;
+use rustrest::middleware::jwt_extractor::Claims;
+use rustrest::middleware::permission_guard::UserPermissions;
+use rustrest::routes;
+use rustrest::services::api_tokens::ApiTokenGrant;
+use rustrest::test_support::unreachable_pool;
+use uuid::Uuid;
+
+const SESSION_ID: &str = "00000000-0000-0000-0000-000000000000";
+
+fn claims(api_token: Option<ApiTokenGrant>) -> Claims {
+    Claims {
+        sub: Uuid::new_v4().to_string(),
+        exp: usize::MAX,
+        role: Uuid::new_v4().to_string(),
+        jti: Uuid::new_v4().to_string(),
+        api_token,
+    }
+}
+
+fn api_token_claims() -> Claims {
+    claims(Some(ApiTokenGrant { token_id: Uuid::new_v4(), scopes: vec!["read".to_string(), "write".to_string()] }))
+}
+
+/// Endpoint yang wajib ditolak untuk token API, dengan body JSON yang valid
+fn session_only_endpoints() -> Vec<(Method, String, Option<serde_json::Value>)> {
+    let code = || Some(serde_json::json!({ "code": "123456" }));
+    vec![
+        (Method::GET, "/api/2fa".to_string(), None),
+        (Method::POST, "/api/2fa/setup".to_string(), None),
+        (Method::POST, "/api/2fa/enable".to_string(), code()),
+        (Method::POST, "/api/2fa/disable".to_string(), code()),
+        (Method::POST, "/api/2fa/recovery-codes".to_string(), code()),
+        (Method::GET, "/api/sessions".to_string(), None),
+        (Method::DELETE, format!("/api/sessions/{}", SESSION_ID), None),
+        (Method::DELETE, "/api/sessions".to_string(), None),
+        (Method::POST, "/api/calendar/feeds".to_string(), Some(serde_json::json!({}))),
+        (Method::GET, "/api/logout".to_string(), None),
+    ]
+}
+
+macro_rules! app_with_claims {
+    ($claims:expr) => {{
+        let claims = $claims;
+        let perms = UserPermissions::for_test(claims.user_id().unwrap(), &[]);
+        init_service(
+            App::new()
+                .app_data(web::Data::new(unreachable_pool()))
+                .app_data(web::Data::new(Config::default()))
+                .wrap_fn(move |req, srv| {
+                    req.extensions_mut().insert(claims.clone());
+                    req.extensions_mut().insert(perms.clone());
+                    srv.call(req)
+                })
+                .configure(routes::configure),
+        )
+        .await
+    }};
+}
+
+fn request(method: &Method, uri: &str, body: &Option<serde_json::Value>) -> TestRequest {
+    let req = TestRequest::default().method(method.clone()).uri(uri);
+    match body {
+        Some(body) => req.set_json(body),
+        None => req,
+    }
+}
+
+#[actix_web::test]
+async fn session_only_endpoints_reject_api_tokens() {
+    let app = app_with_claims!(api_token_claims());
+    for (method, uri, body) in session_only_endpoints() {
+        let res = call_service(&app, request(&method, &uri, &body).to_request()).await;
+        assert_eq!(res.status(), StatusCode::FORBIDDEN, "{} {} harus menolak token API", method, uri);
+        let body: serde_json::Value = read_body_json(res).await;
+        assert_eq!(body["code"], "forbidden", "{} {}", method, uri);
+    }
+}
+
+#[actix_web::test]
+async fn session_only_endpoints_accept_login_sessions() {
+    // Dengan claims dari login biasa, handler melewati pengecekan dan baru gagal di database
+    let app = app_with_claims!(claims(None));
+    for (method, uri, body) in session_only_endpoints() {
+        let res = call_service(&app, request(&method, &uri, &body).to_request()).await;
+        assert_ne!(res.status(), StatusCode::FORBIDDEN, "{} {} tidak boleh menolak session login", method, uri);
+    }
+}